@@ -0,0 +1,19 @@
+-- Raw copies of every downloaded results page, gzip-compressed and keyed by
+-- content hash so identical downloads are stored only once.
+CREATE TABLE IF NOT EXISTS raw_pages (
+    id SERIAL PRIMARY KEY,
+    url TEXT NOT NULL,
+    year INTEGER,
+    http_status INTEGER NOT NULL,
+    etag TEXT,
+    last_modified TEXT,
+    content_sha256 TEXT NOT NULL,
+    content_length INTEGER NOT NULL,
+    body_gzip BYTEA NOT NULL,
+    fetched_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_seen_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    fetch_count INTEGER NOT NULL DEFAULT 1,
+    UNIQUE (url, content_sha256)
+);
+
+CREATE INDEX IF NOT EXISTS raw_pages_url_last_seen_idx ON raw_pages (url, last_seen_at DESC);
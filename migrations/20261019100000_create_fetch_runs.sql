@@ -0,0 +1,17 @@
+-- One row per fetch of the results site, whatever triggered it, so gaps in
+-- scraping show up even when nothing was logged.
+CREATE TABLE IF NOT EXISTS fetch_runs (
+    id SERIAL PRIMARY KEY,
+    trigger TEXT NOT NULL,
+    source TEXT NOT NULL,
+    years INTEGER[] NOT NULL,
+    status TEXT NOT NULL DEFAULT 'running',
+    started_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TIMESTAMP,
+    rows_parsed INTEGER NOT NULL DEFAULT 0,
+    rows_inserted INTEGER NOT NULL DEFAULT 0,
+    rows_rejected INTEGER NOT NULL DEFAULT 0,
+    error TEXT
+);
+
+CREATE INDEX IF NOT EXISTS fetch_runs_started_at_idx ON fetch_runs (started_at DESC);
@@ -0,0 +1,9 @@
+-- Draws and grids belong to a game (see src/games.rs). Existing rows are
+-- EuroMillions; a date is unique per game only.
+ALTER TABLE draws ADD COLUMN IF NOT EXISTS game TEXT NOT NULL DEFAULT 'euromillions';
+ALTER TABLE draws DROP CONSTRAINT IF EXISTS draws_date_key;
+ALTER TABLE draws ADD CONSTRAINT draws_game_date_key UNIQUE (game, date);
+
+ALTER TABLE grids ADD COLUMN IF NOT EXISTS game TEXT NOT NULL DEFAULT 'euromillions';
+DROP INDEX IF EXISTS grids_draw_date_idx;
+CREATE INDEX IF NOT EXISTS grids_game_draw_date_idx ON grids (game, draw_date);
@@ -0,0 +1,16 @@
+-- Where a grid is in its lifecycle: generated (a mere suggestion), played
+-- (bought), checked (against the result of its draw) or archived.
+-- `played_at` stays set once a line is checked or archived, so spending
+-- counts every line that was bought.
+ALTER TABLE grids ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'generated';
+ALTER TABLE grids ADD COLUMN IF NOT EXISTS played_at TIMESTAMP;
+CREATE INDEX IF NOT EXISTS grids_status_idx ON grids (status);
+
+-- Grids given to a user used to be the lines they played.
+UPDATE grids
+SET status = CASE
+        WHEN EXISTS (SELECT 1 FROM draws WHERE draws.game = grids.game AND draws.date = grids.draw_date) THEN 'checked'
+        ELSE 'played'
+    END,
+    played_at = created_at
+WHERE user_id IS NOT NULL AND status = 'generated';
@@ -0,0 +1,15 @@
+-- SQLite has no array type: numbers and stars are stored as JSON arrays.
+CREATE TABLE IF NOT EXISTS draws (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    date DATE NOT NULL UNIQUE,
+    numbers TEXT NOT NULL,
+    stars TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS grids (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    draw_date DATE NOT NULL,
+    numbers TEXT NOT NULL,
+    stars TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
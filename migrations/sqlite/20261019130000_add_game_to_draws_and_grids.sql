@@ -0,0 +1,19 @@
+-- Draws and grids belong to a game (see src/games.rs). Existing rows are
+-- EuroMillions; a date is unique per game only. SQLite cannot drop the
+-- UNIQUE (date) constraint, so the draws table is rebuilt.
+CREATE TABLE draws_by_game (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    game TEXT NOT NULL DEFAULT 'euromillions',
+    date DATE NOT NULL,
+    numbers TEXT NOT NULL,
+    stars TEXT NOT NULL,
+    UNIQUE (game, date)
+);
+INSERT INTO draws_by_game (id, game, date, numbers, stars)
+    SELECT id, 'euromillions', date, numbers, stars FROM draws;
+DROP TABLE draws;
+ALTER TABLE draws_by_game RENAME TO draws;
+
+ALTER TABLE grids ADD COLUMN game TEXT NOT NULL DEFAULT 'euromillions';
+DROP INDEX IF EXISTS grids_draw_date_idx;
+CREATE INDEX IF NOT EXISTS grids_game_draw_date_idx ON grids (game, draw_date);
@@ -0,0 +1,10 @@
+-- Winning My Million / Millionaire Maker codes published with each draw.
+CREATE TABLE IF NOT EXISTS raffle_codes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    draw_id INTEGER NOT NULL REFERENCES draws (id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    code TEXT NOT NULL,
+    UNIQUE (draw_id, kind, code)
+);
+
+CREATE INDEX IF NOT EXISTS raffle_codes_code_idx ON raffle_codes (kind, code);
@@ -0,0 +1,9 @@
+-- Jackpots and tier payouts per draw, for expected value calculations.
+CREATE TABLE IF NOT EXISTS draw_prizes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    draw_id INTEGER NOT NULL REFERENCES draws (id) ON DELETE CASCADE,
+    tier INTEGER NOT NULL,
+    amount_cents BIGINT NOT NULL,
+    winners INTEGER,
+    UNIQUE (draw_id, tier)
+);
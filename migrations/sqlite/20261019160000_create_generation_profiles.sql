@@ -0,0 +1,14 @@
+-- A user's defaults for `/generate`, per game: numbers and stars every grid
+-- holds, numbers no grid holds, and the strategy and grid count to use when
+-- the request does not say. Number lists are JSON arrays.
+CREATE TABLE IF NOT EXISTS generation_profiles (
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    game TEXT NOT NULL,
+    forced_numbers TEXT NOT NULL DEFAULT '[]',
+    forced_stars TEXT NOT NULL DEFAULT '[]',
+    banned_numbers TEXT NOT NULL DEFAULT '[]',
+    strategy TEXT,
+    grid_count INTEGER,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, game)
+);
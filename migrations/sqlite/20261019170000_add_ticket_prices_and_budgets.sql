@@ -0,0 +1,22 @@
+-- Price of a line of `game` for draws from `valid_from` on, until the next
+-- price. Games without a stored price use the one in src/games.rs.
+CREATE TABLE IF NOT EXISTS ticket_prices (
+    game TEXT NOT NULL,
+    valid_from DATE NOT NULL,
+    price_cents BIGINT NOT NULL,
+    PRIMARY KEY (game, valid_from)
+);
+
+-- What a grid cost for its draw. NULL for grids generated before prices.
+ALTER TABLE grids ADD COLUMN cost_cents BIGINT;
+CREATE INDEX IF NOT EXISTS grids_user_draw_date_idx ON grids (user_id, draw_date);
+
+-- Spending caps per user: `period` is week (Monday to Sunday) or month, by
+-- draw date, and the cap covers the games played in `currency`.
+CREATE TABLE IF NOT EXISTS budgets (
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    period TEXT NOT NULL,
+    currency TEXT NOT NULL,
+    limit_cents BIGINT NOT NULL,
+    PRIMARY KEY (user_id, period, currency)
+);
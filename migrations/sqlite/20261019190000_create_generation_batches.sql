@@ -0,0 +1,16 @@
+-- One generation request and the options it ran with. A client retrying
+-- with the same `idempotency_key` gets the batch back instead of new grids.
+CREATE TABLE IF NOT EXISTS generation_batches (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    idempotency_key TEXT UNIQUE,
+    game TEXT NOT NULL,
+    user_id INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    strategy TEXT NOT NULL,
+    -- JSON text of the generation options.
+    params TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- NULL for grids generated before batches.
+ALTER TABLE grids ADD COLUMN batch_id INTEGER REFERENCES generation_batches (id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS grids_batch_id_idx ON grids (batch_id);
@@ -0,0 +1,13 @@
+-- Price of an add-on bought with a line of `game`, such as a second draw or
+-- a raffle entry, for draws from `valid_from` on, until its next price.
+CREATE TABLE IF NOT EXISTS ticket_addons (
+    game TEXT NOT NULL,
+    addon TEXT NOT NULL,
+    valid_from DATE NOT NULL,
+    price_cents BIGINT NOT NULL,
+    PRIMARY KEY (game, addon, valid_from)
+);
+
+-- The add-ons bought with a grid, as a JSON array, included in its
+-- `cost_cents`.
+ALTER TABLE grids ADD COLUMN addons TEXT NOT NULL DEFAULT '[]';
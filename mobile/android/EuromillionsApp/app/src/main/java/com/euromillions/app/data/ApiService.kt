@@ -1,13 +1,15 @@
 package com.euromillions.app.data
 
 import retrofit2.http.GET
+import retrofit2.http.Header
+import retrofit2.http.POST
 
 interface ApiService {
     @GET("history")
     suspend fun fetchHistory(): String
     
-    @GET("generate")
-    suspend fun generateGrids(): List<NewGrid>
+    @POST("generate")
+    suspend fun generateGrids(@Header("Idempotency-Key") idempotencyKey: String): List<NewGrid>
     
     @GET("grids")
     suspend fun fetchGrids(): List<Grid>
@@ -23,6 +23,7 @@ import com.google.accompanist.swiperefresh.SwipeRefresh
 import com.google.accompanist.swiperefresh.rememberSwipeRefreshState
 import kotlinx.coroutines.launch
 import java.time.format.DateTimeFormatter
+import java.util.UUID
 
 @OptIn(ExperimentalMaterial3Api::class)
 @Composable
@@ -249,7 +250,7 @@ suspend fun generateGrids(
 ) {
     onLoading(true)
     try {
-        ApiClient.apiService.generateGrids()
+        ApiClient.apiService.generateGrids(UUID.randomUUID().toString())
         onSuccess()
     } catch (e: Exception) {
         onError(e.message ?: "Failed to generate grids")
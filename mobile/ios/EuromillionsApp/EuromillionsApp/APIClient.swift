@@ -47,13 +47,15 @@ class APIClient {
     }
     
     // MARK: - Generate Grids
-    func generateGrids() async throws -> [NewGrid] {
+    /// Retrying with the same key returns the grids of the first request.
+    func generateGrids(idempotencyKey: String = UUID().uuidString) async throws -> [NewGrid] {
         guard let url = URL(string: "\(baseURL)/generate") else {
             throw APIError.invalidURL
         }
         
         var request = URLRequest(url: url)
-        request.httpMethod = "GET"
+        request.httpMethod = "POST"
+        request.setValue(idempotencyKey, forHTTPHeaderField: "Idempotency-Key")
         
         do {
             let (data, response) = try await URLSession.shared.data(for: request)
@@ -0,0 +1,82 @@
+use crate::config::Config;
+use crate::fetcher::DrawSource;
+use crate::metrics::Metrics;
+use crate::notify::Notifier;
+use crate::routes;
+use crate::storage::Storage;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::{web, App, Error, HttpMessage};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing_actix_web::{RequestId, TracingLogger};
+
+/// Response header carrying the id of the request's tracing span, so a
+/// client report can be matched with the server logs.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Dependencies shared by every request handler.
+#[derive(Clone)]
+pub struct AppState {
+    pub storage: Arc<dyn Storage>,
+    pub source: Arc<dyn DrawSource>,
+    pub metrics: Arc<Metrics>,
+    pub notifier: Arc<Notifier>,
+}
+
+impl AppState {
+    pub fn new(storage: Arc<dyn Storage>, source: Arc<dyn DrawSource>) -> Self {
+        AppState {
+            storage,
+            source,
+            metrics: Arc::new(Metrics::new()),
+            notifier: Arc::new(Notifier::default()),
+        }
+    }
+
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = Arc::new(notifier);
+        self
+    }
+}
+
+/// Builds the actix application with all routes wired to `state`. Used by the
+/// server binary, the test harness and any service embedding the bot.
+pub fn build_app(
+    config: Arc<Config>,
+    state: AppState,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = Error,
+        InitError = (),
+    >,
+> {
+    let metrics = state.metrics.clone();
+    App::new()
+        .app_data(web::Data::from(config))
+        .app_data(web::Data::new(state))
+        .configure(routes::configure)
+        .wrap_fn(move |req, srv| {
+            let metrics = metrics.clone();
+            let method = req.method().to_string();
+            let route = req.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+            let request_id = req.extensions().get::<RequestId>().copied();
+            let started = Instant::now();
+            let response = srv.call(req);
+            async move {
+                let mut response = response.await?;
+                metrics.observe_request(&method, &route, response.status().as_u16(), started.elapsed());
+                if let Some(id) = request_id {
+                    let value = HeaderValue::from_str(&id.to_string()).expect("UUIDs are valid header values");
+                    response.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+                }
+                Ok(response)
+            }
+        })
+        // Outermost: opens a span with a fresh request id around everything else
+        .wrap(TracingLogger::default())
+}
@@ -0,0 +1,132 @@
+use crate::error::{AppError, Result};
+use crate::fetcher::FetchedPage;
+use crate::games::EUROMILLIONS;
+use crate::models::{ArchivedPage, NewArchivedPage};
+use crate::odds;
+use crate::parser::{self, RejectedRow};
+use crate::raffle;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use crate::storage::Storage;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+
+/// Hex-encoded SHA-256 of the page body.
+pub fn content_hash(body: &str) -> String {
+    format!("{:x}", Sha256::digest(body.as_bytes()))
+}
+
+pub fn compress(body: &str) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(body.as_bytes())?;
+    encoder.finish()
+}
+
+pub fn decompress(data: &[u8]) -> std::io::Result<String> {
+    let mut body = String::new();
+    GzDecoder::new(data).read_to_string(&mut body)?;
+    Ok(body)
+}
+
+impl NewArchivedPage {
+    pub fn from_fetched(page: &FetchedPage, year: Option<i32>) -> std::io::Result<Self> {
+        Ok(NewArchivedPage {
+            url: page.url.clone(),
+            year,
+            http_status: i32::from(page.status),
+            etag: page.etag.clone(),
+            last_modified: page.last_modified.clone(),
+            content_sha256: content_hash(&page.body),
+            content_length: page.body.len() as i32,
+            body_gzip: compress(&page.body)?,
+        })
+    }
+}
+
+impl ArchivedPage {
+    /// Decompresses the stored HTML and checks it against its content hash.
+    pub fn body(&self) -> Result<String> {
+        let body = decompress(&self.body_gzip)
+            .map_err(|e| AppError::Parse(format!("archived page {} is corrupt: {}", self.id, e)))?;
+        if content_hash(&body) != self.content_sha256 {
+            return Err(AppError::Parse(format!(
+                "archived page {} does not match its content hash",
+                self.id
+            )));
+        }
+        Ok(body)
+    }
+}
+
+/// Stores a downloaded page in the archive. A 304 answer brings no new
+/// content, so the archived copy it was served from is only marked as seen.
+pub async fn store(storage: &dyn Storage, page: &FetchedPage, year: Option<i32>) -> Result<i32> {
+    if page.not_modified {
+        if let Some(id) = storage.touch_archived_page(&page.url, &content_hash(&page.body)).await? {
+            return Ok(id);
+        }
+    }
+    let mut archived = NewArchivedPage::from_fetched(page, year)
+        .map_err(|e| AppError::Parse(format!("failed to compress {}: {}", page.url, e)))?;
+    if page.not_modified {
+        // The cached body was never archived; it is what the last 200 sent.
+        archived.http_status = 200;
+    }
+    storage.archive_page(&archived).await
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReparsedPage {
+    pub id: i32,
+    pub url: String,
+    pub content_sha256: String,
+    pub rows_seen: usize,
+    pub rows_parsed: usize,
+    pub rejected: Vec<RejectedRow>,
+    pub markup_changed: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReparseSummary {
+    pub pages: Vec<ReparsedPage>,
+    /// Draws that were added or whose numbers changed.
+    pub draws_written: usize,
+}
+
+/// Rebuilds `draws` from the latest archived copy of every page, without any
+/// network access. Existing draws are overwritten with the re-parsed numbers.
+#[tracing::instrument(skip_all)]
+pub async fn reparse(storage: &dyn Storage) -> Result<ReparseSummary> {
+    let mut summary = ReparseSummary::default();
+
+    for page in storage.latest_archived_pages().await? {
+        let body = page.body()?;
+        let report = parser::parse_results_page(&body);
+        let markup_changed = report.markup_changed();
+        if markup_changed {
+            tracing::error!(alert = "markup_changed", page_id = page.id, url = %page.url, "Markup changed in archived page");
+        }
+
+        for draw in report.draws {
+            if storage.replace_draw(draw).await? {
+                summary.draws_written += 1;
+            }
+        }
+        raffle::store(storage, &report.raffles).await?;
+        odds::store_jackpots(storage, &EUROMILLIONS, &report.jackpots).await?;
+
+        summary.pages.push(ReparsedPage {
+            id: page.id,
+            url: page.url,
+            content_sha256: page.content_sha256,
+            rows_seen: report.rows_seen,
+            rows_parsed: report.rows_parsed,
+            rejected: report.rejected,
+            markup_changed,
+        });
+    }
+
+    Ok(summary)
+}
@@ -0,0 +1,102 @@
+//! Discord slash commands through the interactions endpoint
+//! (`POST /discord/interactions`). Discord signs every request with the
+//! application's Ed25519 key, and unsigned requests must be rejected.
+
+use super::{Chat, Platform};
+use crate::error::{AppError, Result};
+use crate::storage::Storage;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::Deserialize;
+
+pub const SIGNATURE_HEADER: &str = "x-signature-ed25519";
+pub const TIMESTAMP_HEADER: &str = "x-signature-timestamp";
+
+const PING: u8 = 1;
+const APPLICATION_COMMAND: u8 = 2;
+const PONG: u8 = 1;
+const CHANNEL_MESSAGE: u8 = 4;
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+pub fn parse_public_key(hex: &str) -> Result<VerifyingKey> {
+    let invalid = || AppError::Config("bot.discord_public_key is not a valid Ed25519 key".to_string());
+    let bytes: [u8; 32] = decode_hex(hex.trim()).ok_or_else(invalid)?.try_into().map_err(|_| invalid())?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| invalid())
+}
+
+/// Checks the signature of `timestamp` followed by the raw body.
+pub fn verify(key: &VerifyingKey, signature: &str, timestamp: &str, body: &[u8]) -> bool {
+    let Some(signature) = decode_hex(signature).and_then(|bytes| Signature::from_slice(&bytes).ok()) else {
+        return false;
+    };
+    let mut message = timestamp.as_bytes().to_vec();
+    message.extend_from_slice(body);
+    key.verify(&message, &signature).is_ok()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Interaction {
+    #[serde(rename = "type")]
+    pub kind: u8,
+    pub channel_id: Option<String>,
+    pub data: Option<CommandData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommandData {
+    pub name: String,
+    #[serde(default)]
+    pub options: Vec<CommandOption>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommandOption {
+    pub name: String,
+    pub value: serde_json::Value,
+}
+
+impl CommandData {
+    /// The slash command as the text a Telegram user would type, so both
+    /// platforms share one parser: `/check` with a `line` option of
+    /// `3 12 19 27 44 * 2 9` becomes `/check 3 12 19 27 44 * 2 9`.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("/{}", self.name);
+        for option in &self.options {
+            text.push(' ');
+            match &option.value {
+                serde_json::Value::String(value) => text.push_str(value),
+                value => text.push_str(&value.to_string()),
+            }
+        }
+        text
+    }
+}
+
+/// Answers a verified interaction.
+pub async fn interaction(storage: &dyn Storage, interaction: Interaction) -> Result<serde_json::Value> {
+    match interaction.kind {
+        PING => Ok(serde_json::json!({ "type": PONG })),
+        APPLICATION_COMMAND => {
+            let data = interaction
+                .data
+                .ok_or_else(|| AppError::Validation("command interaction without data".to_string()))?;
+            let chat = Chat {
+                platform: Platform::Discord,
+                id: interaction.channel_id.unwrap_or_default(),
+            };
+            let reply = super::respond(storage, &chat, &data.to_text())
+                .await
+                .unwrap_or_else(|| super::HELP.to_string());
+            Ok(serde_json::json!({ "type": CHANNEL_MESSAGE, "data": { "content": reply } }))
+        }
+        kind => Err(AppError::Validation(format!("unsupported interaction type {}", kind))),
+    }
+}
@@ -0,0 +1,270 @@
+//! Chat front-end: the same commands answered on Telegram (long polling,
+//! see [`telegram`]) and Discord (interactions endpoint, see [`discord`]).
+//!
+//! ```text
+//! /generate [count]              grids for the next draw
+//! /last                          latest stored draw
+//! /check 3 12 19 27 44 * 2 9     prize tier of a line on the latest draw
+//! /stats                         most drawn numbers and stars
+//! /subscribe                     result notifications in this chat
+//! ```
+//!
+//! Every chat gets its own user (`telegram:<chat id>`, `discord:<channel
+//! id>`), which owns the grids generated there, so `/subscribe` reports
+//! how they did. Commands are about EuroMillions.
+
+pub mod discord;
+pub mod telegram;
+
+use crate::checker;
+use crate::error::{AppError, Result};
+use crate::games::EUROMILLIONS;
+use crate::generator::{self, GenerateOptions};
+use crate::ledger::{self, BudgetStatus};
+use crate::models::{NewNotificationChannel, User};
+use crate::notify::ChannelKind;
+use crate::storage::Storage;
+use std::fmt;
+
+/// Most grids `/generate` returns in one message.
+pub const MAX_CHAT_GRIDS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Telegram,
+    Discord,
+}
+
+impl Platform {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Platform::Telegram => "telegram",
+            Platform::Discord => "discord",
+        }
+    }
+}
+
+/// The conversation a command came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chat {
+    pub platform: Platform,
+    pub id: String,
+}
+
+impl Chat {
+    pub fn user_name(&self) -> String {
+        format!("{}:{}", self.platform.as_str(), self.id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Help,
+    /// Without a count, the user's profile or default count.
+    Generate { count: Option<usize> },
+    Last,
+    Check { numbers: Vec<i32>, stars: Vec<i32> },
+    Stats,
+    /// Discord has no bot-side chat id to post to, so it needs a channel
+    /// webhook URL.
+    Subscribe { target: Option<String> },
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Command::Help => "help",
+            Command::Generate { .. } => "generate",
+            Command::Last => "last",
+            Command::Check { .. } => "check",
+            Command::Stats => "stats",
+            Command::Subscribe { .. } => "subscribe",
+        };
+        f.write_str(name)
+    }
+}
+
+pub const HELP: &str = "Commands:
+/generate [count] - grids for the next draw
+/last - latest draw
+/check 3 12 19 27 44 * 2 9 - check a line against the latest draw
+/stats - most drawn numbers and stars
+/subscribe - get every new result here";
+
+/// Parses a chat message. Returns `None` for anything that is not a
+/// command, and a validation error for a malformed one.
+pub fn parse(text: &str) -> Option<Result<Command>> {
+    let mut words = text.split_whitespace();
+    let name = words.next()?.strip_prefix('/')?;
+    // Telegram appends the bot name in groups: /last@EuroMillionsBot
+    let name = name.split('@').next().unwrap_or(name);
+    let args: Vec<&str> = words.collect();
+
+    let command = match name {
+        "start" | "help" => Ok(Command::Help),
+        "generate" => parse_count(&args).map(|count| Command::Generate { count }),
+        "last" => Ok(Command::Last),
+        "check" => parse_line(&args).map(|(numbers, stars)| Command::Check { numbers, stars }),
+        "stats" => Ok(Command::Stats),
+        "subscribe" => Ok(Command::Subscribe {
+            target: args.first().map(|t| t.to_string()),
+        }),
+        _ => Err(AppError::Validation(format!("Unknown command /{}. Try /help.", name))),
+    };
+    Some(command)
+}
+
+fn parse_count(args: &[&str]) -> Result<Option<usize>> {
+    let Some(arg) = args.first() else {
+        return Ok(None);
+    };
+    match arg.parse::<usize>() {
+        Ok(count) if (1..=MAX_CHAT_GRIDS).contains(&count) => Ok(Some(count)),
+        _ => Err(AppError::Validation(format!(
+            "count must be a number between 1 and {}",
+            MAX_CHAT_GRIDS
+        ))),
+    }
+}
+
+/// `3 12 19 27 44 * 2 9`: numbers, a `*`, then stars.
+fn parse_line(args: &[&str]) -> Result<(Vec<i32>, Vec<i32>)> {
+    let usage = || AppError::Validation("usage: /check 3 12 19 27 44 * 2 9".to_string());
+    let joined = args.join(" ");
+    let (numbers, stars) = joined.split_once('*').ok_or_else(usage)?;
+    let balls = |text: &str| -> Result<Vec<i32>> {
+        text.split_whitespace()
+            .map(|ball| ball.parse().map_err(|_| usage()))
+            .collect()
+    };
+    let (numbers, stars) = (balls(numbers)?, balls(stars)?);
+    EUROMILLIONS.validate_line(&numbers, &stars)?;
+    Ok((numbers, stars))
+}
+
+fn join(balls: &[i32]) -> String {
+    balls.iter().map(|b| b.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+/// The chat's user, created on first use.
+async fn chat_user(storage: &dyn Storage, chat: &Chat) -> Result<User> {
+    let name = chat.user_name();
+    match storage.get_user_by_name(&name).await? {
+        Some(user) => Ok(user),
+        None => storage.create_user(&name).await,
+    }
+}
+
+/// Runs a command and returns the reply.
+#[tracing::instrument(skip(storage), fields(platform = chat.platform.as_str(), chat = %chat.id, %command))]
+pub async fn handle(storage: &dyn Storage, chat: &Chat, command: Command) -> Result<String> {
+    match command {
+        Command::Help => Ok(HELP.to_string()),
+        Command::Generate { count } => {
+            let user = chat_user(storage, chat).await?;
+            let mut options = GenerateOptions::default();
+            if let Some(profile) = storage.get_generation_profile(user.id, options.game.id).await? {
+                generator::apply_profile(&mut options, &profile)?;
+            }
+            options.count = count.unwrap_or(options.count).min(MAX_CHAT_GRIDS);
+            let budgets = ledger::check_budgets(storage, user.id, options.game, options.draw_date, options.count, &options.addons).await?;
+            let mut grids = generator::generate(storage, &options).await?;
+            for grid in &mut grids {
+                grid.user_id = Some(user.id);
+            }
+            let saved = generator::save(storage, &options, Some(user.id), &grids).await?;
+            let mut lines = vec![format!("Grids for {}:", options.draw_date)];
+            lines.extend(saved.iter().map(|g| format!("{} ★ {}", join(&g.numbers), join(&g.stars))));
+            lines.extend(budgets.iter().filter_map(BudgetStatus::warning).map(|w| format!("Careful: {}.", w)));
+            Ok(lines.join("\n"))
+        }
+        Command::Last => Ok(match storage.latest_draw(EUROMILLIONS.id).await? {
+            Some(draw) => format!("Draw of {}: {} ★ {}", draw.date, join(&draw.numbers), join(&draw.stars)),
+            None => "No draw stored yet.".to_string(),
+        }),
+        Command::Check { numbers, stars } => {
+            let Some(draw) = storage.latest_draw(EUROMILLIONS.id).await? else {
+                return Ok("No draw stored yet.".to_string());
+            };
+            let result = checker::check_line(&EUROMILLIONS, &numbers, &stars, &draw);
+            let prize = match result.tier {
+                Some(tier) => format!("prize tier {}", tier),
+                None => "no prize".to_string(),
+            };
+            Ok(format!(
+                "Draw of {}: {} ★ {}\nYou matched {} number(s) and {} star(s): {}.",
+                draw.date,
+                join(&draw.numbers),
+                join(&draw.stars),
+                result.matched_numbers.len(),
+                result.matched_stars.len(),
+                prize
+            ))
+        }
+        Command::Stats => {
+            let draws = storage.count_draws(EUROMILLIONS.id).await?;
+            if draws == 0 {
+                return Ok("No draw stored yet.".to_string());
+            }
+            let top = |freqs: Vec<crate::models::BallFrequency>, n| {
+                freqs
+                    .iter()
+                    .take(n)
+                    .map(|f| format!("{} ({}×)", f.ball, f.count))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            Ok(format!(
+                "{} draws stored.\nMost drawn numbers: {}\nMost drawn stars: {}",
+                draws,
+                top(storage.number_frequencies(EUROMILLIONS.id).await?, 5),
+                top(storage.star_frequencies(EUROMILLIONS.id).await?, 3)
+            ))
+        }
+        Command::Subscribe { target } => {
+            let (kind, target) = match chat.platform {
+                Platform::Telegram => (ChannelKind::Telegram, chat.id.clone()),
+                Platform::Discord => {
+                    let target = target.ok_or_else(|| {
+                        AppError::Validation("usage: /subscribe <channel webhook URL>".to_string())
+                    })?;
+                    (ChannelKind::Discord, target)
+                }
+            };
+            kind.validate_target(&target, false)?;
+            let user = chat_user(storage, chat).await?;
+            let channels = storage.list_notification_channels(user.id).await?;
+            if channels.iter().any(|c| c.kind == kind.as_str() && c.target == target) {
+                return Ok("Already subscribed.".to_string());
+            }
+            storage
+                .add_notification_channel(&NewNotificationChannel {
+                    user_id: user.id,
+                    kind: kind.as_str().to_string(),
+                    target,
+                    notify_results: true,
+                    notify_wins: true,
+                })
+                .await?;
+            Ok("Subscribed: every new result will be posted here, with your grids.".to_string())
+        }
+    }
+}
+
+/// Parses and runs a message. Returns `None` when it is not a command.
+/// Invalid input is explained to the user; other errors are logged and
+/// answered with a generic apology.
+pub async fn respond(storage: &dyn Storage, chat: &Chat, text: &str) -> Option<String> {
+    let reply = match parse(text)? {
+        Ok(command) => handle(storage, chat, command).await,
+        Err(e) => Err(e),
+    };
+    Some(match reply {
+        Ok(reply) => reply,
+        Err(AppError::Validation(message)) => message,
+        Err(e) => {
+            tracing::error!(chat = %chat.id, error = %e, "Bot command failed");
+            "Sorry, something went wrong. Please try again later.".to_string()
+        }
+    })
+}
@@ -0,0 +1,132 @@
+//! Telegram Bot API client using long polling (`getUpdates`), so the bot
+//! works without a public HTTPS endpoint.
+
+use super::{Chat, Platform};
+use crate::error::{AppError, Result};
+use crate::storage::Storage;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Pause after a failed poll before trying again.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse<T> {
+    ok: bool,
+    result: Option<T>,
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Update {
+    pub update_id: i64,
+    pub message: Option<IncomingMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IncomingMessage {
+    pub chat: IncomingChat,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IncomingChat {
+    pub id: i64,
+}
+
+pub struct TelegramBot {
+    client: reqwest::Client,
+    base_url: String,
+    poll_timeout: Duration,
+}
+
+impl TelegramBot {
+    pub fn new(api_url: &str, bot_token: &str, poll_timeout: Duration) -> Result<Self> {
+        // The HTTP timeout must outlast the long poll
+        let client = reqwest::Client::builder()
+            .timeout(poll_timeout + Duration::from_secs(10))
+            .build()?;
+        Ok(TelegramBot {
+            client,
+            base_url: format!("{}/bot{}", api_url.trim_end_matches('/'), bot_token),
+            poll_timeout,
+        })
+    }
+
+    async fn call<T: serde::de::DeserializeOwned>(&self, method: &str, body: serde_json::Value) -> Result<T> {
+        let url = format!("{}/{}", self.base_url, method);
+        // Errors would print the URL, which contains the token
+        let response = self
+            .client
+            .post(&url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| AppError::Fetch(format!("Telegram {} failed: {}", method, e.without_url())))?;
+        let status = response.status();
+        let parsed: ApiResponse<T> = response
+            .json()
+            .await
+            .map_err(|e| AppError::Fetch(format!("Telegram {} returned HTTP {}: {}", method, status.as_u16(), e.without_url())))?;
+        match parsed {
+            ApiResponse { ok: true, result: Some(result), .. } => Ok(result),
+            ApiResponse { description, .. } => Err(AppError::Fetch(format!(
+                "Telegram {} failed: {}",
+                method,
+                description.unwrap_or_else(|| format!("HTTP {}", status.as_u16()))
+            ))),
+        }
+    }
+
+    /// Updates after `offset`, waiting up to the poll timeout for new ones.
+    pub async fn get_updates(&self, offset: i64) -> Result<Vec<Update>> {
+        let body = serde_json::json!({
+            "offset": offset,
+            "timeout": self.poll_timeout.as_secs(),
+            "allowed_updates": ["message"],
+        });
+        self.call("getUpdates", body).await
+    }
+
+    pub async fn send_message(&self, chat_id: i64, text: &str) -> Result<()> {
+        let body = serde_json::json!({ "chat_id": chat_id, "text": text });
+        self.call::<serde_json::Value>("sendMessage", body).await.map(|_| ())
+    }
+
+    /// Answers every pending update and returns the offset for the next
+    /// poll. An update is acknowledged even if its reply could not be sent,
+    /// so one bad chat cannot block the others.
+    pub async fn poll_once(&self, storage: &dyn Storage, offset: i64) -> Result<i64> {
+        let mut next = offset;
+        for update in self.get_updates(offset).await? {
+            next = next.max(update.update_id + 1);
+            let Some(message) = update.message else { continue };
+            let Some(text) = message.text else { continue };
+            let chat = Chat {
+                platform: Platform::Telegram,
+                id: message.chat.id.to_string(),
+            };
+            if let Some(reply) = super::respond(storage, &chat, &text).await {
+                if let Err(e) = self.send_message(message.chat.id, &reply).await {
+                    tracing::warn!(chat = message.chat.id, error = %e, "Failed to answer Telegram message");
+                }
+            }
+        }
+        Ok(next)
+    }
+
+    /// Polls forever.
+    pub async fn run(&self, storage: &dyn Storage) {
+        tracing::info!("Telegram bot started");
+        let mut offset = 0;
+        loop {
+            match self.poll_once(storage, offset).await {
+                Ok(next) => offset = next,
+                Err(e) => {
+                    tracing::warn!(error = %e, "Telegram poll failed");
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Compares a played line with a draw and works out the prize tier.
+
+use crate::error::Result;
+use crate::games::{self, GameRules};
+use crate::grids::GridStatus;
+use crate::models::{Draw, Grid};
+use crate::storage::Storage;
+use crate::webhooks::{self, Event};
+use chrono::NaiveDate;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct CheckResult {
+    pub draw_date: NaiveDate,
+    pub matched_numbers: Vec<i32>,
+    /// Bonus balls matched: stars, dream or chance number, or the UK Lotto
+    /// bonus ball.
+    pub matched_stars: Vec<i32>,
+    /// Prize tier (1 is the jackpot), or `None` for a losing line.
+    pub tier: Option<u8>,
+}
+
+impl CheckResult {
+    pub fn is_winning(&self) -> bool {
+        self.tier.is_some()
+    }
+}
+
+/// Compares a line with a draw of the same game. When the game's bonus ball
+/// is drawn from the main numbers (UK Lotto), it is matched against them.
+pub fn check_line(game: &GameRules, numbers: &[i32], stars: &[i32], draw: &Draw) -> CheckResult {
+    let matched = |line: &[i32], drawn: &[i32]| -> Vec<i32> {
+        line.iter().copied().filter(|b| drawn.contains(b)).collect()
+    };
+    let matched_numbers = matched(numbers, &draw.numbers);
+    let matched_stars = if game.bonus_from_numbers() {
+        matched(numbers, &draw.stars)
+    } else {
+        matched(stars, &draw.stars)
+    };
+    let tier = game.prize_tier(matched_numbers.len(), matched_stars.len());
+
+    CheckResult {
+        draw_date: draw.date,
+        matched_numbers,
+        matched_stars,
+        tier,
+    }
+}
+
+/// Checks every line played on `draw` and not checked yet, marks them as
+/// checked and emits a `grid.result` event for each one. Merely generated
+/// grids are left out.
+pub async fn check_grids(storage: &dyn Storage, draw: &Draw) -> Result<Vec<(Grid, CheckResult)>> {
+    let game = games::get(&draw.game)?;
+    let played = GridStatus::Played.as_str();
+    let ids: Vec<i32> = storage
+        .grids_for_draw(&draw.game, draw.date)
+        .await?
+        .into_iter()
+        .filter(|g| g.status == played)
+        .map(|g| g.id)
+        .collect();
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let grids = storage.set_grid_status(&ids, &[played], GridStatus::Checked.as_str()).await?;
+    let mut checked = Vec::with_capacity(grids.len());
+    for grid in grids {
+        let result = check_line(game, &grid.numbers, &grid.stars, draw);
+        webhooks::emit(storage, Event::GridResult, &serde_json::json!({ "grid": grid, "result": result })).await;
+        checked.push((grid, result));
+    }
+    Ok(checked)
+}
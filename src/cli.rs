@@ -0,0 +1,381 @@
+//! Command-line interface. Every subcommand reuses the library modules the
+//! HTTP API is built on, so maintenance jobs can run from cron without going
+//! through the server.
+
+use crate::checker;
+use crate::config::{Config, ConfigArgs};
+use crate::constraints::Constraints;
+use crate::error::{AppError, Result};
+use crate::fetcher::Fetcher;
+use crate::games::{self, GameRules};
+use crate::generator::{self, AntiPopular, GenerateOptions, ScoredGrid, Strategy};
+use crate::ingest::FetchTrigger;
+use crate::models::{BallFrequency, NewGrid};
+use crate::notify::{self, Notifier};
+use crate::popularity;
+use crate::storage::{self, Storage};
+use crate::transfer::{self, Format};
+use crate::{archive, ingest, server, telemetry};
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Euromillions results scraper and grid generator")]
+pub struct Cli {
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    /// Print results as JSON instead of text
+    #[arg(long, global = true)]
+    pub json: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the HTTP server and the scheduled fetch (default)
+    Serve,
+    /// Fetch results pages and store new draws
+    Fetch {
+        /// First year to fetch [default: configured fetch years]
+        #[arg(long)]
+        from: Option<i32>,
+        /// Last year to fetch [default: same as --from]
+        #[arg(long, requires = "from")]
+        to: Option<i32>,
+    },
+    /// Load draws from a file ("-" for stdin), skipping dates already stored
+    Import {
+        file: PathBuf,
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+        #[arg(long, value_parser = games::get, default_value = "euromillions")]
+        game: &'static GameRules,
+    },
+    /// Write every stored draw of a game to stdout or a file
+    Export {
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+        #[arg(long, value_parser = games::get, default_value = "euromillions")]
+        game: &'static GameRules,
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Generate grids for the next draw
+    Generate {
+        /// euromillions, eurodreams, loto or uk_lotto
+        #[arg(long, value_parser = games::get, default_value = "euromillions")]
+        game: &'static GameRules,
+        #[arg(long, value_enum, default_value_t)]
+        strategy: Strategy,
+        #[arg(long, default_value_t = 4)]
+        count: usize,
+        /// Seed for reproducible output
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Steer grids away from popular lines: weight or filter by score
+        #[arg(long, value_enum, default_value_t)]
+        anti_popular: AntiPopular,
+        /// Lowest popularity score kept with `--anti-popular filter`
+        #[arg(long, default_value_t = generator::DEFAULT_MIN_SCORE, value_parser = clap::value_parser!(u8).range(0..=100))]
+        min_score: u8,
+        #[command(flatten)]
+        constraints: Constraints,
+        /// Store the grids like `POST /generate` does
+        #[arg(long)]
+        save: bool,
+    },
+    /// Check a grid against a stored draw
+    Check {
+        #[arg(long, value_parser = games::get, default_value = "euromillions")]
+        game: &'static GameRules,
+        /// Numbers, comma separated
+        #[arg(long, value_delimiter = ',', required = true)]
+        numbers: Vec<i32>,
+        /// Stars or other bonus balls, comma separated (none for UK Lotto)
+        #[arg(long, value_delimiter = ',')]
+        stars: Vec<i32>,
+        /// Draw date (YYYY-MM-DD) [default: latest stored draw]
+        #[arg(long)]
+        date: Option<NaiveDate>,
+    },
+    /// Show draw count and number/star frequencies
+    Stats {
+        #[arg(long, value_parser = games::get, default_value = "euromillions")]
+        game: &'static GameRules,
+        /// How many numbers and stars to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Apply pending database migrations and exit
+    Migrate,
+    /// Rebuild draws from the raw page archive without touching the network
+    Reparse,
+}
+
+pub async fn run(cli: Cli) -> Result<()> {
+    let config = Config::load(&cli.config)?;
+    telemetry::init(&config.log);
+
+    let storage = storage::connect(&config.database.url, config.database.max_connections).await?;
+    storage.migrate().await?;
+
+    let json = cli.json;
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => server::serve(config, storage).await,
+        Command::Fetch { from, to } => {
+            let years = match from {
+                Some(from) => year_range(from, to.unwrap_or(from))?,
+                None => config.fetch.years.clone(),
+            };
+            fetch(&config, storage.as_ref(), &years, json).await
+        }
+        Command::Import { file, format, game } => import(storage.as_ref(), &file, format, game, json).await,
+        Command::Export { format, game, output } => export(storage.as_ref(), format, game, output).await,
+        Command::Generate { game, strategy, count, seed, anti_popular, min_score, constraints, save } => {
+            let options = GenerateOptions {
+                strategy,
+                count,
+                seed,
+                anti_popular,
+                min_score,
+                constraints,
+                ..GenerateOptions::for_game(game)
+            };
+            generate(storage.as_ref(), &options, save, json).await
+        }
+        Command::Check { game, numbers, stars, date } => {
+            check(storage.as_ref(), game, &numbers, &stars, date, json).await
+        }
+        Command::Stats { game, top } => stats(storage.as_ref(), game, top, json).await,
+        Command::Migrate => {
+            println!("Migrations applied.");
+            Ok(())
+        }
+        Command::Reparse => reparse(storage.as_ref(), json).await,
+    }
+}
+
+pub fn year_range(from: i32, to: i32) -> Result<Vec<i32>> {
+    if to < from {
+        return Err(AppError::Validation(format!(
+            "--to ({}) must not be before --from ({})",
+            to, from
+        )));
+    }
+    Ok((from..=to).collect())
+}
+
+fn print_json<T: Serialize>(value: &T) {
+    println!("{}", serde_json::to_string_pretty(value).expect("serializing output cannot fail"));
+}
+
+async fn fetch(config: &Config, storage: &dyn Storage, years: &[i32], json: bool) -> Result<()> {
+    let fetcher = Fetcher::new(config.fetch.fetcher_config())?;
+    let summary = ingest::run(storage, &fetcher, years, FetchTrigger::Cli).await?;
+    let notifier = Notifier::from_config(&config.notify)?;
+    notify::announce_new_draws(storage, &notifier, &summary).await;
+    let report = &summary.report;
+
+    if json {
+        print_json(&summary);
+    } else {
+        println!(
+            "Fetched {} page(s): {} rows, {} parsed, {} rejected, {} new draw(s).",
+            report.pages.len(),
+            report.rows_seen(),
+            report.rows_parsed(),
+            report.rows_rejected(),
+            summary.inserted
+        );
+        for failure in &report.failures {
+            eprintln!(
+                "{} failed after {} attempt(s): {}",
+                failure.year, failure.attempts, failure.kind
+            );
+        }
+    }
+
+    if report.is_complete() {
+        Ok(())
+    } else {
+        Err(AppError::Fetch(format!(
+            "{} of {} year(s) failed",
+            report.failures.len(),
+            years.len()
+        )))
+    }
+}
+
+async fn import(
+    storage: &dyn Storage,
+    file: &PathBuf,
+    format: Format,
+    game: &GameRules,
+    json: bool,
+) -> Result<()> {
+    let read_error = |e: std::io::Error| {
+        AppError::Validation(format!("cannot read {}: {}", file.display(), e))
+    };
+    let text = if file.as_os_str() == "-" {
+        std::io::read_to_string(std::io::stdin()).map_err(read_error)?
+    } else {
+        std::fs::read_to_string(file).map_err(read_error)?
+    };
+
+    let summary = transfer::import(storage, &text, format, game).await?;
+    if json {
+        print_json(&summary);
+    } else {
+        println!("Read {} draw(s), {} new.", summary.read, summary.inserted);
+    }
+    Ok(())
+}
+
+async fn export(storage: &dyn Storage, format: Format, game: &GameRules, output: Option<PathBuf>) -> Result<()> {
+    let text = transfer::export(storage, format, game).await?;
+    match output {
+        Some(path) => std::fs::write(&path, text)
+            .map_err(|e| AppError::Validation(format!("cannot write {}: {}", path.display(), e))),
+        None => {
+            print!("{}", text);
+            Ok(())
+        }
+    }
+}
+
+async fn generate(storage: &dyn Storage, options: &GenerateOptions, save: bool, json: bool) -> Result<()> {
+    let grids = generator::generate(storage, options).await?;
+
+    if save {
+        let saved = generator::save(storage, options, None, &grids).await?;
+        if json {
+            print_json(&generator::scored(saved)?);
+            return Ok(());
+        }
+    } else if json {
+        let scored: Vec<ScoredGrid<NewGrid>> = grids
+            .into_iter()
+            .map(|grid| ScoredGrid {
+                popularity: popularity::score(options.game, &grid.numbers),
+                grid,
+            })
+            .collect();
+        print_json(&scored);
+        return Ok(());
+    }
+    for grid in &grids {
+        let score = popularity::score(options.game, &grid.numbers).score;
+        println!("{}  {:?} * {:?}  score {}", grid.draw_date, grid.numbers, grid.stars, score);
+    }
+    Ok(())
+}
+
+async fn check(
+    storage: &dyn Storage,
+    game: &GameRules,
+    numbers: &[i32],
+    stars: &[i32],
+    date: Option<NaiveDate>,
+    json: bool,
+) -> Result<()> {
+    game.validate_line(numbers, stars)?;
+    let draw = match date {
+        Some(date) => storage.get_draw(game.id, date).await?,
+        None => storage.latest_draw(game.id).await?,
+    };
+    let draw = draw.ok_or_else(|| match date {
+        Some(date) => AppError::NotFound(format!("no {} draw stored for {}", game.name, date)),
+        None => AppError::NotFound(format!("no {} draws stored yet", game.name)),
+    })?;
+    let result = checker::check_line(game, numbers, stars, &draw);
+
+    if json {
+        print_json(&result);
+    } else {
+        println!("Draw {}: {:?} * {:?}", draw.date, draw.numbers, draw.stars);
+        println!(
+            "Matched {} number(s) {:?} and {} {} {:?}",
+            result.matched_numbers.len(),
+            result.matched_numbers,
+            result.matched_stars.len(),
+            game.bonus_name,
+            result.matched_stars
+        );
+        match result.tier {
+            Some(tier) => println!("Prize tier {}", tier),
+            None => println!("No prize"),
+        }
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Stats {
+    draws: i64,
+    latest_draw: Option<NaiveDate>,
+    numbers: Vec<BallFrequency>,
+    stars: Vec<BallFrequency>,
+}
+
+async fn stats(storage: &dyn Storage, game: &GameRules, top: usize, json: bool) -> Result<()> {
+    let mut numbers = storage.number_frequencies(game.id).await?;
+    let mut stars = storage.star_frequencies(game.id).await?;
+    numbers.truncate(top);
+    stars.truncate(top);
+    let stats = Stats {
+        draws: storage.count_draws(game.id).await?,
+        latest_draw: storage.latest_draw(game.id).await?.map(|d| d.date),
+        numbers,
+        stars,
+    };
+
+    if json {
+        print_json(&stats);
+        return Ok(());
+    }
+    match stats.latest_draw {
+        Some(latest) => println!("{} draw(s), latest on {}", stats.draws, latest),
+        None => println!("No draws stored yet"),
+    }
+    let list = |freqs: &[BallFrequency]| {
+        freqs
+            .iter()
+            .map(|f| format!("{} ({})", f.ball, f.count))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    println!("Most drawn numbers: {}", list(&stats.numbers));
+    println!("Most drawn {}: {}", game.bonus_name, list(&stats.stars));
+    Ok(())
+}
+
+async fn reparse(storage: &dyn Storage, json: bool) -> Result<()> {
+    let summary = archive::reparse(storage).await?;
+    if json {
+        print_json(&summary);
+        return Ok(());
+    }
+    for page in &summary.pages {
+        println!(
+            "{} [{}]: {} rows, {} parsed, {} rejected{}",
+            page.url,
+            &page.content_sha256[..12],
+            page.rows_seen,
+            page.rows_parsed,
+            page.rejected.len(),
+            if page.markup_changed { " (markup changed)" } else { "" }
+        );
+        for rejected in &page.rejected {
+            println!("  row {}: {}", rejected.row, rejected.reason);
+        }
+    }
+    println!(
+        "Reparsed {} archived page(s), {} draw(s) written.",
+        summary.pages.len(),
+        summary.draws_written
+    );
+    Ok(())
+}
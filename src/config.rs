@@ -0,0 +1,488 @@
+//! Runtime settings. Values are layered, each source overriding the previous
+//! one: built-in defaults, a TOML config file, environment variables, then
+//! command-line flags.
+
+use crate::error::{AppError, Result};
+use crate::fetcher::{FetcherConfig, HISTORY_YEARS};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub fetch: FetchConfig,
+    pub log: LogConfig,
+    pub notify: NotifyConfig,
+    pub webhooks: WebhookConfig,
+    pub bot: BotConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// `postgres://...` or `sqlite:...`
+    pub url: String,
+    pub max_connections: u32,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            url: String::new(),
+            max_connections: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FetchConfig {
+    /// Cron expression (with seconds) for the scheduled fetch, in UTC.
+    pub schedule: String,
+    pub years: Vec<i32>,
+    pub base_url: String,
+    pub user_agent: String,
+    pub timeout_secs: u64,
+    pub connect_timeout_secs: u64,
+    pub max_retries: u32,
+    pub backoff_base_ms: u64,
+    pub backoff_max_ms: u64,
+    pub min_request_interval_ms: u64,
+    /// Age of the latest stored draw after which `/status` reports stale data.
+    pub stale_after_hours: u64,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        let fetcher = FetcherConfig::default();
+        FetchConfig {
+            // Every Wednesday and Saturday at 23:00 UTC
+            schedule: "0 0 23 * * Wed,Sat".to_string(),
+            years: HISTORY_YEARS.to_vec(),
+            base_url: fetcher.base_url,
+            user_agent: fetcher.user_agent,
+            timeout_secs: fetcher.timeout.as_secs(),
+            connect_timeout_secs: fetcher.connect_timeout.as_secs(),
+            max_retries: fetcher.max_retries,
+            backoff_base_ms: fetcher.backoff_base.as_millis() as u64,
+            backoff_max_ms: fetcher.backoff_max.as_millis() as u64,
+            min_request_interval_ms: fetcher.min_request_interval.as_millis() as u64,
+            // Draws are three or four days apart and fetched the next evening
+            stale_after_hours: 120,
+        }
+    }
+}
+
+impl FetchConfig {
+    pub fn fetcher_config(&self) -> FetcherConfig {
+        FetcherConfig {
+            base_url: self.base_url.clone(),
+            user_agent: self.user_agent.clone(),
+            timeout: Duration::from_secs(self.timeout_secs),
+            connect_timeout: Duration::from_secs(self.connect_timeout_secs),
+            max_retries: self.max_retries,
+            backoff_base: Duration::from_millis(self.backoff_base_ms),
+            backoff_max: Duration::from_millis(self.backoff_max_ms),
+            min_request_interval: Duration::from_millis(self.min_request_interval_ms),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Human-readable lines for terminals.
+    #[default]
+    Text,
+    /// One JSON object per line, for log aggregators.
+    Json,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    /// Filter directives such as `info` or `info,euromillions_bot=debug`.
+    /// `RUST_LOG` takes precedence when set.
+    pub level: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            format: LogFormat::Text,
+            level: "info".to_string(),
+        }
+    }
+}
+
+/// Result notifications. Channels themselves are stored per user; this only
+/// holds the message templates and the credentials shared by every user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    pub timeout_secs: u64,
+    /// Accept http and private or local hosts as channel URLs. For local
+    /// development only: any API caller could then reach internal services.
+    pub allow_private_urls: bool,
+    pub templates: TemplateConfig,
+    pub smtp: SmtpConfig,
+    pub telegram: TelegramConfig,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        NotifyConfig {
+            timeout_secs: 10,
+            allow_private_urls: false,
+            templates: TemplateConfig::default(),
+            smtp: SmtpConfig::default(),
+            telegram: TelegramConfig::default(),
+        }
+    }
+}
+
+/// Message templates. `{name}` placeholders are replaced when rendering; see
+/// `notify::render` for the available names.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TemplateConfig {
+    /// Email subject. Placeholders: `{date}`.
+    pub subject: String,
+    /// First line of every message. Placeholders: `{date}`, `{numbers}`, `{stars}`.
+    pub draw: String,
+    /// One line per grid of the user. Placeholders: `{numbers}`, `{stars}`,
+    /// `{matched_numbers}`, `{matched_stars}` (counts), `{prize}`.
+    pub grid: String,
+}
+
+impl Default for TemplateConfig {
+    fn default() -> Self {
+        TemplateConfig {
+            subject: "EuroMillions results for {date}".to_string(),
+            draw: "EuroMillions draw of {date}: {numbers} ★ {stars}".to_string(),
+            grid: "{numbers} ★ {stars}: {matched_numbers} number(s), {matched_stars} star(s) — {prize}"
+                .to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpTls {
+    /// Plain connection upgraded with STARTTLS.
+    #[default]
+    Starttls,
+    /// TLS from the first byte (usually port 465).
+    Tls,
+    /// Unencrypted, for local relays and tests only.
+    None,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmtpConfig {
+    /// Email channels are disabled while this is unset.
+    pub host: Option<String>,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub tls: SmtpTls,
+}
+
+impl Default for SmtpConfig {
+    fn default() -> Self {
+        SmtpConfig {
+            host: None,
+            port: 587,
+            username: None,
+            password: None,
+            from: "EuroMillions Bot <noreply@localhost>".to_string(),
+            tls: SmtpTls::Starttls,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelegramConfig {
+    pub api_url: String,
+    /// Telegram channels are disabled while this is unset.
+    pub bot_token: Option<String>,
+}
+
+impl Default for TelegramConfig {
+    fn default() -> Self {
+        TelegramConfig {
+            api_url: "https://api.telegram.org".to_string(),
+            bot_token: None,
+        }
+    }
+}
+
+/// Delivery of outbound webhook events.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub timeout_secs: u64,
+    /// Accept http and private or local hosts as subscription URLs. For
+    /// local development only.
+    pub allow_private_urls: bool,
+    /// Attempts per delivery before it is marked failed.
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled after every failed attempt.
+    pub backoff_base_secs: u64,
+    pub backoff_max_secs: u64,
+    /// How often the server looks for due deliveries.
+    pub poll_interval_secs: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            timeout_secs: 10,
+            allow_private_urls: false,
+            max_attempts: 6,
+            backoff_base_secs: 30,
+            backoff_max_secs: 3600,
+            poll_interval_secs: 10,
+        }
+    }
+}
+
+/// Chat bot front-ends. Telegram uses the token from `notify.telegram`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotConfig {
+    /// Answer commands sent to the Telegram bot, using long polling.
+    pub telegram: bool,
+    /// Seconds a `getUpdates` call waits for new messages.
+    pub poll_timeout_secs: u64,
+    /// Hex Ed25519 public key of the Discord application. The interactions
+    /// endpoint answers only while this is set.
+    pub discord_public_key: Option<String>,
+}
+
+impl Default for BotConfig {
+    fn default() -> Self {
+        BotConfig {
+            telegram: false,
+            poll_timeout_secs: 30,
+            discord_public_key: None,
+        }
+    }
+}
+
+/// Command-line flags overriding the other configuration sources.
+#[derive(Debug, Clone, Default, Args)]
+pub struct ConfigArgs {
+    /// TOML config file [env: EUROMILLIONS_CONFIG]
+    #[arg(long, short = 'c', global = true)]
+    pub config: Option<PathBuf>,
+    /// Address to bind the HTTP server to [env: BIND_HOST]
+    #[arg(long, global = true)]
+    pub host: Option<String>,
+    /// Port to bind the HTTP server to [env: PORT]
+    #[arg(long, global = true)]
+    pub port: Option<u16>,
+    /// postgres:// or sqlite: connection URL [env: DATABASE_URL]
+    #[arg(long, global = true)]
+    pub database_url: Option<String>,
+    /// Size of the database connection pool [env: DATABASE_MAX_CONNECTIONS]
+    #[arg(long, global = true)]
+    pub max_connections: Option<u32>,
+    /// Log output format [env: LOG_FORMAT]
+    #[arg(long, global = true, value_enum)]
+    pub log_format: Option<LogFormat>,
+}
+
+impl Config {
+    /// Loads the configuration from every source. `.env` is read first so its
+    /// variables count as environment variables.
+    pub fn load(args: &ConfigArgs) -> Result<Config> {
+        dotenvy::dotenv().ok();
+
+        let path = args
+            .config
+            .clone()
+            .or_else(|| std::env::var_os("EUROMILLIONS_CONFIG").map(PathBuf::from));
+        let mut config = match path {
+            Some(path) => Config::from_file(&path)?,
+            None => Config::default(),
+        };
+        config.apply_env(|key| std::env::var(key).ok())?;
+        config.apply_args(args);
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| AppError::Config(format!("cannot read {}: {}", path.display(), e)))?;
+        Config::from_toml(&text)
+            .map_err(|e| AppError::Config(format!("invalid config file {}: {}", path.display(), e)))
+    }
+
+    pub fn from_toml(text: &str) -> std::result::Result<Config, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    /// Overrides settings from environment variables, looked up through
+    /// `lookup` so tests need not touch the process environment.
+    pub fn apply_env(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<()> {
+        fn parse<T: std::str::FromStr>(key: &str, value: String) -> Result<T> {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| AppError::Config(format!("invalid value for {}: '{}'", key, value)))
+        }
+
+        if let Some(v) = lookup("BIND_HOST") {
+            self.server.host = v;
+        }
+        if let Some(v) = lookup("PORT") {
+            self.server.port = parse("PORT", v)?;
+        }
+        if let Some(v) = lookup("DATABASE_URL") {
+            self.database.url = v;
+        }
+        if let Some(v) = lookup("DATABASE_MAX_CONNECTIONS") {
+            self.database.max_connections = parse("DATABASE_MAX_CONNECTIONS", v)?;
+        }
+        if let Some(v) = lookup("FETCH_SCHEDULE") {
+            self.fetch.schedule = v;
+        }
+        if let Some(v) = lookup("FETCH_YEARS") {
+            self.fetch.years = v
+                .split(',')
+                .map(|year| parse("FETCH_YEARS", year.to_string()))
+                .collect::<Result<_>>()?;
+        }
+        if let Some(v) = lookup("FETCH_BASE_URL") {
+            self.fetch.base_url = v;
+        }
+        if let Some(v) = lookup("FETCH_USER_AGENT") {
+            self.fetch.user_agent = v;
+        }
+        if let Some(v) = lookup("FETCH_TIMEOUT_SECS") {
+            self.fetch.timeout_secs = parse("FETCH_TIMEOUT_SECS", v)?;
+        }
+        if let Some(v) = lookup("FETCH_MAX_RETRIES") {
+            self.fetch.max_retries = parse("FETCH_MAX_RETRIES", v)?;
+        }
+        if let Some(v) = lookup("FETCH_MIN_REQUEST_INTERVAL_MS") {
+            self.fetch.min_request_interval_ms = parse("FETCH_MIN_REQUEST_INTERVAL_MS", v)?;
+        }
+        if let Some(v) = lookup("FETCH_STALE_AFTER_HOURS") {
+            self.fetch.stale_after_hours = parse("FETCH_STALE_AFTER_HOURS", v)?;
+        }
+        if let Some(v) = lookup("SMTP_HOST") {
+            self.notify.smtp.host = Some(v);
+        }
+        if let Some(v) = lookup("SMTP_PORT") {
+            self.notify.smtp.port = parse("SMTP_PORT", v)?;
+        }
+        if let Some(v) = lookup("SMTP_USERNAME") {
+            self.notify.smtp.username = Some(v);
+        }
+        if let Some(v) = lookup("SMTP_PASSWORD") {
+            self.notify.smtp.password = Some(v);
+        }
+        if let Some(v) = lookup("SMTP_FROM") {
+            self.notify.smtp.from = v;
+        }
+        if let Some(v) = lookup("TELEGRAM_BOT_TOKEN") {
+            self.notify.telegram.bot_token = Some(v);
+        }
+        if let Some(v) = lookup("BOT_TELEGRAM") {
+            self.bot.telegram = parse("BOT_TELEGRAM", v)?;
+        }
+        if let Some(v) = lookup("DISCORD_PUBLIC_KEY") {
+            self.bot.discord_public_key = Some(v);
+        }
+        if let Some(v) = lookup("LOG_FORMAT") {
+            self.log.format = match v.trim() {
+                "text" => LogFormat::Text,
+                "json" => LogFormat::Json,
+                _ => return Err(AppError::Config(format!("invalid value for LOG_FORMAT: '{}'", v))),
+            };
+        }
+        Ok(())
+    }
+
+    pub fn apply_args(&mut self, args: &ConfigArgs) {
+        if let Some(host) = &args.host {
+            self.server.host = host.clone();
+        }
+        if let Some(port) = args.port {
+            self.server.port = port;
+        }
+        if let Some(url) = &args.database_url {
+            self.database.url = url.clone();
+        }
+        if let Some(max) = args.max_connections {
+            self.database.max_connections = max;
+        }
+        if let Some(format) = args.log_format {
+            self.log.format = format;
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.database.url.is_empty() {
+            return Err(AppError::Config("DATABASE_URL must be set".to_string()));
+        }
+        if self.database.max_connections == 0 {
+            return Err(AppError::Config("database.max_connections must be at least 1".to_string()));
+        }
+        if cron::Schedule::from_str(&self.fetch.schedule).is_err() {
+            return Err(AppError::Config(format!(
+                "fetch.schedule is not a valid cron expression: '{}'",
+                self.fetch.schedule
+            )));
+        }
+        if self.fetch.years.is_empty() {
+            return Err(AppError::Config("fetch.years must not be empty".to_string()));
+        }
+        if self.webhooks.max_attempts == 0 || self.webhooks.poll_interval_secs == 0 {
+            return Err(AppError::Config(
+                "webhooks.max_attempts and webhooks.poll_interval_secs must be at least 1".to_string(),
+            ));
+        }
+        if let Some(key) = &self.bot.discord_public_key {
+            crate::bot::discord::parse_public_key(key)?;
+        }
+        if self.bot.telegram && self.notify.telegram.bot_token.is_none() {
+            return Err(AppError::Config("bot.telegram needs TELEGRAM_BOT_TOKEN".to_string()));
+        }
+        if self.notify.smtp.host.is_some() && self.notify.smtp.from.parse::<lettre::message::Mailbox>().is_err() {
+            return Err(AppError::Config(format!(
+                "notify.smtp.from is not a valid address: '{}'",
+                self.notify.smtp.from
+            )));
+        }
+        Ok(())
+    }
+}
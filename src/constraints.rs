@@ -0,0 +1,415 @@
+//! Constraints on the main numbers of generated lines, and the solver that
+//! builds lines meeting them from a strategy's pool. Stars and bonus balls
+//! can only be forced in.
+//!
+//! The solver picks numbers in ascending order and, before each pick, checks
+//! that the rest of the line can still be completed: enough odd, even, low
+//! and high numbers left, every decade still reachable, the sum still within
+//! range, no forced number skipped. Dead ends are cut as soon as they
+//! appear instead of drawing whole lines and throwing them away, and a
+//! search that runs out of options proves the constraints cannot be met.
+
+use crate::error::{AppError, Result};
+use crate::games::GameRules;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Nodes a single search may visit before giving up on a line.
+const SEARCH_BUDGET: usize = 1_000_000;
+
+/// Nodes all the searches of one generation may visit together, whatever
+/// the number of lines and of candidates drawn per line.
+pub const GENERATION_BUDGET: usize = 4 * SEARCH_BUDGET;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, clap::Args)]
+pub struct Constraints {
+    /// Smallest sum of the numbers
+    #[arg(long)]
+    pub sum_min: Option<i32>,
+    /// Largest sum of the numbers
+    #[arg(long)]
+    pub sum_max: Option<i32>,
+    /// How many numbers are odd, the others being even
+    #[arg(long)]
+    pub odd: Option<usize>,
+    /// How many numbers are in the lower half of the range (1-25 for
+    /// EuroMillions), the others being high
+    #[arg(long)]
+    pub low: Option<usize>,
+    /// Longest run of consecutive numbers allowed; 1 forbids neighbours
+    #[arg(long)]
+    pub max_consecutive: Option<usize>,
+    /// At least one number in each group of ten (1-10, 11-20, ...)
+    #[arg(long)]
+    #[serde(default)]
+    pub every_decade: bool,
+    /// Numbers every line must hold, comma separated
+    #[arg(long, value_delimiter = ',')]
+    #[serde(default)]
+    pub include: Vec<i32>,
+    /// Numbers no line may hold, comma separated
+    #[arg(long, value_delimiter = ',')]
+    #[serde(default)]
+    pub exclude: Vec<i32>,
+    /// Stars or other bonus balls every line must hold, comma separated
+    #[arg(long, value_delimiter = ',')]
+    #[serde(default)]
+    pub include_stars: Vec<i32>,
+}
+
+fn join(numbers: &[i32]) -> String {
+    numbers.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+impl fmt::Display for Constraints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        match (self.sum_min, self.sum_max) {
+            (Some(min), Some(max)) => parts.push(format!("sum {}-{}", min, max)),
+            (Some(min), None) => parts.push(format!("sum at least {}", min)),
+            (None, Some(max)) => parts.push(format!("sum at most {}", max)),
+            (None, None) => {}
+        }
+        if let Some(odd) = self.odd {
+            parts.push(format!("{} odd", odd));
+        }
+        if let Some(low) = self.low {
+            parts.push(format!("{} low", low));
+        }
+        if let Some(max) = self.max_consecutive {
+            parts.push(format!("at most {} consecutive", max));
+        }
+        if self.every_decade {
+            parts.push("one per decade".to_string());
+        }
+        if !self.include.is_empty() {
+            parts.push(format!("including {}", join(&self.include)));
+        }
+        if !self.exclude.is_empty() {
+            parts.push(format!("excluding {}", join(&self.exclude)));
+        }
+        if !self.include_stars.is_empty() {
+            parts.push(format!("including stars {}", join(&self.include_stars)));
+        }
+        if parts.is_empty() {
+            parts.push("none".to_string());
+        }
+        f.write_str(&parts.join(", "))
+    }
+}
+
+impl Constraints {
+    pub fn is_empty(&self) -> bool {
+        *self == Constraints::default()
+    }
+
+    /// Rejects constraints that contradict themselves or the game, with the
+    /// reason. Constraints passing this can still be unsatisfiable together;
+    /// the solver reports those.
+    pub fn validate(&self, game: &GameRules) -> Result<()> {
+        let count = game.numbers.count;
+        let invalid = |message: String| Err(AppError::Validation(message));
+
+        for n in self.include.iter().chain(&self.exclude) {
+            if !(1..=game.numbers.max).contains(n) {
+                return invalid(format!("{} is not a {} number (1-{})", n, game.name, game.numbers.max));
+            }
+        }
+        let mut include = self.include.clone();
+        include.sort();
+        include.dedup();
+        if include.len() != self.include.len() {
+            return invalid("included numbers must be distinct".to_string());
+        }
+        if include.len() > count {
+            return invalid(format!("cannot include {} numbers in a line of {}", include.len(), count));
+        }
+        if let Some(n) = include.iter().find(|n| self.exclude.contains(n)) {
+            return invalid(format!("{} is both included and excluded", n));
+        }
+
+        let bonus = &game.bonus;
+        if let Some(n) = self.include_stars.iter().find(|n| !(1..=bonus.max).contains(*n)) {
+            return invalid(format!("{} is not a {} star or bonus ball (1-{})", n, game.name, bonus.max));
+        }
+        let mut stars = self.include_stars.clone();
+        stars.sort();
+        stars.dedup();
+        if stars.len() != self.include_stars.len() {
+            return invalid("included stars must be distinct".to_string());
+        }
+        if stars.len() > bonus.count {
+            return invalid(format!("cannot include {} stars in a line of {}", stars.len(), bonus.count));
+        }
+
+        let half = game.numbers.max / 2;
+        let splits = [
+            ("odd", "even", self.odd, include.iter().filter(|n| *n % 2 == 1).count()),
+            ("low", "high", self.low, include.iter().filter(|n| **n <= half).count()),
+        ];
+        for (name, other, wanted, included) in splits {
+            let Some(wanted) = wanted else { continue };
+            if wanted > count {
+                return invalid(format!("cannot have {} {} numbers in a line of {}", wanted, name, count));
+            }
+            if included > wanted {
+                return invalid(format!("{} included numbers are {}, only {} allowed", included, name, wanted));
+            }
+            if include.len() - included > count - wanted {
+                return invalid(format!(
+                    "{} included numbers are {}, only {} allowed",
+                    include.len() - included,
+                    other,
+                    count - wanted
+                ));
+            }
+        }
+
+        if self.max_consecutive == Some(0) {
+            return invalid("max_consecutive must be at least 1".to_string());
+        }
+        let decades = decade(game.numbers.max) + 1;
+        if self.every_decade && decades > count {
+            return invalid(format!("{} decades cannot all be covered by {} numbers", decades, count));
+        }
+
+        if let (Some(min), Some(max)) = (self.sum_min, self.sum_max) {
+            if min > max {
+                return invalid(format!("sum_min ({}) is above sum_max ({})", min, max));
+            }
+            // The parity of the sum follows from the number of odd numbers
+            if let Some(odd) = self.odd {
+                if min == max && min.rem_euclid(2) as usize != odd % 2 {
+                    return invalid(format!("{} odd numbers cannot add up to {}", odd, min));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Groups of ten: 1-10 is 0, 11-20 is 1...
+fn decade(n: i32) -> usize {
+    ((n - 1) / 10) as usize
+}
+
+/// Builds lines meeting a set of constraints from a pool of numbers.
+#[derive(Debug)]
+pub struct Solver<'a> {
+    count: usize,
+    half: i32,
+    decades: usize,
+    /// Sorted pool with the included numbers added and the excluded removed.
+    candidates: Vec<i32>,
+    include: Vec<i32>,
+    constraints: &'a Constraints,
+}
+
+/// The line being built.
+#[derive(Debug, Clone)]
+struct Partial {
+    numbers: Vec<i32>,
+    sum: i32,
+    odd: usize,
+    low: usize,
+    /// Bit per decade covered.
+    decades: u32,
+    /// Length of the run of consecutive numbers ending the line.
+    run: usize,
+}
+
+impl<'a> Solver<'a> {
+    pub fn new(game: &GameRules, pool: &[i32], constraints: &'a Constraints) -> Result<Self> {
+        constraints.validate(game)?;
+        let mut candidates: Vec<i32> = pool
+            .iter()
+            .chain(&constraints.include)
+            .copied()
+            .filter(|n| !constraints.exclude.contains(n))
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+        let mut include = constraints.include.clone();
+        include.sort();
+
+        let solver = Solver {
+            count: game.numbers.count,
+            half: game.numbers.max / 2,
+            decades: decade(game.numbers.max) + 1,
+            candidates,
+            include,
+            constraints,
+        };
+        if !solver.feasible(&solver.empty(), 0) {
+            return Err(solver.unsatisfiable());
+        }
+        Ok(solver)
+    }
+
+    /// The included stars completed with random ones from `pool`, sorted.
+    pub fn stars(&self, count: usize, pool: &[i32], rng: &mut impl Rng) -> Vec<i32> {
+        let include = &self.constraints.include_stars;
+        let mut others: Vec<i32> = pool.iter().copied().filter(|n| !include.contains(n)).collect();
+        others.shuffle(rng);
+        let mut stars: Vec<i32> = include.iter().chain(&others).take(count).copied().collect();
+        stars.sort();
+        stars
+    }
+
+    fn empty(&self) -> Partial {
+        Partial {
+            numbers: Vec::with_capacity(self.count),
+            sum: 0,
+            odd: 0,
+            low: 0,
+            decades: 0,
+            run: 0,
+        }
+    }
+
+    fn unsatisfiable(&self) -> AppError {
+        AppError::Validation(format!(
+            "no line of {} numbers from the {} candidates meets the constraints ({})",
+            self.count,
+            self.candidates.len(),
+            self.constraints
+        ))
+    }
+
+    /// A random line meeting the constraints, sorted.
+    pub fn solve(&self, rng: &mut impl Rng) -> Result<Vec<i32>> {
+        let mut budget = SEARCH_BUDGET;
+        self.solve_within(rng, &mut budget)
+    }
+
+    /// Like [`Solver::solve`], taking the nodes visited out of `budget`,
+    /// shared by several searches. A single search still stops at its own
+    /// budget.
+    pub fn solve_within(&self, rng: &mut impl Rng, budget: &mut usize) -> Result<Vec<i32>> {
+        let allowed = (*budget).min(SEARCH_BUDGET);
+        let mut left = allowed;
+        let found = self.search(self.empty(), 0, rng, &mut left);
+        *budget -= allowed - left;
+        match found {
+            Some(numbers) => Ok(numbers),
+            None if left == 0 => Err(AppError::Validation(format!(
+                "the constraints ({}) are too tight to find a line",
+                self.constraints
+            ))),
+            None => Err(self.unsatisfiable()),
+        }
+    }
+
+    fn search(&self, line: Partial, from: usize, rng: &mut impl Rng, budget: &mut usize) -> Option<Vec<i32>> {
+        if line.numbers.len() == self.count {
+            return Some(line.numbers);
+        }
+        let remaining = self.count - line.numbers.len();
+        // Skipping past a forced number would lose it
+        let next_forced = self.include.iter().find(|n| !line.numbers.contains(n)).copied();
+
+        // Weighting each number by the lines it can start, ignoring the
+        // constraints, keeps unconstrained draws uniform over the pool
+        let mut order: Vec<(f64, usize)> = (from..self.candidates.len())
+            .filter(|&i| next_forced.is_none_or(|forced| self.candidates[i] <= forced))
+            .map(|i| (binomial(self.candidates.len() - i - 1, remaining - 1), i))
+            .filter(|(weight, _)| *weight > 0.0)
+            .map(|(weight, i)| (rng.gen::<f64>().powf(1.0 / weight), i))
+            .collect();
+        order.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        for (_, index) in order {
+            if *budget == 0 {
+                return None;
+            }
+            *budget -= 1;
+            let Some(next) = self.push(&line, self.candidates[index]) else {
+                continue;
+            };
+            if self.feasible(&next, index + 1) {
+                if let Some(numbers) = self.search(next, index + 1, rng, budget) {
+                    return Some(numbers);
+                }
+            }
+        }
+        None
+    }
+
+    /// `line` with `n` appended, or `None` if that makes too long a run.
+    fn push(&self, line: &Partial, n: i32) -> Option<Partial> {
+        let mut next = line.clone();
+        next.run = if line.numbers.last() == Some(&(n - 1)) { line.run + 1 } else { 1 };
+        if self.constraints.max_consecutive.is_some_and(|max| next.run > max) {
+            return None;
+        }
+        next.numbers.push(n);
+        next.sum += n;
+        next.odd += (n % 2 == 1) as usize;
+        next.low += (n <= self.half) as usize;
+        next.decades |= 1 << decade(n);
+        Some(next)
+    }
+
+    /// Whether `line` can still be completed from `candidates[from..]`.
+    /// Necessary conditions only: the search backtracks on the rest.
+    fn feasible(&self, line: &Partial, from: usize) -> bool {
+        let remaining = self.count - line.numbers.len();
+        let rest = &self.candidates[from..];
+        if rest.len() < remaining {
+            return false;
+        }
+        let missing_forced = self.include.iter().filter(|n| !line.numbers.contains(n));
+        let mut missing_forced_count = 0;
+        for n in missing_forced {
+            if rest.binary_search(n).is_err() {
+                return false;
+            }
+            missing_forced_count += 1;
+        }
+        if missing_forced_count > remaining {
+            return false;
+        }
+
+        let c = self.constraints;
+        let splits = [
+            (c.odd, line.odd, rest.iter().filter(|n| *n % 2 == 1).count()),
+            (c.low, line.low, rest.iter().filter(|n| **n <= self.half).count()),
+        ];
+        for (wanted, have, available) in splits {
+            let Some(wanted) = wanted else { continue };
+            let Some(needed) = wanted.checked_sub(have) else { return false };
+            // The others of the split come from what is left
+            if needed > remaining || needed > available || remaining - needed > rest.len() - available {
+                return false;
+            }
+        }
+
+        if c.every_decade {
+            let reachable = rest.iter().fold(line.decades, |mask, n| mask | 1 << decade(*n));
+            let uncovered = self.decades - line.decades.count_ones() as usize;
+            if reachable.count_ones() as usize != self.decades || uncovered > remaining {
+                return false;
+            }
+        }
+
+        if c.sum_min.is_some() || c.sum_max.is_some() {
+            let lowest: i32 = rest[..remaining].iter().sum();
+            let highest: i32 = rest[rest.len() - remaining..].iter().sum();
+            if c.sum_max.is_some_and(|max| line.sum + lowest > max)
+                || c.sum_min.is_some_and(|min| line.sum + highest < min)
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    (0..k).fold(1.0, |acc, i| acc * (n - i) as f64 / (i + 1) as f64)
+}
@@ -1,44 +1,280 @@
-use sqlx::postgres::{PgPool, PgPoolOptions};
-use std::env;
-use dotenvy::dotenv;
-use crate::models::NewDraw;
-
-pub async fn init_db() -> PgPool {
-    dotenv().ok();
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    
-    PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&database_url)
-        .await
-        .expect("Failed to create pool")
-}
-
-pub async fn upsert_draw(pool: &PgPool, draw: NewDraw) -> Result<(), sqlx::Error> {
-    sqlx::query!(
+//! Postgres queries, checked at compile time against the schema in
+//! `migrations/`. Most callers go through `storage::PgStorage`.
+
+use chrono::{NaiveDate, NaiveDateTime};
+use sqlx::postgres::{PgConnection, PgPool};
+use crate::error::Result;
+use crate::models::{
+    ArchivedPage, BallFrequency, Budget, DeliveryAttempt, Draw, DrawPrize, FetchRun, FinishedFetchRun,
+    GenerationBatch, GenerationProfile, Grid, GridPlay, NewArchivedPage, NewDraw, NewDrawPrize, NewFetchRun,
+    NewGenerationBatch, NewGenerationProfile, NewGrid, NewNotificationChannel, NewRaffleCode, NewWebhook,
+    NotificationChannel, PendingDelivery, RaffleCode, SpendingCap, TicketAddon, TicketPrice, User, Webhook,
+    WebhookDelivery,
+};
+use crate::webhooks::Event;
+
+/// Inserts a draw unless the game already has one for that date. Returns
+/// whether a row was inserted.
+pub async fn upsert_draw(pool: &PgPool, draw: NewDraw) -> Result<bool> {
+    let mut tx = pool.begin().await?;
+    let inserted = sqlx::query_as!(
+        Draw,
         r#"
-        INSERT INTO draws (date, numbers, stars)
-        VALUES ($1, $2, $3)
-        ON CONFLICT (date) DO NOTHING
+        INSERT INTO draws (game, date, numbers, stars)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (game, date) DO NOTHING
+        RETURNING id, game, date, numbers, stars
+        "#,
+        draw.game,
+        draw.date,
+        &draw.numbers,
+        &draw.stars
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    // Queued in the same transaction so a stored draw is never missed
+    if let Some(draw) = &inserted {
+        let data = serde_json::to_value(draw).expect("draws serialize to JSON");
+        emit_event_in(&mut tx, Event::DrawCreated.as_str(), &data).await?;
+    }
+    tx.commit().await?;
+    Ok(inserted.is_some())
+}
+
+/// Inserts a draw or overwrites the stored numbers for that date. Returns
+/// whether a row was inserted or changed. Used when rebuilding draws from the
+/// page archive.
+pub async fn replace_draw(pool: &PgPool, draw: NewDraw) -> Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO draws (game, date, numbers, stars)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (game, date) DO UPDATE
+        SET numbers = EXCLUDED.numbers, stars = EXCLUDED.stars
+        WHERE draws.numbers <> EXCLUDED.numbers OR draws.stars <> EXCLUDED.stars
         "#,
+        draw.game,
         draw.date,
         &draw.numbers,
         &draw.stars
     )
     .execute(pool)
     .await?;
-    Ok(())
+    Ok(result.rows_affected() == 1)
+}
+
+/// Inserts all grids in a single transaction and returns the stored rows.
+/// Either every grid is saved or none is.
+pub async fn insert_grids(pool: &PgPool, grids: &[NewGrid]) -> Result<Vec<Grid>> {
+    let mut tx = pool.begin().await?;
+    let saved = insert_grid_rows(&mut tx, grids, None).await?;
+    tx.commit().await?;
+    Ok(saved)
+}
+
+async fn insert_grid_rows(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    grids: &[NewGrid],
+    batch_id: Option<i32>,
+) -> Result<Vec<Grid>> {
+    let mut saved = Vec::with_capacity(grids.len());
+    for grid in grids {
+        let row = sqlx::query_as!(
+            Grid,
+            r#"
+            INSERT INTO grids (game, draw_date, numbers, stars, user_id, cost_cents, addons, batch_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id, game, draw_date, numbers, stars, created_at, user_id, cost_cents, addons, status, played_at, batch_id
+            "#,
+            grid.game,
+            grid.draw_date,
+            &grid.numbers,
+            &grid.stars,
+            grid.user_id,
+            grid.cost_cents,
+            &grid.addons,
+            batch_id
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+        saved.push(row);
+    }
+    Ok(saved)
+}
+
+/// Inserts the batch and its grids in a single transaction. Nothing is
+/// stored when another batch holds the idempotency key.
+pub async fn insert_generation_batch(
+    pool: &PgPool,
+    batch: &NewGenerationBatch,
+    grids: &[NewGrid],
+) -> Result<Option<Vec<Grid>>> {
+    let mut tx = pool.begin().await?;
+    let batch_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO generation_batches (idempotency_key, game, user_id, strategy, params)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (idempotency_key) DO NOTHING
+        RETURNING id
+        "#,
+        batch.idempotency_key,
+        batch.game,
+        batch.user_id,
+        batch.strategy,
+        batch.params
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(batch_id) = batch_id else {
+        return Ok(None);
+    };
+    let saved = insert_grid_rows(&mut tx, grids, Some(batch_id)).await?;
+    tx.commit().await?;
+    Ok(Some(saved))
+}
+
+pub async fn get_generation_batch(pool: &PgPool, idempotency_key: &str) -> Result<Option<GenerationBatch>> {
+    let batch = sqlx::query_as!(
+        GenerationBatch,
+        r#"
+        SELECT id, idempotency_key, game, user_id, strategy, params, created_at
+        FROM generation_batches
+        WHERE idempotency_key = $1
+        "#,
+        idempotency_key
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(batch)
+}
+
+pub async fn batch_grids(pool: &PgPool, batch_id: i32) -> Result<Vec<Grid>> {
+    let grids = sqlx::query_as!(
+        Grid,
+        "SELECT id, game, draw_date, numbers, stars, created_at, user_id, cost_cents, addons, status, played_at, batch_id FROM grids WHERE batch_id = $1 ORDER BY id",
+        batch_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(grids)
+}
+
+pub async fn recent_grids(pool: &PgPool, limit: i64, status: Option<&str>) -> Result<Vec<Grid>> {
+    let grids = sqlx::query_as!(
+        Grid,
+        r#"
+        SELECT id, game, draw_date, numbers, stars, created_at, user_id, cost_cents, addons, status, played_at, batch_id
+        FROM grids
+        WHERE $2::TEXT IS NULL OR status = $2
+        ORDER BY created_at DESC, id DESC
+        LIMIT $1
+        "#,
+        limit,
+        status
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(grids)
+}
+
+pub async fn get_grids(pool: &PgPool, ids: &[i32]) -> Result<Vec<Grid>> {
+    let grids = sqlx::query_as!(
+        Grid,
+        "SELECT id, game, draw_date, numbers, stars, created_at, user_id, cost_cents, addons, status, played_at, batch_id FROM grids WHERE id = ANY($1) ORDER BY id",
+        ids
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(grids)
+}
+
+/// Moves the grids among `ids` whose status is in `from` to `status`.
+/// `played_at` is set when they become played and cleared when they go back
+/// to generated.
+pub async fn set_grid_status(pool: &PgPool, ids: &[i32], from: &[&str], status: &str) -> Result<Vec<Grid>> {
+    let mut grids = sqlx::query_as!(
+        Grid,
+        r#"
+        UPDATE grids
+        SET status = $3,
+            played_at = CASE $3
+                WHEN 'played' THEN COALESCE(played_at, NOW())
+                WHEN 'generated' THEN NULL
+                ELSE played_at
+            END
+        WHERE id = ANY($1) AND status = ANY($2)
+        RETURNING id, game, draw_date, numbers, stars, created_at, user_id, cost_cents, addons, status, played_at, batch_id
+        "#,
+        ids,
+        from as &[&str],
+        status
+    )
+    .fetch_all(pool)
+    .await?;
+    grids.sort_by_key(|g| g.id);
+    Ok(grids)
+}
+
+/// Plays the grids and sums the caps in one transaction. The users' rows
+/// are locked first, so concurrent plays by a user are checked one after
+/// the other.
+pub async fn play_grids(pool: &PgPool, plays: &[GridPlay], caps: &[SpendingCap]) -> Result<Vec<i64>> {
+    let mut tx = pool.begin().await?;
+    let mut users: Vec<i32> = caps.iter().map(|cap| cap.user_id).collect();
+    users.sort();
+    users.dedup();
+    sqlx::query!("SELECT id FROM users WHERE id = ANY($1) ORDER BY id FOR UPDATE", &users)
+        .fetch_all(&mut *tx)
+        .await?;
+    for play in plays {
+        sqlx::query!(
+            r#"
+            UPDATE grids
+            SET addons = $2, cost_cents = $3, status = 'played', played_at = COALESCE(played_at, NOW())
+            WHERE id = $1 AND status IN ('generated', 'played')
+            "#,
+            play.id,
+            &play.addons,
+            play.cost_cents
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    let mut sums = Vec::with_capacity(caps.len());
+    for cap in caps {
+        let spent = sqlx::query_scalar!(
+            r#"
+            SELECT COALESCE(SUM(cost_cents), 0)::BIGINT AS "spent!"
+            FROM grids
+            WHERE user_id = $1 AND game = ANY($2) AND draw_date BETWEEN $3 AND $4 AND played_at IS NOT NULL
+            "#,
+            cap.user_id,
+            &cap.games,
+            cap.from,
+            cap.to
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        sums.push(spent);
+    }
+    if caps.iter().zip(&sums).all(|(cap, spent)| *spent <= cap.limit_cents) {
+        tx.commit().await?;
+    }
+    Ok(sums)
 }
 
-pub async fn get_most_frequent_numbers(pool: &PgPool, limit: i64) -> Result<Vec<i32>, sqlx::Error> {
+pub async fn get_most_frequent_numbers(pool: &PgPool, game: &str, limit: i64) -> Result<Vec<i32>> {
     let recs = sqlx::query!(
         r#"
         SELECT unnest(numbers) as num, count(*) as freq
         FROM draws
+        WHERE game = $1
         GROUP BY num
         ORDER BY freq DESC
-        LIMIT $1
+        LIMIT $2
         "#,
+        game,
         limit
     )
     .fetch_all(pool)
@@ -47,15 +283,17 @@ pub async fn get_most_frequent_numbers(pool: &PgPool, limit: i64) -> Result<Vec<
     Ok(recs.into_iter().map(|r| r.num.unwrap_or(0)).collect())
 }
 
-pub async fn get_most_frequent_stars(pool: &PgPool, limit: i64) -> Result<Vec<i32>, sqlx::Error> {
+pub async fn get_most_frequent_stars(pool: &PgPool, game: &str, limit: i64) -> Result<Vec<i32>> {
     let recs = sqlx::query!(
         r#"
         SELECT unnest(stars) as num, count(*) as freq
         FROM draws
+        WHERE game = $1
         GROUP BY num
         ORDER BY freq DESC
-        LIMIT $1
+        LIMIT $2
         "#,
+        game,
         limit
     )
     .fetch_all(pool)
@@ -63,3 +301,843 @@ pub async fn get_most_frequent_stars(pool: &PgPool, limit: i64) -> Result<Vec<i3
 
     Ok(recs.into_iter().map(|r| r.num.unwrap_or(0)).collect())
 }
+
+/// Stores a draw's winning raffle codes in one transaction, skipping codes
+/// already stored.
+pub async fn add_raffle_codes(pool: &PgPool, draw_id: i32, codes: &[NewRaffleCode]) -> Result<usize> {
+    let mut tx = pool.begin().await?;
+    let mut inserted = 0;
+    for code in codes {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO raffle_codes (draw_id, kind, code)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (draw_id, kind, code) DO NOTHING
+            "#,
+            draw_id,
+            code.kind,
+            code.code
+        )
+        .execute(&mut *tx)
+        .await?;
+        inserted += result.rows_affected() as usize;
+    }
+    tx.commit().await?;
+    Ok(inserted)
+}
+
+pub async fn raffle_codes(pool: &PgPool, draw_id: i32) -> Result<Vec<RaffleCode>> {
+    let codes = sqlx::query_as!(
+        RaffleCode,
+        r#"
+        SELECT r.id, r.draw_id, d.date AS draw_date, r.kind, r.code
+        FROM raffle_codes r
+        JOIN draws d ON d.id = r.draw_id
+        WHERE r.draw_id = $1
+        ORDER BY r.kind, r.code
+        "#,
+        draw_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(codes)
+}
+
+pub async fn find_raffle_code(pool: &PgPool, kind: &str, code: &str) -> Result<Vec<RaffleCode>> {
+    let codes = sqlx::query_as!(
+        RaffleCode,
+        r#"
+        SELECT r.id, r.draw_id, d.date AS draw_date, r.kind, r.code
+        FROM raffle_codes r
+        JOIN draws d ON d.id = r.draw_id
+        WHERE r.kind = $1 AND r.code = $2
+        ORDER BY d.date
+        "#,
+        kind,
+        code
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(codes)
+}
+
+/// Inserts or overwrites a draw's tier payouts in one transaction. A payout
+/// without a winner count keeps the count already stored.
+pub async fn upsert_draw_prizes(pool: &PgPool, draw_id: i32, prizes: &[NewDrawPrize]) -> Result<usize> {
+    let mut tx = pool.begin().await?;
+    let mut written = 0;
+    for prize in prizes {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO draw_prizes (draw_id, tier, amount_cents, winners)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (draw_id, tier) DO UPDATE
+            SET amount_cents = EXCLUDED.amount_cents,
+                winners = COALESCE(EXCLUDED.winners, draw_prizes.winners)
+            "#,
+            draw_id,
+            prize.tier,
+            prize.amount_cents,
+            prize.winners
+        )
+        .execute(&mut *tx)
+        .await?;
+        written += result.rows_affected() as usize;
+    }
+    tx.commit().await?;
+    Ok(written)
+}
+
+pub async fn list_draw_prizes(pool: &PgPool, game: &str) -> Result<Vec<DrawPrize>> {
+    let prizes = sqlx::query_as!(
+        DrawPrize,
+        r#"
+        SELECT p.id, p.draw_id, d.date AS draw_date, p.tier, p.amount_cents, p.winners
+        FROM draw_prizes p
+        JOIN draws d ON d.id = p.draw_id
+        WHERE d.game = $1
+        ORDER BY d.date, p.tier
+        "#,
+        game
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(prizes)
+}
+
+pub async fn set_ticket_price(pool: &PgPool, price: &TicketPrice) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO ticket_prices (game, valid_from, price_cents)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (game, valid_from) DO UPDATE SET price_cents = EXCLUDED.price_cents
+        "#,
+        price.game,
+        price.valid_from,
+        price.price_cents
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn list_ticket_prices(pool: &PgPool, game: &str) -> Result<Vec<TicketPrice>> {
+    let prices = sqlx::query_as!(
+        TicketPrice,
+        "SELECT game, valid_from, price_cents FROM ticket_prices WHERE game = $1 ORDER BY valid_from",
+        game
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(prices)
+}
+
+pub async fn set_ticket_addon(pool: &PgPool, addon: &TicketAddon) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO ticket_addons (game, addon, valid_from, price_cents)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (game, addon, valid_from) DO UPDATE SET price_cents = EXCLUDED.price_cents
+        "#,
+        addon.game,
+        addon.addon,
+        addon.valid_from,
+        addon.price_cents
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn list_ticket_addons(pool: &PgPool, game: &str) -> Result<Vec<TicketAddon>> {
+    let addons = sqlx::query_as!(
+        TicketAddon,
+        "SELECT game, addon, valid_from, price_cents FROM ticket_addons WHERE game = $1 ORDER BY addon, valid_from",
+        game
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(addons)
+}
+
+/// Stores a raw page. A page whose content is already archived for the same
+/// URL only has its `last_seen_at` and `fetch_count` bumped.
+pub async fn archive_page(pool: &PgPool, page: &NewArchivedPage) -> Result<i32> {
+    let rec = sqlx::query!(
+        r#"
+        INSERT INTO raw_pages
+            (url, year, http_status, etag, last_modified, content_sha256, content_length, body_gzip)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (url, content_sha256) DO UPDATE
+        SET last_seen_at = CURRENT_TIMESTAMP,
+            fetch_count = raw_pages.fetch_count + 1,
+            http_status = EXCLUDED.http_status,
+            etag = COALESCE(EXCLUDED.etag, raw_pages.etag),
+            last_modified = COALESCE(EXCLUDED.last_modified, raw_pages.last_modified)
+        RETURNING id
+        "#,
+        page.url,
+        page.year,
+        page.http_status,
+        page.etag,
+        page.last_modified,
+        page.content_sha256,
+        page.content_length,
+        page.body_gzip
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(rec.id)
+}
+
+/// Bumps `last_seen_at` of an archived page, for a download answered with
+/// 304 Not Modified.
+pub async fn touch_archived_page(pool: &PgPool, url: &str, content_sha256: &str) -> Result<Option<i32>> {
+    let id = sqlx::query_scalar!(
+        r#"
+        UPDATE raw_pages SET last_seen_at = CURRENT_TIMESTAMP
+        WHERE url = $1 AND content_sha256 = $2
+        RETURNING id
+        "#,
+        url,
+        content_sha256
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(id)
+}
+
+/// Most recently seen archived copy of every URL.
+pub async fn latest_archived_pages(pool: &PgPool) -> Result<Vec<ArchivedPage>> {
+    let pages = sqlx::query_as!(
+        ArchivedPage,
+        r#"
+        SELECT DISTINCT ON (url)
+            id, url, year, http_status, etag, last_modified, content_sha256,
+            content_length, body_gzip, fetched_at, last_seen_at, fetch_count
+        FROM raw_pages
+        ORDER BY url, last_seen_at DESC, id DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(pages)
+}
+
+pub async fn latest_archived_page(pool: &PgPool, url: &str) -> Result<Option<ArchivedPage>> {
+    let page = sqlx::query_as!(
+        ArchivedPage,
+        r#"
+        SELECT id, url, year, http_status, etag, last_modified, content_sha256,
+            content_length, body_gzip, fetched_at, last_seen_at, fetch_count
+        FROM raw_pages
+        WHERE url = $1
+        ORDER BY last_seen_at DESC, id DESC
+        LIMIT 1
+        "#,
+        url
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(page)
+}
+
+/// Every stored draw of every game, by game then oldest first.
+pub async fn list_draws(pool: &PgPool) -> Result<Vec<Draw>> {
+    let draws = sqlx::query_as!(Draw, "SELECT id, game, date, numbers, stars FROM draws ORDER BY game, date")
+        .fetch_all(pool)
+        .await?;
+    Ok(draws)
+}
+
+pub async fn get_draw(pool: &PgPool, game: &str, date: NaiveDate) -> Result<Option<Draw>> {
+    let draw = sqlx::query_as!(
+        Draw,
+        "SELECT id, game, date, numbers, stars FROM draws WHERE game = $1 AND date = $2",
+        game,
+        date
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(draw)
+}
+
+pub async fn latest_draw(pool: &PgPool, game: &str) -> Result<Option<Draw>> {
+    let draw = sqlx::query_as!(
+        Draw,
+        "SELECT id, game, date, numbers, stars FROM draws WHERE game = $1 ORDER BY date DESC LIMIT 1",
+        game
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(draw)
+}
+
+pub async fn count_draws(pool: &PgPool, game: &str) -> Result<i64> {
+    let rec = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM draws WHERE game = $1"#, game)
+        .fetch_one(pool)
+        .await?;
+    Ok(rec.count)
+}
+
+/// How often every number has been drawn, most frequent first.
+pub async fn number_frequencies(pool: &PgPool, game: &str) -> Result<Vec<BallFrequency>> {
+    let freqs = sqlx::query_as!(
+        BallFrequency,
+        r#"
+        SELECT unnest(numbers) AS "ball!", COUNT(*) AS "count!"
+        FROM draws
+        WHERE game = $1
+        GROUP BY 1
+        ORDER BY 2 DESC, 1
+        "#,
+        game
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(freqs)
+}
+
+/// How often every star has been drawn, most frequent first.
+pub async fn star_frequencies(pool: &PgPool, game: &str) -> Result<Vec<BallFrequency>> {
+    let freqs = sqlx::query_as!(
+        BallFrequency,
+        r#"
+        SELECT unnest(stars) AS "ball!", COUNT(*) AS "count!"
+        FROM draws
+        WHERE game = $1
+        GROUP BY 1
+        ORDER BY 2 DESC, 1
+        "#,
+        game
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(freqs)
+}
+
+/// Versions of the migrations recorded as successfully applied.
+/// Versions already applied; none on a database never migrated.
+pub async fn applied_migrations(pool: &PgPool) -> Result<Vec<i64>> {
+    let exists = sqlx::query_scalar!(r#"SELECT to_regclass('_sqlx_migrations') IS NOT NULL AS "exists!""#)
+        .fetch_one(pool)
+        .await?;
+    if !exists {
+        return Ok(Vec::new());
+    }
+    let versions = sqlx::query_scalar!("SELECT version FROM _sqlx_migrations WHERE success ORDER BY version")
+        .fetch_all(pool)
+        .await?;
+    Ok(versions)
+}
+
+/// When the last succeeded or partial fetch run finished.
+pub async fn last_fetched_at(pool: &PgPool) -> Result<Option<NaiveDateTime>> {
+    let rec = sqlx::query!(
+        "SELECT MAX(finished_at) AS finished_at FROM fetch_runs WHERE status IN ('succeeded', 'partial')"
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(rec.finished_at)
+}
+
+/// Records the start of a fetch and returns the run id.
+pub async fn start_fetch_run(pool: &PgPool, run: &NewFetchRun) -> Result<i32> {
+    let rec = sqlx::query!(
+        "INSERT INTO fetch_runs (trigger, source, years) VALUES ($1, $2, $3) RETURNING id",
+        run.trigger,
+        run.source,
+        &run.years
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(rec.id)
+}
+
+pub async fn finish_fetch_run(pool: &PgPool, id: i32, result: &FinishedFetchRun) -> Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE fetch_runs
+        SET finished_at = CURRENT_TIMESTAMP, status = $2, rows_parsed = $3,
+            rows_inserted = $4, rows_rejected = $5, error = $6
+        WHERE id = $1
+        "#,
+        id,
+        result.status,
+        result.rows_parsed,
+        result.rows_inserted,
+        result.rows_rejected,
+        result.error
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Most recent runs first.
+pub async fn list_fetch_runs(pool: &PgPool, limit: i64) -> Result<Vec<FetchRun>> {
+    let runs = sqlx::query_as!(
+        FetchRun,
+        r#"
+        SELECT id, trigger, source, years, status, started_at, finished_at,
+            rows_parsed, rows_inserted, rows_rejected, error
+        FROM fetch_runs
+        ORDER BY started_at DESC, id DESC
+        LIMIT $1
+        "#,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(runs)
+}
+
+pub async fn get_fetch_run(pool: &PgPool, id: i32) -> Result<Option<FetchRun>> {
+    let run = sqlx::query_as!(
+        FetchRun,
+        r#"
+        SELECT id, trigger, source, years, status, started_at, finished_at,
+            rows_parsed, rows_inserted, rows_rejected, error
+        FROM fetch_runs
+        WHERE id = $1
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(run)
+}
+
+/// Every grid of `game` played on `date`, oldest first.
+pub async fn grids_for_draw(pool: &PgPool, game: &str, date: NaiveDate) -> Result<Vec<Grid>> {
+    let grids = sqlx::query_as!(
+        Grid,
+        "SELECT id, game, draw_date, numbers, stars, created_at, user_id, cost_cents, addons, status, played_at, batch_id FROM grids WHERE game = $1 AND draw_date = $2 ORDER BY id",
+        game,
+        date
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(grids)
+}
+
+pub async fn user_grids(
+    pool: &PgPool,
+    user_id: i32,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<Vec<Grid>> {
+    let grids = sqlx::query_as!(
+        Grid,
+        r#"
+        SELECT id, game, draw_date, numbers, stars, created_at, user_id, cost_cents, addons, status, played_at, batch_id
+        FROM grids
+        WHERE user_id = $1
+          AND ($2::DATE IS NULL OR draw_date >= $2)
+          AND ($3::DATE IS NULL OR draw_date <= $3)
+        ORDER BY draw_date, id
+        "#,
+        user_id,
+        from,
+        to
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(grids)
+}
+
+pub async fn create_user(pool: &PgPool, name: &str) -> Result<User> {
+    let user = sqlx::query_as!(
+        User,
+        "INSERT INTO users (name) VALUES ($1) RETURNING id, name, created_at",
+        name
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(user)
+}
+
+pub async fn get_user(pool: &PgPool, id: i32) -> Result<Option<User>> {
+    let user = sqlx::query_as!(User, "SELECT id, name, created_at FROM users WHERE id = $1", id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(user)
+}
+
+pub async fn get_user_by_name(pool: &PgPool, name: &str) -> Result<Option<User>> {
+    let user = sqlx::query_as!(User, "SELECT id, name, created_at FROM users WHERE name = $1", name)
+        .fetch_optional(pool)
+        .await?;
+    Ok(user)
+}
+
+pub async fn add_notification_channel(
+    pool: &PgPool,
+    channel: &NewNotificationChannel,
+) -> Result<NotificationChannel> {
+    let channel = sqlx::query_as!(
+        NotificationChannel,
+        r#"
+        INSERT INTO notification_channels (user_id, kind, target, notify_results, notify_wins)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, user_id, kind, target, notify_results, notify_wins, enabled, created_at
+        "#,
+        channel.user_id,
+        channel.kind,
+        channel.target,
+        channel.notify_results,
+        channel.notify_wins
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(channel)
+}
+
+pub async fn list_notification_channels(pool: &PgPool, user_id: i32) -> Result<Vec<NotificationChannel>> {
+    let channels = sqlx::query_as!(
+        NotificationChannel,
+        r#"
+        SELECT id, user_id, kind, target, notify_results, notify_wins, enabled, created_at
+        FROM notification_channels
+        WHERE user_id = $1
+        ORDER BY id
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(channels)
+}
+
+/// Enabled channels of every user.
+pub async fn enabled_notification_channels(pool: &PgPool) -> Result<Vec<NotificationChannel>> {
+    let channels = sqlx::query_as!(
+        NotificationChannel,
+        r#"
+        SELECT id, user_id, kind, target, notify_results, notify_wins, enabled, created_at
+        FROM notification_channels
+        WHERE enabled
+        ORDER BY user_id, id
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(channels)
+}
+
+/// Returns whether a channel with that id belonged to the user.
+pub async fn delete_notification_channel(pool: &PgPool, user_id: i32, id: i32) -> Result<bool> {
+    let result = sqlx::query!(
+        "DELETE FROM notification_channels WHERE id = $1 AND user_id = $2",
+        id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn upsert_generation_profile(
+    pool: &PgPool,
+    user_id: i32,
+    game: &str,
+    profile: &NewGenerationProfile,
+) -> Result<GenerationProfile> {
+    let profile = sqlx::query_as!(
+        GenerationProfile,
+        r#"
+        INSERT INTO generation_profiles
+            (user_id, game, forced_numbers, forced_stars, banned_numbers, strategy, grid_count)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (user_id, game) DO UPDATE
+        SET forced_numbers = EXCLUDED.forced_numbers,
+            forced_stars = EXCLUDED.forced_stars,
+            banned_numbers = EXCLUDED.banned_numbers,
+            strategy = EXCLUDED.strategy,
+            grid_count = EXCLUDED.grid_count,
+            updated_at = CURRENT_TIMESTAMP
+        RETURNING user_id, game, forced_numbers, forced_stars, banned_numbers, strategy, grid_count, updated_at
+        "#,
+        user_id,
+        game,
+        &profile.forced_numbers,
+        &profile.forced_stars,
+        &profile.banned_numbers,
+        profile.strategy,
+        profile.grid_count
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(profile)
+}
+
+pub async fn get_generation_profile(pool: &PgPool, user_id: i32, game: &str) -> Result<Option<GenerationProfile>> {
+    let profile = sqlx::query_as!(
+        GenerationProfile,
+        r#"
+        SELECT user_id, game, forced_numbers, forced_stars, banned_numbers, strategy, grid_count, updated_at
+        FROM generation_profiles
+        WHERE user_id = $1 AND game = $2
+        "#,
+        user_id,
+        game
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(profile)
+}
+
+pub async fn delete_generation_profile(pool: &PgPool, user_id: i32, game: &str) -> Result<bool> {
+    let result = sqlx::query!(
+        "DELETE FROM generation_profiles WHERE user_id = $1 AND game = $2",
+        user_id,
+        game
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn set_budget(pool: &PgPool, budget: &Budget) -> Result<Budget> {
+    let budget = sqlx::query_as!(
+        Budget,
+        r#"
+        INSERT INTO budgets (user_id, period, currency, limit_cents)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (user_id, period, currency) DO UPDATE SET limit_cents = EXCLUDED.limit_cents
+        RETURNING user_id, period, currency, limit_cents
+        "#,
+        budget.user_id,
+        budget.period,
+        budget.currency,
+        budget.limit_cents
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(budget)
+}
+
+pub async fn list_budgets(pool: &PgPool, user_id: i32) -> Result<Vec<Budget>> {
+    let budgets = sqlx::query_as!(
+        Budget,
+        "SELECT user_id, period, currency, limit_cents FROM budgets WHERE user_id = $1 ORDER BY currency, period",
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(budgets)
+}
+
+pub async fn delete_budget(pool: &PgPool, user_id: i32, period: &str, currency: &str) -> Result<bool> {
+    let result = sqlx::query!(
+        "DELETE FROM budgets WHERE user_id = $1 AND period = $2 AND currency = $3",
+        user_id,
+        period,
+        currency
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn create_webhook(pool: &PgPool, webhook: &NewWebhook) -> Result<Webhook> {
+    let webhook = sqlx::query_as!(
+        Webhook,
+        r#"
+        INSERT INTO webhooks (url, secret, events)
+        VALUES ($1, $2, $3)
+        RETURNING id, url, secret, events, enabled, created_at
+        "#,
+        webhook.url,
+        webhook.secret,
+        &webhook.events
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(webhook)
+}
+
+pub async fn list_webhooks(pool: &PgPool) -> Result<Vec<Webhook>> {
+    let webhooks = sqlx::query_as!(
+        Webhook,
+        "SELECT id, url, secret, events, enabled, created_at FROM webhooks ORDER BY id"
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(webhooks)
+}
+
+pub async fn get_webhook(pool: &PgPool, id: i32) -> Result<Option<Webhook>> {
+    let webhook = sqlx::query_as!(
+        Webhook,
+        "SELECT id, url, secret, events, enabled, created_at FROM webhooks WHERE id = $1",
+        id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(webhook)
+}
+
+pub async fn delete_webhook(pool: &PgPool, id: i32) -> Result<bool> {
+    let result = sqlx::query!("DELETE FROM webhooks WHERE id = $1", id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Stores the event and one pending delivery per enabled webhook subscribed
+/// to it. Returns the number of deliveries queued; nothing is stored when no
+/// webhook wants the event.
+async fn emit_event_in(conn: &mut PgConnection, event: &str, data: &serde_json::Value) -> Result<usize> {
+    let subscribers = sqlx::query_scalar!(
+        "SELECT id FROM webhooks WHERE enabled AND $1 = ANY(events) ORDER BY id",
+        event
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    if subscribers.is_empty() {
+        return Ok(0);
+    }
+
+    let event_id = sqlx::query_scalar!(
+        "INSERT INTO webhook_events (event, payload) VALUES ($1, $2) RETURNING id",
+        event,
+        data.to_string()
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+    sqlx::query!(
+        "INSERT INTO webhook_deliveries (webhook_id, event_id) SELECT unnest($1::INTEGER[]), $2",
+        &subscribers,
+        event_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(subscribers.len())
+}
+
+pub async fn emit_event(pool: &PgPool, event: &str, data: &serde_json::Value) -> Result<usize> {
+    let mut tx = pool.begin().await?;
+    let queued = emit_event_in(&mut tx, event, data).await?;
+    tx.commit().await?;
+    Ok(queued)
+}
+
+/// Claims the due deliveries until `lease_until`. Rows another dispatcher is
+/// claiming at the same time are skipped rather than waited for.
+pub async fn claim_due_deliveries(
+    pool: &PgPool,
+    now: NaiveDateTime,
+    lease_until: NaiveDateTime,
+    limit: i64,
+) -> Result<Vec<PendingDelivery>> {
+    let deliveries = sqlx::query_as!(
+        PendingDelivery,
+        r#"
+        WITH claimed AS (
+            UPDATE webhook_deliveries SET next_attempt_at = $2
+            WHERE id IN (
+                SELECT d.id
+                FROM webhook_deliveries d
+                JOIN webhooks w ON w.id = d.webhook_id
+                WHERE d.status = 'pending' AND w.enabled
+                  AND (d.next_attempt_at IS NULL OR d.next_attempt_at <= $1)
+                ORDER BY d.id
+                LIMIT $3
+                FOR UPDATE OF d SKIP LOCKED
+            )
+            RETURNING id, webhook_id, event_id, attempts
+        )
+        SELECT c.id AS "id!", c.webhook_id AS "webhook_id!", w.url, w.secret, c.event_id AS "event_id!",
+               e.event, e.payload, e.created_at AS event_created_at, c.attempts AS "attempts!"
+        FROM claimed c
+        JOIN webhooks w ON w.id = c.webhook_id
+        JOIN webhook_events e ON e.id = c.event_id
+        ORDER BY c.id
+        "#,
+        now,
+        lease_until,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(deliveries)
+}
+
+pub async fn record_delivery_attempt(pool: &PgPool, id: i32, attempt: &DeliveryAttempt) -> Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = $2,
+            attempts = attempts + 1,
+            response_status = $3,
+            error = $4,
+            next_attempt_at = $5,
+            delivered_at = CASE WHEN $2 = 'delivered' THEN CURRENT_TIMESTAMP ELSE NULL END
+        WHERE id = $1
+        "#,
+        id,
+        attempt.status,
+        attempt.response_status,
+        attempt.error,
+        attempt.next_attempt_at
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn list_deliveries(pool: &PgPool, webhook_id: i32, limit: i64) -> Result<Vec<WebhookDelivery>> {
+    let deliveries = sqlx::query_as!(
+        WebhookDelivery,
+        r#"
+        SELECT d.id, d.webhook_id, d.event_id, e.event, d.status, d.attempts, d.next_attempt_at,
+               d.response_status, d.error, d.created_at, d.delivered_at
+        FROM webhook_deliveries d
+        JOIN webhook_events e ON e.id = d.event_id
+        WHERE d.webhook_id = $1
+        ORDER BY d.id DESC
+        LIMIT $2
+        "#,
+        webhook_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(deliveries)
+}
+
+pub async fn get_delivery(pool: &PgPool, id: i32) -> Result<Option<WebhookDelivery>> {
+    let delivery = sqlx::query_as!(
+        WebhookDelivery,
+        r#"
+        SELECT d.id, d.webhook_id, d.event_id, e.event, d.status, d.attempts, d.next_attempt_at,
+               d.response_status, d.error, d.created_at, d.delivered_at
+        FROM webhook_deliveries d
+        JOIN webhook_events e ON e.id = d.event_id
+        WHERE d.id = $1
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(delivery)
+}
+
+/// Queues a new delivery of the same event to the same webhook.
+pub async fn replay_delivery(pool: &PgPool, id: i32) -> Result<Option<WebhookDelivery>> {
+    let replay = sqlx::query_scalar!(
+        r#"
+        INSERT INTO webhook_deliveries (webhook_id, event_id)
+        SELECT webhook_id, event_id FROM webhook_deliveries WHERE id = $1
+        RETURNING id
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await?;
+    match replay {
+        Some(replay) => get_delivery(pool, replay).await,
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,152 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+use std::fmt;
+
+pub type Result<T, E = AppError> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum AppError {
+    /// The results site could not be reached or answered with an error.
+    Fetch(String),
+    /// A downloaded page could not be turned into draws.
+    Parse(String),
+    /// The request carried invalid input.
+    Validation(String),
+    NotFound(String),
+    /// The request clashes with an earlier one, such as a reused
+    /// idempotency key.
+    Conflict(String),
+    Database(sqlx::Error),
+    /// Missing or invalid settings (environment, config file, HTTP client).
+    Config(String),
+    /// A dependency the service needs is not ready, e.g. during startup.
+    Unavailable(String),
+    /// A notification could not be delivered.
+    Notify(String),
+    /// The request is not signed or authenticated as required.
+    Unauthorized(String),
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: ErrorDetail<'a>,
+}
+
+#[derive(Serialize)]
+struct ErrorDetail<'a> {
+    code: &'a str,
+    message: String,
+}
+
+impl AppError {
+    /// Stable, machine-readable identifier included in JSON error bodies.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Fetch(_) => "fetch_failed",
+            AppError::Parse(_) => "parse_failed",
+            AppError::Validation(_) => "invalid_request",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
+            AppError::Database(_) => "database_error",
+            AppError::Config(_) => "configuration_error",
+            AppError::Unavailable(_) => "service_unavailable",
+            AppError::Notify(_) => "notification_failed",
+            AppError::Unauthorized(_) => "unauthorized",
+        }
+    }
+
+    /// Message safe to return to clients. Database and configuration details
+    /// stay in the logs.
+    fn public_message(&self) -> String {
+        match self {
+            AppError::Fetch(msg)
+            | AppError::Parse(msg)
+            | AppError::Validation(msg)
+            | AppError::NotFound(msg)
+            | AppError::Conflict(msg)
+            | AppError::Unavailable(msg)
+            | AppError::Notify(msg)
+            | AppError::Unauthorized(msg) => msg.clone(),
+            AppError::Database(_) => "A database error occurred".to_string(),
+            AppError::Config(_) => "The server is misconfigured".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Fetch(msg) => write!(f, "fetch error: {}", msg),
+            AppError::Parse(msg) => write!(f, "parse error: {}", msg),
+            AppError::Validation(msg) => write!(f, "validation error: {}", msg),
+            AppError::NotFound(msg) => write!(f, "not found: {}", msg),
+            AppError::Conflict(msg) => write!(f, "conflict: {}", msg),
+            AppError::Database(e) => write!(f, "database error: {}", e),
+            AppError::Config(msg) => write!(f, "configuration error: {}", msg),
+            AppError::Unavailable(msg) => write!(f, "service unavailable: {}", msg),
+            AppError::Notify(msg) => write!(f, "notification error: {}", msg),
+            AppError::Unauthorized(msg) => write!(f, "unauthorized: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Database(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => AppError::NotFound("Resource not found".to_string()),
+            e => AppError::Database(e),
+        }
+    }
+}
+
+impl From<sqlx::migrate::MigrateError> for AppError {
+    fn from(e: sqlx::migrate::MigrateError) -> Self {
+        AppError::Database(sqlx::Error::Migrate(Box::new(e)))
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_builder() {
+            AppError::Config(format!("invalid HTTP client settings: {}", e))
+        } else {
+            AppError::Fetch(e.to_string())
+        }
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Fetch(_) | AppError::Parse(_) | AppError::Notify(_) => StatusCode::BAD_GATEWAY,
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Database(_) | AppError::Config(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if self.status_code().is_server_error() {
+            tracing::error!(code = self.code(), error = %self, "Request failed");
+        }
+        HttpResponse::build(self.status_code()).json(ErrorBody {
+            error: ErrorDetail {
+                code: self.code(),
+                message: self.public_message(),
+            },
+        })
+    }
+}
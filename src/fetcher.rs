@@ -1,106 +1,446 @@
+use crate::error::Result;
 use crate::models::NewDraw;
-use chrono::NaiveDate;
-use reqwest::Client;
-use scraper::{Html, Selector};
-use std::error::Error;
-
-pub async fn fetch_history() -> Result<Vec<NewDraw>, Box<dyn Error + Send + Sync>> {
-    let client = Client::new();
-    let years = vec![2024, 2025];
-    let mut all_draws = Vec::new();
-
-    for year in years {
-        let url = format!("https://www.euro-millions.com/results-history-{}", year);
-        let resp = match client.get(&url).send().await {
-            Ok(r) => r.text().await?,
-            Err(e) => {
-                eprintln!("Failed to fetch {}: {}", url, e);
-                continue;
-            }
-        };
+use crate::odds::ParsedJackpot;
+use crate::parser::{self, ParseReport};
+use crate::raffle::ParsedRaffle;
+use async_trait::async_trait;
+use rand::Rng;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RETRY_AFTER};
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
 
-        let document = Html::parse_document(&resp);
-        let row_selector = Selector::parse("tr.resultRow").unwrap();
-        let date_selector = Selector::parse("td:nth-child(1) > a").unwrap();
-        let ball_selector = Selector::parse("td:nth-child(2) > ul > li").unwrap();
+/// Settings for the HTTP layer used to download result pages.
+#[derive(Debug, Clone)]
+pub struct FetcherConfig {
+    pub base_url: String,
+    pub user_agent: String,
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    /// Number of retries after the first attempt.
+    pub max_retries: u32,
+    pub backoff_base: Duration,
+    pub backoff_max: Duration,
+    /// Minimum delay between two requests to the same host.
+    pub min_request_interval: Duration,
+}
 
-        for row in document.select(&row_selector) {
-            let date_text = match row.select(&date_selector).next() {
-                Some(el) => el.text().collect::<String>(),
-                None => continue,
-            };
+impl Default for FetcherConfig {
+    fn default() -> Self {
+        FetcherConfig {
+            base_url: "https://www.euro-millions.com".to_string(),
+            user_agent: format!(
+                "euromillions-bot/{} (+https://github.com/cyrilleguipie/euromillions-bot)",
+                env!("CARGO_PKG_VERSION")
+            ),
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            max_retries: 3,
+            backoff_base: Duration::from_millis(500),
+            backoff_max: Duration::from_secs(30),
+            min_request_interval: Duration::from_secs(1),
+        }
+    }
+}
 
-            // Date format: "Tuesday\n18th March 2025" (with lots of whitespace)
-            // Clean up and remove ordinal suffixes
-            let cleaned = date_text
-                .split_whitespace()
-                .filter(|s| !s.is_empty())
-                .collect::<Vec<&str>>()
-                .join(" ");
-            
-            // Remove day of week (first word) and ordinal suffixes (st, nd, rd, th)
-            let parts: Vec<&str> = cleaned.split_whitespace().collect();
-            if parts.len() < 4 {
-                eprintln!("Failed to parse date '{}': not enough parts", date_text);
-                continue;
+/// Why a year could not be fetched.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FetchFailureKind {
+    /// Connection, timeout or body read error.
+    Transport { message: String },
+    /// The server answered with a non-success status.
+    Status { status: u16 },
+    /// The server answered 304 but no cached copy of the page is available.
+    NotModifiedWithoutCache,
+    /// The page has content but none of its rows could be parsed.
+    MarkupChanged { rows_seen: usize },
+}
+
+impl fmt::Display for FetchFailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchFailureKind::Transport { message } => write!(f, "transport error: {}", message),
+            FetchFailureKind::Status { status } => write!(f, "unexpected HTTP status {}", status),
+            FetchFailureKind::NotModifiedWithoutCache => {
+                write!(f, "server returned 304 but no cached page is available")
             }
-            
-            //parts: ["Tuesday", "18th", "March", "2025"]
-            // We want: "18 March 2025" (without ordinal)
-            let day_with_ordinal = parts[1];
-            let day = day_with_ordinal.trim_end_matches("st")
-                .trim_end_matches("nd")
-                .trim_end_matches("rd")
-                .trim_end_matches("th");
-            let formatted_date = format!("{} {} {}", day, parts[2], parts[3]);
-            
-            let date = match NaiveDate::parse_from_str(&formatted_date, "%d %B %Y") {
-                Ok(d) => d,
-                Err(e) => {
-                    eprintln!("Failed to parse date '{}' (formatted: '{}'): {}", date_text, formatted_date, e);
-                    continue;
-                }
-            };
+            FetchFailureKind::MarkupChanged { rows_seen } => {
+                write!(f, "markup changed: parsed no draw out of {} result row(s)", rows_seen)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct YearFailure {
+    pub year: i32,
+    pub url: String,
+    pub attempts: u32,
+    #[serde(flatten)]
+    pub kind: FetchFailureKind,
+}
+
+/// A downloaded page together with the response metadata worth archiving.
+#[derive(Debug, Clone)]
+pub struct FetchedPage {
+    pub url: String,
+    pub status: u16,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// The server answered 304 and `body` comes from the local cache.
+    pub not_modified: bool,
+    pub body: String,
+}
+
+/// Parse outcome for one downloaded page.
+#[derive(Debug, Serialize)]
+pub struct PageReport {
+    pub year: i32,
+    pub url: String,
+    #[serde(skip)]
+    pub page: FetchedPage,
+    #[serde(flatten)]
+    pub parse: ParseReport,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct FetchReport {
+    pub draws: Vec<NewDraw>,
+    #[serde(skip)]
+    pub raffles: Vec<ParsedRaffle>,
+    #[serde(skip)]
+    pub jackpots: Vec<ParsedJackpot>,
+    pub pages: Vec<PageReport>,
+    pub failures: Vec<YearFailure>,
+}
+
+impl FetchReport {
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty()
+    }
 
-            let mut numbers = Vec::new();
-            let mut stars = Vec::new();
-            let mut balls_iter = row.select(&ball_selector);
+    pub fn rows_seen(&self) -> usize {
+        self.pages.iter().map(|p| p.parse.rows_seen).sum()
+    }
+
+    pub fn rows_parsed(&self) -> usize {
+        self.pages.iter().map(|p| p.parse.rows_parsed).sum()
+    }
+
+    pub fn rows_rejected(&self) -> usize {
+        self.pages.iter().map(|p| p.parse.rejected.len()).sum()
+    }
+
+    /// Whether any page came back with content but no parseable rows.
+    pub fn markup_changed(&self) -> bool {
+        self.pages.iter().any(|p| p.parse.markup_changed())
+    }
+
+    /// Records a parsed page, raising the markup-changed alert when the page
+    /// had content but yielded no draw. An empty results table is not one:
+    /// the year has simply had no draw yet.
+    pub fn add_page(&mut self, year: i32, page: FetchedPage, mut parse: ParseReport) {
+        let url = page.url.clone();
+        if parse.markup_changed() {
+            tracing::error!(
+                alert = "markup_changed",
+                url = %url,
+                rows_seen = parse.rows_seen,
+                "Markup changed: result rows seen, none parsed"
+            );
+            self.failures.push(YearFailure {
+                year,
+                url: url.clone(),
+                attempts: 1,
+                kind: FetchFailureKind::MarkupChanged { rows_seen: parse.rows_seen },
+            });
+        } else if !parse.rejected.is_empty() {
+            tracing::warn!(
+                url = %url,
+                rows_rejected = parse.rejected.len(),
+                rows_seen = parse.rows_seen,
+                "Rejected result rows"
+            );
+        }
+        self.draws.append(&mut parse.draws);
+        self.raffles.append(&mut parse.raffles);
+        self.jackpots.append(&mut parse.jackpots);
+        self.pages.push(PageReport { year, url, page, parse });
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedPage {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    body: String,
+}
 
-            // First 5 are numbers
-            for _ in 0..5 {
-                if let Some(ball) = balls_iter.next() {
-                    if let Ok(num) = ball.text().collect::<String>().trim().parse::<i32>() {
-                        numbers.push(num);
-                    }
+/// HTTP client with timeouts, retries, per-host rate limiting and
+/// conditional requests.
+pub struct Fetcher {
+    client: Client,
+    config: FetcherConfig,
+    last_request: Mutex<HashMap<String, Instant>>,
+    cache: Mutex<HashMap<String, CachedPage>>,
+}
+
+impl Fetcher {
+    pub fn new(config: FetcherConfig) -> Result<Self> {
+        let client = Client::builder()
+            .user_agent(config.user_agent.clone())
+            .timeout(config.timeout)
+            .connect_timeout(config.connect_timeout)
+            .build()?;
+
+        Ok(Fetcher {
+            client,
+            config,
+            last_request: Mutex::new(HashMap::new()),
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn config(&self) -> &FetcherConfig {
+        &self.config
+    }
+
+    pub fn year_url(&self, year: i32) -> String {
+        format!("{}/results-history-{}", self.config.base_url.trim_end_matches('/'), year)
+    }
+
+    /// Fetches and parses every requested year. Years that fail are reported
+    /// in `FetchReport::failures` instead of aborting the whole run.
+    #[tracing::instrument(skip(self))]
+    pub async fn fetch_years(&self, years: &[i32]) -> FetchReport {
+        let mut report = FetchReport::default();
+
+        for &year in years {
+            let url = self.year_url(year);
+            match self.get_page(&url).await {
+                Ok(page) => {
+                    let parse = parser::parse_results_page(&page.body);
+                    report.add_page(year, page, parse);
+                }
+                Err((attempts, kind)) => {
+                    tracing::warn!(url = %url, attempts, error = %kind, "Failed to fetch page");
+                    report.failures.push(YearFailure { year, url, attempts, kind });
                 }
             }
+        }
+
+        report
+    }
+
+    /// Downloads a page, retrying transient failures with exponential backoff.
+    /// Returns the number of attempts made alongside the failure.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_page(&self, url: &str) -> Result<FetchedPage, (u32, FetchFailureKind)> {
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+            self.wait_for_host(url).await;
+
+            let (kind, retry_after) = match self.send_conditional(url).await {
+                Ok(page) => return Ok(page),
+                Err(failure) => failure,
+            };
+
+            if attempt > self.config.max_retries || !is_retryable(&kind) {
+                return Err((attempt, kind));
+            }
 
-            // Next 2 are stars
-            for _ in 0..2 {
-                if let Some(star) = balls_iter.next() {
-                    if let Ok(num) = star.text().collect::<String>().trim().parse::<i32>() {
-                        stars.push(num);
-                    }
-                }
+            let delay = retry_after.unwrap_or_else(|| self.backoff_delay(attempt));
+            tracing::debug!(url, attempt, ?delay, error = %kind, "Retrying");
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    async fn send_conditional(
+        &self,
+        url: &str,
+    ) -> Result<FetchedPage, (FetchFailureKind, Option<Duration>)> {
+        let cached = self.cache.lock().await.get(url).cloned();
+
+        let mut request = self.client.get(url);
+        if let Some(page) = &cached {
+            if let Some(etag) = &page.etag {
+                request = request.header(IF_NONE_MATCH, etag);
             }
+            if let Some(last_modified) = &page.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let resp = request.send().await.map_err(|e| {
+            (FetchFailureKind::Transport { message: e.to_string() }, None)
+        })?;
+
+        let status = resp.status();
+        if status == StatusCode::NOT_MODIFIED {
+            return match cached {
+                Some(page) => Ok(FetchedPage {
+                    url: url.to_string(),
+                    status: status.as_u16(),
+                    etag: page.etag,
+                    last_modified: page.last_modified,
+                    not_modified: true,
+                    body: page.body,
+                }),
+                None => Err((FetchFailureKind::NotModifiedWithoutCache, None)),
+            };
+        }
+        if !status.is_success() {
+            let retry_after = resp
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(|secs| Duration::from_secs(secs).min(self.config.backoff_max));
+            return Err((FetchFailureKind::Status { status: status.as_u16() }, retry_after));
+        }
+
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+
+        let body = resp.text().await.map_err(|e| {
+            (FetchFailureKind::Transport { message: e.to_string() }, None)
+        })?;
+
+        if etag.is_some() || last_modified.is_some() {
+            self.prime_cache(url, etag.clone(), last_modified.clone(), body.clone()).await;
+        }
 
-            if numbers.len() == 5 && stars.len() == 2 {
-                numbers.sort();
-                stars.sort();
-                all_draws.push(NewDraw {
-                    date,
-                    numbers,
-                    stars,
-                });
+        Ok(FetchedPage {
+            url: url.to_string(),
+            status: status.as_u16(),
+            etag,
+            last_modified,
+            not_modified: false,
+            body,
+        })
+    }
+
+    /// Seeds the conditional-request cache, e.g. from the page archive, so
+    /// that the next download of `url` can be answered with 304.
+    pub async fn prime_cache(
+        &self,
+        url: &str,
+        etag: Option<String>,
+        last_modified: Option<String>,
+        body: String,
+    ) {
+        self.cache
+            .lock()
+            .await
+            .insert(url.to_string(), CachedPage { etag, last_modified, body });
+    }
+
+    /// Sleeps until `min_request_interval` has elapsed since the last request
+    /// to the same host.
+    async fn wait_for_host(&self, url: &str) {
+        let host = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_default();
+
+        let mut last_request = self.last_request.lock().await;
+        if let Some(previous) = last_request.get(&host) {
+            let elapsed = previous.elapsed();
+            if elapsed < self.config.min_request_interval {
+                tokio::time::sleep(self.config.min_request_interval - elapsed).await;
             }
         }
+        last_request.insert(host, Instant::now());
     }
 
-    Ok(all_draws)
+    fn backoff_delay(&self, attempt: u32) -> Duration {
+        let exp = self
+            .config
+            .backoff_base
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.config.backoff_max);
+        // Up to 25% jitter so that parallel clients do not retry in lockstep.
+        let jitter_ms = (exp.as_millis() as u64) / 4;
+        let jitter = if jitter_ms > 0 {
+            Duration::from_millis(rand::thread_rng().gen_range(0..=jitter_ms))
+        } else {
+            Duration::ZERO
+        };
+        exp + jitter
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    include!("fetcher_tests.rs");
+fn is_retryable(kind: &FetchFailureKind) -> bool {
+    match kind {
+        FetchFailureKind::Transport { .. } => true,
+        FetchFailureKind::Status { status } => *status == 429 || *status >= 500,
+        FetchFailureKind::NotModifiedWithoutCache | FetchFailureKind::MarkupChanged { .. } => false,
+    }
 }
 
+/// Where draws come from. `Fetcher` scrapes the results site; tests plug in
+/// local fakes.
+#[async_trait]
+pub trait DrawSource: Send + Sync {
+    /// Identifies the source in the fetch history, e.g. its base URL.
+    fn name(&self) -> String;
+
+    fn year_url(&self, year: i32) -> String;
+
+    async fn fetch_years(&self, years: &[i32]) -> FetchReport;
+
+    /// Seeds conditional requests with a previously downloaded copy of `url`.
+    async fn prime_cache(
+        &self,
+        _url: &str,
+        _etag: Option<String>,
+        _last_modified: Option<String>,
+        _body: String,
+    ) {
+    }
+}
+
+#[async_trait]
+impl DrawSource for Fetcher {
+    fn name(&self) -> String {
+        self.config.base_url.clone()
+    }
+
+    fn year_url(&self, year: i32) -> String {
+        Fetcher::year_url(self, year)
+    }
+
+    async fn fetch_years(&self, years: &[i32]) -> FetchReport {
+        Fetcher::fetch_years(self, years).await
+    }
+
+    async fn prime_cache(
+        &self,
+        url: &str,
+        etag: Option<String>,
+        last_modified: Option<String>,
+        body: String,
+    ) {
+        Fetcher::prime_cache(self, url, etag, last_modified, body).await
+    }
+}
+
+/// Years scraped by default by `/history` and the scheduled job.
+pub const HISTORY_YEARS: [i32; 2] = [2024, 2025];
+
+pub async fn fetch_history() -> Result<FetchReport> {
+    let fetcher = Fetcher::new(FetcherConfig::default())?;
+    Ok(fetcher.fetch_years(&HISTORY_YEARS).await)
+}
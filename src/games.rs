@@ -0,0 +1,273 @@
+//! Lottery game definitions. Every draw and grid belongs to a game; the
+//! generator, the checker and the imports read ball counts, ranges, draw
+//! days and prize tiers from here instead of assuming EuroMillions.
+//!
+//! Draws and grids keep their balls in `numbers` and `stars` whatever the
+//! game: `stars` holds the bonus balls (EuroMillions stars, the EuroDreams
+//! dream number, the Loto chance number or the UK Lotto bonus ball).
+
+use crate::error::{AppError, Result};
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::Serialize;
+
+/// How many balls are picked, each in `1..=max`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Balls {
+    pub count: usize,
+    pub max: i32,
+}
+
+/// A winning combination. `bonus: None` matches any number of bonus balls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PrizeTier {
+    /// 1 is the jackpot.
+    pub tier: u8,
+    pub numbers: usize,
+    pub bonus: Option<usize>,
+}
+
+/// How the top prize grows while nobody wins it, in cents of the game's
+/// currency. Used to project the jackpot of upcoming draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct JackpotRules {
+    /// Jackpot of the first draw after it was won.
+    pub minimum_cents: i64,
+    /// Typical growth per draw without a jackpot winner.
+    pub rollover_cents: i64,
+    /// Past the cap, the rest rolls down to the next tier.
+    pub cap_cents: Option<i64>,
+}
+
+const fn tier(tier: u8, numbers: usize, bonus: Option<usize>) -> PrizeTier {
+    PrizeTier { tier, numbers, bonus }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct GameRules {
+    /// Stored in the `game` column of draws and grids.
+    pub id: &'static str,
+    pub name: &'static str,
+    pub numbers: Balls,
+    /// Bonus balls the player picks. A count of 0 means the bonus balls are
+    /// drawn from the main numbers and matched against them (UK Lotto).
+    pub bonus: Balls,
+    pub bonus_name: &'static str,
+    /// Bonus balls in a draw.
+    pub drawn_bonus: usize,
+    pub draw_days: &'static [Weekday],
+    /// Ordered from the jackpot down; the first match wins.
+    pub prize_tiers: &'static [PrizeTier],
+    /// Price of one line, in cents of `currency`.
+    pub price_cents: i64,
+    pub currency: &'static str,
+    /// `None` for a fixed top prize, which is then paid like other tiers.
+    pub jackpot: Option<JackpotRules>,
+}
+
+pub static EUROMILLIONS: GameRules = GameRules {
+    id: "euromillions",
+    name: "EuroMillions",
+    numbers: Balls { count: 5, max: 50 },
+    bonus: Balls { count: 2, max: 12 },
+    bonus_name: "stars",
+    drawn_bonus: 2,
+    draw_days: &[Weekday::Tue, Weekday::Fri],
+    prize_tiers: &[
+        tier(1, 5, Some(2)),
+        tier(2, 5, Some(1)),
+        tier(3, 5, Some(0)),
+        tier(4, 4, Some(2)),
+        tier(5, 4, Some(1)),
+        tier(6, 3, Some(2)),
+        tier(7, 4, Some(0)),
+        tier(8, 2, Some(2)),
+        tier(9, 3, Some(1)),
+        tier(10, 3, Some(0)),
+        tier(11, 1, Some(2)),
+        tier(12, 2, Some(1)),
+        tier(13, 2, Some(0)),
+    ],
+    price_cents: 250,
+    currency: "EUR",
+    jackpot: Some(JackpotRules {
+        minimum_cents: 1_700_000_000,
+        rollover_cents: 1_000_000_000,
+        cap_cents: Some(25_000_000_000),
+    }),
+};
+
+pub static EURODREAMS: GameRules = GameRules {
+    id: "eurodreams",
+    name: "EuroDreams",
+    numbers: Balls { count: 6, max: 40 },
+    bonus: Balls { count: 1, max: 5 },
+    bonus_name: "dream number",
+    drawn_bonus: 1,
+    draw_days: &[Weekday::Mon, Weekday::Thu],
+    prize_tiers: &[
+        tier(1, 6, Some(1)),
+        tier(2, 6, Some(0)),
+        tier(3, 5, None),
+        tier(4, 4, None),
+        tier(5, 3, None),
+        tier(6, 2, None),
+    ],
+    price_cents: 250,
+    currency: "EUR",
+    // 20,000 a month for 30 years
+    jackpot: None,
+};
+
+pub static LOTO: GameRules = GameRules {
+    id: "loto",
+    name: "Loto",
+    numbers: Balls { count: 5, max: 49 },
+    bonus: Balls { count: 1, max: 10 },
+    bonus_name: "chance number",
+    drawn_bonus: 1,
+    draw_days: &[Weekday::Mon, Weekday::Wed, Weekday::Sat],
+    prize_tiers: &[
+        tier(1, 5, Some(1)),
+        tier(2, 5, Some(0)),
+        tier(3, 4, Some(1)),
+        tier(4, 4, Some(0)),
+        tier(5, 3, Some(1)),
+        tier(6, 3, Some(0)),
+        tier(7, 2, Some(1)),
+        tier(8, 2, Some(0)),
+        tier(9, 1, Some(1)),
+        tier(9, 0, Some(1)),
+    ],
+    price_cents: 220,
+    currency: "EUR",
+    jackpot: Some(JackpotRules {
+        minimum_cents: 200_000_000,
+        rollover_cents: 100_000_000,
+        cap_cents: None,
+    }),
+};
+
+pub static UK_LOTTO: GameRules = GameRules {
+    id: "uk_lotto",
+    name: "UK Lotto",
+    numbers: Balls { count: 6, max: 59 },
+    bonus: Balls { count: 0, max: 59 },
+    bonus_name: "bonus ball",
+    drawn_bonus: 1,
+    draw_days: &[Weekday::Wed, Weekday::Sat],
+    prize_tiers: &[
+        tier(1, 6, None),
+        tier(2, 5, Some(1)),
+        tier(3, 5, None),
+        tier(4, 4, None),
+        tier(5, 3, None),
+        tier(6, 2, None),
+    ],
+    price_cents: 200,
+    currency: "GBP",
+    jackpot: Some(JackpotRules {
+        minimum_cents: 200_000_000,
+        rollover_cents: 100_000_000,
+        cap_cents: None,
+    }),
+};
+
+/// Every supported game. EuroMillions, the game the results site covers,
+/// comes first and is the default.
+pub static GAMES: [&GameRules; 4] = [&EUROMILLIONS, &EURODREAMS, &LOTO, &UK_LOTTO];
+
+/// Looks a game up by id.
+pub fn get(id: &str) -> Result<&'static GameRules> {
+    GAMES.iter().copied().find(|game| game.id == id).ok_or_else(|| {
+        let ids: Vec<_> = GAMES.iter().map(|g| g.id).collect();
+        AppError::Validation(format!("unknown game '{}', expected one of {}", id, ids.join(", ")))
+    })
+}
+
+fn check_balls(kind: &str, balls: &[i32], count: usize, max: i32) -> Result<()> {
+    if balls.len() != count {
+        return Err(AppError::Validation(format!(
+            "expected {} {}, got {}",
+            count,
+            kind,
+            balls.len()
+        )));
+    }
+    if let Some(ball) = balls.iter().find(|b| !(1..=max).contains(*b)) {
+        return Err(AppError::Validation(format!(
+            "{} must be between 1 and {}, got {}",
+            kind, max, ball
+        )));
+    }
+    let mut sorted = balls.to_vec();
+    sorted.sort();
+    if sorted.windows(2).any(|w| w[0] == w[1]) {
+        return Err(AppError::Validation(format!("{} must be distinct", kind)));
+    }
+    Ok(())
+}
+
+impl GameRules {
+    /// Whether the bonus balls come out of the main drum.
+    pub fn bonus_from_numbers(&self) -> bool {
+        self.bonus.count == 0
+    }
+
+    /// Ensures a played line has the right number of distinct balls in range.
+    pub fn validate_line(&self, numbers: &[i32], bonus: &[i32]) -> Result<()> {
+        check_balls("numbers", numbers, self.numbers.count, self.numbers.max)?;
+        check_balls(self.bonus_name, bonus, self.bonus.count, self.bonus.max)
+    }
+
+    /// Like [`validate_line`](Self::validate_line) for drawn results, where
+    /// a bonus ball drawn from the main numbers cannot repeat one of them.
+    pub fn validate_draw(&self, numbers: &[i32], bonus: &[i32]) -> Result<()> {
+        check_balls("numbers", numbers, self.numbers.count, self.numbers.max)?;
+        check_balls(self.bonus_name, bonus, self.drawn_bonus, self.bonus.max)?;
+        if self.bonus_from_numbers() && bonus.iter().any(|b| numbers.contains(b)) {
+            return Err(AppError::Validation(format!(
+                "the {} must not be one of the numbers",
+                self.bonus_name
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn prize_tier(&self, numbers_matched: usize, bonus_matched: usize) -> Option<u8> {
+        self.prize_tiers
+            .iter()
+            .find(|t| t.numbers == numbers_matched && t.bonus.is_none_or(|b| b == bonus_matched))
+            .map(|t| t.tier)
+    }
+
+    /// First draw day strictly after `date`, `None` past the last date
+    /// chrono can represent.
+    pub fn next_draw_after(&self, date: NaiveDate) -> Option<NaiveDate> {
+        date.iter_days()
+            .skip(1)
+            .take(7)
+            .find(|day| self.draw_days.contains(&day.weekday()))
+    }
+
+    pub fn next_draw_date(&self) -> NaiveDate {
+        self.next_draw_after(chrono::Local::now().date_naive())
+            .expect("today is far from the end of time")
+    }
+
+    /// Number of draws after `from`, up to and including `to`.
+    pub fn draws_between(&self, from: NaiveDate, to: NaiveDate) -> u32 {
+        let days = (to - from).num_days();
+        if days <= 0 {
+            return 0;
+        }
+        let mut weekday = from.weekday();
+        let mut rest = 0;
+        for _ in 0..days % 7 {
+            weekday = weekday.succ();
+            if self.draw_days.contains(&weekday) {
+                rest += 1;
+            }
+        }
+        (days / 7) as u32 * self.draw_days.len() as u32 + rest
+    }
+}
@@ -1,66 +1,352 @@
-use crate::models::NewGrid;
-use crate::db;
-use sqlx::PgPool;
-use rand::seq::SliceRandom;
-use rand::thread_rng;
+use crate::constraints::{Constraints, Solver, GENERATION_BUDGET};
+use crate::error::{AppError, Result};
+use crate::games::{self, GameRules, EUROMILLIONS};
+use crate::ledger;
+use crate::models::{GenerationBatch, GenerationProfile, Grid, NewGenerationBatch, NewGrid};
+use crate::popularity::{self, PopularityScore};
+use crate::storage::Storage;
+use crate::webhooks::{self, Event};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use chrono::NaiveDate;
-use chrono::Datelike;
-use chrono::Weekday;
+use std::str::FromStr;
 
-pub async fn generate_grids(pool: &PgPool) -> Result<Vec<NewGrid>, sqlx::Error> {
-    let mut grids = Vec::new();
-    let next_draw_date = get_next_draw_date();
+/// Maximum number of grids per generation request.
+pub const MAX_GRIDS: usize = 100;
 
-    // Fetch top 15 frequent numbers and top 6 stars to allow for some variation
-    // while still focusing on the "most drawn".
-    let frequent_numbers = db::get_most_frequent_numbers(pool, 15).await?;
-    let frequent_stars = db::get_most_frequent_stars(pool, 6).await?;
+/// Candidates drawn per grid when weighting by popularity score.
+const WEIGHTED_CANDIDATES: usize = 32;
 
-    // Fallback if DB is empty (e.g. first run before scrape)
-    let pool_numbers = if frequent_numbers.len() >= 5 { frequent_numbers } else { (1..=50).collect() };
-    let pool_stars = if frequent_stars.len() >= 2 { frequent_stars } else { (1..=12).collect() };
+/// Candidates tried per grid before a popularity filter gives up.
+const FILTER_ATTEMPTS: usize = 1000;
 
-    for _ in 0..4 {
-        grids.push(generate_single_grid(next_draw_date, &pool_numbers, &pool_stars));
+/// Minimum popularity score kept by [`AntiPopular::Filter`] by default.
+pub const DEFAULT_MIN_SCORE: u8 = 60;
+
+/// How the candidate numbers and stars are chosen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// Pick among the most frequently drawn numbers and stars.
+    #[default]
+    Frequent,
+    /// Pick uniformly among all numbers and stars.
+    Random,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 2] = [Strategy::Frequent, Strategy::Random];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Strategy::Frequent => "frequent",
+            Strategy::Random => "random",
+        }
     }
-    Ok(grids)
 }
 
-fn generate_single_grid(date: NaiveDate, number_pool: &[i32], star_pool: &[i32]) -> NewGrid {
-    let mut rng = thread_rng();
-    
-    let mut numbers = number_pool.to_vec();
-    numbers.shuffle(&mut rng);
-    let selected_numbers: Vec<i32> = numbers.into_iter().take(5).collect();
-    let mut selected_numbers = selected_numbers;
-    selected_numbers.sort();
+impl FromStr for Strategy {
+    type Err = AppError;
 
-    let mut stars = star_pool.to_vec();
-    stars.shuffle(&mut rng);
-    let selected_stars: Vec<i32> = stars.into_iter().take(2).collect();
-    let mut selected_stars = selected_stars;
-    selected_stars.sort();
+    fn from_str(s: &str) -> Result<Self> {
+        Strategy::ALL
+            .into_iter()
+            .find(|strategy| strategy.as_str() == s)
+            .ok_or_else(|| AppError::Validation(format!("unknown strategy '{}'", s)))
+    }
+}
 
-    NewGrid {
-        draw_date: date,
-        numbers: selected_numbers,
-        stars: selected_stars,
+/// How the strategy's grids are steered away from lines other players pick,
+/// see [`popularity`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum AntiPopular {
+    /// Keep the strategy's grids as drawn.
+    #[default]
+    Off,
+    /// Prefer grids with a higher popularity score.
+    Weight,
+    /// Only keep grids scoring at least `min_score`.
+    Filter,
+}
+
+#[derive(Debug, Clone)]
+pub struct GenerateOptions {
+    pub game: &'static GameRules,
+    pub strategy: Strategy,
+    pub count: usize,
+    /// Makes the output reproducible when set.
+    pub seed: Option<u64>,
+    pub draw_date: NaiveDate,
+    pub anti_popular: AntiPopular,
+    /// Used by [`AntiPopular::Filter`].
+    pub min_score: u8,
+    /// Applied to the main numbers of every grid, whatever the strategy.
+    pub constraints: Constraints,
+    /// Add-ons bought with every grid, see [`ledger::line_cost_cents`].
+    pub addons: Vec<String>,
+}
+
+impl GenerateOptions {
+    /// The batch recording a generation with these options for `user_id`.
+    pub fn batch(&self, user_id: Option<i32>, idempotency_key: Option<String>) -> NewGenerationBatch {
+        let params = serde_json::json!({
+            "game": self.game.id,
+            "draw_date": self.draw_date,
+            "strategy": self.strategy,
+            "count": self.count,
+            "seed": self.seed,
+            "anti_popular": self.anti_popular,
+            "min_score": self.min_score,
+            "constraints": self.constraints,
+            "addons": self.addons,
+        });
+        NewGenerationBatch {
+            idempotency_key,
+            game: self.game.id.to_string(),
+            user_id,
+            strategy: self.strategy.as_str().to_string(),
+            params: params.to_string(),
+        }
+    }
+
+    /// Whether `batch` recorded a generation with these options for
+    /// `user_id`. The draw date is left out, so that a retry after the draw
+    /// still matches the batch generated for it.
+    pub fn matches_batch(&self, batch: &GenerationBatch, user_id: Option<i32>) -> bool {
+        let without_date = |params: &str| {
+            let mut params: serde_json::Value = serde_json::from_str(params).unwrap_or_default();
+            if let Some(params) = params.as_object_mut() {
+                params.remove("draw_date");
+            }
+            params
+        };
+        let expected = self.batch(user_id, None);
+        batch.game == expected.game
+            && batch.user_id == user_id
+            && without_date(&batch.params) == without_date(&expected.params)
+    }
+
+    /// Default options for the next draw of `game`.
+    pub fn for_game(game: &'static GameRules) -> Self {
+        GenerateOptions {
+            game,
+            strategy: Strategy::default(),
+            count: 4,
+            seed: None,
+            draw_date: game.next_draw_date(),
+            anti_popular: AntiPopular::default(),
+            min_score: DEFAULT_MIN_SCORE,
+            constraints: Constraints::default(),
+            addons: Vec::new(),
+        }
     }
 }
 
-pub fn get_next_draw_date() -> NaiveDate {
-    let today = chrono::Local::now().date_naive();
-    let mut current = today;
-    loop {
-        current = current.succ_opt().unwrap();
-        match current.weekday() {
-            Weekday::Tue | Weekday::Fri => return current,
-            _ => continue,
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        GenerateOptions::for_game(&EUROMILLIONS)
+    }
+}
+
+/// Adds a user's profile to the options: its strategy and grid count
+/// replace the defaults, and its numbers join the constraints.
+pub fn apply_profile(options: &mut GenerateOptions, profile: &GenerationProfile) -> Result<()> {
+    if let Some(strategy) = &profile.strategy {
+        options.strategy = strategy.parse()?;
+    }
+    if let Some(count) = profile.grid_count {
+        options.count = count as usize;
+    }
+    let merge = |into: &mut Vec<i32>, numbers: &[i32]| {
+        for n in numbers {
+            if !into.contains(n) {
+                into.push(*n);
+            }
         }
+    };
+    let constraints = &mut options.constraints;
+    merge(&mut constraints.include, &profile.forced_numbers);
+    merge(&mut constraints.include_stars, &profile.forced_stars);
+    merge(&mut constraints.exclude, &profile.banned_numbers);
+    Ok(())
+}
+
+pub async fn generate_grids(storage: &dyn Storage) -> Result<Vec<NewGrid>> {
+    generate(storage, &GenerateOptions::default()).await
+}
+
+#[tracing::instrument(skip(storage))]
+pub async fn generate(storage: &dyn Storage, options: &GenerateOptions) -> Result<Vec<NewGrid>> {
+    if options.count == 0 || options.count > MAX_GRIDS {
+        return Err(AppError::Validation(format!(
+            "count must be between 1 and {}",
+            MAX_GRIDS
+        )));
+    }
+
+    let game = options.game;
+    let all_numbers: Vec<i32> = (1..=game.numbers.max).collect();
+    let all_stars: Vec<i32> = (1..=game.bonus.max).collect();
+    let (pool_numbers, pool_stars) = match options.strategy {
+        Strategy::Frequent => {
+            // Fetch three times as many frequent numbers and stars as a grid
+            // holds to allow for some variation while still focusing on the
+            // "most drawn".
+            let frequent_numbers = storage
+                .get_most_frequent_numbers(game.id, game.numbers.count as i64 * 3)
+                .await?;
+            let frequent_stars = storage
+                .get_most_frequent_stars(game.id, game.bonus.count as i64 * 3)
+                .await?;
+
+            // Fallback if DB is empty (e.g. first run before scrape)
+            let pool_numbers = if frequent_numbers.len() >= game.numbers.count { frequent_numbers } else { all_numbers };
+            let pool_stars = if frequent_stars.len() >= game.bonus.count { frequent_stars } else { all_stars };
+            (pool_numbers, pool_stars)
+        }
+        Strategy::Random => (all_numbers, all_stars),
+    };
+
+    let price = ledger::line_cost_cents(storage, game, options.draw_date, &options.addons).await?;
+    // Drawing is CPU bound and, with tight constraints, slow: keep it off
+    // the async workers
+    let options = options.clone();
+    let drawn = tokio::task::spawn_blocking(move || draw_grids(&options, &pool_numbers, &pool_stars, price)).await;
+    match drawn {
+        Ok(grids) => grids,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(AppError::Unavailable(format!("generation was cancelled: {}", e))),
     }
 }
 
-#[cfg(test)]
-mod tests {
-    include!("generator_tests.rs");
+/// Draws the grids from the pools. All the solver searches share
+/// [`GENERATION_BUDGET`], so the candidates drawn per line by the
+/// popularity modes cannot multiply the work of tight constraints.
+fn draw_grids(options: &GenerateOptions, pool_numbers: &[i32], pool_stars: &[i32], price: i64) -> Result<Vec<NewGrid>> {
+    let game = options.game;
+    let solver = Solver::new(game, pool_numbers, &options.constraints)?;
+    let mut rng = match options.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let mut budget = GENERATION_BUDGET;
+
+    let mut draw =
+        |rng: &mut StdRng| generate_single_grid(game, options.draw_date, &solver, pool_stars, rng, &mut budget);
+    let score = |grid: &NewGrid| popularity::score(game, &grid.numbers).score;
+    let mut grids = Vec::with_capacity(options.count);
+    for _ in 0..options.count {
+        let grid = match options.anti_popular {
+            AntiPopular::Off => draw(&mut rng)?,
+            AntiPopular::Weight => {
+                let candidates = (0..WEIGHTED_CANDIDATES)
+                    .map(|_| draw(&mut rng))
+                    .collect::<Result<Vec<NewGrid>>>()?;
+                // Every 10 points lost divide the chances by e
+                let weights = candidates.iter().map(|g| ((f64::from(score(g)) - 100.0) / 10.0).exp());
+                let index = WeightedIndex::new(weights).expect("weights are positive").sample(&mut rng);
+                candidates.into_iter().nth(index).expect("index is within the candidates")
+            }
+            AntiPopular::Filter => {
+                let mut found = None;
+                for _ in 0..FILTER_ATTEMPTS {
+                    let grid = draw(&mut rng)?;
+                    if score(&grid) >= options.min_score {
+                        found = Some(grid);
+                        break;
+                    }
+                }
+                found.ok_or_else(|| {
+                    AppError::Validation(format!(
+                        "no {} grid with a popularity score of at least {} found",
+                        options.strategy.as_str(),
+                        options.min_score
+                    ))
+                })?
+            }
+        };
+        grids.push(NewGrid {
+            cost_cents: Some(price),
+            addons: options.addons.clone(),
+            ..grid
+        });
+    }
+    Ok(grids)
+}
+
+/// A grid with its popularity score, as returned to users.
+#[derive(Debug, Serialize)]
+pub struct ScoredGrid<G = Grid> {
+    #[serde(flatten)]
+    pub grid: G,
+    pub popularity: PopularityScore,
+}
+
+pub fn scored(grids: Vec<Grid>) -> Result<Vec<ScoredGrid>> {
+    grids
+        .into_iter()
+        .map(|grid| {
+            let game = games::get(&grid.game)?;
+            let popularity = popularity::score(game, &grid.numbers);
+            Ok(ScoredGrid { grid, popularity })
+        })
+        .collect()
+}
+
+/// Stores grids generated with `options` for `user_id`, with their batch,
+/// in one transaction and emits `grid.generated`.
+pub async fn save(
+    storage: &dyn Storage,
+    options: &GenerateOptions,
+    user_id: Option<i32>,
+    grids: &[NewGrid],
+) -> Result<Vec<Grid>> {
+    let saved = save_batch(storage, &options.batch(user_id, None), grids).await?;
+    Ok(saved.expect("batches without an idempotency key never conflict"))
+}
+
+/// Like [`save`] for a batch of its own. Returns `None`, storing nothing,
+/// when another batch already holds its idempotency key.
+pub async fn save_batch(storage: &dyn Storage, batch: &NewGenerationBatch, grids: &[NewGrid]) -> Result<Option<Vec<Grid>>> {
+    let Some(saved) = storage.insert_generation_batch(batch, grids).await? else {
+        return Ok(None);
+    };
+    webhooks::emit(
+        storage,
+        Event::GridGenerated,
+        &serde_json::json!({ "strategy": batch.strategy, "grids": saved }),
+    )
+    .await;
+    Ok(Some(saved))
+}
+
+fn generate_single_grid(
+    game: &GameRules,
+    date: NaiveDate,
+    solver: &Solver,
+    star_pool: &[i32],
+    rng: &mut impl Rng,
+    budget: &mut usize,
+) -> Result<NewGrid> {
+    let selected_numbers = solver.solve_within(rng, budget)?;
+
+    let selected_stars = solver.stars(game.bonus.count, star_pool, rng);
+
+    Ok(NewGrid {
+        game: game.id.to_string(),
+        draw_date: date,
+        numbers: selected_numbers,
+        stars: selected_stars,
+        user_id: None,
+        cost_cents: None,
+        addons: Vec::new(),
+    })
+}
+
+/// Next EuroMillions draw.
+pub fn get_next_draw_date() -> NaiveDate {
+    EUROMILLIONS.next_draw_date()
 }
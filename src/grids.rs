@@ -0,0 +1,176 @@
+//! Grid lifecycle. Generation stores suggestions as `generated`; the lines
+//! actually bought are marked `played`, and only those are checked against
+//! their draw, becoming `checked`, and counted in spending. Suggestions and
+//! checked lines can be `archived` to keep them out of the way.
+//!
+//! ```text
+//! generated <-> played -> checked -> archived
+//!     |                                 ^
+//!     +---------------------------------+
+//! ```
+
+use crate::checker;
+use crate::error::{AppError, Result};
+use crate::games;
+use crate::ledger;
+use crate::models::{Grid, GridPlay};
+use crate::storage::Storage;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GridStatus {
+    Generated,
+    Played,
+    Checked,
+    Archived,
+}
+
+impl GridStatus {
+    pub const ALL: [GridStatus; 4] = [
+        GridStatus::Generated,
+        GridStatus::Played,
+        GridStatus::Checked,
+        GridStatus::Archived,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GridStatus::Generated => "generated",
+            GridStatus::Played => "played",
+            GridStatus::Checked => "checked",
+            GridStatus::Archived => "archived",
+        }
+    }
+
+    /// Statuses a grid can move to this one from.
+    fn previous(self) -> &'static [GridStatus] {
+        match self {
+            GridStatus::Generated => &[GridStatus::Played],
+            GridStatus::Played => &[GridStatus::Generated],
+            GridStatus::Checked => &[GridStatus::Played],
+            GridStatus::Archived => &[GridStatus::Generated, GridStatus::Checked],
+        }
+    }
+}
+
+impl fmt::Display for GridStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for GridStatus {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        GridStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| {
+                AppError::Validation(format!(
+                    "unknown grid status '{}', expected generated, played, checked or archived",
+                    s
+                ))
+            })
+    }
+}
+
+/// Whether the line was bought, whatever became of it since.
+pub fn is_played(grid: &Grid) -> bool {
+    grid.played_at.is_some()
+}
+
+/// Moves the grids to `status` and returns them, by id. Grids already there
+/// are left alone; any other move the lifecycle does not allow, or lines
+/// whose cost would go over one of their owner's budgets, fail before a grid
+/// changes. Lines marked as played for a draw already stored are checked
+/// straight away.
+pub async fn set_status(storage: &dyn Storage, ids: &[i32], status: GridStatus) -> Result<Vec<Grid>> {
+    if ids.is_empty() {
+        return Err(AppError::Validation("no grid ids given".to_string()));
+    }
+    if status == GridStatus::Checked {
+        return Err(AppError::Validation(
+            "grids are checked when the result of their draw is stored".to_string(),
+        ));
+    }
+    if status == GridStatus::Played {
+        return play(storage, ids, None).await;
+    }
+    let grids = find_grids(storage, ids).await?;
+    for grid in &grids {
+        let current: GridStatus = grid.status.parse()?;
+        if current != status && !status.previous().contains(&current) {
+            return Err(AppError::Validation(format!(
+                "grid {} is {} and cannot become {}",
+                grid.id, current, status
+            )));
+        }
+    }
+
+    let from: Vec<&str> = status.previous().iter().map(|s| s.as_str()).collect();
+    storage.set_grid_status(ids, &from, status.as_str()).await?;
+    storage.get_grids(ids).await
+}
+
+/// Marks the grids played and returns them, by id. With `addons`, these
+/// replace the ones the lines are bought with and their cost is recomputed,
+/// even for lines already played; an add-on without a price for a grid's
+/// draw fails before any grid changes. Every line then counts at its new
+/// cost against its owner's budgets.
+pub async fn play(storage: &dyn Storage, ids: &[i32], addons: Option<&[String]>) -> Result<Vec<Grid>> {
+    if ids.is_empty() {
+        return Err(AppError::Validation("no grid ids given".to_string()));
+    }
+    let grids = find_grids(storage, ids).await?;
+    let mut plays = Vec::with_capacity(grids.len());
+    for grid in &grids {
+        let current: GridStatus = grid.status.parse()?;
+        if !matches!(current, GridStatus::Generated | GridStatus::Played) {
+            return Err(AppError::Validation(format!(
+                "grid {} is {} and cannot become {}",
+                grid.id,
+                current,
+                GridStatus::Played
+            )));
+        }
+        plays.push(match addons {
+            Some(addons) => GridPlay {
+                id: grid.id,
+                addons: addons.to_vec(),
+                cost_cents: Some(
+                    ledger::line_cost_cents(storage, games::get(&grid.game)?, grid.draw_date, addons).await?,
+                ),
+            },
+            None => GridPlay {
+                id: grid.id,
+                addons: grid.addons.clone(),
+                cost_cents: grid.cost_cents,
+            },
+        });
+    }
+    ledger::play_within_budgets(storage, &grids, &plays).await?;
+
+    let mut draws: Vec<(&str, NaiveDate)> = grids.iter().map(|g| (g.game.as_str(), g.draw_date)).collect();
+    draws.sort();
+    draws.dedup();
+    for (game, date) in draws {
+        if let Some(draw) = storage.get_draw(game, date).await? {
+            checker::check_grids(storage, &draw).await?;
+        }
+    }
+    storage.get_grids(ids).await
+}
+
+/// The grids with these ids, failing if one is missing.
+async fn find_grids(storage: &dyn Storage, ids: &[i32]) -> Result<Vec<Grid>> {
+    let grids = storage.get_grids(ids).await?;
+    if let Some(missing) = ids.iter().find(|id| !grids.iter().any(|g| g.id == **id)) {
+        return Err(AppError::NotFound(format!("grid {} not found", missing)));
+    }
+    Ok(grids)
+}
@@ -0,0 +1,204 @@
+use crate::archive;
+use crate::error::Result;
+use crate::fetcher::{DrawSource, FetchReport};
+use crate::games::EUROMILLIONS;
+use crate::models::{FinishedFetchRun, NewFetchRun};
+use crate::odds;
+use crate::raffle;
+use crate::storage::Storage;
+use crate::webhooks::{self, Event};
+use chrono::NaiveDate;
+use serde::Serialize;
+
+/// What started a fetch, recorded in `fetch_runs.trigger`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchTrigger {
+    /// The scheduled job.
+    Cron,
+    /// `GET /history`.
+    Manual,
+    /// The `fetch` subcommand.
+    Cli,
+}
+
+impl FetchTrigger {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FetchTrigger::Cron => "cron",
+            FetchTrigger::Manual => "manual",
+            FetchTrigger::Cli => "cli",
+        }
+    }
+}
+
+/// Outcome of a fetch run, recorded in `fetch_runs.status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchRunStatus {
+    Succeeded,
+    /// Some years could not be fetched.
+    Partial,
+    /// No draw could be fetched, or the run itself errored.
+    Failed,
+}
+
+impl FetchRunStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FetchRunStatus::Succeeded => "succeeded",
+            FetchRunStatus::Partial => "partial",
+            FetchRunStatus::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct IngestSummary {
+    /// Row in `fetch_runs` describing this fetch.
+    pub run_id: i32,
+    /// Draws that were not already stored.
+    pub inserted: usize,
+    /// Dates of the inserted draws.
+    #[serde(skip)]
+    pub new_draws: Vec<NaiveDate>,
+    #[serde(flatten)]
+    pub report: FetchReport,
+}
+
+impl IngestSummary {
+    /// A run that failed somewhere and parsed no row at all failed, even if
+    /// pages came back: their markup may have changed.
+    pub fn status(&self) -> FetchRunStatus {
+        if self.report.failures.is_empty() {
+            FetchRunStatus::Succeeded
+        } else if self.report.rows_parsed() == 0 {
+            FetchRunStatus::Failed
+        } else {
+            FetchRunStatus::Partial
+        }
+    }
+}
+
+/// Downloads the given years, archives every page, then stores the parsed
+/// draws. The run is recorded in `fetch_runs`, including when it fails.
+#[tracing::instrument(skip(storage, source))]
+pub async fn run(
+    storage: &dyn Storage,
+    source: &dyn DrawSource,
+    years: &[i32],
+    trigger: FetchTrigger,
+) -> Result<IngestSummary> {
+    let run_id = storage
+        .start_fetch_run(&NewFetchRun {
+            trigger: trigger.as_str().to_string(),
+            source: source.name(),
+            years: years.to_vec(),
+        })
+        .await?;
+
+    match fetch_and_store(storage, source, years).await {
+        Ok((new_draws, report)) => {
+            let summary = IngestSummary {
+                run_id,
+                inserted: new_draws.len(),
+                new_draws,
+                report,
+            };
+            let report = &summary.report;
+            let error = (!report.failures.is_empty()).then(|| {
+                report
+                    .failures
+                    .iter()
+                    .map(|f| format!("{}: {} after {} attempt(s)", f.year, f.kind, f.attempts))
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            });
+            let finished = FinishedFetchRun {
+                status: summary.status().as_str().to_string(),
+                rows_parsed: report.rows_parsed() as i32,
+                rows_inserted: summary.inserted as i32,
+                rows_rejected: report.rows_rejected() as i32,
+                error,
+            };
+            storage.finish_fetch_run(run_id, &finished).await?;
+            if summary.status() != FetchRunStatus::Succeeded {
+                emit_failure(storage, run_id, trigger, &finished).await;
+            }
+            tracing::info!(
+                run_id,
+                status = summary.status().as_str(),
+                rows_parsed = report.rows_parsed(),
+                rows_rejected = report.rows_rejected(),
+                inserted = summary.inserted,
+                failures = report.failures.len(),
+                "Ingest finished"
+            );
+            Ok(summary)
+        }
+        Err(e) => {
+            let failed = FinishedFetchRun {
+                status: FetchRunStatus::Failed.as_str().to_string(),
+                rows_parsed: 0,
+                rows_inserted: 0,
+                rows_rejected: 0,
+                error: Some(e.to_string()),
+            };
+            if let Err(record_error) = storage.finish_fetch_run(run_id, &failed).await {
+                tracing::error!(run_id, error = %record_error, "Failed to record fetch run");
+            }
+            emit_failure(storage, run_id, trigger, &failed).await;
+            Err(e)
+        }
+    }
+}
+
+async fn emit_failure(storage: &dyn Storage, run_id: i32, trigger: FetchTrigger, run: &FinishedFetchRun) {
+    let data = serde_json::json!({
+        "run_id": run_id,
+        "trigger": trigger.as_str(),
+        "status": run.status,
+        "error": run.error,
+    });
+    webhooks::emit(storage, Event::FetchFailed, &data).await;
+}
+
+async fn fetch_and_store(
+    storage: &dyn Storage,
+    source: &dyn DrawSource,
+    years: &[i32],
+) -> Result<(Vec<NaiveDate>, FetchReport)> {
+    // Seed conditional requests from the archive so unchanged pages come back
+    // as 304 even after a restart.
+    for &year in years {
+        let url = source.year_url(year);
+        if let Some(page) = storage.latest_archived_page(&url).await? {
+            if page.etag.is_some() || page.last_modified.is_some() {
+                let body = page.body()?;
+                source.prime_cache(&url, page.etag, page.last_modified, body).await;
+            }
+        }
+    }
+
+    let mut report = source.fetch_years(years).await;
+
+    for page in &report.pages {
+        if let Err(e) = archive::store(storage, &page.page, Some(page.year)).await {
+            tracing::error!(url = %page.url, error = %e, "Failed to archive page");
+        }
+    }
+
+    let mut inserted = Vec::new();
+    for draw in std::mem::take(&mut report.draws) {
+        let date = draw.date;
+        if storage.upsert_draw(draw).await? {
+            inserted.push(date);
+        }
+    }
+    // After the draws, which the codes and jackpots reference
+    let raffles = raffle::store(storage, &std::mem::take(&mut report.raffles)).await?;
+    if raffles > 0 {
+        tracing::info!(raffles, "Stored new raffle codes");
+    }
+    odds::store_jackpots(storage, &EUROMILLIONS, &std::mem::take(&mut report.jackpots)).await?;
+
+    Ok((inserted, report))
+}
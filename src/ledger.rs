@@ -0,0 +1,420 @@
+//! Money: ticket and add-on prices over time, what each user spends and
+//! wins, and the weekly or monthly budget caps that generating and playing
+//! lines have to respect.
+//!
+//! Every grid records the price of a line for its draw, with the add-ons
+//! bought with it, when it is generated, but only the lines marked as
+//! played count: merely generated grids cost nothing. Spending and budget
+//! periods go by draw date, and amounts stay in the currency of the game: a
+//! budget only covers the games played in its currency.
+
+use crate::checker;
+use crate::error::{AppError, Result};
+use crate::games::{self, GameRules};
+use crate::grids;
+use crate::models::{Budget, Grid, GridPlay, SpendingCap};
+use crate::storage::Storage;
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Share of a budget from which users are warned.
+pub const WARN_RATIO: f64 = 0.8;
+
+/// Price of a line of `game` for the draw of `date`: the latest stored price
+/// starting on or before it, else the game's usual price.
+pub async fn price_cents(storage: &dyn Storage, game: &GameRules, date: NaiveDate) -> Result<i64> {
+    let prices = storage.list_ticket_prices(game.id).await?;
+    Ok(prices
+        .iter()
+        .rfind(|p| p.valid_from <= date)
+        .map_or(game.price_cents, |p| p.price_cents))
+}
+
+/// Price of a line of `game` for the draw of `date` with `addons`: the line
+/// price plus, for each add-on, its latest stored price starting on or
+/// before the draw. An add-on without such a price cannot be bought.
+pub async fn line_cost_cents(storage: &dyn Storage, game: &GameRules, date: NaiveDate, addons: &[String]) -> Result<i64> {
+    let mut cost = price_cents(storage, game, date).await?;
+    if addons.is_empty() {
+        return Ok(cost);
+    }
+    let prices = storage.list_ticket_addons(game.id).await?;
+    for addon in addons {
+        let price = prices
+            .iter()
+            .rfind(|p| &p.addon == addon && p.valid_from <= date)
+            .ok_or_else(|| {
+                AppError::Validation(format!("{} add-on '{}' has no price for the draw of {}", game.name, addon, date))
+            })?;
+        cost += price.price_cents;
+    }
+    Ok(cost)
+}
+
+/// Add-on names as given by users, trimmed, sorted and without duplicates.
+pub fn normalize_addons<S: AsRef<str>>(addons: impl IntoIterator<Item = S>) -> Vec<String> {
+    let mut addons: Vec<String> = addons
+        .into_iter()
+        .map(|addon| addon.as_ref().trim().to_string())
+        .filter(|addon| !addon.is_empty())
+        .collect();
+    addons.sort();
+    addons.dedup();
+    addons
+}
+
+/// `1234` and `EUR` as `12.34 EUR`.
+pub fn format_cents(cents: i64, currency: &str) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{}{}.{:02} {}", sign, cents.abs() / 100, cents.abs() % 100, currency)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetPeriod {
+    /// Monday to Sunday.
+    Week,
+    /// Calendar month.
+    Month,
+}
+
+impl BudgetPeriod {
+    pub const ALL: [BudgetPeriod; 2] = [BudgetPeriod::Week, BudgetPeriod::Month];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BudgetPeriod::Week => "week",
+            BudgetPeriod::Month => "month",
+        }
+    }
+
+    fn adjective(self) -> &'static str {
+        match self {
+            BudgetPeriod::Week => "weekly",
+            BudgetPeriod::Month => "monthly",
+        }
+    }
+
+    /// First and last day of the period holding `date`.
+    pub fn bounds(self, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match self {
+            BudgetPeriod::Week => {
+                let monday = date - Duration::days(date.weekday().num_days_from_monday().into());
+                (monday, monday + Duration::days(6))
+            }
+            BudgetPeriod::Month => {
+                let first = date.with_day(1).expect("every month has a first day");
+                let next = first
+                    .checked_add_months(chrono::Months::new(1))
+                    .expect("dates are far from the end of time");
+                (first, next.pred_opt().expect("the first of a month has a day before"))
+            }
+        }
+    }
+}
+
+impl fmt::Display for BudgetPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for BudgetPeriod {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        BudgetPeriod::ALL
+            .into_iter()
+            .find(|period| period.as_str() == s)
+            .ok_or_else(|| AppError::Validation(format!("unknown budget period '{}', expected week or month", s)))
+    }
+}
+
+/// Where a user stands against one of their budgets.
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetStatus {
+    pub period: BudgetPeriod,
+    pub currency: String,
+    pub limit_cents: i64,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub spent_cents: i64,
+    pub remaining_cents: i64,
+    /// At least [`WARN_RATIO`] of the limit is spent.
+    pub near_limit: bool,
+}
+
+impl BudgetStatus {
+    pub fn warning(&self) -> Option<String> {
+        self.near_limit.then(|| {
+            format!(
+                "{} budget: {} of {} spent",
+                self.period.adjective(),
+                format_cents(self.spent_cents, &self.currency),
+                format_cents(self.limit_cents, &self.currency)
+            )
+        })
+    }
+}
+
+/// What the played grids cost in games played in `currency`.
+fn spent_in(grids: &[Grid], currency: &str) -> i64 {
+    grids
+        .iter()
+        .filter(|g| grids::is_played(g))
+        .filter(|g| games::get(&g.game).is_ok_and(|game| game.currency == currency))
+        .filter_map(|g| g.cost_cents)
+        .sum()
+}
+
+/// The budget's status for the period holding `date`, with `extra_cents`
+/// more spent.
+async fn status(storage: &dyn Storage, budget: &Budget, date: NaiveDate, extra_cents: i64) -> Result<BudgetStatus> {
+    let period: BudgetPeriod = budget.period.parse()?;
+    let (from, to) = period.bounds(date);
+    let grids = storage.user_grids(budget.user_id, Some(from), Some(to)).await?;
+    let spent_cents = spent_in(&grids, &budget.currency) + extra_cents;
+    Ok(BudgetStatus {
+        period,
+        currency: budget.currency.clone(),
+        limit_cents: budget.limit_cents,
+        from,
+        to,
+        spent_cents,
+        remaining_cents: budget.limit_cents - spent_cents,
+        near_limit: spent_cents as f64 >= budget.limit_cents as f64 * WARN_RATIO,
+    })
+}
+
+/// Every budget of the user for the periods holding `date`.
+pub async fn budget_statuses(storage: &dyn Storage, user_id: i32, date: NaiveDate) -> Result<Vec<BudgetStatus>> {
+    let mut statuses = Vec::new();
+    for budget in storage.list_budgets(user_id).await? {
+        statuses.push(status(storage, &budget, date, 0).await?);
+    }
+    Ok(statuses)
+}
+
+/// Checks that `lines` more lines of `game` for the draw of `date`, each
+/// with `addons`, fit in the user's budgets, and returns where the budgets
+/// of the game's currency would then stand.
+pub async fn check_budgets(
+    storage: &dyn Storage,
+    user_id: i32,
+    game: &GameRules,
+    date: NaiveDate,
+    lines: usize,
+    addons: &[String],
+) -> Result<Vec<BudgetStatus>> {
+    let cost = line_cost_cents(storage, game, date, addons).await?;
+    check_spending(storage, user_id, game.currency, &vec![(date, cost); lines], true).await
+}
+
+/// Plays `grids` as `plays`, in the same order, unless that takes an owner
+/// over a budget. Every line counts at its play cost, lines already played
+/// included, and the check and the writes are one storage transaction.
+pub async fn play_within_budgets(storage: &dyn Storage, grids: &[Grid], plays: &[GridPlay]) -> Result<()> {
+    let mut owners: Vec<i32> = grids.iter().filter_map(|g| g.user_id).collect();
+    owners.sort();
+    owners.dedup();
+    let mut caps = Vec::new();
+    // What each cap holds of the plays: period, currency, lines and cost
+    let mut played = Vec::new();
+    for user_id in owners {
+        for budget in storage.list_budgets(user_id).await? {
+            let period: BudgetPeriod = budget.period.parse()?;
+            let games: Vec<String> = games::GAMES
+                .iter()
+                .filter(|game| game.currency == budget.currency)
+                .map(|game| game.id.to_string())
+                .collect();
+            let mut by_start: BTreeMap<NaiveDate, (usize, i64)> = BTreeMap::new();
+            for (grid, play) in grids.iter().zip(plays) {
+                if grid.user_id == Some(user_id) && games.contains(&grid.game) {
+                    let (lines, cost) = by_start.entry(period.bounds(grid.draw_date).0).or_default();
+                    *lines += 1;
+                    *cost += play.cost_cents.unwrap_or(0);
+                }
+            }
+            for (from, (lines, cost)) in by_start {
+                caps.push(SpendingCap {
+                    user_id,
+                    games: games.clone(),
+                    from,
+                    to: period.bounds(from).1,
+                    limit_cents: budget.limit_cents,
+                });
+                played.push((period, budget.currency.clone(), lines, cost));
+            }
+        }
+    }
+
+    let sums = storage.play_grids(plays, &caps).await?;
+    for ((cap, spent), (period, currency, lines, cost)) in caps.iter().zip(sums).zip(played) {
+        if spent > cap.limit_cents {
+            return Err(AppError::Validation(format!(
+                "{} line(s) cost {}, over the {} budget: {} of {} already spent from {} to {}",
+                lines,
+                format_cents(cost, &currency),
+                period.adjective(),
+                format_cents(spent - cost, &currency),
+                format_cents(cap.limit_cents, &currency),
+                cap.from,
+                cap.to
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Where the budgets involved would stand once the user's lines among
+/// `grids` are played, even over a limit. Lines already played are not
+/// counted twice.
+pub async fn statuses_if_played(storage: &dyn Storage, user_id: i32, grids: &[Grid]) -> Result<Vec<BudgetStatus>> {
+    let mut by_currency: BTreeMap<&str, Vec<(NaiveDate, i64)>> = BTreeMap::new();
+    for grid in grids.iter().filter(|g| g.user_id == Some(user_id) && !grids::is_played(g)) {
+        let game = games::get(&grid.game)?;
+        by_currency
+            .entry(game.currency)
+            .or_default()
+            .push((grid.draw_date, grid.cost_cents.unwrap_or(0)));
+    }
+    let mut statuses = Vec::new();
+    for (currency, costs) in by_currency {
+        statuses.extend(check_spending(storage, user_id, currency, &costs, false).await?);
+    }
+    Ok(statuses)
+}
+
+/// Where every budget the user has in `currency` stands, in each period they
+/// fall in, with lines costing `costs` by draw date. With `refuse`, going
+/// over a limit fails.
+async fn check_spending(
+    storage: &dyn Storage,
+    user_id: i32,
+    currency: &str,
+    costs: &[(NaiveDate, i64)],
+    refuse: bool,
+) -> Result<Vec<BudgetStatus>> {
+    let mut statuses = Vec::new();
+    for budget in storage.list_budgets(user_id).await? {
+        if budget.currency != currency {
+            continue;
+        }
+        let period: BudgetPeriod = budget.period.parse()?;
+        let mut starts: Vec<NaiveDate> = costs.iter().map(|(date, _)| period.bounds(*date).0).collect();
+        starts.sort();
+        starts.dedup();
+        for start in starts {
+            let lines: Vec<i64> = costs
+                .iter()
+                .filter(|(date, _)| period.bounds(*date).0 == start)
+                .map(|(_, cost)| *cost)
+                .collect();
+            let cost: i64 = lines.iter().sum();
+            let status = status(storage, &budget, start, cost).await?;
+            if refuse && status.remaining_cents < 0 {
+                return Err(AppError::Validation(format!(
+                    "{} line(s) cost {}, over the {} budget: {} of {} already spent from {} to {}",
+                    lines.len(),
+                    format_cents(cost, currency),
+                    status.period.adjective(),
+                    format_cents(status.spent_cents - cost, currency),
+                    format_cents(status.limit_cents, currency),
+                    status.from,
+                    status.to
+                )));
+            }
+            statuses.push(status);
+        }
+    }
+    Ok(statuses)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LedgerEntry {
+    pub draw_date: NaiveDate,
+    pub grids: usize,
+    pub spent_cents: i64,
+    pub won_cents: i64,
+    pub winning_grids: usize,
+    /// The draw is not stored yet.
+    pub pending: bool,
+    /// Winning grids whose tier payout is not stored, left out of `won_cents`.
+    pub unknown_payouts: usize,
+}
+
+/// A user's spending and winnings in one game, per draw.
+#[derive(Debug, Clone, Serialize)]
+pub struct Ledger {
+    pub user_id: i32,
+    pub game: &'static str,
+    pub currency: &'static str,
+    pub entries: Vec<LedgerEntry>,
+    pub spent_cents: i64,
+    pub won_cents: i64,
+    /// `won_cents` minus `spent_cents`.
+    pub net_cents: i64,
+    pub unknown_payouts: usize,
+}
+
+/// The user's ledger for `game` over draws between `from` and `to`, from
+/// the lines they played. Grids win the stored payout of their tier.
+pub async fn ledger(
+    storage: &dyn Storage,
+    user_id: i32,
+    game: &'static GameRules,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<Ledger> {
+    let mut by_date: BTreeMap<NaiveDate, Vec<Grid>> = BTreeMap::new();
+    for grid in storage.user_grids(user_id, from, to).await? {
+        if grid.game == game.id && grids::is_played(&grid) {
+            by_date.entry(grid.draw_date).or_default().push(grid);
+        }
+    }
+    let prizes = storage.list_draw_prizes(game.id).await?;
+
+    let mut entries = Vec::with_capacity(by_date.len());
+    for (date, grids) in by_date {
+        let draw = storage.get_draw(game.id, date).await?;
+        let mut entry = LedgerEntry {
+            draw_date: date,
+            grids: grids.len(),
+            spent_cents: grids.iter().filter_map(|g| g.cost_cents).sum(),
+            won_cents: 0,
+            winning_grids: 0,
+            pending: draw.is_none(),
+            unknown_payouts: 0,
+        };
+        if let Some(draw) = draw {
+            for grid in &grids {
+                let Some(tier) = checker::check_line(game, &grid.numbers, &grid.stars, &draw).tier else {
+                    continue;
+                };
+                entry.winning_grids += 1;
+                match prizes.iter().find(|p| p.draw_id == draw.id && p.tier == i32::from(tier)) {
+                    Some(prize) => entry.won_cents += prize.amount_cents,
+                    None => entry.unknown_payouts += 1,
+                }
+            }
+        }
+        entries.push(entry);
+    }
+
+    let spent_cents = entries.iter().map(|e| e.spent_cents).sum();
+    let won_cents = entries.iter().map(|e| e.won_cents).sum();
+    Ok(Ledger {
+        user_id,
+        game: game.id,
+        currency: game.currency,
+        unknown_payouts: entries.iter().map(|e| e.unknown_payouts).sum(),
+        entries,
+        spent_cents,
+        won_cents,
+        net_cents: won_cents - spent_cents,
+    })
+}
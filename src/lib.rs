@@ -1,5 +1,30 @@
+pub mod app;
+pub mod archive;
+pub mod bot;
+pub mod checker;
+pub mod cli;
+pub mod config;
+pub mod constraints;
 pub mod db;
+pub mod error;
 pub mod fetcher;
+pub mod games;
 pub mod generator;
+pub mod grids;
+pub mod ingest;
+pub mod ledger;
+pub mod metrics;
 pub mod models;
+pub mod notify;
+pub mod odds;
+pub mod outbound;
+pub mod parser;
+pub mod popularity;
+pub mod raffle;
 pub mod routes;
+pub mod server;
+pub mod status;
+pub mod storage;
+pub mod telemetry;
+pub mod transfer;
+pub mod webhooks;
@@ -0,0 +1,163 @@
+//! Prometheus metrics served on `/metrics`. Every `Metrics` owns its own
+//! registry, so tests can build as many applications as they like.
+
+use crate::generator::Strategy;
+use crate::ingest::{FetchRunStatus, IngestSummary};
+use crate::storage::PoolStatus;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use std::time::Duration;
+
+/// Outcome of a scheduled fetch, used as the `outcome` label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    Success,
+    /// Some years failed to download.
+    Partial,
+    /// Nothing could be fetched, or the run itself errored.
+    Failure,
+}
+
+impl RunOutcome {
+    pub fn of(summary: &IngestSummary) -> Self {
+        match summary.status() {
+            FetchRunStatus::Succeeded => RunOutcome::Success,
+            FetchRunStatus::Partial => RunOutcome::Partial,
+            FetchRunStatus::Failed => RunOutcome::Failure,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            RunOutcome::Success => "success",
+            RunOutcome::Partial => "partial",
+            RunOutcome::Failure => "failure",
+        }
+    }
+}
+
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    fetch_duration: Histogram,
+    fetch_rows: IntCounterVec,
+    scheduler_runs: IntCounterVec,
+    grids_generated: IntCounterVec,
+    db_connections: IntGaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("euromillions".to_string()), None)
+            .expect("prefix is a valid metric name");
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latency by route"),
+            &["method", "route"],
+        )
+        .unwrap();
+        let fetch_duration = Histogram::with_opts(
+            HistogramOpts::new("fetch_duration_seconds", "Duration of a full results fetch")
+                .buckets(vec![0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0]),
+        )
+        .unwrap();
+        let fetch_rows = IntCounterVec::new(
+            Opts::new("fetch_rows_total", "Result rows by outcome: parsed, inserted or rejected"),
+            &["outcome"],
+        )
+        .unwrap();
+        let scheduler_runs = IntCounterVec::new(
+            Opts::new("scheduler_runs_total", "Scheduled fetches by outcome"),
+            &["outcome"],
+        )
+        .unwrap();
+        let grids_generated = IntCounterVec::new(
+            Opts::new("grids_generated_total", "Generated grids by strategy"),
+            &["strategy"],
+        )
+        .unwrap();
+        let db_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Database pool connections by state: active, idle or max"),
+            &["state"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_request_duration.clone())).unwrap();
+        registry.register(Box::new(fetch_duration.clone())).unwrap();
+        registry.register(Box::new(fetch_rows.clone())).unwrap();
+        registry.register(Box::new(scheduler_runs.clone())).unwrap();
+        registry.register(Box::new(grids_generated.clone())).unwrap();
+        registry.register(Box::new(db_connections.clone())).unwrap();
+
+        Metrics {
+            registry,
+            http_requests,
+            http_request_duration,
+            fetch_duration,
+            fetch_rows,
+            scheduler_runs,
+            grids_generated,
+            db_connections,
+        }
+    }
+
+    /// `route` is the matched pattern (e.g. `/grids`), never the raw path,
+    /// to keep label cardinality bounded.
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.http_requests
+            .with_label_values(&[method, route, &status.to_string()])
+            .inc();
+        self.http_request_duration
+            .with_label_values(&[method, route])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_fetch(&self, summary: &IngestSummary, elapsed: Duration) {
+        let report = &summary.report;
+        self.fetch_duration.observe(elapsed.as_secs_f64());
+        self.fetch_rows.with_label_values(&["parsed"]).inc_by(report.rows_parsed() as u64);
+        self.fetch_rows.with_label_values(&["inserted"]).inc_by(summary.inserted as u64);
+        self.fetch_rows.with_label_values(&["rejected"]).inc_by(report.rows_rejected() as u64);
+    }
+
+    pub fn observe_scheduler_run(&self, outcome: RunOutcome) {
+        self.scheduler_runs.with_label_values(&[outcome.as_str()]).inc();
+    }
+
+    pub fn observe_generated(&self, strategy: Strategy, count: usize) {
+        self.grids_generated
+            .with_label_values(&[strategy.as_str()])
+            .inc_by(count as u64);
+    }
+
+    pub fn set_pool_status(&self, pool: PoolStatus) {
+        let idle = pool.idle as i64;
+        self.db_connections.with_label_values(&["active"]).set(pool.size as i64 - idle);
+        self.db_connections.with_label_values(&["idle"]).set(idle);
+        self.db_connections.with_label_values(&["max"]).set(pool.max as i64);
+    }
+
+    /// Every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("encoding metrics cannot fail");
+        String::from_utf8(buffer).expect("metrics are valid UTF-8")
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
@@ -2,16 +2,19 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use chrono::NaiveDate;
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
 pub struct Draw {
     pub id: i32,
+    /// Id of the game in [`crate::games`].
+    pub game: String,
     pub date: NaiveDate,
     pub numbers: Vec<i32>,
     pub stars: Vec<i32>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NewDraw {
+    pub game: String,
     pub date: NaiveDate,
     pub numbers: Vec<i32>,
     pub stars: Vec<i32>,
@@ -20,15 +23,334 @@ pub struct NewDraw {
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Grid {
     pub id: i32,
+    pub game: String,
     pub draw_date: NaiveDate,
     pub numbers: Vec<i32>,
     pub stars: Vec<i32>,
     pub created_at: Option<chrono::NaiveDateTime>,
+    pub user_id: Option<i32>,
+    /// Price of the line for its draw, in cents of the game's currency.
+    pub cost_cents: Option<i64>,
+    /// Add-ons bought with the line, included in `cost_cents`.
+    pub addons: Vec<String>,
+    /// One of [`crate::grids::GridStatus`].
+    pub status: String,
+    /// When the line was marked as played, kept once it is checked or
+    /// archived.
+    pub played_at: Option<chrono::NaiveDateTime>,
+    /// The generation request that created the grid.
+    pub batch_id: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NewGrid {
+    pub game: String,
     pub draw_date: NaiveDate,
     pub numbers: Vec<i32>,
     pub stars: Vec<i32>,
+    pub user_id: Option<i32>,
+    pub cost_cents: Option<i64>,
+    pub addons: Vec<String>,
+}
+
+/// A grid to mark played, with the add-ons it is bought with and what it
+/// then costs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GridPlay {
+    pub id: i32,
+    pub addons: Vec<String>,
+    pub cost_cents: Option<i64>,
+}
+
+/// Price of a line for the draws of `game` from `valid_from` on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
+pub struct TicketPrice {
+    pub game: String,
+    pub valid_from: NaiveDate,
+    pub price_cents: i64,
+}
+
+/// Price of `addon`, an option bought with a line such as a second draw, for
+/// the draws of `game` from `valid_from` on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
+pub struct TicketAddon {
+    pub game: String,
+    pub addon: String,
+    pub valid_from: NaiveDate,
+    pub price_cents: i64,
+}
+
+/// A winning raffle code of a draw. `kind` is `my_million` or
+/// `millionaire_maker`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
+pub struct RaffleCode {
+    pub id: i32,
+    pub draw_id: i32,
+    pub draw_date: NaiveDate,
+    pub kind: String,
+    pub code: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewRaffleCode {
+    pub kind: String,
+    pub code: String,
+}
+
+/// What a prize tier paid in a draw. For tier 1 of a game with a rolling
+/// jackpot, `amount_cents` is the jackpot, whether or not it was won.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
+pub struct DrawPrize {
+    pub id: i32,
+    pub draw_id: i32,
+    pub draw_date: NaiveDate,
+    pub tier: i32,
+    /// Per winner, in cents of the game's currency.
+    pub amount_cents: i64,
+    /// `None` when not known, e.g. for scraped jackpots.
+    pub winners: Option<i32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewDrawPrize {
+    pub tier: i32,
+    pub amount_cents: i64,
+    pub winners: Option<i32>,
+}
+
+/// How many times a number or star has been drawn.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
+pub struct BallFrequency {
+    pub ball: i32,
+    pub count: i64,
+}
+
+/// A results page stored in the raw archive. `body_gzip` holds the
+/// compressed HTML exactly as it was downloaded.
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ArchivedPage {
+    pub id: i32,
+    pub url: String,
+    pub year: Option<i32>,
+    pub http_status: i32,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub content_sha256: String,
+    pub content_length: i32,
+    #[serde(skip)]
+    pub body_gzip: Vec<u8>,
+    pub fetched_at: chrono::NaiveDateTime,
+    pub last_seen_at: chrono::NaiveDateTime,
+    pub fetch_count: i32,
+}
+
+#[derive(Debug)]
+pub struct NewArchivedPage {
+    pub url: String,
+    pub year: Option<i32>,
+    pub http_status: i32,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub content_sha256: String,
+    pub content_length: i32,
+    pub body_gzip: Vec<u8>,
+}
+
+/// One fetch of the results site. `trigger` is `cron`, `manual` or `cli`;
+/// `status` is `running`, `succeeded`, `partial` or `failed`.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct FetchRun {
+    pub id: i32,
+    pub trigger: String,
+    pub source: String,
+    pub years: Vec<i32>,
+    pub status: String,
+    pub started_at: chrono::NaiveDateTime,
+    pub finished_at: Option<chrono::NaiveDateTime>,
+    pub rows_parsed: i32,
+    pub rows_inserted: i32,
+    pub rows_rejected: i32,
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct NewFetchRun {
+    pub trigger: String,
+    pub source: String,
+    pub years: Vec<i32>,
+}
+
+/// Final state of a fetch run.
+#[derive(Debug)]
+pub struct FinishedFetchRun {
+    pub status: String,
+    pub rows_parsed: i32,
+    pub rows_inserted: i32,
+    pub rows_rejected: i32,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct User {
+    pub id: i32,
+    pub name: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// Where and when a user wants to hear about results. `kind` is `webhook`,
+/// `email`, `slack`, `discord` or `telegram`.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct NotificationChannel {
+    pub id: i32,
+    pub user_id: i32,
+    pub kind: String,
+    pub target: String,
+    /// Send every new result.
+    pub notify_results: bool,
+    /// Send when one of the user's grids wins, even if `notify_results` is off.
+    pub notify_wins: bool,
+    pub enabled: bool,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewNotificationChannel {
+    pub user_id: i32,
+    pub kind: String,
+    pub target: String,
+    pub notify_results: bool,
+    pub notify_wins: bool,
+}
+
+/// A user's defaults for generating grids of one game. `strategy` and
+/// `grid_count` apply when a request leaves them out; the number lists are
+/// added to the request's own constraints.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
+pub struct GenerationProfile {
+    pub user_id: i32,
+    pub game: String,
+    /// Numbers every grid holds.
+    pub forced_numbers: Vec<i32>,
+    /// Stars or other bonus balls every grid holds.
+    pub forced_stars: Vec<i32>,
+    /// Numbers no grid holds.
+    pub banned_numbers: Vec<i32>,
+    pub strategy: Option<String>,
+    pub grid_count: Option<i32>,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NewGenerationProfile {
+    pub forced_numbers: Vec<i32>,
+    pub forced_stars: Vec<i32>,
+    pub banned_numbers: Vec<i32>,
+    pub strategy: Option<String>,
+    pub grid_count: Option<i32>,
+}
+
+/// One generation request: the options it ran with, and the key a client
+/// retries it with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
+pub struct GenerationBatch {
+    pub id: i32,
+    pub idempotency_key: Option<String>,
+    pub game: String,
+    pub user_id: Option<i32>,
+    pub strategy: String,
+    /// JSON text of the generation options.
+    pub params: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewGenerationBatch {
+    pub idempotency_key: Option<String>,
+    pub game: String,
+    pub user_id: Option<i32>,
+    pub strategy: String,
+    pub params: String,
+}
+
+/// A user's spending cap over a `week` or a `month`, in cents of `currency`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
+pub struct Budget {
+    pub user_id: i32,
+    pub period: String,
+    pub currency: String,
+    pub limit_cents: i64,
+}
+
+/// What a user may spend on the lines of `games` played for draws from
+/// `from` to `to`, both included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpendingCap {
+    pub user_id: i32,
+    pub games: Vec<String>,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub limit_cents: i64,
+}
+
+/// A machine subscription to events such as `draw.created`. Payloads are
+/// signed with `secret`, which is only returned when the webhook is created.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Webhook {
+    pub id: i32,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub events: Vec<String>,
+    pub enabled: bool,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewWebhook {
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<String>,
+}
+
+/// One event sent to one webhook, with the outcome of the latest attempt.
+/// `status` is `pending`, `delivered` or `failed`.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: i32,
+    pub webhook_id: i32,
+    pub event_id: i32,
+    pub event: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: Option<chrono::NaiveDateTime>,
+    /// HTTP status of the last attempt, if the endpoint answered.
+    pub response_status: Option<i32>,
+    pub error: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub delivered_at: Option<chrono::NaiveDateTime>,
+}
+
+/// A due delivery with everything needed to send it.
+#[derive(Debug, Clone, FromRow)]
+pub struct PendingDelivery {
+    pub id: i32,
+    pub webhook_id: i32,
+    pub url: String,
+    pub secret: String,
+    pub event_id: i32,
+    pub event: String,
+    /// JSON text of the event data.
+    pub payload: String,
+    pub event_created_at: chrono::NaiveDateTime,
+    pub attempts: i32,
+}
+
+/// Outcome of one delivery attempt.
+#[derive(Debug)]
+pub struct DeliveryAttempt {
+    pub status: String,
+    pub response_status: Option<i32>,
+    pub error: Option<String>,
+    /// When to try again while `status` is still `pending`.
+    pub next_attempt_at: Option<chrono::NaiveDateTime>,
 }
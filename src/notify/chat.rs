@@ -0,0 +1,102 @@
+//! Channels delivered with an HTTP POST: the generic webhook and the Slack,
+//! Discord and Telegram APIs.
+
+use super::{Channel, Message};
+use crate::error::{AppError, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+
+async fn post_json(client: &Client, url: &str, body: &serde_json::Value) -> Result<()> {
+    let response = client
+        .post(url)
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| AppError::Notify(e.to_string()))?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(AppError::Notify(format!("{} answered HTTP {}", url, status.as_u16())))
+    }
+}
+
+/// Posts the structured result (`Message::payload`) to the target URL.
+pub struct WebhookChannel {
+    client: Client,
+}
+
+impl WebhookChannel {
+    pub fn new(client: Client) -> Self {
+        WebhookChannel { client }
+    }
+}
+
+#[async_trait]
+impl Channel for WebhookChannel {
+    async fn send(&self, target: &str, message: &Message) -> Result<()> {
+        post_json(&self.client, target, &message.payload).await
+    }
+}
+
+/// Slack incoming webhook.
+pub struct SlackChannel {
+    client: Client,
+}
+
+impl SlackChannel {
+    pub fn new(client: Client) -> Self {
+        SlackChannel { client }
+    }
+}
+
+#[async_trait]
+impl Channel for SlackChannel {
+    async fn send(&self, target: &str, message: &Message) -> Result<()> {
+        post_json(&self.client, target, &serde_json::json!({ "text": message.text })).await
+    }
+}
+
+/// Discord channel webhook.
+pub struct DiscordChannel {
+    client: Client,
+}
+
+impl DiscordChannel {
+    pub fn new(client: Client) -> Self {
+        DiscordChannel { client }
+    }
+}
+
+#[async_trait]
+impl Channel for DiscordChannel {
+    async fn send(&self, target: &str, message: &Message) -> Result<()> {
+        post_json(&self.client, target, &serde_json::json!({ "content": message.text })).await
+    }
+}
+
+/// Bot API `sendMessage`; the target is a chat id or `@channel` name.
+pub struct TelegramChannel {
+    client: Client,
+    send_url: String,
+}
+
+impl TelegramChannel {
+    pub fn new(client: Client, api_url: &str, bot_token: &str) -> Self {
+        TelegramChannel {
+            client,
+            send_url: format!("{}/bot{}/sendMessage", api_url.trim_end_matches('/'), bot_token),
+        }
+    }
+}
+
+#[async_trait]
+impl Channel for TelegramChannel {
+    async fn send(&self, target: &str, message: &Message) -> Result<()> {
+        let body = serde_json::json!({ "chat_id": target, "text": message.text });
+        // The URL embeds the bot token, keep it out of error messages
+        post_json(&self.client, &self.send_url, &body)
+            .await
+            .map_err(|_| AppError::Notify(format!("Telegram rejected the message for chat {}", target)))
+    }
+}
@@ -0,0 +1,63 @@
+use super::{Channel, Message};
+use crate::config::{SmtpConfig, SmtpTls};
+use crate::error::{AppError, Result};
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use std::time::Duration;
+
+/// Plain-text email through the configured SMTP relay.
+pub struct EmailChannel {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl EmailChannel {
+    pub fn new(config: &SmtpConfig, timeout: Duration) -> Result<Self> {
+        let host = config
+            .host
+            .as_deref()
+            .ok_or_else(|| AppError::Config("notify.smtp.host must be set for email".to_string()))?;
+        let smtp_error = |e: lettre::transport::smtp::Error| AppError::Config(format!("invalid SMTP settings: {}", e));
+        let builder = match config.tls {
+            SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host).map_err(smtp_error)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host).map_err(smtp_error)?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        };
+        let mut builder = builder.port(config.port).timeout(Some(timeout));
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        let from = config
+            .from
+            .parse()
+            .map_err(|_| AppError::Config(format!("notify.smtp.from is not a valid address: '{}'", config.from)))?;
+        Ok(EmailChannel {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Channel for EmailChannel {
+    async fn send(&self, target: &str, message: &Message) -> Result<()> {
+        let to: Mailbox = target
+            .parse()
+            .map_err(|_| AppError::Notify(format!("'{}' is not a valid email address", target)))?;
+        let email = lettre::Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(&message.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(message.text.clone())
+            .map_err(|e| AppError::Notify(e.to_string()))?;
+        self.transport
+            .send(email)
+            .await
+            .map_err(|e| AppError::Notify(format!("SMTP delivery to {} failed: {}", target, e)))?;
+        Ok(())
+    }
+}
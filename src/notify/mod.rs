@@ -0,0 +1,386 @@
+//! Result notifications. After a fetch stores a new draw, every user with an
+//! enabled channel receives the result together with the lines they played
+//! for that draw and the prize tier each one reached.
+//!
+//! Delivery goes through pluggable [`Channel`]s registered on a [`Notifier`]
+//! by kind; the channels users configure are rows in `notification_channels`.
+
+mod chat;
+mod email;
+
+pub use self::chat::{DiscordChannel, SlackChannel, TelegramChannel, WebhookChannel};
+pub use self::email::EmailChannel;
+
+use crate::checker::{self, CheckResult};
+use crate::config::{NotifyConfig, TemplateConfig};
+use crate::error::{AppError, Result};
+use crate::games::EUROMILLIONS;
+use crate::ingest::IngestSummary;
+use crate::models::{Draw, Grid, NotificationChannel};
+use crate::outbound;
+use crate::storage::Storage;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::Instrument;
+
+/// Where a notification is delivered, stored in `notification_channels.kind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelKind {
+    /// JSON POST of the full result to any URL.
+    Webhook,
+    Email,
+    /// Slack incoming webhook URL.
+    Slack,
+    /// Discord channel webhook URL.
+    Discord,
+    /// Telegram chat id, sent through the configured bot.
+    Telegram,
+}
+
+impl ChannelKind {
+    pub const ALL: [ChannelKind; 5] = [
+        ChannelKind::Webhook,
+        ChannelKind::Email,
+        ChannelKind::Slack,
+        ChannelKind::Discord,
+        ChannelKind::Telegram,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChannelKind::Webhook => "webhook",
+            ChannelKind::Email => "email",
+            ChannelKind::Slack => "slack",
+            ChannelKind::Discord => "discord",
+            ChannelKind::Telegram => "telegram",
+        }
+    }
+
+    /// Checks that `target` makes sense for this kind of channel. URLs must
+    /// be public https ones unless `allow_private_urls`; see [`outbound`].
+    pub fn validate_target(self, target: &str, allow_private_urls: bool) -> Result<()> {
+        let valid = match self {
+            ChannelKind::Webhook | ChannelKind::Slack | ChannelKind::Discord => {
+                return outbound::check_url(target, allow_private_urls).map(|_| ());
+            }
+            ChannelKind::Email => target.parse::<lettre::Address>().is_ok(),
+            ChannelKind::Telegram => {
+                target.parse::<i64>().is_ok() || (target.starts_with('@') && target.len() > 1)
+            }
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(AppError::Validation(format!(
+                "'{}' is not a valid {} target",
+                target,
+                self.as_str()
+            )))
+        }
+    }
+}
+
+impl fmt::Display for ChannelKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ChannelKind {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        ChannelKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == s)
+            .ok_or_else(|| {
+                let kinds: Vec<_> = ChannelKind::ALL.iter().map(|k| k.as_str()).collect();
+                AppError::Validation(format!(
+                    "unknown channel kind '{}', expected one of {}",
+                    s,
+                    kinds.join(", ")
+                ))
+            })
+    }
+}
+
+/// A rendered notification. Chat channels send `text`, email adds
+/// `subject`, and the generic webhook posts `payload`.
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub subject: String,
+    pub text: String,
+    pub payload: serde_json::Value,
+}
+
+#[async_trait]
+pub trait Channel: Send + Sync {
+    async fn send(&self, target: &str, message: &Message) -> Result<()>;
+}
+
+/// The registered channels plus the templates messages are rendered with.
+pub struct Notifier {
+    templates: TemplateConfig,
+    channels: HashMap<ChannelKind, Arc<dyn Channel>>,
+}
+
+impl Notifier {
+    /// A notifier without any channel: every notification is skipped.
+    pub fn new(templates: TemplateConfig) -> Self {
+        Notifier {
+            templates,
+            channels: HashMap::new(),
+        }
+    }
+
+    /// Registers every channel the configuration allows. Email needs an SMTP
+    /// host and Telegram a bot token; the webhook kinds are always available.
+    pub fn from_config(config: &NotifyConfig) -> Result<Self> {
+        let client = outbound::client_builder(config.allow_private_urls)
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()?;
+        let mut notifier = Notifier::new(config.templates.clone())
+            .with_channel(ChannelKind::Webhook, WebhookChannel::new(client.clone()))
+            .with_channel(ChannelKind::Slack, SlackChannel::new(client.clone()))
+            .with_channel(ChannelKind::Discord, DiscordChannel::new(client.clone()));
+        if let Some(token) = &config.telegram.bot_token {
+            notifier = notifier.with_channel(
+                ChannelKind::Telegram,
+                TelegramChannel::new(client, &config.telegram.api_url, token),
+            );
+        }
+        if config.smtp.host.is_some() {
+            let timeout = Duration::from_secs(config.timeout_secs);
+            notifier = notifier.with_channel(ChannelKind::Email, EmailChannel::new(&config.smtp, timeout)?);
+        }
+        Ok(notifier)
+    }
+
+    pub fn with_channel(mut self, kind: ChannelKind, channel: impl Channel + 'static) -> Self {
+        self.channels.insert(kind, Arc::new(channel));
+        self
+    }
+
+    pub fn supports(&self, kind: ChannelKind) -> bool {
+        self.channels.contains_key(&kind)
+    }
+
+    pub fn templates(&self) -> &TemplateConfig {
+        &self.templates
+    }
+
+    pub async fn send(&self, kind: ChannelKind, target: &str, message: &Message) -> Result<()> {
+        let channel = self
+            .channels
+            .get(&kind)
+            .ok_or_else(|| AppError::Notify(format!("{} notifications are not configured", kind)))?;
+        channel.send(target, message).await
+    }
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Notifier::new(TemplateConfig::default())
+    }
+}
+
+/// Replaces every `{name}` in `template` with its value. Unknown
+/// placeholders are left as they are.
+pub fn render(template: &str, values: &[(&str, String)]) -> String {
+    values.iter().fold(template.to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{}}}", name), value)
+    })
+}
+
+fn join(balls: &[i32]) -> String {
+    balls.iter().map(|b| b.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+/// A user's grid for the announced draw and how it did.
+#[derive(Debug, Serialize)]
+struct CheckedGrid<'a> {
+    id: i32,
+    numbers: &'a [i32],
+    stars: &'a [i32],
+    #[serde(flatten)]
+    result: CheckResult,
+}
+
+/// Builds the message announcing `draw` to one user.
+pub fn draw_message(templates: &TemplateConfig, draw: &Draw, grids: &[(&Grid, CheckResult)]) -> Message {
+    let date = draw.date.to_string();
+    let mut lines = vec![render(
+        &templates.draw,
+        &[
+            ("date", date.clone()),
+            ("numbers", join(&draw.numbers)),
+            ("stars", join(&draw.stars)),
+        ],
+    )];
+    for (grid, result) in grids {
+        let prize = match result.tier {
+            Some(tier) => format!("prize tier {}", tier),
+            None => "no prize".to_string(),
+        };
+        lines.push(render(
+            &templates.grid,
+            &[
+                ("numbers", join(&grid.numbers)),
+                ("stars", join(&grid.stars)),
+                ("matched_numbers", result.matched_numbers.len().to_string()),
+                ("matched_stars", result.matched_stars.len().to_string()),
+                ("prize", prize),
+            ],
+        ));
+    }
+
+    let checked: Vec<_> = grids
+        .iter()
+        .map(|(grid, result)| CheckedGrid {
+            id: grid.id,
+            numbers: &grid.numbers,
+            stars: &grid.stars,
+            result: result.clone(),
+        })
+        .collect();
+    Message {
+        subject: render(&templates.subject, &[("date", date)]),
+        text: lines.join("\n"),
+        payload: serde_json::json!({
+            "event": "draw.result",
+            "draw": draw,
+            "grids": checked,
+        }),
+    }
+}
+
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct NotifySummary {
+    pub sent: usize,
+    /// Channels whose preferences did not ask for this result.
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+/// Whether a channel wants this result: always with `notify_results`,
+/// otherwise only when one of the user's grids won something.
+fn wants(channel: &NotificationChannel, won: bool) -> bool {
+    channel.notify_results || (channel.notify_wins && won)
+}
+
+/// Sends `draw` to every enabled channel. Delivery failures are logged and
+/// counted; only storage errors abort.
+#[tracing::instrument(skip_all, fields(draw_date = %draw.date))]
+pub async fn announce(storage: &dyn Storage, notifier: &Notifier, draw: &Draw) -> Result<NotifySummary> {
+    // Checked even without channels: the checker emits `grid.result` events
+    let checked = checker::check_grids(storage, draw).await?;
+    let channels = storage.enabled_notification_channels().await?;
+    let mut summary = NotifySummary::default();
+    if channels.is_empty() {
+        return Ok(summary);
+    }
+
+    let mut by_user: HashMap<i32, Vec<(&Grid, CheckResult)>> = HashMap::new();
+    for (grid, result) in &checked {
+        if let Some(user_id) = grid.user_id {
+            by_user.entry(user_id).or_default().push((grid, result.clone()));
+        }
+    }
+
+    let mut messages: HashMap<i32, Message> = HashMap::new();
+    for channel in &channels {
+        let user_grids = by_user.get(&channel.user_id).map(Vec::as_slice).unwrap_or_default();
+        let won = user_grids.iter().any(|(_, result)| result.is_winning());
+        if !wants(channel, won) {
+            summary.skipped += 1;
+            continue;
+        }
+        let message = messages
+            .entry(channel.user_id)
+            .or_insert_with(|| draw_message(notifier.templates(), draw, user_grids));
+
+        let sent = match channel.kind.parse::<ChannelKind>() {
+            Ok(kind) => notifier.send(kind, &channel.target, message).await,
+            Err(e) => Err(e),
+        };
+        match sent {
+            Ok(()) => summary.sent += 1,
+            Err(e) => {
+                summary.failed += 1;
+                tracing::warn!(
+                    channel_id = channel.id,
+                    user_id = channel.user_id,
+                    kind = %channel.kind,
+                    error = %e,
+                    "Notification failed"
+                );
+            }
+        }
+    }
+
+    tracing::info!(sent = summary.sent, skipped = summary.skipped, failed = summary.failed, "Draw announced");
+    Ok(summary)
+}
+
+/// Checks the played lines of every EuroMillions draw a fetch stored and
+/// announces the most recent one, if any. Older draws of the same fetch (a
+/// backfill) are not announced. Errors are only logged so a notification
+/// problem never fails the fetch.
+pub async fn announce_new_draws(storage: &dyn Storage, notifier: &Notifier, summary: &IngestSummary) {
+    announce_latest(storage, notifier, &summary.new_draws).await;
+}
+
+/// [`announce_new_draws`] in a background task, for request handlers that
+/// should not wait on every subscriber's delivery.
+pub fn spawn_announce_new_draws(storage: Arc<dyn Storage>, notifier: Arc<Notifier>, summary: &IngestSummary) {
+    if summary.new_draws.is_empty() {
+        return;
+    }
+    let new_draws = summary.new_draws.clone();
+    tokio::spawn(
+        async move { announce_latest(storage.as_ref(), &notifier, &new_draws).await }.in_current_span(),
+    );
+}
+
+/// [`announce`] in a background task; errors are logged.
+pub fn spawn_announce(storage: Arc<dyn Storage>, notifier: Arc<Notifier>, draw: Draw) {
+    tokio::spawn(
+        async move {
+            if let Err(e) = announce(storage.as_ref(), &notifier, &draw).await {
+                tracing::error!(game = %draw.game, date = %draw.date, error = %e, "Failed to announce draw");
+            }
+        }
+        .in_current_span(),
+    );
+}
+
+async fn announce_latest(storage: &dyn Storage, notifier: &Notifier, new_draws: &[NaiveDate]) {
+    let Some(date) = new_draws.iter().max().copied() else {
+        return;
+    };
+    // The latest draw is checked by `announce`, which reports the results
+    for &older in new_draws.iter().filter(|d| **d != date) {
+        let checked = match storage.get_draw(EUROMILLIONS.id, older).await {
+            Ok(Some(draw)) => checker::check_grids(storage, &draw).await.map(|_| ()),
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = checked {
+            tracing::error!(date = %older, error = %e, "Failed to check grids");
+        }
+    }
+    let result = match storage.get_draw(EUROMILLIONS.id, date).await {
+        Ok(Some(draw)) => announce(storage, notifier, &draw).await.map(|_| ()),
+        Ok(None) => Ok(()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        tracing::error!(%date, error = %e, "Failed to announce draw");
+    }
+}
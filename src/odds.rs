@@ -0,0 +1,287 @@
+//! Exact odds of every prize tier, counted from the game's combinatorics
+//! (5 of 50 and 2 of 12 for EuroMillions), and the expected value of a
+//! ticket from the stored jackpots and tier payouts.
+//!
+//! The expected value is per ticket and does not split prizes between
+//! winners: it is the number to compare with the price, not a forecast.
+
+use crate::error::{AppError, Result};
+use crate::games::GameRules;
+use crate::ledger;
+use crate::models::{DrawPrize, NewDrawPrize};
+use crate::storage::Storage;
+use chrono::{Datelike, Months, NaiveDate};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// How many months after the next draw the expected value can be asked for.
+const HORIZON_MONTHS: u32 = 12;
+
+fn choose(n: usize, k: usize) -> u64 {
+    if k > n {
+        return 0;
+    }
+    let k = k.min(n - k);
+    // Each partial product is itself a binomial coefficient, so the
+    // division is always exact.
+    (0..k).fold(1u64, |acc, i| acc * (n - i) as u64 / (i as u64 + 1))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TierOdds {
+    pub tier: u8,
+    /// Outcomes of the draw that pay this tier to a given line.
+    pub combinations: u64,
+    pub probability: f64,
+    /// The usual "1 in N" form.
+    pub one_in: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Odds {
+    pub game: &'static str,
+    /// Every possible outcome of a draw.
+    pub combinations: u64,
+    /// Ordered from the jackpot down.
+    pub tiers: Vec<TierOdds>,
+    /// Probability of winning any prize.
+    pub any_prize: f64,
+}
+
+/// Odds of every tier for a single line.
+pub fn odds(game: &GameRules) -> Odds {
+    let numbers = game.numbers.count;
+    let others = game.numbers.max as usize - numbers;
+
+    let mut by_tier: BTreeMap<u8, u64> = BTreeMap::new();
+    let mut combinations = 0;
+    for matched in 0..=numbers {
+        let number_ways = choose(numbers, matched) * choose(others, numbers - matched);
+        // Bonus balls come from their own drum, or from the balls left once
+        // the numbers are out; then only the line's unmatched numbers count.
+        let (picked, pool) = if game.bonus_from_numbers() {
+            (numbers - matched, others)
+        } else {
+            (game.bonus.count, game.bonus.max as usize)
+        };
+        for bonus in 0..=game.drawn_bonus {
+            let ways = number_ways * choose(picked, bonus) * choose(pool - picked, game.drawn_bonus - bonus);
+            combinations += ways;
+            if let Some(tier) = game.prize_tier(matched, bonus) {
+                *by_tier.entry(tier).or_default() += ways;
+            }
+        }
+    }
+
+    let tiers: Vec<TierOdds> = by_tier
+        .into_iter()
+        .map(|(tier, ways)| TierOdds {
+            tier,
+            combinations: ways,
+            probability: ways as f64 / combinations as f64,
+            one_in: combinations as f64 / ways as f64,
+        })
+        .collect();
+    Odds {
+        game: game.id,
+        combinations,
+        any_prize: tiers.iter().map(|t| t.probability).sum(),
+        tiers,
+    }
+}
+
+/// A jackpot found on a results page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ParsedJackpot {
+    pub draw_date: NaiveDate,
+    pub amount_cents: i64,
+}
+
+/// Reads an amount such as `€17,000,000` or `£2,500,000.50` into cents.
+pub fn parse_amount(text: &str) -> Option<i64> {
+    let text: String = text.chars().filter(|c| c.is_ascii_digit() || *c == '.').collect();
+    let (units, cents) = text.split_once('.').unwrap_or((&text, ""));
+    let units: i64 = units.parse().ok()?;
+    let cents: i64 = match cents.len() {
+        0 => 0,
+        2 => cents.parse().ok()?,
+        _ => return None,
+    };
+    Some(units * 100 + cents)
+}
+
+/// Stores scraped jackpots as the tier 1 payout of draws that are already
+/// stored, keeping any known winner count.
+pub async fn store_jackpots(storage: &dyn Storage, game: &GameRules, jackpots: &[ParsedJackpot]) -> Result<usize> {
+    let mut written = 0;
+    for jackpot in jackpots {
+        let Some(draw) = storage.get_draw(game.id, jackpot.draw_date).await? else {
+            continue;
+        };
+        let prize = NewDrawPrize {
+            tier: 1,
+            amount_cents: jackpot.amount_cents,
+            winners: None,
+        };
+        written += storage.upsert_draw_prizes(draw.id, &[prize]).await?;
+    }
+    Ok(written)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JackpotSource {
+    /// The jackpot stored for the draw itself.
+    Stored,
+    /// Grown from an earlier stored jackpot.
+    Projected,
+    /// Nothing stored: the game's minimum.
+    Minimum,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JackpotEstimate {
+    pub amount_cents: i64,
+    pub source: JackpotSource,
+    /// Draw of the stored jackpot a projection started from.
+    pub based_on: Option<NaiveDate>,
+    /// Draws without a winner assumed since `based_on`.
+    pub rollovers: u32,
+    pub capped: bool,
+    /// Part of the projected jackpot above the cap, paid to the next tier.
+    pub rolldown_cents: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TierValue {
+    pub tier: u8,
+    pub probability: f64,
+    /// Payout per winner; `None` when nothing is stored for the tier.
+    pub payout_cents: Option<f64>,
+    pub expected_cents: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpectedValue {
+    pub game: &'static str,
+    pub date: NaiveDate,
+    pub price_cents: i64,
+    pub currency: &'static str,
+    pub jackpot: Option<JackpotEstimate>,
+    pub tiers: Vec<TierValue>,
+    /// Tiers left out of the expected value for lack of stored payouts.
+    pub missing_tiers: Vec<u8>,
+    /// What a ticket wins on average.
+    pub expected_cents: f64,
+    /// `expected_cents` minus the price.
+    pub net_cents: f64,
+    /// Expected winnings per unit staked.
+    pub return_ratio: f64,
+}
+
+/// Projects the jackpot of the draw on `date` from the stored ones. A
+/// jackpot without a known winner is assumed to have rolled over.
+fn estimate_jackpot(game: &GameRules, prizes: &[DrawPrize], date: NaiveDate) -> Option<JackpotEstimate> {
+    let rules = game.jackpot?;
+    let mut estimate = JackpotEstimate {
+        amount_cents: rules.minimum_cents,
+        source: JackpotSource::Minimum,
+        based_on: None,
+        rollovers: 0,
+        capped: false,
+        rolldown_cents: 0,
+    };
+
+    let previous = prizes.iter().rfind(|p| p.tier == 1 && p.draw_date <= date);
+    if let Some(previous) = previous {
+        if previous.draw_date == date {
+            estimate.amount_cents = previous.amount_cents;
+            estimate.source = JackpotSource::Stored;
+        } else {
+            let draws = game.draws_between(previous.draw_date, date);
+            estimate.source = JackpotSource::Projected;
+            estimate.based_on = Some(previous.draw_date);
+            if previous.winners.unwrap_or(0) > 0 {
+                estimate.rollovers = draws - 1;
+                estimate.amount_cents = rules.minimum_cents;
+            } else {
+                estimate.rollovers = draws;
+                estimate.amount_cents = previous.amount_cents;
+            }
+            estimate.amount_cents += rules.rollover_cents * estimate.rollovers as i64;
+        }
+    }
+
+    if let Some(cap) = rules.cap_cents {
+        if estimate.amount_cents >= cap {
+            estimate.capped = true;
+            estimate.rolldown_cents = estimate.amount_cents - cap;
+            estimate.amount_cents = cap;
+        }
+    }
+    Some(estimate)
+}
+
+/// Expected value of one line in the draw of `date`, against the price of
+/// a line for that draw. Tier payouts are averaged over every stored draw of
+/// the game; the jackpot is the stored or projected one, and anything above
+/// the cap is shared by the winners of the next tier.
+pub async fn expected_value(storage: &dyn Storage, game: &'static GameRules, date: NaiveDate) -> Result<ExpectedValue> {
+    if !game.draw_days.contains(&date.weekday()) {
+        return Err(AppError::Validation(format!("there is no {} draw on {}", game.name, date)));
+    }
+    let horizon = game
+        .next_draw_date()
+        .checked_add_months(Months::new(HORIZON_MONTHS))
+        .expect("the next draw is far from the end of time");
+    if date > horizon {
+        return Err(AppError::Validation(format!(
+            "{} is more than {} months after the next {} draw",
+            date, HORIZON_MONTHS, game.name
+        )));
+    }
+    let prizes = storage.list_draw_prizes(game.id).await?;
+    let jackpot = estimate_jackpot(game, &prizes, date);
+
+    let average = |tier: u8, value: fn(&DrawPrize) -> Option<f64>| {
+        let values: Vec<f64> = prizes.iter().filter(|p| p.tier == tier as i32).filter_map(value).collect();
+        (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+    };
+
+    let mut tiers = Vec::new();
+    let mut missing_tiers = Vec::new();
+    for odds in odds(game).tiers {
+        let mut payout = match &jackpot {
+            Some(jackpot) if odds.tier == 1 => Some(jackpot.amount_cents as f64),
+            _ => average(odds.tier, |p| Some(p.amount_cents as f64)),
+        };
+        if let Some(jackpot) = jackpot.as_ref().filter(|j| odds.tier == 2 && j.rolldown_cents > 0) {
+            let winners = average(odds.tier, |p| p.winners.map(f64::from)).unwrap_or(1.0).max(1.0);
+            payout = Some(payout.unwrap_or(0.0) + jackpot.rolldown_cents as f64 / winners);
+        }
+        if payout.is_none() {
+            missing_tiers.push(odds.tier);
+        }
+        tiers.push(TierValue {
+            tier: odds.tier,
+            probability: odds.probability,
+            payout_cents: payout,
+            expected_cents: odds.probability * payout.unwrap_or(0.0),
+        });
+    }
+
+    let expected_cents: f64 = tiers.iter().map(|t| t.expected_cents).sum();
+    let price_cents = ledger::price_cents(storage, game, date).await?;
+    Ok(ExpectedValue {
+        game: game.id,
+        date,
+        price_cents,
+        currency: game.currency,
+        jackpot,
+        tiers,
+        missing_tiers,
+        expected_cents,
+        net_cents: expected_cents - price_cents as f64,
+        return_ratio: expected_cents / price_cents as f64,
+    })
+}
@@ -0,0 +1,116 @@
+//! URLs supplied by API callers that the server later POSTs to: webhook,
+//! Slack and Discord channels and outbound webhook subscriptions. They must
+//! be https and must not name a loopback, private or link-local host, so a
+//! caller cannot make the server reach internal services.
+//!
+//! A public name can still resolve, or later rebind, to an internal
+//! address, so the clients posting to these URLs also check the addresses
+//! they connect to; see [`client_builder`].
+
+use crate::error::{AppError, Result};
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+
+/// Parses `url` and checks that the server may post to it. With
+/// `allow_private`, meant for local development, any http(s) URL passes.
+pub fn check_url(url: &str, allow_private: bool) -> Result<reqwest::Url> {
+    let parsed = reqwest::Url::parse(url)
+        .ok()
+        .filter(|parsed| matches!(parsed.scheme(), "http" | "https"))
+        .ok_or_else(|| AppError::Validation(format!("'{}' is not an http(s) URL", url)))?;
+    if allow_private {
+        return Ok(parsed);
+    }
+    if parsed.scheme() != "https" {
+        return Err(AppError::Validation(format!("'{}' is not an https URL", url)));
+    }
+    let host = parsed.host_str().unwrap_or_default().trim_start_matches('[').trim_end_matches(']');
+    let private = match host.parse::<IpAddr>() {
+        Ok(ip) => is_private_ip(ip),
+        Err(_) => is_internal_name(host),
+    };
+    if private {
+        return Err(AppError::Validation(format!(
+            "'{}' points to a private or local host",
+            url
+        )));
+    }
+    Ok(parsed)
+}
+
+/// A client builder for posting to checked URLs. It follows no redirect,
+/// which could lead to an internal host, and unless `allow_private` it
+/// refuses to connect to a name that resolves to a private address.
+pub fn client_builder(allow_private: bool) -> reqwest::ClientBuilder {
+    let builder = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none());
+    if allow_private {
+        builder
+    } else {
+        builder.dns_resolver(Arc::new(PublicResolver))
+    }
+}
+
+/// Resolves names with the system resolver and fails when any address is
+/// private.
+pub struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0)).await?.collect();
+            if addrs.iter().any(|addr| is_private_ip(addr.ip())) {
+                return Err(format!("'{}' resolves to a private or local address", host).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Addresses that are not reachable from the public internet.
+pub fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_v4(ip),
+            None => is_private_v6(ip),
+        },
+    }
+}
+
+fn is_private_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        // Carrier-grade NAT, 100.64.0.0/10
+        || (a == 100 && (64..128).contains(&b))
+}
+
+fn is_private_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // Unique local, fc00::/7
+        || (first & 0xfe00) == 0xfc00
+        // Link-local, fe80::/10
+        || (first & 0xffc0) == 0xfe80
+}
+
+/// Names that only resolve inside a network: `localhost`, single labels
+/// such as `db`, and the suffixes reserved for local use.
+fn is_internal_name(host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    !host.contains('.')
+        || [".localhost", ".local", ".internal", ".lan", ".home.arpa"]
+            .iter()
+            .any(|suffix| host.ends_with(suffix))
+}
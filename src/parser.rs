@@ -0,0 +1,212 @@
+use crate::games::EUROMILLIONS;
+use crate::models::NewDraw;
+use crate::odds::{self, ParsedJackpot};
+use crate::raffle::{ParsedRaffle, RaffleKind};
+use chrono::NaiveDate;
+use scraper::{ElementRef, Html, Selector};
+use serde::Serialize;
+use std::fmt;
+
+/// Why a `tr.resultRow` could not be turned into a draw.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum RejectReason {
+    MissingDate,
+    InvalidDate { text: String },
+    InvalidBall { text: String },
+    WrongBallCount { numbers: usize, stars: usize },
+    OutOfRange { numbers: Vec<i32>, stars: Vec<i32> },
+    DuplicateBall { numbers: Vec<i32>, stars: Vec<i32> },
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectReason::MissingDate => write!(f, "no date link in first column"),
+            RejectReason::InvalidDate { text } => write!(f, "unparseable date '{}'", text),
+            RejectReason::InvalidBall { text } => write!(f, "unparseable ball '{}'", text),
+            RejectReason::WrongBallCount { numbers, stars } => {
+                write!(f, "expected 5 numbers and 2 stars, found {} and {}", numbers, stars)
+            }
+            RejectReason::OutOfRange { numbers, stars } => {
+                write!(f, "balls out of range: {:?} + {:?}", numbers, stars)
+            }
+            RejectReason::DuplicateBall { numbers, stars } => {
+                write!(f, "duplicate balls: {:?} + {:?}", numbers, stars)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RejectedRow {
+    /// Zero-based position of the row among the result rows of the page.
+    pub row: usize,
+    #[serde(flatten)]
+    pub reason: RejectReason,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ParseReport {
+    #[serde(skip)]
+    pub draws: Vec<NewDraw>,
+    /// Winning raffle codes of the parsed draws.
+    #[serde(skip)]
+    pub raffles: Vec<ParsedRaffle>,
+    /// Jackpots of the parsed draws.
+    #[serde(skip)]
+    pub jackpots: Vec<ParsedJackpot>,
+    pub rows_seen: usize,
+    pub rows_parsed: usize,
+    pub rejected: Vec<RejectedRow>,
+    /// Whether the page had any visible text at all.
+    pub has_content: bool,
+    /// Whether the page still has the results table, even an empty one.
+    pub has_results_table: bool,
+}
+
+impl ParseReport {
+    /// A page with content that yields no draw means the site markup no
+    /// longer matches our selectors, unless it is an empty results table,
+    /// as in early January before the first draw of the year.
+    pub fn markup_changed(&self) -> bool {
+        self.has_content && self.rows_parsed == 0 && (self.rows_seen > 0 || !self.has_results_table)
+    }
+}
+
+/// Parses a results-history page into draws, keeping track of every row that
+/// had to be skipped.
+#[tracing::instrument(skip_all, fields(bytes = html.len(), rows_seen, rows_parsed))]
+pub fn parse_results_page(html: &str) -> ParseReport {
+    let document = Html::parse_document(html);
+    let row_selector = Selector::parse("tr.resultRow").unwrap();
+    let date_selector = Selector::parse("td:nth-child(1) > a").unwrap();
+    let ball_selector = Selector::parse("td:nth-child(2) > ul > li").unwrap();
+    let raffle_selector = Selector::parse("td.raffles .raffle").unwrap();
+    let jackpot_selector = Selector::parse("td.jackpot").unwrap();
+    let body_selector = Selector::parse("body").unwrap();
+    let table_selector = Selector::parse("table.resultsHistory").unwrap();
+
+    let mut report = ParseReport {
+        has_content: document
+            .select(&body_selector)
+            .next()
+            .map(|body| body.text().any(|t| !t.trim().is_empty()))
+            .unwrap_or(false),
+        has_results_table: document.select(&table_selector).next().is_some(),
+        ..Default::default()
+    };
+
+    for (index, row) in document.select(&row_selector).enumerate() {
+        report.rows_seen += 1;
+        match parse_row(row, &date_selector, &ball_selector) {
+            Ok(draw) => {
+                report.rows_parsed += 1;
+                report.raffles.extend(parse_raffles(row, &raffle_selector, draw.date));
+                let jackpot = row.select(&jackpot_selector).next().map(|el| el.text().collect::<String>());
+                if let Some(amount_cents) = jackpot.as_deref().and_then(odds::parse_amount) {
+                    report.jackpots.push(ParsedJackpot { draw_date: draw.date, amount_cents });
+                }
+                report.draws.push(draw);
+            }
+            Err(reason) => {
+                tracing::debug!(row = index, %reason, "Rejected result row");
+                report.rejected.push(RejectedRow { row: index, reason });
+            }
+        }
+    }
+
+    let span = tracing::Span::current();
+    span.record("rows_seen", report.rows_seen);
+    span.record("rows_parsed", report.rows_parsed);
+    report
+}
+
+fn parse_row(
+    row: ElementRef,
+    date_selector: &Selector,
+    ball_selector: &Selector,
+) -> Result<NewDraw, RejectReason> {
+    let date_text = row
+        .select(date_selector)
+        .next()
+        .map(|el| el.text().collect::<String>())
+        .ok_or(RejectReason::MissingDate)?;
+
+    let date = parse_draw_date(&date_text).ok_or_else(|| RejectReason::InvalidDate {
+        text: date_text.split_whitespace().collect::<Vec<_>>().join(" "),
+    })?;
+
+    let mut balls = Vec::new();
+    for ball in row.select(ball_selector) {
+        let text = ball.text().collect::<String>();
+        let num = text
+            .trim()
+            .parse::<i32>()
+            .map_err(|_| RejectReason::InvalidBall { text: text.trim().to_string() })?;
+        balls.push(num);
+    }
+
+    // First 5 are numbers, next 2 are stars
+    if balls.len() != 7 {
+        return Err(RejectReason::WrongBallCount {
+            numbers: balls.len().min(5),
+            stars: balls.len().saturating_sub(5),
+        });
+    }
+    let mut numbers = balls[..5].to_vec();
+    let mut stars = balls[5..].to_vec();
+    numbers.sort();
+    stars.sort();
+
+    if numbers.iter().any(|n| !(1..=50).contains(n)) || stars.iter().any(|s| !(1..=12).contains(s)) {
+        return Err(RejectReason::OutOfRange { numbers, stars });
+    }
+    if numbers.windows(2).any(|w| w[0] == w[1]) || stars[0] == stars[1] {
+        return Err(RejectReason::DuplicateBall { numbers, stars });
+    }
+
+    Ok(NewDraw {
+        game: EUROMILLIONS.id.to_string(),
+        date,
+        numbers,
+        stars,
+    })
+}
+
+/// Winning raffle codes listed in a row. A code we cannot recognise is
+/// skipped; it never costs us the draw itself.
+fn parse_raffles(row: ElementRef, raffle_selector: &Selector, draw_date: NaiveDate) -> Vec<ParsedRaffle> {
+    row.select(raffle_selector)
+        .filter_map(|el| {
+            let text = el.text().collect::<String>();
+            match RaffleKind::detect(&text) {
+                Some((kind, code)) => Some(ParsedRaffle { draw_date, kind, code }),
+                None => {
+                    tracing::debug!(%draw_date, code = text.trim(), "Skipped unrecognised raffle code");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Parses dates such as "Tuesday\n   18th March 2025".
+pub fn parse_draw_date(text: &str) -> Option<NaiveDate> {
+    // Remove day of week (first word) and ordinal suffixes (st, nd, rd, th)
+    let parts: Vec<&str> = text.split_whitespace().collect();
+    if parts.len() < 4 {
+        return None;
+    }
+
+    //parts: ["Tuesday", "18th", "March", "2025"]
+    // We want: "18 March 2025" (without ordinal)
+    let day = parts[1]
+        .trim_end_matches("st")
+        .trim_end_matches("nd")
+        .trim_end_matches("rd")
+        .trim_end_matches("th");
+    let formatted_date = format!("{} {} {}", day, parts[2], parts[3]);
+
+    NaiveDate::parse_from_str(&formatted_date, "%d %B %Y").ok()
+}
@@ -0,0 +1,92 @@
+//! Scores how unlike the lines other players pick a line is. Prizes are
+//! shared between winners, so a line of birthdays (1-31), an arithmetic
+//! sequence or a straight line on the bet slip wins less when it hits. Only
+//! the main numbers are scored: every star or bonus ball is a plausible
+//! birthday month or lucky number.
+
+use crate::games::GameRules;
+use serde::Serialize;
+
+/// Bet slips lay the numbers out in rows of ten.
+const SLIP_COLUMNS: i32 = 10;
+
+/// Largest number that can be a day of the month.
+const LAST_BIRTHDAY: i32 = 31;
+
+const BIRTHDAY_PENALTY: f64 = 40.0;
+const SEQUENCE_PENALTY: f64 = 35.0;
+const SLIP_PENALTY: f64 = 25.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PopularityScore {
+    /// From 0, a line many players pick, to 100, nothing popular about it.
+    pub score: u8,
+    /// Numbers that can be a day of the month.
+    pub birthdays: usize,
+    /// Longest run of numbers with the same gap, like 5, 10, 15.
+    pub longest_sequence: usize,
+    /// Most numbers in a single row or column of the slip.
+    pub slip_line: usize,
+}
+
+/// Penalty growing from nothing for `free` patterned numbers to `max` when
+/// every number of the line follows the pattern.
+fn penalty(max: f64, found: f64, free: f64, count: usize) -> f64 {
+    if count as f64 <= free {
+        return 0.0;
+    }
+    max * ((found - free) / (count as f64 - free)).clamp(0.0, 1.0)
+}
+
+fn longest_sequence(sorted: &[i32]) -> usize {
+    let mut longest = sorted.len().min(2);
+    for (i, first) in sorted.iter().enumerate() {
+        for second in &sorted[i + 1..] {
+            let gap = second - first;
+            let mut length = 2;
+            let mut next = second + gap;
+            while sorted.binary_search(&next).is_ok() {
+                length += 1;
+                next += gap;
+            }
+            longest = longest.max(length);
+        }
+    }
+    longest
+}
+
+fn slip_line(numbers: &[i32]) -> usize {
+    let row = |n: i32| (n - 1) / SLIP_COLUMNS;
+    let column = |n: i32| (n - 1) % SLIP_COLUMNS;
+    let sharing = |n: i32, line: &dyn Fn(i32) -> i32| numbers.iter().filter(|m| line(**m) == line(n)).count();
+    numbers
+        .iter()
+        .map(|&n| sharing(n, &row).max(sharing(n, &column)))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Scores the main numbers of a line.
+pub fn score(game: &GameRules, numbers: &[i32]) -> PopularityScore {
+    let mut sorted = numbers.to_vec();
+    sorted.sort();
+    let count = sorted.len();
+
+    let birthdays = sorted.iter().filter(|n| **n <= LAST_BIRTHDAY).count();
+    let longest_sequence = longest_sequence(&sorted);
+    let slip_line = slip_line(&sorted);
+
+    // As many birthdays as a random line holds on average cost nothing;
+    // neither do two numbers, which always form a sequence.
+    let expected_birthdays = count as f64 * LAST_BIRTHDAY.min(game.numbers.max) as f64 / game.numbers.max as f64;
+    let total = penalty(BIRTHDAY_PENALTY, birthdays as f64, expected_birthdays, count)
+        + penalty(SEQUENCE_PENALTY, longest_sequence as f64, 2.0, count)
+        + penalty(SLIP_PENALTY, slip_line as f64, 2.0, count);
+
+    PopularityScore {
+        score: (100.0 - total).round().clamp(0.0, 100.0) as u8,
+        birthdays,
+        longest_sequence,
+        slip_line,
+    }
+}
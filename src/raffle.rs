@@ -0,0 +1,141 @@
+//! EuroMillions raffles: the French My Million and the UK Millionaire Maker.
+//! Every ticket carries a raffle code, and each draw publishes the winning
+//! codes. Codes are scraped with the results, stored per draw in
+//! `raffle_codes`, and users can check their ticket's code at any time.
+
+use crate::error::{AppError, Result};
+use crate::games::EUROMILLIONS;
+use crate::models::NewRaffleCode;
+use crate::storage::Storage;
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RaffleKind {
+    /// France: two letters and seven digits, e.g. `HZ 652 4871`.
+    MyMillion,
+    /// UK: four letters and five digits, e.g. `XBKL 53681`.
+    MillionaireMaker,
+}
+
+impl RaffleKind {
+    pub const ALL: [RaffleKind; 2] = [RaffleKind::MyMillion, RaffleKind::MillionaireMaker];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RaffleKind::MyMillion => "my_million",
+            RaffleKind::MillionaireMaker => "millionaire_maker",
+        }
+    }
+
+    fn letters(self) -> usize {
+        match self {
+            RaffleKind::MyMillion => 2,
+            RaffleKind::MillionaireMaker => 4,
+        }
+    }
+
+    /// Recognises a code as printed on a ticket or a results page, ignoring
+    /// case and spaces, and returns its kind with the canonical form.
+    pub fn detect(code: &str) -> Option<(RaffleKind, String)> {
+        let code: String = code
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_ascii_uppercase();
+        if code.len() != 9 {
+            return None;
+        }
+        let letters = code.chars().take_while(|c| c.is_ascii_uppercase()).count();
+        let kind = RaffleKind::ALL.into_iter().find(|kind| kind.letters() == letters)?;
+        code[letters..]
+            .chars()
+            .all(|c| c.is_ascii_digit())
+            .then_some((kind, code))
+    }
+}
+
+impl fmt::Display for RaffleKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for RaffleKind {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        RaffleKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == s)
+            .ok_or_else(|| AppError::Validation(format!("unknown raffle '{}'", s)))
+    }
+}
+
+/// A winning code found on a results page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ParsedRaffle {
+    pub draw_date: NaiveDate,
+    pub kind: RaffleKind,
+    pub code: String,
+}
+
+/// Stores the winning codes of EuroMillions draws that are already stored
+/// and returns how many codes were new.
+pub async fn store(storage: &dyn Storage, raffles: &[ParsedRaffle]) -> Result<usize> {
+    let mut by_date: BTreeMap<NaiveDate, Vec<NewRaffleCode>> = BTreeMap::new();
+    for raffle in raffles {
+        by_date.entry(raffle.draw_date).or_default().push(NewRaffleCode {
+            kind: raffle.kind.as_str().to_string(),
+            code: raffle.code.clone(),
+        });
+    }
+
+    let mut inserted = 0;
+    for (date, codes) in by_date {
+        match storage.get_draw(EUROMILLIONS.id, date).await? {
+            Some(draw) => inserted += storage.add_raffle_codes(draw.id, &codes).await?,
+            None => tracing::warn!(%date, codes = codes.len(), "Raffle codes for a draw that is not stored"),
+        }
+    }
+    Ok(inserted)
+}
+
+#[derive(Debug, Serialize)]
+pub struct RaffleCheck {
+    pub code: String,
+    pub kind: RaffleKind,
+    pub won: bool,
+    /// Draws in which the code won.
+    pub draws: Vec<NaiveDate>,
+}
+
+/// Looks a ticket's code up among the winning codes, of one draw or of every
+/// stored draw.
+pub async fn check(storage: &dyn Storage, code: &str, date: Option<NaiveDate>) -> Result<RaffleCheck> {
+    let (kind, code) = RaffleKind::detect(code).ok_or_else(|| {
+        AppError::Validation(format!(
+            "'{}' is not a My Million (AB 123 4567) or Millionaire Maker (ABCD 12345) code",
+            code.trim()
+        ))
+    })?;
+    let mut draws: Vec<NaiveDate> = storage
+        .find_raffle_code(kind.as_str(), &code)
+        .await?
+        .into_iter()
+        .map(|winning| winning.draw_date)
+        .collect();
+    if let Some(date) = date {
+        draws.retain(|d| *d == date);
+    }
+    Ok(RaffleCheck {
+        code,
+        kind,
+        won: !draws.is_empty(),
+        draws,
+    })
+}
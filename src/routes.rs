@@ -1,62 +1,1006 @@
-use actix_web::{web, HttpResponse, Responder};
-use crate::db;
-use crate::fetcher;
-use crate::generator;
-use crate::models::{Grid, NewGrid};
-use sqlx::PgPool;
-
-pub async fn get_history(pool: web::Data<PgPool>) -> impl Responder {
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use crate::app::AppState;
+use crate::bot::discord;
+use crate::config::Config;
+use crate::constraints::Constraints;
+use crate::error::{AppError, Result};
+use crate::games::{self, EUROMILLIONS};
+use crate::generator::{self, AntiPopular, GenerateOptions, Strategy};
+use crate::grids::{self, GridStatus};
+use crate::ingest::{self, FetchRunStatus, FetchTrigger};
+use crate::ledger::{self, BudgetPeriod, BudgetStatus};
+use crate::models::{
+    Budget, NewDraw, NewDrawPrize, NewGenerationProfile, NewNotificationChannel, NewWebhook, TicketAddon, TicketPrice, User,
+    Webhook,
+};
+use crate::notify::{self, ChannelKind};
+use crate::odds;
+use crate::outbound;
+use crate::raffle;
+use crate::status;
+use crate::webhooks::{self, Event};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::time::Instant;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/history", web::get().to(get_history))
+        .route("/generate", web::post().to(generate_grids))
+        .route("/grids", web::get().to(list_grids))
+        .route("/grids/played", web::post().to(mark_played))
+        .route("/grids/{id}/status", web::put().to(set_grid_status))
+        .route("/games", web::get().to(list_games))
+        .route("/games/{game}/draws", web::post().to(add_draw))
+        .route("/games/{game}/draws/{date}/prizes", web::put().to(set_prizes))
+        .route("/games/{game}/prices", web::get().to(list_prices))
+        .route("/games/{game}/prices/{date}", web::put().to(set_price))
+        .route("/games/{game}/addons", web::get().to(list_addons))
+        .route("/games/{game}/addons/{addon}/prices/{date}", web::put().to(set_addon_price))
+        .route("/odds", web::get().to(get_odds))
+        .route("/ev", web::get().to(get_expected_value))
+        .route("/raffles", web::get().to(list_raffles))
+        .route("/raffles/check", web::get().to(check_raffle))
+        .route("/health/live", web::get().to(health_live))
+        .route("/health/ready", web::get().to(health_ready))
+        .route("/status", web::get().to(get_status))
+        .route("/metrics", web::get().to(get_metrics))
+        .route("/fetch-runs", web::get().to(list_fetch_runs))
+        .route("/fetch-runs/{id}", web::get().to(get_fetch_run))
+        .route("/users", web::post().to(create_user))
+        .route("/users/{id}", web::get().to(get_user))
+        .route("/users/{id}/channels", web::post().to(add_channel))
+        .route("/users/{id}/channels", web::get().to(list_channels))
+        .route("/users/{id}/channels/{channel_id}", web::delete().to(delete_channel))
+        .route("/users/{id}/profiles/{game}", web::put().to(put_profile))
+        .route("/users/{id}/profiles/{game}", web::get().to(get_profile))
+        .route("/users/{id}/profiles/{game}", web::delete().to(delete_profile))
+        .route("/users/{id}/budgets", web::get().to(list_budgets))
+        .route("/users/{id}/budgets/{period}", web::put().to(set_budget))
+        .route("/users/{id}/budgets/{period}", web::delete().to(delete_budget))
+        .route("/users/{id}/ledger", web::get().to(get_ledger))
+        .route("/webhooks", web::post().to(create_webhook))
+        .route("/webhooks", web::get().to(list_webhooks))
+        .route("/webhooks/{id}", web::get().to(get_webhook))
+        .route("/webhooks/{id}", web::delete().to(delete_webhook))
+        .route("/webhooks/{id}/deliveries", web::get().to(list_deliveries))
+        .route("/webhook-deliveries/{id}", web::get().to(get_delivery))
+        .route("/webhook-deliveries/{id}/replay", web::post().to(replay_delivery))
+        .route("/discord/interactions", web::post().to(discord_interaction))
+        .route("/", web::get().to(index));
+}
+
+pub async fn get_history(
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
     // Trigger fetch
-    match fetcher::fetch_history().await {
-        Ok(draws) => {
-            // Save to DB
-            let mut count = 0;
-            for draw in draws {
-                if let Ok(_) = db::upsert_draw(pool.get_ref(), draw).await {
-                    count += 1;
-                }
-            }
-            HttpResponse::Ok().body(format!("History fetched. Processed {} draws.", count))
+    let started = Instant::now();
+    let summary = ingest::run(
+        state.storage.as_ref(),
+        state.source.as_ref(),
+        &config.fetch.years,
+        FetchTrigger::Manual,
+    )
+    .await?;
+    state.metrics.observe_fetch(&summary, started.elapsed());
+    notify::spawn_announce_new_draws(state.storage.clone(), state.notifier.clone(), &summary);
+    let report = &summary.report;
+
+    if summary.status() == FetchRunStatus::Failed {
+        let years: Vec<String> = report.failures.iter().map(|f| f.year.to_string()).collect();
+        return Err(AppError::Fetch(format!(
+            "Could not fetch results for {}",
+            years.join(", ")
+        )));
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "run_id": summary.run_id,
+        "processed": report.rows_parsed(),
+        "inserted": summary.inserted,
+        "rows_seen": report.rows_seen(),
+        "rows_parsed": report.rows_parsed(),
+        "rows_rejected": report.rows_rejected(),
+        "markup_changed": report.markup_changed(),
+        "failures": report.failures,
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GenerateQuery {
+    /// Id of the game [default: euromillions].
+    pub game: Option<String>,
+    /// Owner of the generated grids, whose channels get their results and
+    /// whose generation profile for the game applies.
+    pub user_id: Option<i32>,
+    /// [default: the profile's, or frequent]
+    pub strategy: Option<Strategy>,
+    /// [default: the profile's, or 4]
+    pub count: Option<usize>,
+    /// Weight or filter the grids by popularity score [default: off].
+    pub anti_popular: Option<AntiPopular>,
+    /// Lowest score kept by `anti_popular=filter`.
+    pub min_score: Option<u8>,
+    pub sum_min: Option<i32>,
+    pub sum_max: Option<i32>,
+    pub odd: Option<usize>,
+    pub low: Option<usize>,
+    pub max_consecutive: Option<usize>,
+    pub every_decade: Option<bool>,
+    /// Numbers every grid must hold, comma separated.
+    pub include: Option<String>,
+    /// Numbers no grid may hold, comma separated.
+    pub exclude: Option<String>,
+    /// Stars or other bonus balls every grid must hold, comma separated.
+    pub include_stars: Option<String>,
+    /// Add-ons bought with every grid, comma separated.
+    pub addons: Option<String>,
+}
+
+impl GenerateQuery {
+    fn constraints(&self) -> Result<Constraints> {
+        Ok(Constraints {
+            sum_min: self.sum_min,
+            sum_max: self.sum_max,
+            odd: self.odd,
+            low: self.low,
+            max_consecutive: self.max_consecutive,
+            every_decade: self.every_decade.unwrap_or(false),
+            include: parse_number_list("include", self.include.as_deref())?,
+            exclude: parse_number_list("exclude", self.exclude.as_deref())?,
+            include_stars: parse_number_list("include_stars", self.include_stars.as_deref())?,
+        })
+    }
+}
+
+/// Reads a comma separated list of numbers from a query parameter.
+fn parse_number_list(name: &str, list: Option<&str>) -> Result<Vec<i32>> {
+    list.unwrap_or("")
+        .split(',')
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(|n| {
+            n.parse()
+                .map_err(|_| AppError::Validation(format!("{}: '{}' is not a number", name, n)))
+        })
+        .collect()
+}
+
+/// Set on `/generate` and `/grids/played` responses when a user is close to
+/// a budget limit.
+pub const BUDGET_WARNING_HEADER: &str = "Budget-Warning";
+
+/// Lets clients retry `POST /generate` without generating twice.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Set to `true` on `POST /generate` responses returning an earlier batch.
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+fn idempotency_key(req: &HttpRequest) -> Result<Option<String>> {
+    let Some(value) = req.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+    value
+        .to_str()
+        .ok()
+        .map(str::trim)
+        .filter(|key| !key.is_empty() && key.len() <= 255)
+        .map(|key| Some(key.to_string()))
+        .ok_or_else(|| {
+            AppError::Validation(format!("{} must hold 1 to 255 visible characters", IDEMPOTENCY_KEY_HEADER))
+        })
+}
+
+/// Answers a retry with the grids of the batch first generated for `key`,
+/// which must have been generated with the same options for the same user,
+/// and the budget warnings they would now bring.
+async fn replay_batch(
+    state: &AppState,
+    key: &str,
+    options: &GenerateOptions,
+    user_id: Option<i32>,
+) -> Result<Option<HttpResponse>> {
+    let Some(batch) = state.storage.get_generation_batch(key).await? else {
+        return Ok(None);
+    };
+    if !options.matches_batch(&batch, user_id) {
+        return Err(AppError::Conflict(format!(
+            "{} '{}' was already used for another request",
+            IDEMPOTENCY_KEY_HEADER, key
+        )));
+    }
+    let grids = state.storage.batch_grids(batch.id).await?;
+    let mut warnings = Vec::new();
+    if let Some(user_id) = user_id {
+        let budgets = ledger::statuses_if_played(state.storage.as_ref(), user_id, &grids).await?;
+        warnings.extend(budgets.iter().filter_map(BudgetStatus::warning));
+    }
+    let mut response = HttpResponse::Ok();
+    response.insert_header((IDEMPOTENT_REPLAYED_HEADER, "true"));
+    if !warnings.is_empty() {
+        response.insert_header((BUDGET_WARNING_HEADER, warnings.join("; ")));
+    }
+    Ok(Some(response.json(generator::scored(grids)?)))
+}
+
+pub async fn generate_grids(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<GenerateQuery>,
+) -> Result<HttpResponse> {
+    let game = games::get(query.game.as_deref().unwrap_or(EUROMILLIONS.id))?;
+    let key = idempotency_key(&req)?;
+    let mut options = GenerateOptions::for_game(game);
+    options.constraints = query.constraints()?;
+    if let Some(user_id) = query.user_id {
+        find_user(&state, user_id).await?;
+        if let Some(profile) = state.storage.get_generation_profile(user_id, game.id).await? {
+            generator::apply_profile(&mut options, &profile)?;
         }
-        Err(e) => HttpResponse::InternalServerError().body(format!("Error fetching history: {}", e)),
-    }
-}
-
-pub async fn generate_grids(pool: web::Data<PgPool>) -> impl Responder {
-    match generator::generate_grids(pool.get_ref()).await {
-        Ok(grids) => {
-            // Save generated grids
-            for grid in &grids {
-                let _ = sqlx::query!(
-                    "INSERT INTO grids (draw_date, numbers, stars) VALUES ($1, $2, $3)",
-                    grid.draw_date,
-                    &grid.numbers,
-                    &grid.stars
-                )
-                .execute(pool.get_ref())
-                .await;
-            }
-            HttpResponse::Ok().json(grids)
+    }
+    if let Some(strategy) = query.strategy {
+        options.strategy = strategy;
+    }
+    if let Some(count) = query.count {
+        options.count = count;
+    }
+    options.anti_popular = query.anti_popular.unwrap_or_default();
+    if let Some(min_score) = query.min_score {
+        if min_score > 100 {
+            return Err(AppError::Validation("min_score must be between 0 and 100".to_string()));
         }
-        Err(e) => {
-            eprintln!("Error generating grids: {}", e);
-            HttpResponse::InternalServerError().finish()
+        options.min_score = min_score;
+    }
+    options.addons = ledger::normalize_addons(query.addons.as_deref().unwrap_or("").split(','));
+    if let Some(key) = &key {
+        if let Some(response) = replay_batch(&state, key, &options, query.user_id).await? {
+            return Ok(response);
         }
     }
+    let mut warnings = Vec::new();
+    if let Some(user_id) = query.user_id {
+        let budgets = ledger::check_budgets(
+            state.storage.as_ref(),
+            user_id,
+            game,
+            options.draw_date,
+            options.count,
+            &options.addons,
+        )
+        .await?;
+        warnings.extend(budgets.iter().filter_map(BudgetStatus::warning));
+    }
+    let mut grids = generator::generate(state.storage.as_ref(), &options).await?;
+    for grid in &mut grids {
+        grid.user_id = query.user_id;
+    }
+
+    let batch = options.batch(query.user_id, key.clone());
+    let Some(saved) = generator::save_batch(state.storage.as_ref(), &batch, &grids).await? else {
+        // A concurrent request with the same key got there first
+        let key = key.as_deref().expect("only batches with a key conflict");
+        let replayed = replay_batch(&state, key, &options, query.user_id).await?;
+        return replayed.ok_or_else(|| AppError::NotFound(format!("no batch for {} '{}'", IDEMPOTENCY_KEY_HEADER, key)));
+    };
+    state.metrics.observe_generated(options.strategy, saved.len());
+    let mut response = HttpResponse::Ok();
+    if !warnings.is_empty() {
+        response.insert_header((BUDGET_WARNING_HEADER, warnings.join("; ")));
+    }
+    Ok(response.json(generator::scored(saved)?))
 }
 
-pub async fn list_grids(pool: web::Data<PgPool>) -> impl Responder {
-    let result = sqlx::query_as!(
-        Grid,
-        "SELECT id, draw_date, numbers, stars, created_at FROM grids ORDER BY created_at DESC LIMIT 20"
-    )
-    .fetch_all(pool.get_ref())
-    .await;
+#[derive(Debug, Deserialize)]
+pub struct GridsQuery {
+    pub status: Option<GridStatus>,
+}
+
+pub async fn list_grids(state: web::Data<AppState>, query: web::Query<GridsQuery>) -> Result<HttpResponse> {
+    let status = query.status.map(GridStatus::as_str);
+    let grids = state.storage.recent_grids(20, status).await?;
+    Ok(HttpResponse::Ok().json(generator::scored(grids)?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlayedBody {
+    pub ids: Vec<i32>,
+    /// Add-ons bought with the lines, replacing those chosen when they were
+    /// generated.
+    pub addons: Option<Vec<String>>,
+}
+
+/// Records the lines bought among generated grids. Lines that would take
+/// their owner over a budget are refused, and owners close to a budget limit
+/// are warned with a `Budget-Warning` header.
+pub async fn mark_played(state: web::Data<AppState>, body: web::Json<PlayedBody>) -> Result<HttpResponse> {
+    let addons = body.addons.as_ref().map(ledger::normalize_addons);
+    let grids = grids::play(state.storage.as_ref(), &body.ids, addons.as_deref()).await?;
+    let mut owners: Vec<(i32, NaiveDate)> = grids.iter().filter_map(|g| Some((g.user_id?, g.draw_date))).collect();
+    owners.sort();
+    owners.dedup();
+    let mut warnings = Vec::new();
+    for (user_id, date) in owners {
+        for status in ledger::budget_statuses(state.storage.as_ref(), user_id, date).await? {
+            warnings.extend(status.warning().filter(|w| !warnings.contains(w)));
+        }
+    }
+    let mut response = HttpResponse::Ok();
+    if !warnings.is_empty() {
+        response.insert_header((BUDGET_WARNING_HEADER, warnings.join("; ")));
+    }
+    Ok(response.json(generator::scored(grids)?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GridStatusBody {
+    pub status: GridStatus,
+}
 
-    match result {
-        Ok(grids) => HttpResponse::Ok().json(grids),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Error listing grids: {}", e)),
+pub async fn set_grid_status(
+    state: web::Data<AppState>,
+    id: web::Path<i32>,
+    body: web::Json<GridStatusBody>,
+) -> Result<HttpResponse> {
+    let grids = grids::set_status(state.storage.as_ref(), &[id.into_inner()], body.status).await?;
+    Ok(HttpResponse::Ok().json(generator::scored(grids)?.pop()))
+}
+
+pub async fn list_games() -> impl Responder {
+    HttpResponse::Ok().json(games::GAMES)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewDrawBody {
+    pub date: NaiveDate,
+    pub numbers: Vec<i32>,
+    /// Bonus balls: stars, dream or chance number, or the UK Lotto bonus ball.
+    pub stars: Vec<i32>,
+}
+
+/// Records a result for a game the fetcher does not cover. A new draw is
+/// announced like a fetched one; an existing one is returned unchanged.
+pub async fn add_draw(
+    state: web::Data<AppState>,
+    game: web::Path<String>,
+    body: web::Json<NewDrawBody>,
+) -> Result<HttpResponse> {
+    let game = games::get(&game)?;
+    let body = body.into_inner();
+    game.validate_draw(&body.numbers, &body.stars)?;
+    let mut numbers = body.numbers;
+    let mut stars = body.stars;
+    numbers.sort();
+    stars.sort();
+
+    let inserted = state
+        .storage
+        .upsert_draw(NewDraw {
+            game: game.id.to_string(),
+            date: body.date,
+            numbers,
+            stars,
+        })
+        .await?;
+    let draw = state
+        .storage
+        .get_draw(game.id, body.date)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("{} draw of {} not found", game.name, body.date)))?;
+    if !inserted {
+        return Ok(HttpResponse::Ok().json(draw));
+    }
+    notify::spawn_announce(state.storage.clone(), state.notifier.clone(), draw.clone());
+    Ok(HttpResponse::Created().json(draw))
+}
+
+/// Records what each tier paid in a draw, for the expected value. A payout
+/// sent again replaces the stored one.
+pub async fn set_prizes(
+    state: web::Data<AppState>,
+    path: web::Path<(String, NaiveDate)>,
+    body: web::Json<Vec<NewDrawPrize>>,
+) -> Result<HttpResponse> {
+    let (game, date) = path.into_inner();
+    let game = games::get(&game)?;
+    for prize in body.iter() {
+        if !game.prize_tiers.iter().any(|t| i32::from(t.tier) == prize.tier) {
+            return Err(AppError::Validation(format!("{} has no prize tier {}", game.name, prize.tier)));
+        }
+        if prize.amount_cents < 0 || prize.winners.is_some_and(|w| w < 0) {
+            return Err(AppError::Validation(format!("invalid payout for tier {}", prize.tier)));
+        }
     }
+    let draw = state
+        .storage
+        .get_draw(game.id, date)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("{} draw of {} not found", game.name, date)))?;
+
+    state.storage.upsert_draw_prizes(draw.id, &body).await?;
+    let mut prizes = state.storage.list_draw_prizes(game.id).await?;
+    prizes.retain(|p| p.draw_id == draw.id);
+    Ok(HttpResponse::Ok().json(prizes))
+}
+
+pub async fn list_prices(state: web::Data<AppState>, game: web::Path<String>) -> Result<HttpResponse> {
+    let game = games::get(&game)?;
+    let prices = state.storage.list_ticket_prices(game.id).await?;
+    Ok(HttpResponse::Ok().json(prices))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PriceBody {
+    pub price_cents: i64,
+}
+
+pub async fn set_price(
+    state: web::Data<AppState>,
+    path: web::Path<(String, NaiveDate)>,
+    body: web::Json<PriceBody>,
+) -> Result<HttpResponse> {
+    let (game, valid_from) = path.into_inner();
+    let game = games::get(&game)?;
+    if body.price_cents <= 0 {
+        return Err(AppError::Validation("price_cents must be positive".to_string()));
+    }
+    let price = TicketPrice {
+        game: game.id.to_string(),
+        valid_from,
+        price_cents: body.price_cents,
+    };
+    state.storage.set_ticket_price(&price).await?;
+    Ok(HttpResponse::Ok().json(price))
+}
+
+pub async fn list_addons(state: web::Data<AppState>, game: web::Path<String>) -> Result<HttpResponse> {
+    let game = games::get(&game)?;
+    let addons = state.storage.list_ticket_addons(game.id).await?;
+    Ok(HttpResponse::Ok().json(addons))
+}
+
+/// Sets the price of an add-on of the game from a draw date on. Add-on
+/// names are made of lowercase letters, digits and underscores.
+pub async fn set_addon_price(
+    state: web::Data<AppState>,
+    path: web::Path<(String, String, NaiveDate)>,
+    body: web::Json<PriceBody>,
+) -> Result<HttpResponse> {
+    let (game, addon, valid_from) = path.into_inner();
+    let game = games::get(&game)?;
+    let valid_name = addon.len() <= 64
+        && !addon.is_empty()
+        && addon.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid_name {
+        return Err(AppError::Validation(format!(
+            "add-on '{}' must be 1 to 64 lowercase letters, digits or underscores",
+            addon
+        )));
+    }
+    if body.price_cents <= 0 {
+        return Err(AppError::Validation("price_cents must be positive".to_string()));
+    }
+    let addon = TicketAddon {
+        game: game.id.to_string(),
+        addon,
+        valid_from,
+        price_cents: body.price_cents,
+    };
+    state.storage.set_ticket_addon(&addon).await?;
+    Ok(HttpResponse::Ok().json(addon))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GameQuery {
+    /// Id of the game [default: euromillions].
+    pub game: Option<String>,
+}
+
+pub async fn get_odds(query: web::Query<GameQuery>) -> Result<HttpResponse> {
+    let game = games::get(query.game.as_deref().unwrap_or(EUROMILLIONS.id))?;
+    Ok(HttpResponse::Ok().json(odds::odds(game)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExpectedValueQuery {
+    /// Id of the game [default: euromillions].
+    pub game: Option<String>,
+    /// Draw date [default: the next draw].
+    pub date: Option<NaiveDate>,
+}
+
+pub async fn get_expected_value(
+    state: web::Data<AppState>,
+    query: web::Query<ExpectedValueQuery>,
+) -> Result<HttpResponse> {
+    let game = games::get(query.game.as_deref().unwrap_or(EUROMILLIONS.id))?;
+    let date = query.date.unwrap_or_else(|| game.next_draw_date());
+    let value = odds::expected_value(state.storage.as_ref(), game, date).await?;
+    Ok(HttpResponse::Ok().json(value))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RaffleQuery {
+    /// EuroMillions draw [default: latest].
+    pub date: Option<NaiveDate>,
+}
+
+/// Winning raffle codes of a EuroMillions draw.
+pub async fn list_raffles(state: web::Data<AppState>, query: web::Query<RaffleQuery>) -> Result<HttpResponse> {
+    let draw = match query.date {
+        Some(date) => state.storage.get_draw(EUROMILLIONS.id, date).await?,
+        None => state.storage.latest_draw(EUROMILLIONS.id).await?,
+    }
+    .ok_or_else(|| AppError::NotFound("draw not found".to_string()))?;
+    let codes = state.storage.raffle_codes(draw.id).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "draw_date": draw.date,
+        "codes": codes,
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RaffleCheckQuery {
+    pub code: String,
+    /// Only check this draw instead of every stored one.
+    pub date: Option<NaiveDate>,
+}
+
+pub async fn check_raffle(state: web::Data<AppState>, query: web::Query<RaffleCheckQuery>) -> Result<HttpResponse> {
+    let check = raffle::check(state.storage.as_ref(), &query.code, query.date).await?;
+    Ok(HttpResponse::Ok().json(check))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListQuery {
+    pub limit: Option<i64>,
+}
+
+/// Largest page the list endpoints return.
+const MAX_LIST_LIMIT: i64 = 100;
+
+fn list_limit(query: &ListQuery) -> Result<i64> {
+    let limit = query.limit.unwrap_or(20);
+    if !(1..=MAX_LIST_LIMIT).contains(&limit) {
+        return Err(AppError::Validation(format!(
+            "limit must be between 1 and {}",
+            MAX_LIST_LIMIT
+        )));
+    }
+    Ok(limit)
+}
+
+pub async fn list_fetch_runs(
+    state: web::Data<AppState>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse> {
+    let limit = list_limit(&query)?;
+    let runs = state.storage.list_fetch_runs(limit).await?;
+    Ok(HttpResponse::Ok().json(runs))
+}
+
+pub async fn get_fetch_run(state: web::Data<AppState>, id: web::Path<i32>) -> Result<HttpResponse> {
+    let id = id.into_inner();
+    let run = state
+        .storage
+        .get_fetch_run(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("fetch run {} not found", id)))?;
+    Ok(HttpResponse::Ok().json(run))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewUserBody {
+    pub name: String,
+}
+
+pub async fn create_user(state: web::Data<AppState>, body: web::Json<NewUserBody>) -> Result<HttpResponse> {
+    let name = body.name.trim();
+    if name.is_empty() {
+        return Err(AppError::Validation("name must not be empty".to_string()));
+    }
+    let user = state.storage.create_user(name).await.map_err(|e| match e {
+        AppError::Database(sqlx::Error::Database(db)) if db.is_unique_violation() => {
+            AppError::Validation(format!("user '{}' already exists", name))
+        }
+        e => e,
+    })?;
+    Ok(HttpResponse::Created().json(user))
+}
+
+async fn find_user(state: &AppState, id: i32) -> Result<User> {
+    state
+        .storage
+        .get_user(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("user {} not found", id)))
+}
+
+pub async fn get_user(state: web::Data<AppState>, id: web::Path<i32>) -> Result<HttpResponse> {
+    let user = find_user(&state, id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(user))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewChannelBody {
+    pub kind: String,
+    pub target: String,
+    /// Send every result.
+    #[serde(default = "default_true")]
+    pub notify_results: bool,
+    /// Send results in which one of the user's grids won a prize.
+    #[serde(default = "default_true")]
+    pub notify_wins: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+pub async fn add_channel(
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+    id: web::Path<i32>,
+    body: web::Json<NewChannelBody>,
+) -> Result<HttpResponse> {
+    let user = find_user(&state, id.into_inner()).await?;
+    let kind: ChannelKind = body.kind.parse()?;
+    let target = body.target.trim();
+    kind.validate_target(target, config.notify.allow_private_urls)?;
+    if !state.notifier.supports(kind) {
+        return Err(AppError::Validation(format!(
+            "{} notifications are not configured on this server",
+            kind
+        )));
+    }
+    let channel = state
+        .storage
+        .add_notification_channel(&NewNotificationChannel {
+            user_id: user.id,
+            kind: kind.as_str().to_string(),
+            target: target.to_string(),
+            notify_results: body.notify_results,
+            notify_wins: body.notify_wins,
+        })
+        .await?;
+    Ok(HttpResponse::Created().json(channel))
+}
+
+pub async fn list_channels(state: web::Data<AppState>, id: web::Path<i32>) -> Result<HttpResponse> {
+    let user = find_user(&state, id.into_inner()).await?;
+    let channels = state.storage.list_notification_channels(user.id).await?;
+    Ok(HttpResponse::Ok().json(channels))
+}
+
+pub async fn delete_channel(state: web::Data<AppState>, path: web::Path<(i32, i32)>) -> Result<HttpResponse> {
+    let (user_id, channel_id) = path.into_inner();
+    if !state.storage.delete_notification_channel(user_id, channel_id).await? {
+        return Err(AppError::NotFound(format!(
+            "channel {} of user {} not found",
+            channel_id, user_id
+        )));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProfileBody {
+    #[serde(default)]
+    pub forced_numbers: Vec<i32>,
+    #[serde(default)]
+    pub forced_stars: Vec<i32>,
+    #[serde(default)]
+    pub banned_numbers: Vec<i32>,
+    pub strategy: Option<Strategy>,
+    pub count: Option<usize>,
+}
+
+pub async fn put_profile(
+    state: web::Data<AppState>,
+    path: web::Path<(i32, String)>,
+    body: web::Json<ProfileBody>,
+) -> Result<HttpResponse> {
+    let (user_id, game) = path.into_inner();
+    let user = find_user(&state, user_id).await?;
+    let game = games::get(&game)?;
+    let body = body.into_inner();
+    if body.count.is_some_and(|count| count == 0 || count > generator::MAX_GRIDS) {
+        return Err(AppError::Validation(format!(
+            "count must be between 1 and {}",
+            generator::MAX_GRIDS
+        )));
+    }
+    let constraints = Constraints {
+        include: body.forced_numbers.clone(),
+        exclude: body.banned_numbers.clone(),
+        include_stars: body.forced_stars.clone(),
+        ..Constraints::default()
+    };
+    constraints.validate(game)?;
+
+    let profile = NewGenerationProfile {
+        forced_numbers: body.forced_numbers,
+        forced_stars: body.forced_stars,
+        banned_numbers: body.banned_numbers,
+        strategy: body.strategy.map(|s| s.as_str().to_string()),
+        grid_count: body.count.map(|count| count as i32),
+    };
+    let profile = state.storage.upsert_generation_profile(user.id, game.id, &profile).await?;
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+pub async fn get_profile(state: web::Data<AppState>, path: web::Path<(i32, String)>) -> Result<HttpResponse> {
+    let (user_id, game) = path.into_inner();
+    let user = find_user(&state, user_id).await?;
+    let game = games::get(&game)?;
+    let profile = state
+        .storage
+        .get_generation_profile(user.id, game.id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("user {} has no {} profile", user.id, game.name)))?;
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+pub async fn delete_profile(state: web::Data<AppState>, path: web::Path<(i32, String)>) -> Result<HttpResponse> {
+    let (user_id, game) = path.into_inner();
+    let user = find_user(&state, user_id).await?;
+    let game = games::get(&game)?;
+    if !state.storage.delete_generation_profile(user.id, game.id).await? {
+        return Err(AppError::NotFound(format!("user {} has no {} profile", user.id, game.name)));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BudgetBody {
+    pub limit_cents: i64,
+    /// [default: EUR]
+    pub currency: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CurrencyQuery {
+    /// [default: EUR]
+    pub currency: Option<String>,
+}
+
+/// The currency of a budget, one some game is played in.
+fn budget_currency(currency: Option<&str>) -> Result<&'static str> {
+    let currency = currency.unwrap_or(EUROMILLIONS.currency);
+    games::GAMES
+        .iter()
+        .map(|game| game.currency)
+        .find(|c| c.eq_ignore_ascii_case(currency))
+        .ok_or_else(|| AppError::Validation(format!("no game is played in {}", currency)))
+}
+
+pub async fn list_budgets(state: web::Data<AppState>, id: web::Path<i32>) -> Result<HttpResponse> {
+    let user = find_user(&state, id.into_inner()).await?;
+    let today = chrono::Local::now().date_naive();
+    let statuses = ledger::budget_statuses(state.storage.as_ref(), user.id, today).await?;
+    Ok(HttpResponse::Ok().json(statuses))
+}
+
+pub async fn set_budget(
+    state: web::Data<AppState>,
+    path: web::Path<(i32, String)>,
+    body: web::Json<BudgetBody>,
+) -> Result<HttpResponse> {
+    let (user_id, period) = path.into_inner();
+    let user = find_user(&state, user_id).await?;
+    let period: BudgetPeriod = period.parse()?;
+    if body.limit_cents <= 0 {
+        return Err(AppError::Validation("limit_cents must be positive".to_string()));
+    }
+    let budget = Budget {
+        user_id: user.id,
+        period: period.as_str().to_string(),
+        currency: budget_currency(body.currency.as_deref())?.to_string(),
+        limit_cents: body.limit_cents,
+    };
+    Ok(HttpResponse::Ok().json(state.storage.set_budget(&budget).await?))
+}
+
+pub async fn delete_budget(
+    state: web::Data<AppState>,
+    path: web::Path<(i32, String)>,
+    query: web::Query<CurrencyQuery>,
+) -> Result<HttpResponse> {
+    let (user_id, period) = path.into_inner();
+    let period: BudgetPeriod = period.parse()?;
+    let currency = budget_currency(query.currency.as_deref())?;
+    if !state.storage.delete_budget(user_id, period.as_str(), currency).await? {
+        return Err(AppError::NotFound(format!(
+            "user {} has no {} budget in {}",
+            user_id, period, currency
+        )));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LedgerQuery {
+    /// [default: euromillions]
+    pub game: Option<String>,
+    /// First draw date included.
+    pub from: Option<NaiveDate>,
+    /// Last draw date included.
+    pub to: Option<NaiveDate>,
+}
+
+pub async fn get_ledger(
+    state: web::Data<AppState>,
+    id: web::Path<i32>,
+    query: web::Query<LedgerQuery>,
+) -> Result<HttpResponse> {
+    let user = find_user(&state, id.into_inner()).await?;
+    let game = games::get(query.game.as_deref().unwrap_or(EUROMILLIONS.id))?;
+    let ledger = ledger::ledger(state.storage.as_ref(), user.id, game, query.from, query.to).await?;
+    Ok(HttpResponse::Ok().json(ledger))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewWebhookBody {
+    pub url: String,
+    pub events: Vec<String>,
+    /// Generated when omitted.
+    pub secret: Option<String>,
+}
+
+pub async fn create_webhook(
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+    body: web::Json<NewWebhookBody>,
+) -> Result<HttpResponse> {
+    let body = body.into_inner();
+    outbound::check_url(&body.url, config.webhooks.allow_private_urls)?;
+    if body.events.is_empty() {
+        return Err(AppError::Validation("events must not be empty".to_string()));
+    }
+    let mut events = Vec::with_capacity(body.events.len());
+    for event in &body.events {
+        let event = event.parse::<Event>()?.as_str().to_string();
+        if !events.contains(&event) {
+            events.push(event);
+        }
+    }
+    let secret = match body.secret {
+        Some(secret) if secret.len() < 16 => {
+            return Err(AppError::Validation("secret must be at least 16 characters".to_string()))
+        }
+        Some(secret) => secret,
+        None => webhooks::generate_secret(),
+    };
+
+    let webhook = state
+        .storage
+        .create_webhook(&NewWebhook {
+            url: body.url,
+            secret,
+            events,
+        })
+        .await?;
+    // The only time the secret is shown
+    let mut json = serde_json::to_value(&webhook).expect("webhooks serialize to JSON");
+    json["secret"] = serde_json::Value::String(webhook.secret.clone());
+    Ok(HttpResponse::Created().json(json))
+}
+
+async fn find_webhook(state: &AppState, id: i32) -> Result<Webhook> {
+    state
+        .storage
+        .get_webhook(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("webhook {} not found", id)))
+}
+
+pub async fn list_webhooks(state: web::Data<AppState>) -> Result<HttpResponse> {
+    let webhooks = state.storage.list_webhooks().await?;
+    Ok(HttpResponse::Ok().json(webhooks))
+}
+
+pub async fn get_webhook(state: web::Data<AppState>, id: web::Path<i32>) -> Result<HttpResponse> {
+    let webhook = find_webhook(&state, id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(webhook))
+}
+
+pub async fn delete_webhook(state: web::Data<AppState>, id: web::Path<i32>) -> Result<HttpResponse> {
+    let id = id.into_inner();
+    if !state.storage.delete_webhook(id).await? {
+        return Err(AppError::NotFound(format!("webhook {} not found", id)));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn list_deliveries(
+    state: web::Data<AppState>,
+    id: web::Path<i32>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse> {
+    let webhook = find_webhook(&state, id.into_inner()).await?;
+    let limit = list_limit(&query)?;
+    let deliveries = state.storage.list_deliveries(webhook.id, limit).await?;
+    Ok(HttpResponse::Ok().json(deliveries))
+}
+
+pub async fn get_delivery(state: web::Data<AppState>, id: web::Path<i32>) -> Result<HttpResponse> {
+    let id = id.into_inner();
+    let delivery = state
+        .storage
+        .get_delivery(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("delivery {} not found", id)))?;
+    Ok(HttpResponse::Ok().json(delivery))
+}
+
+/// Queues the event again for the same webhook; the dispatcher sends it on
+/// its next pass.
+pub async fn replay_delivery(state: web::Data<AppState>, id: web::Path<i32>) -> Result<HttpResponse> {
+    let id = id.into_inner();
+    let replay = state
+        .storage
+        .replay_delivery(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("delivery {} not found", id)))?;
+    Ok(HttpResponse::Accepted().json(replay))
+}
+
+/// Discord slash commands. Discord checks this endpoint with bad signatures
+/// before accepting it, so they must be answered with 401.
+pub async fn discord_interaction(
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> Result<HttpResponse> {
+    let key = config
+        .bot
+        .discord_public_key
+        .as_deref()
+        .ok_or_else(|| AppError::NotFound("the Discord bot is not configured".to_string()))?;
+    let key = discord::parse_public_key(key)?;
+    let header = |name| req.headers().get(name).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    if !discord::verify(&key, header(discord::SIGNATURE_HEADER), header(discord::TIMESTAMP_HEADER), &body) {
+        return Err(AppError::Unauthorized("invalid request signature".to_string()));
+    }
+    let interaction = serde_json::from_slice(&body)
+        .map_err(|e| AppError::Validation(format!("invalid interaction: {}", e)))?;
+    let reply = discord::interaction(state.storage.as_ref(), interaction).await?;
+    Ok(HttpResponse::Ok().json(reply))
+}
+
+/// Liveness: the process is up and serving requests.
+pub async fn health_live() -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Readiness: the database answers and every migration is applied.
+pub async fn health_ready(state: web::Data<AppState>) -> Result<HttpResponse> {
+    let pending = state.storage.pending_migrations().await.map_err(|e| {
+        tracing::warn!(error = %e, "Readiness check failed");
+        AppError::Unavailable("database is not reachable".to_string())
+    })?;
+    if !pending.is_empty() {
+        return Err(AppError::Unavailable(format!(
+            "{} database migration(s) pending",
+            pending.len()
+        )));
+    }
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "status": "ready" })))
+}
+
+pub async fn get_status(
+    config: web::Data<Config>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let status = status::status(state.storage.as_ref(), &config, chrono::Utc::now()).await?;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+pub async fn get_metrics(state: web::Data<AppState>) -> impl Responder {
+    state.metrics.set_pool_status(state.storage.pool_status());
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(state.metrics.render())
 }
 
 pub async fn index() -> impl Responder {
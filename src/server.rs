@@ -0,0 +1,106 @@
+//! The long-running `serve` mode: HTTP API plus the scheduled fetch.
+
+use crate::app::{build_app, AppState};
+use crate::bot::telegram::TelegramBot;
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::fetcher::Fetcher;
+use crate::ingest::{self, FetchTrigger};
+use crate::metrics::RunOutcome;
+use crate::notify::{self, Notifier};
+use crate::storage::Storage;
+use crate::webhooks::Dispatcher;
+use actix_web::HttpServer;
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_cron_scheduler::{Job, JobScheduler};
+use tracing::Instrument;
+
+pub async fn serve(config: Config, storage: Arc<dyn Storage>) -> Result<()> {
+    let config = Arc::new(config);
+    let fetcher = Arc::new(Fetcher::new(config.fetch.fetcher_config())?);
+    let state = AppState::new(storage, fetcher).with_notifier(Notifier::from_config(&config.notify)?);
+
+    let sched = JobScheduler::new().await.map_err(scheduler_error)?;
+    let job_state = state.clone();
+    let years = config.fetch.years.clone();
+
+    // Cron: sec min hour day_of_month month day_of_week year
+    let job = Job::new_async(config.fetch.schedule.as_str(), move |job_id, _l| {
+        let state = job_state.clone();
+        let years = years.clone();
+        let span = tracing::info_span!("scheduled_fetch", %job_id);
+        Box::pin(
+            async move {
+                tracing::info!("Running scheduled fetch");
+                let started = Instant::now();
+                match ingest::run(state.storage.as_ref(), state.source.as_ref(), &years, FetchTrigger::Cron).await {
+                    Ok(summary) => {
+                        state.metrics.observe_fetch(&summary, started.elapsed());
+                        state.metrics.observe_scheduler_run(RunOutcome::of(&summary));
+                        notify::announce_new_draws(state.storage.as_ref(), &state.notifier, &summary).await;
+                        for failure in &summary.report.failures {
+                            tracing::error!(
+                                year = failure.year,
+                                url = %failure.url,
+                                attempts = failure.attempts,
+                                error = %failure.kind,
+                                "Scheduled fetch failed for year"
+                            );
+                        }
+                    }
+                    Err(e) => {
+                        state.metrics.observe_scheduler_run(RunOutcome::Failure);
+                        tracing::error!(error = %e, "Scheduled fetch failed");
+                    }
+                }
+            }
+            .instrument(span),
+        )
+    })
+    .map_err(|e| AppError::Config(format!("invalid fetch schedule: {}", e)))?;
+
+    sched.add(job).await.map_err(scheduler_error)?;
+    sched.start().await.map_err(scheduler_error)?;
+
+    let dispatcher = Dispatcher::new(&config.webhooks)?;
+    let storage = state.storage.clone();
+    let poll_interval = Duration::from_secs(config.webhooks.poll_interval_secs);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(poll_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(e) = dispatcher.deliver_due(storage.as_ref(), Utc::now().naive_utc()).await {
+                tracing::error!(error = %e, "Webhook dispatch failed");
+            }
+        }
+    });
+
+    if config.bot.telegram {
+        if let Some(token) = &config.notify.telegram.bot_token {
+            let bot = TelegramBot::new(
+                &config.notify.telegram.api_url,
+                token,
+                Duration::from_secs(config.bot.poll_timeout_secs),
+            )?;
+            let storage = state.storage.clone();
+            tokio::spawn(async move { bot.run(storage.as_ref()).await });
+        }
+    }
+
+    let (host, port) = (config.server.host.clone(), config.server.port);
+    tracing::info!(%host, port, "Starting server");
+
+    HttpServer::new(move || build_app(config.clone(), state.clone()))
+        .bind((host.as_str(), port))
+        .map_err(|e| AppError::Config(format!("cannot bind {}:{}: {}", host, port, e)))?
+        .run()
+        .await
+        .map_err(|e| AppError::Config(format!("server error: {}", e)))
+}
+
+fn scheduler_error(e: tokio_cron_scheduler::JobSchedulerError) -> AppError {
+    AppError::Config(format!("scheduler error: {}", e))
+}
@@ -0,0 +1,49 @@
+//! Data freshness report behind `/status`. Only EuroMillions is fetched,
+//! so the report is about its draws.
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::games::EUROMILLIONS;
+use crate::storage::Storage;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::Serialize;
+use std::str::FromStr;
+
+#[derive(Debug, Serialize)]
+pub struct Status {
+    /// When the last fetch that stored results finished (UTC).
+    pub last_fetch_at: Option<NaiveDateTime>,
+    pub latest_draw_date: Option<NaiveDate>,
+    pub draws: i64,
+    pub next_scheduled_run: Option<DateTime<Utc>>,
+    /// True when no draw is stored or the latest one is older than
+    /// `fetch.stale_after_hours`.
+    pub stale: bool,
+}
+
+pub async fn status(storage: &dyn Storage, config: &Config, now: DateTime<Utc>) -> Result<Status> {
+    let latest_draw_date = storage.latest_draw(EUROMILLIONS.id).await?.map(|d| d.date);
+    Ok(Status {
+        last_fetch_at: storage.last_fetched_at().await?,
+        latest_draw_date,
+        draws: storage.count_draws(EUROMILLIONS.id).await?,
+        next_scheduled_run: next_run(&config.fetch.schedule, now),
+        stale: is_stale(latest_draw_date, now, config.fetch.stale_after_hours),
+    })
+}
+
+/// Next time the cron `schedule` fires after `now`, or `None` if the
+/// expression is invalid or never fires again.
+pub fn next_run(schedule: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    cron::Schedule::from_str(schedule).ok()?.after(&now).next()
+}
+
+pub fn is_stale(latest_draw_date: Option<NaiveDate>, now: DateTime<Utc>, stale_after_hours: u64) -> bool {
+    match latest_draw_date {
+        Some(date) => {
+            let drawn_at = date.and_time(chrono::NaiveTime::MIN).and_utc();
+            (now - drawn_at).num_hours() > stale_after_hours as i64
+        }
+        None => true,
+    }
+}
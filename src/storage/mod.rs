@@ -0,0 +1,227 @@
+//! Persistence behind a single trait so the bot can run against Postgres in
+//! production and SQLite (file or in-memory) everywhere else.
+
+mod postgres;
+mod sqlite;
+
+pub use self::postgres::PgStorage;
+pub use self::sqlite::SqliteStorage;
+
+use crate::error::{AppError, Result};
+use crate::models::{
+    ArchivedPage, BallFrequency, Budget, DeliveryAttempt, Draw, DrawPrize, FetchRun, FinishedFetchRun,
+    GenerationBatch, GenerationProfile, Grid, GridPlay, NewArchivedPage, NewDraw, NewDrawPrize, NewFetchRun,
+    NewGenerationBatch, NewGenerationProfile, NewGrid, NewNotificationChannel, NewRaffleCode, NewWebhook,
+    NotificationChannel, PendingDelivery, RaffleCode, SpendingCap, TicketAddon, TicketPrice, User, Webhook,
+    WebhookDelivery,
+};
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveDateTime};
+use sqlx::migrate::Migrator;
+use std::sync::Arc;
+
+/// Snapshot of the connection pool, for metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStatus {
+    /// Open connections, idle or in use.
+    pub size: u32,
+    pub idle: usize,
+    pub max: u32,
+}
+
+#[async_trait]
+pub trait Storage: Send + Sync {
+    fn pool_status(&self) -> PoolStatus;
+
+    /// Applies the backend's pending migrations.
+    async fn migrate(&self) -> Result<()>;
+    /// Versions of the bundled migrations not yet applied. Doubles as a
+    /// connectivity check for readiness probes.
+    async fn pending_migrations(&self) -> Result<Vec<i64>>;
+
+    // Draws
+
+    /// Inserts a draw unless the game already has one for that date. Returns
+    /// whether a row was inserted.
+    async fn upsert_draw(&self, draw: NewDraw) -> Result<bool>;
+    /// Inserts a draw or overwrites the stored numbers for that game and date.
+    /// Returns whether a row was inserted or changed.
+    async fn replace_draw(&self, draw: NewDraw) -> Result<bool>;
+    /// Every stored draw of every game, by game then oldest first.
+    async fn list_draws(&self) -> Result<Vec<Draw>>;
+    async fn get_draw(&self, game: &str, date: NaiveDate) -> Result<Option<Draw>>;
+    async fn latest_draw(&self, game: &str) -> Result<Option<Draw>>;
+    async fn count_draws(&self, game: &str) -> Result<i64>;
+
+    // Stats, per game
+
+    async fn get_most_frequent_numbers(&self, game: &str, limit: i64) -> Result<Vec<i32>>;
+    async fn get_most_frequent_stars(&self, game: &str, limit: i64) -> Result<Vec<i32>>;
+    /// Draw count of every number, most frequent first.
+    async fn number_frequencies(&self, game: &str) -> Result<Vec<BallFrequency>>;
+    /// Draw count of every star (bonus ball), most frequent first.
+    async fn star_frequencies(&self, game: &str) -> Result<Vec<BallFrequency>>;
+
+    // Grids
+
+    /// Inserts all grids atomically and returns the stored rows.
+    async fn insert_grids(&self, grids: &[NewGrid]) -> Result<Vec<Grid>>;
+    /// Inserts the batch and its grids atomically and returns the stored
+    /// grids, or `None` without storing anything when another batch already
+    /// holds the idempotency key.
+    async fn insert_generation_batch(&self, batch: &NewGenerationBatch, grids: &[NewGrid]) -> Result<Option<Vec<Grid>>>;
+    async fn get_generation_batch(&self, idempotency_key: &str) -> Result<Option<GenerationBatch>>;
+    /// The grids of a batch, by id.
+    async fn batch_grids(&self, batch_id: i32) -> Result<Vec<Grid>>;
+    /// Latest grids first, only those with `status` when given.
+    async fn recent_grids(&self, limit: i64, status: Option<&str>) -> Result<Vec<Grid>>;
+    /// The grids with these ids that exist, by id.
+    async fn get_grids(&self, ids: &[i32]) -> Result<Vec<Grid>>;
+    /// Moves the grids among `ids` whose status is one of `from` to `status`
+    /// and returns them, by id. Grids becoming played get `played_at` if they
+    /// had none; grids going back to generated lose it.
+    async fn set_grid_status(&self, ids: &[i32], from: &[&str], status: &str) -> Result<Vec<Grid>>;
+    /// Marks generated or played grids played with the add-ons and cost of
+    /// each play, then sums what every cap's user has spent within it, all in
+    /// one transaction holding those users' rows. Returns the sums, by cap;
+    /// when one is over its limit nothing is written.
+    async fn play_grids(&self, plays: &[GridPlay], caps: &[SpendingCap]) -> Result<Vec<i64>>;
+    /// Every grid of `game` played on `date`, oldest first.
+    async fn grids_for_draw(&self, game: &str, date: NaiveDate) -> Result<Vec<Grid>>;
+    /// A user's grids for draws between `from` and `to`, both included and
+    /// both optional, by draw date.
+    async fn user_grids(&self, user_id: i32, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Result<Vec<Grid>>;
+
+    // Raffle codes
+
+    /// Stores a draw's winning codes, skipping those already stored, and
+    /// returns how many were inserted.
+    async fn add_raffle_codes(&self, draw_id: i32, codes: &[NewRaffleCode]) -> Result<usize>;
+    async fn raffle_codes(&self, draw_id: i32) -> Result<Vec<RaffleCode>>;
+    /// Every draw a code won in, oldest first.
+    async fn find_raffle_code(&self, kind: &str, code: &str) -> Result<Vec<RaffleCode>>;
+
+    // Prizes
+
+    /// Inserts or overwrites a draw's tier payouts. A payout stored without
+    /// a winner count keeps the count already stored. Returns how many rows
+    /// were written.
+    async fn upsert_draw_prizes(&self, draw_id: i32, prizes: &[NewDrawPrize]) -> Result<usize>;
+    /// Every stored payout of a game, by draw date then tier.
+    async fn list_draw_prizes(&self, game: &str) -> Result<Vec<DrawPrize>>;
+    /// Inserts or overwrites the price starting on `valid_from`.
+    async fn set_ticket_price(&self, price: &TicketPrice) -> Result<()>;
+    /// Every stored price of a game, oldest first.
+    async fn list_ticket_prices(&self, game: &str) -> Result<Vec<TicketPrice>>;
+    /// Inserts or overwrites the add-on price starting on `valid_from`.
+    async fn set_ticket_addon(&self, addon: &TicketAddon) -> Result<()>;
+    /// Every stored add-on price of a game, by add-on then oldest first.
+    async fn list_ticket_addons(&self, game: &str) -> Result<Vec<TicketAddon>>;
+
+    // Raw page archive
+
+    /// Stores a raw page, or bumps `last_seen_at` if the same content is
+    /// already archived for that URL.
+    async fn archive_page(&self, page: &NewArchivedPage) -> Result<i32>;
+    /// Bumps `last_seen_at` of the archived copy of `url` with this content,
+    /// returning its id, or `None` if that copy is not archived.
+    async fn touch_archived_page(&self, url: &str, content_sha256: &str) -> Result<Option<i32>>;
+    /// Most recently seen archived copy of every URL.
+    async fn latest_archived_pages(&self) -> Result<Vec<ArchivedPage>>;
+    async fn latest_archived_page(&self, url: &str) -> Result<Option<ArchivedPage>>;
+
+    // Fetch runs
+
+    /// When the last fetch that stored results, succeeded or partial,
+    /// finished. Failed runs do not count.
+    async fn last_fetched_at(&self) -> Result<Option<NaiveDateTime>>;
+    /// Records the start of a fetch and returns the run id.
+    async fn start_fetch_run(&self, run: &NewFetchRun) -> Result<i32>;
+    async fn finish_fetch_run(&self, id: i32, result: &FinishedFetchRun) -> Result<()>;
+    /// Most recent runs first.
+    async fn list_fetch_runs(&self, limit: i64) -> Result<Vec<FetchRun>>;
+    async fn get_fetch_run(&self, id: i32) -> Result<Option<FetchRun>>;
+
+    // Users and notification channels
+
+    async fn create_user(&self, name: &str) -> Result<User>;
+    async fn get_user(&self, id: i32) -> Result<Option<User>>;
+    async fn get_user_by_name(&self, name: &str) -> Result<Option<User>>;
+    async fn add_notification_channel(&self, channel: &NewNotificationChannel) -> Result<NotificationChannel>;
+    async fn list_notification_channels(&self, user_id: i32) -> Result<Vec<NotificationChannel>>;
+    /// Enabled channels of every user.
+    async fn enabled_notification_channels(&self) -> Result<Vec<NotificationChannel>>;
+    /// Returns whether a channel with that id belonged to the user.
+    async fn delete_notification_channel(&self, user_id: i32, id: i32) -> Result<bool>;
+
+    // Generation profiles
+
+    /// Creates or replaces the user's profile for `game`.
+    async fn upsert_generation_profile(
+        &self,
+        user_id: i32,
+        game: &str,
+        profile: &NewGenerationProfile,
+    ) -> Result<GenerationProfile>;
+    async fn get_generation_profile(&self, user_id: i32, game: &str) -> Result<Option<GenerationProfile>>;
+    /// Returns whether the user had a profile for `game`.
+    async fn delete_generation_profile(&self, user_id: i32, game: &str) -> Result<bool>;
+
+    // Budgets
+
+    /// Creates or replaces the user's cap for that period and currency.
+    async fn set_budget(&self, budget: &Budget) -> Result<Budget>;
+    async fn list_budgets(&self, user_id: i32) -> Result<Vec<Budget>>;
+    /// Returns whether the cap existed.
+    async fn delete_budget(&self, user_id: i32, period: &str, currency: &str) -> Result<bool>;
+
+    // Outbound webhooks
+
+    async fn create_webhook(&self, webhook: &NewWebhook) -> Result<Webhook>;
+    async fn list_webhooks(&self) -> Result<Vec<Webhook>>;
+    async fn get_webhook(&self, id: i32) -> Result<Option<Webhook>>;
+    async fn delete_webhook(&self, id: i32) -> Result<bool>;
+    /// Stores the event with a pending delivery per enabled webhook
+    /// subscribed to it and returns the number of deliveries queued. New
+    /// draws emit `draw.created` from `upsert_draw` itself.
+    async fn emit_event(&self, event: &str, data: &serde_json::Value) -> Result<usize>;
+    /// Takes the deliveries due at `now` for sending by pushing their next
+    /// attempt to `lease_until`, so that no other dispatcher picks them up
+    /// meanwhile. A claim never followed by an attempt, after a crash,
+    /// simply expires.
+    async fn claim_due_deliveries(
+        &self,
+        now: NaiveDateTime,
+        lease_until: NaiveDateTime,
+        limit: i64,
+    ) -> Result<Vec<PendingDelivery>>;
+    async fn record_delivery_attempt(&self, id: i32, attempt: &DeliveryAttempt) -> Result<()>;
+    /// Most recent deliveries of a webhook first.
+    async fn list_deliveries(&self, webhook_id: i32, limit: i64) -> Result<Vec<WebhookDelivery>>;
+    async fn get_delivery(&self, id: i32) -> Result<Option<WebhookDelivery>>;
+    /// Queues a new delivery of the same event to the same webhook.
+    async fn replay_delivery(&self, id: i32) -> Result<Option<WebhookDelivery>>;
+}
+
+/// Versions in `migrator` missing from `applied`.
+fn pending_versions(migrator: &Migrator, applied: &[i64]) -> Vec<i64> {
+    migrator
+        .iter()
+        .map(|m| m.version)
+        .filter(|version| !applied.contains(version))
+        .collect()
+}
+
+/// Opens the backend matching the URL scheme: `postgres://` or `sqlite:`.
+pub async fn connect(database_url: &str, max_connections: u32) -> Result<Arc<dyn Storage>> {
+    if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+        Ok(Arc::new(PgStorage::connect(database_url, max_connections).await?))
+    } else if database_url.starts_with("sqlite:") {
+        Ok(Arc::new(SqliteStorage::connect(database_url, max_connections).await?))
+    } else {
+        Err(AppError::Config(format!(
+            "unsupported DATABASE_URL scheme in '{}', expected postgres:// or sqlite:",
+            database_url.split(':').next().unwrap_or_default()
+        )))
+    }
+}
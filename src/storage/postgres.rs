@@ -0,0 +1,379 @@
+use super::{PoolStatus, Storage};
+use crate::db;
+use crate::error::Result;
+use crate::models::{
+    ArchivedPage, BallFrequency, Budget, DeliveryAttempt, Draw, DrawPrize, FetchRun, FinishedFetchRun,
+    GenerationBatch, GenerationProfile, Grid, GridPlay, NewArchivedPage, NewDraw, NewDrawPrize, NewFetchRun,
+    NewGenerationBatch, NewGenerationProfile, NewGrid, NewNotificationChannel, NewRaffleCode, NewWebhook,
+    NotificationChannel, PendingDelivery, RaffleCode, SpendingCap, TicketAddon, TicketPrice, User, Webhook,
+    WebhookDelivery,
+};
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveDateTime};
+use sqlx::migrate::Migrator;
+use sqlx::postgres::{PgPool, PgPoolOptions};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Postgres backend. Queries live in `crate::db` and are checked at compile
+/// time by `sqlx::query!`.
+#[derive(Clone)]
+pub struct PgStorage {
+    pool: PgPool,
+}
+
+impl PgStorage {
+    pub fn new(pool: PgPool) -> Self {
+        PgStorage { pool }
+    }
+
+    pub async fn connect(database_url: &str, max_connections: u32) -> Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(max_connections)
+            .connect(database_url)
+            .await?;
+        Ok(PgStorage { pool })
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl Storage for PgStorage {
+    fn pool_status(&self) -> PoolStatus {
+        PoolStatus {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+            max: self.pool.options().get_max_connections(),
+        }
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn migrate(&self) -> Result<()> {
+        MIGRATOR.run(&self.pool).await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn pending_migrations(&self) -> Result<Vec<i64>> {
+        let applied = db::applied_migrations(&self.pool).await?;
+        Ok(super::pending_versions(&MIGRATOR, &applied))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn upsert_draw(&self, draw: NewDraw) -> Result<bool> {
+        db::upsert_draw(&self.pool, draw).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn replace_draw(&self, draw: NewDraw) -> Result<bool> {
+        db::replace_draw(&self.pool, draw).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_draws(&self) -> Result<Vec<Draw>> {
+        db::list_draws(&self.pool).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_draw(&self, game: &str, date: NaiveDate) -> Result<Option<Draw>> {
+        db::get_draw(&self.pool, game, date).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn latest_draw(&self, game: &str) -> Result<Option<Draw>> {
+        db::latest_draw(&self.pool, game).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn count_draws(&self, game: &str) -> Result<i64> {
+        db::count_draws(&self.pool, game).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_most_frequent_numbers(&self, game: &str, limit: i64) -> Result<Vec<i32>> {
+        db::get_most_frequent_numbers(&self.pool, game, limit).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_most_frequent_stars(&self, game: &str, limit: i64) -> Result<Vec<i32>> {
+        db::get_most_frequent_stars(&self.pool, game, limit).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn number_frequencies(&self, game: &str) -> Result<Vec<BallFrequency>> {
+        db::number_frequencies(&self.pool, game).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn star_frequencies(&self, game: &str) -> Result<Vec<BallFrequency>> {
+        db::star_frequencies(&self.pool, game).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn insert_grids(&self, grids: &[NewGrid]) -> Result<Vec<Grid>> {
+        db::insert_grids(&self.pool, grids).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn insert_generation_batch(&self, batch: &NewGenerationBatch, grids: &[NewGrid]) -> Result<Option<Vec<Grid>>> {
+        db::insert_generation_batch(&self.pool, batch, grids).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_generation_batch(&self, idempotency_key: &str) -> Result<Option<GenerationBatch>> {
+        db::get_generation_batch(&self.pool, idempotency_key).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn batch_grids(&self, batch_id: i32) -> Result<Vec<Grid>> {
+        db::batch_grids(&self.pool, batch_id).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn recent_grids(&self, limit: i64, status: Option<&str>) -> Result<Vec<Grid>> {
+        db::recent_grids(&self.pool, limit, status).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_grids(&self, ids: &[i32]) -> Result<Vec<Grid>> {
+        db::get_grids(&self.pool, ids).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn set_grid_status(&self, ids: &[i32], from: &[&str], status: &str) -> Result<Vec<Grid>> {
+        db::set_grid_status(&self.pool, ids, from, status).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn play_grids(&self, plays: &[GridPlay], caps: &[SpendingCap]) -> Result<Vec<i64>> {
+        db::play_grids(&self.pool, plays, caps).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn grids_for_draw(&self, game: &str, date: NaiveDate) -> Result<Vec<Grid>> {
+        db::grids_for_draw(&self.pool, game, date).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn user_grids(&self, user_id: i32, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Result<Vec<Grid>> {
+        db::user_grids(&self.pool, user_id, from, to).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn add_raffle_codes(&self, draw_id: i32, codes: &[NewRaffleCode]) -> Result<usize> {
+        db::add_raffle_codes(&self.pool, draw_id, codes).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn raffle_codes(&self, draw_id: i32) -> Result<Vec<RaffleCode>> {
+        db::raffle_codes(&self.pool, draw_id).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn find_raffle_code(&self, kind: &str, code: &str) -> Result<Vec<RaffleCode>> {
+        db::find_raffle_code(&self.pool, kind, code).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn upsert_draw_prizes(&self, draw_id: i32, prizes: &[NewDrawPrize]) -> Result<usize> {
+        db::upsert_draw_prizes(&self.pool, draw_id, prizes).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_draw_prizes(&self, game: &str) -> Result<Vec<DrawPrize>> {
+        db::list_draw_prizes(&self.pool, game).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn set_ticket_price(&self, price: &TicketPrice) -> Result<()> {
+        db::set_ticket_price(&self.pool, price).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_ticket_prices(&self, game: &str) -> Result<Vec<TicketPrice>> {
+        db::list_ticket_prices(&self.pool, game).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn set_ticket_addon(&self, addon: &TicketAddon) -> Result<()> {
+        db::set_ticket_addon(&self.pool, addon).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_ticket_addons(&self, game: &str) -> Result<Vec<TicketAddon>> {
+        db::list_ticket_addons(&self.pool, game).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn archive_page(&self, page: &NewArchivedPage) -> Result<i32> {
+        db::archive_page(&self.pool, page).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn touch_archived_page(&self, url: &str, content_sha256: &str) -> Result<Option<i32>> {
+        db::touch_archived_page(&self.pool, url, content_sha256).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn latest_archived_pages(&self) -> Result<Vec<ArchivedPage>> {
+        db::latest_archived_pages(&self.pool).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn latest_archived_page(&self, url: &str) -> Result<Option<ArchivedPage>> {
+        db::latest_archived_page(&self.pool, url).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn last_fetched_at(&self) -> Result<Option<NaiveDateTime>> {
+        db::last_fetched_at(&self.pool).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn start_fetch_run(&self, run: &NewFetchRun) -> Result<i32> {
+        db::start_fetch_run(&self.pool, run).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn finish_fetch_run(&self, id: i32, result: &FinishedFetchRun) -> Result<()> {
+        db::finish_fetch_run(&self.pool, id, result).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_fetch_runs(&self, limit: i64) -> Result<Vec<FetchRun>> {
+        db::list_fetch_runs(&self.pool, limit).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_fetch_run(&self, id: i32) -> Result<Option<FetchRun>> {
+        db::get_fetch_run(&self.pool, id).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn create_user(&self, name: &str) -> Result<User> {
+        db::create_user(&self.pool, name).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_user(&self, id: i32) -> Result<Option<User>> {
+        db::get_user(&self.pool, id).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_user_by_name(&self, name: &str) -> Result<Option<User>> {
+        db::get_user_by_name(&self.pool, name).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn add_notification_channel(&self, channel: &NewNotificationChannel) -> Result<NotificationChannel> {
+        db::add_notification_channel(&self.pool, channel).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_notification_channels(&self, user_id: i32) -> Result<Vec<NotificationChannel>> {
+        db::list_notification_channels(&self.pool, user_id).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn enabled_notification_channels(&self) -> Result<Vec<NotificationChannel>> {
+        db::enabled_notification_channels(&self.pool).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_notification_channel(&self, user_id: i32, id: i32) -> Result<bool> {
+        db::delete_notification_channel(&self.pool, user_id, id).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn upsert_generation_profile(
+        &self,
+        user_id: i32,
+        game: &str,
+        profile: &NewGenerationProfile,
+    ) -> Result<GenerationProfile> {
+        db::upsert_generation_profile(&self.pool, user_id, game, profile).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_generation_profile(&self, user_id: i32, game: &str) -> Result<Option<GenerationProfile>> {
+        db::get_generation_profile(&self.pool, user_id, game).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_generation_profile(&self, user_id: i32, game: &str) -> Result<bool> {
+        db::delete_generation_profile(&self.pool, user_id, game).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn set_budget(&self, budget: &Budget) -> Result<Budget> {
+        db::set_budget(&self.pool, budget).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_budgets(&self, user_id: i32) -> Result<Vec<Budget>> {
+        db::list_budgets(&self.pool, user_id).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_budget(&self, user_id: i32, period: &str, currency: &str) -> Result<bool> {
+        db::delete_budget(&self.pool, user_id, period, currency).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn create_webhook(&self, webhook: &NewWebhook) -> Result<Webhook> {
+        db::create_webhook(&self.pool, webhook).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_webhooks(&self) -> Result<Vec<Webhook>> {
+        db::list_webhooks(&self.pool).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_webhook(&self, id: i32) -> Result<Option<Webhook>> {
+        db::get_webhook(&self.pool, id).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_webhook(&self, id: i32) -> Result<bool> {
+        db::delete_webhook(&self.pool, id).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn emit_event(&self, event: &str, data: &serde_json::Value) -> Result<usize> {
+        db::emit_event(&self.pool, event, data).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn claim_due_deliveries(
+        &self,
+        now: NaiveDateTime,
+        lease_until: NaiveDateTime,
+        limit: i64,
+    ) -> Result<Vec<PendingDelivery>> {
+        db::claim_due_deliveries(&self.pool, now, lease_until, limit).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn record_delivery_attempt(&self, id: i32, attempt: &DeliveryAttempt) -> Result<()> {
+        db::record_delivery_attempt(&self.pool, id, attempt).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_deliveries(&self, webhook_id: i32, limit: i64) -> Result<Vec<WebhookDelivery>> {
+        db::list_deliveries(&self.pool, webhook_id, limit).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_delivery(&self, id: i32) -> Result<Option<WebhookDelivery>> {
+        db::get_delivery(&self.pool, id).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn replay_delivery(&self, id: i32) -> Result<Option<WebhookDelivery>> {
+        db::replay_delivery(&self.pool, id).await
+    }
+}
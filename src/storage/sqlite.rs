@@ -0,0 +1,1272 @@
+use super::{PoolStatus, Storage};
+use crate::error::{AppError, Result};
+use crate::models::{
+    ArchivedPage, BallFrequency, Budget, DeliveryAttempt, Draw, DrawPrize, FetchRun, FinishedFetchRun,
+    GenerationBatch, GenerationProfile, Grid, GridPlay, NewArchivedPage, NewDraw, NewDrawPrize, NewFetchRun,
+    NewGenerationBatch, NewGenerationProfile, NewGrid, NewNotificationChannel, NewRaffleCode, NewWebhook,
+    NotificationChannel, PendingDelivery, RaffleCode, SpendingCap, TicketAddon, TicketPrice, User, Webhook,
+    WebhookDelivery,
+};
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveDateTime};
+use sqlx::migrate::Migrator;
+use crate::webhooks::Event;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sqlx::sqlite::{SqliteConnection, SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow};
+use sqlx::Row;
+use std::str::FromStr;
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+/// SQLite backend for single-binary deployments and tests. Ball arrays are
+/// stored as JSON text, so queries are built at runtime rather than checked
+/// by `sqlx::query!`.
+#[derive(Clone)]
+pub struct SqliteStorage {
+    pool: SqlitePool,
+}
+
+impl SqliteStorage {
+    pub async fn connect(database_url: &str, max_connections: u32) -> Result<Self> {
+        let options = SqliteConnectOptions::from_str(database_url)?
+            .create_if_missing(true)
+            .foreign_keys(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .connect_with(options)
+            .await?;
+        Ok(SqliteStorage { pool })
+    }
+
+    /// Fresh, migrated in-memory database. Every pool connection would see
+    /// its own empty database, so the pool is pinned to one connection that
+    /// is never recycled.
+    pub async fn in_memory() -> Result<Self> {
+        let options = SqliteConnectOptions::from_str("sqlite::memory:")?.foreign_keys(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options)
+            .await?;
+        let storage = SqliteStorage { pool };
+        storage.migrate().await?;
+        Ok(storage)
+    }
+
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    async fn most_frequent(&self, game: &str, column: &str, limit: i64) -> Result<Vec<i32>> {
+        let freqs = self.frequencies(game, column, limit).await?;
+        Ok(freqs.into_iter().map(|f| f.ball).collect())
+    }
+
+    /// Ball counts for `column` in the game's draws; a negative `limit`
+    /// returns every ball.
+    async fn frequencies(&self, game: &str, column: &str, limit: i64) -> Result<Vec<BallFrequency>> {
+        let sql = format!(
+            r#"
+            SELECT CAST(j.value AS INTEGER) AS num, COUNT(*) AS freq
+            FROM draws, json_each(draws.{}) AS j
+            WHERE draws.game = ?
+            GROUP BY num
+            ORDER BY freq DESC, num
+            LIMIT ?
+            "#,
+            column
+        );
+        let rows = sqlx::query(&sql).bind(game).bind(limit).fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|r| {
+                Ok(BallFrequency {
+                    ball: r.try_get("num")?,
+                    count: r.try_get("freq")?,
+                })
+            })
+            .collect()
+    }
+}
+
+fn to_json<T: Serialize + ?Sized>(values: &T) -> String {
+    serde_json::to_string(values).expect("serializing integers and strings cannot fail")
+}
+
+fn from_json<T: DeserializeOwned>(row: &SqliteRow, column: &str) -> Result<T> {
+    let text: String = row.try_get(column)?;
+    serde_json::from_str(&text)
+        .map_err(|e| AppError::Database(sqlx::Error::Decode(Box::new(e))))
+}
+
+fn draw_from_row(row: &SqliteRow) -> Result<Draw> {
+    Ok(Draw {
+        id: row.try_get("id")?,
+        game: row.try_get("game")?,
+        date: row.try_get("date")?,
+        numbers: from_json(row, "numbers")?,
+        stars: from_json(row, "stars")?,
+    })
+}
+
+fn grid_from_row(row: &SqliteRow) -> Result<Grid> {
+    Ok(Grid {
+        id: row.try_get("id")?,
+        game: row.try_get("game")?,
+        draw_date: row.try_get("draw_date")?,
+        numbers: from_json(row, "numbers")?,
+        stars: from_json(row, "stars")?,
+        created_at: row.try_get("created_at")?,
+        user_id: row.try_get("user_id")?,
+        cost_cents: row.try_get("cost_cents")?,
+        addons: from_json(row, "addons")?,
+        status: row.try_get("status")?,
+        played_at: row.try_get("played_at")?,
+        batch_id: row.try_get("batch_id")?,
+    })
+}
+
+const DRAW_COLUMNS: &str = "id, game, date, numbers, stars";
+
+const GRID_COLUMNS: &str = "id, game, draw_date, numbers, stars, created_at, user_id, cost_cents, addons, status, played_at, batch_id";
+
+fn raffle_code_from_row(row: &SqliteRow) -> Result<RaffleCode> {
+    Ok(RaffleCode {
+        id: row.try_get("id")?,
+        draw_id: row.try_get("draw_id")?,
+        draw_date: row.try_get("draw_date")?,
+        kind: row.try_get("kind")?,
+        code: row.try_get("code")?,
+    })
+}
+
+const RAFFLE_CODE_SELECT: &str = r#"
+    SELECT r.id, r.draw_id, d.date AS draw_date, r.kind, r.code
+    FROM raffle_codes r
+    JOIN draws d ON d.id = r.draw_id
+"#;
+
+fn draw_prize_from_row(row: &SqliteRow) -> Result<DrawPrize> {
+    Ok(DrawPrize {
+        id: row.try_get("id")?,
+        draw_id: row.try_get("draw_id")?,
+        draw_date: row.try_get("draw_date")?,
+        tier: row.try_get("tier")?,
+        amount_cents: row.try_get("amount_cents")?,
+        winners: row.try_get("winners")?,
+    })
+}
+
+fn profile_from_row(row: &SqliteRow) -> Result<GenerationProfile> {
+    Ok(GenerationProfile {
+        user_id: row.try_get("user_id")?,
+        game: row.try_get("game")?,
+        forced_numbers: from_json(row, "forced_numbers")?,
+        forced_stars: from_json(row, "forced_stars")?,
+        banned_numbers: from_json(row, "banned_numbers")?,
+        strategy: row.try_get("strategy")?,
+        grid_count: row.try_get("grid_count")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+fn batch_from_row(row: &SqliteRow) -> Result<GenerationBatch> {
+    Ok(GenerationBatch {
+        id: row.try_get("id")?,
+        idempotency_key: row.try_get("idempotency_key")?,
+        game: row.try_get("game")?,
+        user_id: row.try_get("user_id")?,
+        strategy: row.try_get("strategy")?,
+        params: row.try_get("params")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+const BATCH_COLUMNS: &str = "id, idempotency_key, game, user_id, strategy, params, created_at";
+
+const PROFILE_COLUMNS: &str =
+    "user_id, game, forced_numbers, forced_stars, banned_numbers, strategy, grid_count, updated_at";
+
+fn budget_from_row(row: &SqliteRow) -> Result<Budget> {
+    Ok(Budget {
+        user_id: row.try_get("user_id")?,
+        period: row.try_get("period")?,
+        currency: row.try_get("currency")?,
+        limit_cents: row.try_get("limit_cents")?,
+    })
+}
+
+const BUDGET_COLUMNS: &str = "user_id, period, currency, limit_cents";
+
+fn user_from_row(row: &SqliteRow) -> Result<User> {
+    Ok(User {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+fn channel_from_row(row: &SqliteRow) -> Result<NotificationChannel> {
+    Ok(NotificationChannel {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        kind: row.try_get("kind")?,
+        target: row.try_get("target")?,
+        notify_results: row.try_get("notify_results")?,
+        notify_wins: row.try_get("notify_wins")?,
+        enabled: row.try_get("enabled")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+const CHANNEL_COLUMNS: &str = "id, user_id, kind, target, notify_results, notify_wins, enabled, created_at";
+
+fn webhook_from_row(row: &SqliteRow) -> Result<Webhook> {
+    Ok(Webhook {
+        id: row.try_get("id")?,
+        url: row.try_get("url")?,
+        secret: row.try_get("secret")?,
+        events: from_json(row, "events")?,
+        enabled: row.try_get("enabled")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+const WEBHOOK_COLUMNS: &str = "id, url, secret, events, enabled, created_at";
+
+fn pending_delivery_from_row(row: &SqliteRow) -> Result<PendingDelivery> {
+    Ok(PendingDelivery {
+        id: row.try_get("id")?,
+        webhook_id: row.try_get("webhook_id")?,
+        url: row.try_get("url")?,
+        secret: row.try_get("secret")?,
+        event_id: row.try_get("event_id")?,
+        event: row.try_get("event")?,
+        payload: row.try_get("payload")?,
+        event_created_at: row.try_get("event_created_at")?,
+        attempts: row.try_get("attempts")?,
+    })
+}
+
+const PENDING_DELIVERY_COLUMNS: &str = "d.id, d.webhook_id, w.url, w.secret, d.event_id, e.event, e.payload, \
+     e.created_at AS event_created_at, d.attempts";
+
+fn delivery_from_row(row: &SqliteRow) -> Result<WebhookDelivery> {
+    Ok(WebhookDelivery {
+        id: row.try_get("id")?,
+        webhook_id: row.try_get("webhook_id")?,
+        event_id: row.try_get("event_id")?,
+        event: row.try_get("event")?,
+        status: row.try_get("status")?,
+        attempts: row.try_get("attempts")?,
+        next_attempt_at: row.try_get("next_attempt_at")?,
+        response_status: row.try_get("response_status")?,
+        error: row.try_get("error")?,
+        created_at: row.try_get("created_at")?,
+        delivered_at: row.try_get("delivered_at")?,
+    })
+}
+
+const DELIVERY_SELECT: &str = r#"
+    SELECT d.id, d.webhook_id, d.event_id, e.event, d.status, d.attempts, d.next_attempt_at,
+           d.response_status, d.error, d.created_at, d.delivered_at
+    FROM webhook_deliveries d
+    JOIN webhook_events e ON e.id = d.event_id
+"#;
+
+/// See `Storage::emit_event`; runs on the caller's connection so
+/// `upsert_draw` can queue `draw.created` in its own transaction.
+async fn emit_event_in(conn: &mut SqliteConnection, event: &str, data: &serde_json::Value) -> Result<usize> {
+    let subscribers: Vec<i32> = sqlx::query_scalar(
+        r#"
+        SELECT id FROM webhooks
+        WHERE enabled AND EXISTS (SELECT 1 FROM json_each(webhooks.events) WHERE value = ?)
+        ORDER BY id
+        "#,
+    )
+    .bind(event)
+    .fetch_all(&mut *conn)
+    .await?;
+    if subscribers.is_empty() {
+        return Ok(0);
+    }
+
+    let event_id: i32 = sqlx::query_scalar("INSERT INTO webhook_events (event, payload) VALUES (?, ?) RETURNING id")
+        .bind(event)
+        .bind(data.to_string())
+        .fetch_one(&mut *conn)
+        .await?;
+    for webhook_id in &subscribers {
+        sqlx::query("INSERT INTO webhook_deliveries (webhook_id, event_id) VALUES (?, ?)")
+            .bind(webhook_id)
+            .bind(event_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(subscribers.len())
+}
+
+/// Inserts grids on the caller's connection, so a batch and its grids share
+/// a transaction.
+async fn insert_grid_rows(conn: &mut SqliteConnection, grids: &[NewGrid], batch_id: Option<i32>) -> Result<Vec<Grid>> {
+    let sql = format!(
+        r#"
+        INSERT INTO grids (game, draw_date, numbers, stars, user_id, cost_cents, addons, batch_id)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING {}
+        "#,
+        GRID_COLUMNS
+    );
+    let mut saved = Vec::with_capacity(grids.len());
+    for grid in grids {
+        let row = sqlx::query(&sql)
+            .bind(&grid.game)
+            .bind(grid.draw_date)
+            .bind(to_json(&grid.numbers))
+            .bind(to_json(&grid.stars))
+            .bind(grid.user_id)
+            .bind(grid.cost_cents)
+            .bind(to_json(&grid.addons))
+            .bind(batch_id)
+            .fetch_one(&mut *conn)
+            .await?;
+        saved.push(grid_from_row(&row)?);
+    }
+    Ok(saved)
+}
+
+fn archived_page_from_row(row: &SqliteRow) -> Result<ArchivedPage> {
+    Ok(ArchivedPage {
+        id: row.try_get("id")?,
+        url: row.try_get("url")?,
+        year: row.try_get("year")?,
+        http_status: row.try_get("http_status")?,
+        etag: row.try_get("etag")?,
+        last_modified: row.try_get("last_modified")?,
+        content_sha256: row.try_get("content_sha256")?,
+        content_length: row.try_get("content_length")?,
+        body_gzip: row.try_get("body_gzip")?,
+        fetched_at: row.try_get("fetched_at")?,
+        last_seen_at: row.try_get("last_seen_at")?,
+        fetch_count: row.try_get("fetch_count")?,
+    })
+}
+
+const ARCHIVED_PAGE_COLUMNS: &str = "id, url, year, http_status, etag, last_modified, content_sha256, \
+     content_length, body_gzip, fetched_at, last_seen_at, fetch_count";
+
+fn fetch_run_from_row(row: &SqliteRow) -> Result<FetchRun> {
+    Ok(FetchRun {
+        id: row.try_get("id")?,
+        trigger: row.try_get("trigger")?,
+        source: row.try_get("source")?,
+        years: from_json(row, "years")?,
+        status: row.try_get("status")?,
+        started_at: row.try_get("started_at")?,
+        finished_at: row.try_get("finished_at")?,
+        rows_parsed: row.try_get("rows_parsed")?,
+        rows_inserted: row.try_get("rows_inserted")?,
+        rows_rejected: row.try_get("rows_rejected")?,
+        error: row.try_get("error")?,
+    })
+}
+
+const FETCH_RUN_COLUMNS: &str = "id, trigger, source, years, status, started_at, finished_at, \
+     rows_parsed, rows_inserted, rows_rejected, error";
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    fn pool_status(&self) -> PoolStatus {
+        PoolStatus {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+            max: self.pool.options().get_max_connections(),
+        }
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn migrate(&self) -> Result<()> {
+        MIGRATOR.run(&self.pool).await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn pending_migrations(&self) -> Result<Vec<i64>> {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        let applied: Vec<i64> = if exists {
+            sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success ORDER BY version")
+                .fetch_all(&self.pool)
+                .await?
+        } else {
+            Vec::new()
+        };
+        Ok(super::pending_versions(&MIGRATOR, &applied))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn upsert_draw(&self, draw: NewDraw) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let row = sqlx::query(
+            r#"
+            INSERT INTO draws (game, date, numbers, stars) VALUES (?, ?, ?, ?)
+            ON CONFLICT (game, date) DO NOTHING
+            RETURNING id, game, date, numbers, stars
+            "#,
+        )
+        .bind(&draw.game)
+        .bind(draw.date)
+        .bind(to_json(&draw.numbers))
+        .bind(to_json(&draw.stars))
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let inserted = row.as_ref().map(draw_from_row).transpose()?;
+        if let Some(draw) = &inserted {
+            let data = serde_json::to_value(draw).expect("draws serialize to JSON");
+            emit_event_in(&mut tx, Event::DrawCreated.as_str(), &data).await?;
+        }
+        tx.commit().await?;
+        Ok(inserted.is_some())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn replace_draw(&self, draw: NewDraw) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO draws (game, date, numbers, stars)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (game, date) DO UPDATE
+            SET numbers = excluded.numbers, stars = excluded.stars
+            WHERE draws.numbers <> excluded.numbers OR draws.stars <> excluded.stars
+            "#,
+        )
+        .bind(&draw.game)
+        .bind(draw.date)
+        .bind(to_json(&draw.numbers))
+        .bind(to_json(&draw.stars))
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_draws(&self) -> Result<Vec<Draw>> {
+        let sql = format!("SELECT {} FROM draws ORDER BY game, date", DRAW_COLUMNS);
+        let rows = sqlx::query(&sql).fetch_all(&self.pool).await?;
+        rows.iter().map(draw_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_draw(&self, game: &str, date: NaiveDate) -> Result<Option<Draw>> {
+        let sql = format!("SELECT {} FROM draws WHERE game = ? AND date = ?", DRAW_COLUMNS);
+        let row = sqlx::query(&sql).bind(game).bind(date).fetch_optional(&self.pool).await?;
+        row.as_ref().map(draw_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn latest_draw(&self, game: &str) -> Result<Option<Draw>> {
+        let sql = format!("SELECT {} FROM draws WHERE game = ? ORDER BY date DESC LIMIT 1", DRAW_COLUMNS);
+        let row = sqlx::query(&sql).bind(game).fetch_optional(&self.pool).await?;
+        row.as_ref().map(draw_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn count_draws(&self, game: &str) -> Result<i64> {
+        let row = sqlx::query("SELECT COUNT(*) AS count FROM draws WHERE game = ?")
+            .bind(game)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.try_get("count")?)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_most_frequent_numbers(&self, game: &str, limit: i64) -> Result<Vec<i32>> {
+        self.most_frequent(game, "numbers", limit).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_most_frequent_stars(&self, game: &str, limit: i64) -> Result<Vec<i32>> {
+        self.most_frequent(game, "stars", limit).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn number_frequencies(&self, game: &str) -> Result<Vec<BallFrequency>> {
+        self.frequencies(game, "numbers", -1).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn star_frequencies(&self, game: &str) -> Result<Vec<BallFrequency>> {
+        self.frequencies(game, "stars", -1).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn insert_grids(&self, grids: &[NewGrid]) -> Result<Vec<Grid>> {
+        let mut tx = self.pool.begin().await?;
+        let saved = insert_grid_rows(&mut tx, grids, None).await?;
+        tx.commit().await?;
+        Ok(saved)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn insert_generation_batch(&self, batch: &NewGenerationBatch, grids: &[NewGrid]) -> Result<Option<Vec<Grid>>> {
+        let mut tx = self.pool.begin().await?;
+        let batch_id: Option<i32> = sqlx::query_scalar(
+            r#"
+            INSERT INTO generation_batches (idempotency_key, game, user_id, strategy, params)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (idempotency_key) DO NOTHING
+            RETURNING id
+            "#,
+        )
+        .bind(&batch.idempotency_key)
+        .bind(&batch.game)
+        .bind(batch.user_id)
+        .bind(&batch.strategy)
+        .bind(&batch.params)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(batch_id) = batch_id else {
+            return Ok(None);
+        };
+        let saved = insert_grid_rows(&mut tx, grids, Some(batch_id)).await?;
+        tx.commit().await?;
+        Ok(Some(saved))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_generation_batch(&self, idempotency_key: &str) -> Result<Option<GenerationBatch>> {
+        let sql = format!("SELECT {} FROM generation_batches WHERE idempotency_key = ?", BATCH_COLUMNS);
+        let row = sqlx::query(&sql).bind(idempotency_key).fetch_optional(&self.pool).await?;
+        row.as_ref().map(batch_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn batch_grids(&self, batch_id: i32) -> Result<Vec<Grid>> {
+        let sql = format!("SELECT {} FROM grids WHERE batch_id = ? ORDER BY id", GRID_COLUMNS);
+        let rows = sqlx::query(&sql).bind(batch_id).fetch_all(&self.pool).await?;
+        rows.iter().map(grid_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn recent_grids(&self, limit: i64, status: Option<&str>) -> Result<Vec<Grid>> {
+        let sql = format!(
+            "SELECT {} FROM grids WHERE ?2 IS NULL OR status = ?2 ORDER BY created_at DESC, id DESC LIMIT ?1",
+            GRID_COLUMNS
+        );
+        let rows = sqlx::query(&sql).bind(limit).bind(status).fetch_all(&self.pool).await?;
+        rows.iter().map(grid_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_grids(&self, ids: &[i32]) -> Result<Vec<Grid>> {
+        let sql = format!(
+            "SELECT {} FROM grids WHERE id IN (SELECT value FROM json_each(?)) ORDER BY id",
+            GRID_COLUMNS
+        );
+        let rows = sqlx::query(&sql).bind(to_json(ids)).fetch_all(&self.pool).await?;
+        rows.iter().map(grid_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn set_grid_status(&self, ids: &[i32], from: &[&str], status: &str) -> Result<Vec<Grid>> {
+        let sql = format!(
+            r#"
+            UPDATE grids
+            SET status = ?3,
+                played_at = CASE ?3
+                    WHEN 'played' THEN COALESCE(played_at, CURRENT_TIMESTAMP)
+                    WHEN 'generated' THEN NULL
+                    ELSE played_at
+                END
+            WHERE id IN (SELECT value FROM json_each(?1))
+              AND status IN (SELECT value FROM json_each(?2))
+            RETURNING {}
+            "#,
+            GRID_COLUMNS
+        );
+        let rows = sqlx::query(&sql)
+            .bind(to_json(ids))
+            .bind(to_json(from))
+            .bind(status)
+            .fetch_all(&self.pool)
+            .await?;
+        let mut grids = rows.iter().map(grid_from_row).collect::<Result<Vec<_>>>()?;
+        grids.sort_by_key(|g| g.id);
+        Ok(grids)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn play_grids(&self, plays: &[GridPlay], caps: &[SpendingCap]) -> Result<Vec<i64>> {
+        // The first update takes SQLite's single write lock, so the sums see
+        // every other play either before or after this one
+        let mut tx = self.pool.begin().await?;
+        for play in plays {
+            sqlx::query(
+                r#"
+                UPDATE grids
+                SET addons = ?, cost_cents = ?, status = 'played', played_at = COALESCE(played_at, CURRENT_TIMESTAMP)
+                WHERE id = ? AND status IN ('generated', 'played')
+                "#,
+            )
+            .bind(to_json(&play.addons))
+            .bind(play.cost_cents)
+            .bind(play.id)
+            .execute(&mut *tx)
+            .await?;
+        }
+        let mut sums = Vec::with_capacity(caps.len());
+        for cap in caps {
+            let spent: i64 = sqlx::query_scalar(
+                r#"
+                SELECT COALESCE(SUM(cost_cents), 0)
+                FROM grids
+                WHERE user_id = ? AND game IN (SELECT value FROM json_each(?))
+                  AND draw_date BETWEEN ? AND ? AND played_at IS NOT NULL
+                "#,
+            )
+            .bind(cap.user_id)
+            .bind(to_json(&cap.games))
+            .bind(cap.from)
+            .bind(cap.to)
+            .fetch_one(&mut *tx)
+            .await?;
+            sums.push(spent);
+        }
+        if caps.iter().zip(&sums).all(|(cap, spent)| *spent <= cap.limit_cents) {
+            tx.commit().await?;
+        }
+        Ok(sums)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn grids_for_draw(&self, game: &str, date: NaiveDate) -> Result<Vec<Grid>> {
+        let sql = format!("SELECT {} FROM grids WHERE game = ? AND draw_date = ? ORDER BY id", GRID_COLUMNS);
+        let rows = sqlx::query(&sql).bind(game).bind(date).fetch_all(&self.pool).await?;
+        rows.iter().map(grid_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn user_grids(&self, user_id: i32, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Result<Vec<Grid>> {
+        let sql = format!(
+            r#"
+            SELECT {} FROM grids
+            WHERE user_id = ?
+              AND (?2 IS NULL OR draw_date >= ?2)
+              AND (?3 IS NULL OR draw_date <= ?3)
+            ORDER BY draw_date, id
+            "#,
+            GRID_COLUMNS
+        );
+        let rows = sqlx::query(&sql).bind(user_id).bind(from).bind(to).fetch_all(&self.pool).await?;
+        rows.iter().map(grid_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn add_raffle_codes(&self, draw_id: i32, codes: &[NewRaffleCode]) -> Result<usize> {
+        let mut tx = self.pool.begin().await?;
+        let mut inserted = 0;
+        for code in codes {
+            let result = sqlx::query(
+                "INSERT INTO raffle_codes (draw_id, kind, code) VALUES (?, ?, ?) \
+                 ON CONFLICT (draw_id, kind, code) DO NOTHING",
+            )
+            .bind(draw_id)
+            .bind(&code.kind)
+            .bind(&code.code)
+            .execute(&mut *tx)
+            .await?;
+            inserted += result.rows_affected() as usize;
+        }
+        tx.commit().await?;
+        Ok(inserted)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn raffle_codes(&self, draw_id: i32) -> Result<Vec<RaffleCode>> {
+        let sql = format!("{} WHERE r.draw_id = ? ORDER BY r.kind, r.code", RAFFLE_CODE_SELECT);
+        let rows = sqlx::query(&sql).bind(draw_id).fetch_all(&self.pool).await?;
+        rows.iter().map(raffle_code_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn find_raffle_code(&self, kind: &str, code: &str) -> Result<Vec<RaffleCode>> {
+        let sql = format!("{} WHERE r.kind = ? AND r.code = ? ORDER BY d.date", RAFFLE_CODE_SELECT);
+        let rows = sqlx::query(&sql).bind(kind).bind(code).fetch_all(&self.pool).await?;
+        rows.iter().map(raffle_code_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn upsert_draw_prizes(&self, draw_id: i32, prizes: &[NewDrawPrize]) -> Result<usize> {
+        let mut tx = self.pool.begin().await?;
+        let mut written = 0;
+        for prize in prizes {
+            let result = sqlx::query(
+                r#"
+                INSERT INTO draw_prizes (draw_id, tier, amount_cents, winners)
+                VALUES (?, ?, ?, ?)
+                ON CONFLICT (draw_id, tier) DO UPDATE
+                SET amount_cents = excluded.amount_cents,
+                    winners = COALESCE(excluded.winners, draw_prizes.winners)
+                "#,
+            )
+            .bind(draw_id)
+            .bind(prize.tier)
+            .bind(prize.amount_cents)
+            .bind(prize.winners)
+            .execute(&mut *tx)
+            .await?;
+            written += result.rows_affected() as usize;
+        }
+        tx.commit().await?;
+        Ok(written)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_draw_prizes(&self, game: &str) -> Result<Vec<DrawPrize>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT p.id, p.draw_id, d.date AS draw_date, p.tier, p.amount_cents, p.winners
+            FROM draw_prizes p
+            JOIN draws d ON d.id = p.draw_id
+            WHERE d.game = ?
+            ORDER BY d.date, p.tier
+            "#,
+        )
+        .bind(game)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(draw_prize_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn set_ticket_price(&self, price: &TicketPrice) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO ticket_prices (game, valid_from, price_cents) VALUES (?, ?, ?) \
+             ON CONFLICT (game, valid_from) DO UPDATE SET price_cents = excluded.price_cents",
+        )
+        .bind(&price.game)
+        .bind(price.valid_from)
+        .bind(price.price_cents)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_ticket_prices(&self, game: &str) -> Result<Vec<TicketPrice>> {
+        let rows = sqlx::query(
+            "SELECT game, valid_from, price_cents FROM ticket_prices WHERE game = ? ORDER BY valid_from",
+        )
+        .bind(game)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(TicketPrice {
+                    game: row.try_get("game")?,
+                    valid_from: row.try_get("valid_from")?,
+                    price_cents: row.try_get("price_cents")?,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn set_ticket_addon(&self, addon: &TicketAddon) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO ticket_addons (game, addon, valid_from, price_cents) VALUES (?, ?, ?, ?) \
+             ON CONFLICT (game, addon, valid_from) DO UPDATE SET price_cents = excluded.price_cents",
+        )
+        .bind(&addon.game)
+        .bind(&addon.addon)
+        .bind(addon.valid_from)
+        .bind(addon.price_cents)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_ticket_addons(&self, game: &str) -> Result<Vec<TicketAddon>> {
+        let rows = sqlx::query(
+            "SELECT game, addon, valid_from, price_cents FROM ticket_addons WHERE game = ? ORDER BY addon, valid_from",
+        )
+        .bind(game)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(TicketAddon {
+                    game: row.try_get("game")?,
+                    addon: row.try_get("addon")?,
+                    valid_from: row.try_get("valid_from")?,
+                    price_cents: row.try_get("price_cents")?,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn archive_page(&self, page: &NewArchivedPage) -> Result<i32> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO raw_pages
+                (url, year, http_status, etag, last_modified, content_sha256, content_length, body_gzip)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (url, content_sha256) DO UPDATE
+            SET last_seen_at = CURRENT_TIMESTAMP,
+                fetch_count = raw_pages.fetch_count + 1,
+                http_status = excluded.http_status,
+                etag = COALESCE(excluded.etag, raw_pages.etag),
+                last_modified = COALESCE(excluded.last_modified, raw_pages.last_modified)
+            RETURNING id
+            "#,
+        )
+        .bind(&page.url)
+        .bind(page.year)
+        .bind(page.http_status)
+        .bind(&page.etag)
+        .bind(&page.last_modified)
+        .bind(&page.content_sha256)
+        .bind(page.content_length)
+        .bind(&page.body_gzip)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.try_get("id")?)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn touch_archived_page(&self, url: &str, content_sha256: &str) -> Result<Option<i32>> {
+        let id = sqlx::query_scalar(
+            "UPDATE raw_pages SET last_seen_at = CURRENT_TIMESTAMP WHERE url = ? AND content_sha256 = ? RETURNING id",
+        )
+        .bind(url)
+        .bind(content_sha256)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(id)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn latest_archived_pages(&self) -> Result<Vec<ArchivedPage>> {
+        let sql = format!(
+            r#"
+            SELECT {} FROM raw_pages p
+            WHERE p.id = (
+                SELECT id FROM raw_pages WHERE url = p.url
+                ORDER BY last_seen_at DESC, id DESC LIMIT 1
+            )
+            ORDER BY url
+            "#,
+            ARCHIVED_PAGE_COLUMNS
+        );
+        let rows = sqlx::query(&sql).fetch_all(&self.pool).await?;
+        rows.iter().map(archived_page_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn latest_archived_page(&self, url: &str) -> Result<Option<ArchivedPage>> {
+        let sql = format!(
+            "SELECT {} FROM raw_pages WHERE url = ? ORDER BY last_seen_at DESC, id DESC LIMIT 1",
+            ARCHIVED_PAGE_COLUMNS
+        );
+        let row = sqlx::query(&sql).bind(url).fetch_optional(&self.pool).await?;
+        row.as_ref().map(archived_page_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn last_fetched_at(&self) -> Result<Option<NaiveDateTime>> {
+        let last = sqlx::query_scalar(
+            "SELECT MAX(finished_at) FROM fetch_runs WHERE status IN ('succeeded', 'partial')",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(last)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn start_fetch_run(&self, run: &NewFetchRun) -> Result<i32> {
+        let row = sqlx::query("INSERT INTO fetch_runs (trigger, source, years) VALUES (?, ?, ?) RETURNING id")
+            .bind(&run.trigger)
+            .bind(&run.source)
+            .bind(to_json(&run.years))
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.try_get("id")?)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn finish_fetch_run(&self, id: i32, result: &FinishedFetchRun) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE fetch_runs
+            SET finished_at = CURRENT_TIMESTAMP, status = ?, rows_parsed = ?,
+                rows_inserted = ?, rows_rejected = ?, error = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(&result.status)
+        .bind(result.rows_parsed)
+        .bind(result.rows_inserted)
+        .bind(result.rows_rejected)
+        .bind(&result.error)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_fetch_runs(&self, limit: i64) -> Result<Vec<FetchRun>> {
+        let sql = format!(
+            "SELECT {} FROM fetch_runs ORDER BY started_at DESC, id DESC LIMIT ?",
+            FETCH_RUN_COLUMNS
+        );
+        let rows = sqlx::query(&sql).bind(limit).fetch_all(&self.pool).await?;
+        rows.iter().map(fetch_run_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_fetch_run(&self, id: i32) -> Result<Option<FetchRun>> {
+        let sql = format!("SELECT {} FROM fetch_runs WHERE id = ?", FETCH_RUN_COLUMNS);
+        let row = sqlx::query(&sql).bind(id).fetch_optional(&self.pool).await?;
+        row.as_ref().map(fetch_run_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn create_user(&self, name: &str) -> Result<User> {
+        let row = sqlx::query("INSERT INTO users (name) VALUES (?) RETURNING id, name, created_at")
+            .bind(name)
+            .fetch_one(&self.pool)
+            .await?;
+        user_from_row(&row)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_user(&self, id: i32) -> Result<Option<User>> {
+        let row = sqlx::query("SELECT id, name, created_at FROM users WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(user_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_user_by_name(&self, name: &str) -> Result<Option<User>> {
+        let row = sqlx::query("SELECT id, name, created_at FROM users WHERE name = ?")
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(user_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn add_notification_channel(&self, channel: &NewNotificationChannel) -> Result<NotificationChannel> {
+        let sql = format!(
+            r#"
+            INSERT INTO notification_channels (user_id, kind, target, notify_results, notify_wins)
+            VALUES (?, ?, ?, ?, ?)
+            RETURNING {}
+            "#,
+            CHANNEL_COLUMNS
+        );
+        let row = sqlx::query(&sql)
+            .bind(channel.user_id)
+            .bind(&channel.kind)
+            .bind(&channel.target)
+            .bind(channel.notify_results)
+            .bind(channel.notify_wins)
+            .fetch_one(&self.pool)
+            .await?;
+        channel_from_row(&row)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_notification_channels(&self, user_id: i32) -> Result<Vec<NotificationChannel>> {
+        let sql = format!(
+            "SELECT {} FROM notification_channels WHERE user_id = ? ORDER BY id",
+            CHANNEL_COLUMNS
+        );
+        let rows = sqlx::query(&sql).bind(user_id).fetch_all(&self.pool).await?;
+        rows.iter().map(channel_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn enabled_notification_channels(&self) -> Result<Vec<NotificationChannel>> {
+        let sql = format!(
+            "SELECT {} FROM notification_channels WHERE enabled ORDER BY user_id, id",
+            CHANNEL_COLUMNS
+        );
+        let rows = sqlx::query(&sql).fetch_all(&self.pool).await?;
+        rows.iter().map(channel_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_notification_channel(&self, user_id: i32, id: i32) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM notification_channels WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn upsert_generation_profile(
+        &self,
+        user_id: i32,
+        game: &str,
+        profile: &NewGenerationProfile,
+    ) -> Result<GenerationProfile> {
+        let sql = format!(
+            r#"
+            INSERT INTO generation_profiles
+                (user_id, game, forced_numbers, forced_stars, banned_numbers, strategy, grid_count)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (user_id, game) DO UPDATE
+            SET forced_numbers = excluded.forced_numbers,
+                forced_stars = excluded.forced_stars,
+                banned_numbers = excluded.banned_numbers,
+                strategy = excluded.strategy,
+                grid_count = excluded.grid_count,
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING {}
+            "#,
+            PROFILE_COLUMNS
+        );
+        let row = sqlx::query(&sql)
+            .bind(user_id)
+            .bind(game)
+            .bind(to_json(&profile.forced_numbers))
+            .bind(to_json(&profile.forced_stars))
+            .bind(to_json(&profile.banned_numbers))
+            .bind(&profile.strategy)
+            .bind(profile.grid_count)
+            .fetch_one(&self.pool)
+            .await?;
+        profile_from_row(&row)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_generation_profile(&self, user_id: i32, game: &str) -> Result<Option<GenerationProfile>> {
+        let sql = format!(
+            "SELECT {} FROM generation_profiles WHERE user_id = ? AND game = ?",
+            PROFILE_COLUMNS
+        );
+        let row = sqlx::query(&sql)
+            .bind(user_id)
+            .bind(game)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(profile_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_generation_profile(&self, user_id: i32, game: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM generation_profiles WHERE user_id = ? AND game = ?")
+            .bind(user_id)
+            .bind(game)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn set_budget(&self, budget: &Budget) -> Result<Budget> {
+        let sql = format!(
+            r#"
+            INSERT INTO budgets (user_id, period, currency, limit_cents)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (user_id, period, currency) DO UPDATE SET limit_cents = excluded.limit_cents
+            RETURNING {}
+            "#,
+            BUDGET_COLUMNS
+        );
+        let row = sqlx::query(&sql)
+            .bind(budget.user_id)
+            .bind(&budget.period)
+            .bind(&budget.currency)
+            .bind(budget.limit_cents)
+            .fetch_one(&self.pool)
+            .await?;
+        budget_from_row(&row)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_budgets(&self, user_id: i32) -> Result<Vec<Budget>> {
+        let sql = format!(
+            "SELECT {} FROM budgets WHERE user_id = ? ORDER BY currency, period",
+            BUDGET_COLUMNS
+        );
+        let rows = sqlx::query(&sql).bind(user_id).fetch_all(&self.pool).await?;
+        rows.iter().map(budget_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_budget(&self, user_id: i32, period: &str, currency: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM budgets WHERE user_id = ? AND period = ? AND currency = ?")
+            .bind(user_id)
+            .bind(period)
+            .bind(currency)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn create_webhook(&self, webhook: &NewWebhook) -> Result<Webhook> {
+        let sql = format!(
+            "INSERT INTO webhooks (url, secret, events) VALUES (?, ?, ?) RETURNING {}",
+            WEBHOOK_COLUMNS
+        );
+        let row = sqlx::query(&sql)
+            .bind(&webhook.url)
+            .bind(&webhook.secret)
+            .bind(to_json(&webhook.events))
+            .fetch_one(&self.pool)
+            .await?;
+        webhook_from_row(&row)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_webhooks(&self) -> Result<Vec<Webhook>> {
+        let sql = format!("SELECT {} FROM webhooks ORDER BY id", WEBHOOK_COLUMNS);
+        let rows = sqlx::query(&sql).fetch_all(&self.pool).await?;
+        rows.iter().map(webhook_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_webhook(&self, id: i32) -> Result<Option<Webhook>> {
+        let sql = format!("SELECT {} FROM webhooks WHERE id = ?", WEBHOOK_COLUMNS);
+        let row = sqlx::query(&sql).bind(id).fetch_optional(&self.pool).await?;
+        row.as_ref().map(webhook_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_webhook(&self, id: i32) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM webhooks WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn emit_event(&self, event: &str, data: &serde_json::Value) -> Result<usize> {
+        let mut tx = self.pool.begin().await?;
+        let queued = emit_event_in(&mut tx, event, data).await?;
+        tx.commit().await?;
+        Ok(queued)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn claim_due_deliveries(
+        &self,
+        now: NaiveDateTime,
+        lease_until: NaiveDateTime,
+        limit: i64,
+    ) -> Result<Vec<PendingDelivery>> {
+        // SQLite has a single writer, so the update alone makes the claim
+        let mut tx = self.pool.begin().await?;
+        let ids: Vec<i32> = sqlx::query_scalar(
+            r#"
+            UPDATE webhook_deliveries SET next_attempt_at = ?2
+            WHERE id IN (
+                SELECT d.id
+                FROM webhook_deliveries d
+                JOIN webhooks w ON w.id = d.webhook_id
+                WHERE d.status = 'pending' AND w.enabled
+                  AND (d.next_attempt_at IS NULL OR d.next_attempt_at <= ?1)
+                ORDER BY d.id
+                LIMIT ?3
+            )
+            RETURNING id
+            "#,
+        )
+        .bind(now)
+        .bind(lease_until)
+        .bind(limit)
+        .fetch_all(&mut *tx)
+        .await?;
+        let sql = format!(
+            r#"
+            SELECT {}
+            FROM webhook_deliveries d
+            JOIN webhooks w ON w.id = d.webhook_id
+            JOIN webhook_events e ON e.id = d.event_id
+            WHERE d.id IN (SELECT value FROM json_each(?))
+            ORDER BY d.id
+            "#,
+            PENDING_DELIVERY_COLUMNS
+        );
+        let rows = sqlx::query(&sql).bind(to_json(&ids)).fetch_all(&mut *tx).await?;
+        tx.commit().await?;
+        rows.iter().map(pending_delivery_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn record_delivery_attempt(&self, id: i32, attempt: &DeliveryAttempt) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = ?2,
+                attempts = attempts + 1,
+                response_status = ?3,
+                error = ?4,
+                next_attempt_at = ?5,
+                delivered_at = CASE WHEN ?2 = 'delivered' THEN CURRENT_TIMESTAMP ELSE NULL END
+            WHERE id = ?1
+            "#,
+        )
+        .bind(id)
+        .bind(&attempt.status)
+        .bind(attempt.response_status)
+        .bind(&attempt.error)
+        .bind(attempt.next_attempt_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_deliveries(&self, webhook_id: i32, limit: i64) -> Result<Vec<WebhookDelivery>> {
+        let sql = format!("{} WHERE d.webhook_id = ? ORDER BY d.id DESC LIMIT ?", DELIVERY_SELECT);
+        let rows = sqlx::query(&sql)
+            .bind(webhook_id)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(delivery_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_delivery(&self, id: i32) -> Result<Option<WebhookDelivery>> {
+        let sql = format!("{} WHERE d.id = ?", DELIVERY_SELECT);
+        let row = sqlx::query(&sql).bind(id).fetch_optional(&self.pool).await?;
+        row.as_ref().map(delivery_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn replay_delivery(&self, id: i32) -> Result<Option<WebhookDelivery>> {
+        let replay: Option<i32> = sqlx::query_scalar(
+            r#"
+            INSERT INTO webhook_deliveries (webhook_id, event_id)
+            SELECT webhook_id, event_id FROM webhook_deliveries WHERE id = ?
+            RETURNING id
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        match replay {
+            Some(replay) => self.get_delivery(replay).await,
+            None => Ok(None),
+        }
+    }
+}
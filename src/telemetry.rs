@@ -0,0 +1,29 @@
+//! Tracing subscriber setup. Spans are logged when they close, so every
+//! fetch, parse, generation and database call reports how long it took.
+
+use crate::config::{LogConfig, LogFormat};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+/// Installs the global subscriber. `RUST_LOG` overrides `config.level`.
+pub fn init(config: &LogConfig) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.level));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        // stdout is reserved for command output
+        .with_writer(std::io::stderr);
+
+    let result = match config.format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .try_init(),
+    };
+    if let Err(e) = result {
+        eprintln!("Logging is already initialized: {}", e);
+    }
+}
@@ -0,0 +1,149 @@
+//! Import and export of one game's draws as CSV (`date,n1,...,n5,s1,s2` for
+//! EuroMillions) or JSON, so history can be moved between databases or
+//! seeded without scraping.
+
+use crate::error::{AppError, Result};
+use crate::games::GameRules;
+use crate::models::{Draw, NewDraw};
+use crate::storage::Storage;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    #[default]
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportSummary {
+    pub read: usize,
+    pub inserted: usize,
+}
+
+/// A draw in a file. The game is given by the caller, not the file.
+#[derive(Debug, Serialize, Deserialize)]
+struct DrawRecord {
+    date: NaiveDate,
+    numbers: Vec<i32>,
+    stars: Vec<i32>,
+}
+
+/// `date`, then a column per number (`n1`...) and per bonus ball (`s1`...).
+pub fn csv_header(game: &GameRules) -> String {
+    let numbers = (1..=game.numbers.count).map(|i| format!("n{}", i));
+    let stars = (1..=game.drawn_bonus).map(|i| format!("s{}", i));
+    std::iter::once("date".to_string())
+        .chain(numbers)
+        .chain(stars)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn parse_draws(text: &str, format: Format, game: &GameRules) -> Result<Vec<NewDraw>> {
+    let records = match format {
+        Format::Csv => parse_csv(text, game)?,
+        Format::Json => serde_json::from_str(text)
+            .map_err(|e| AppError::Validation(format!("invalid JSON: {}", e)))?,
+    };
+    records
+        .into_iter()
+        .map(|record| {
+            game.validate_draw(&record.numbers, &record.stars)
+                .map_err(|e| AppError::Validation(format!("draw of {}: {}", record.date, e)))?;
+            Ok(NewDraw {
+                game: game.id.to_string(),
+                date: record.date,
+                numbers: record.numbers,
+                stars: record.stars,
+            })
+        })
+        .collect()
+}
+
+fn parse_csv(text: &str, game: &GameRules) -> Result<Vec<DrawRecord>> {
+    let fields_per_line = 1 + game.numbers.count + game.drawn_bonus;
+    let mut draws = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (index == 0 && line.starts_with("date")) {
+            continue;
+        }
+        let invalid = |what: &str| AppError::Validation(format!("line {}: {}", index + 1, what));
+
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields.len() != fields_per_line {
+            return Err(invalid(&format!(
+                "expected {} fields, got {}",
+                fields_per_line,
+                fields.len()
+            )));
+        }
+        let date = NaiveDate::parse_from_str(fields[0], "%Y-%m-%d")
+            .map_err(|_| invalid(&format!("invalid date '{}'", fields[0])))?;
+        let balls = fields[1..]
+            .iter()
+            .map(|f| f.parse::<i32>().map_err(|_| invalid(&format!("invalid ball '{}'", f))))
+            .collect::<Result<Vec<_>>>()?;
+
+        draws.push(DrawRecord {
+            date,
+            numbers: balls[..game.numbers.count].to_vec(),
+            stars: balls[game.numbers.count..].to_vec(),
+        });
+    }
+    Ok(draws)
+}
+
+pub fn format_draws(draws: &[Draw], format: Format, game: &GameRules) -> String {
+    match format {
+        Format::Csv => {
+            let mut out = csv_header(game);
+            out.push('\n');
+            for draw in draws {
+                let balls: Vec<String> = draw
+                    .numbers
+                    .iter()
+                    .chain(&draw.stars)
+                    .map(|b| b.to_string())
+                    .collect();
+                out.push_str(&format!("{},{}\n", draw.date, balls.join(",")));
+            }
+            out
+        }
+        Format::Json => {
+            let draws: Vec<DrawRecord> = draws
+                .iter()
+                .map(|d| DrawRecord {
+                    date: d.date,
+                    numbers: d.numbers.clone(),
+                    stars: d.stars.clone(),
+                })
+                .collect();
+            serde_json::to_string_pretty(&draws).expect("serializing draws cannot fail")
+        }
+    }
+}
+
+/// Parses `text` and inserts draws that are not stored yet. Nothing is
+/// written if any line is invalid.
+pub async fn import(storage: &dyn Storage, text: &str, format: Format, game: &GameRules) -> Result<ImportSummary> {
+    let draws = parse_draws(text, format, game)?;
+    let mut summary = ImportSummary {
+        read: draws.len(),
+        inserted: 0,
+    };
+    for draw in draws {
+        if storage.upsert_draw(draw).await? {
+            summary.inserted += 1;
+        }
+    }
+    Ok(summary)
+}
+
+pub async fn export(storage: &dyn Storage, format: Format, game: &GameRules) -> Result<String> {
+    let mut draws = storage.list_draws().await?;
+    draws.retain(|d| d.game == game.id);
+    Ok(format_draws(&draws, format, game))
+}
@@ -0,0 +1,223 @@
+//! Outbound webhooks: machine events for downstream services.
+//!
+//! Emitting an event stores it with one pending delivery per subscribed
+//! webhook (`webhook_events` and `webhook_deliveries`). The [`Dispatcher`]
+//! later posts due deliveries, signed with the webhook's secret, and retries
+//! failures with exponential backoff. Deliveries are at least once:
+//! consumers should use the event `id` to drop duplicates.
+
+use crate::config::WebhookConfig;
+use crate::error::{AppError, Result};
+use crate::models::{DeliveryAttempt, PendingDelivery};
+use crate::outbound;
+use crate::storage::Storage;
+use chrono::NaiveDateTime;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::Serialize;
+use sha2::Sha256;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// `sha256=` followed by the hex HMAC-SHA256 of the request body.
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+pub const EVENT_HEADER: &str = "x-webhook-event";
+pub const DELIVERY_HEADER: &str = "x-webhook-delivery";
+
+/// Deliveries sent per dispatcher pass.
+const BATCH_SIZE: i64 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A draw was stored for the first time.
+    DrawCreated,
+    /// Grids were generated and saved.
+    GridGenerated,
+    /// A stored grid was checked against its draw.
+    GridResult,
+    /// A fetch run ended with failures.
+    FetchFailed,
+}
+
+impl Event {
+    pub const ALL: [Event; 4] = [Event::DrawCreated, Event::GridGenerated, Event::GridResult, Event::FetchFailed];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Event::DrawCreated => "draw.created",
+            Event::GridGenerated => "grid.generated",
+            Event::GridResult => "grid.result",
+            Event::FetchFailed => "fetch.failed",
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Event {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Event::ALL.into_iter().find(|e| e.as_str() == s).ok_or_else(|| {
+            let events: Vec<_> = Event::ALL.iter().map(|e| e.as_str()).collect();
+            AppError::Validation(format!("unknown event '{}', expected one of {}", s, events.join(", ")))
+        })
+    }
+}
+
+/// Queues `event` for every subscribed webhook. Failures are logged, never
+/// returned, so a webhook problem cannot break the action that emitted it.
+pub async fn emit(storage: &dyn Storage, event: Event, data: &impl Serialize) {
+    let data = match serde_json::to_value(data) {
+        Ok(data) => data,
+        Err(e) => {
+            tracing::error!(%event, error = %e, "Cannot serialize webhook event");
+            return;
+        }
+    };
+    match storage.emit_event(event.as_str(), &data).await {
+        Ok(0) => {}
+        Ok(queued) => tracing::debug!(%event, queued, "Webhook event queued"),
+        Err(e) => tracing::error!(%event, error = %e, "Failed to queue webhook event"),
+    }
+}
+
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    let digest: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256={}", digest)
+}
+
+/// 32 random bytes, hex encoded.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The JSON body posted for a delivery. Replays of an event send the same
+/// body, including its `id`.
+pub fn envelope(delivery: &PendingDelivery) -> Result<serde_json::Value> {
+    let data: serde_json::Value = serde_json::from_str(&delivery.payload)
+        .map_err(|e| AppError::Notify(format!("event {} has an invalid payload: {}", delivery.event_id, e)))?;
+    Ok(serde_json::json!({
+        "id": delivery.event_id,
+        "event": delivery.event,
+        "created_at": delivery.event_created_at,
+        "data": data,
+    }))
+}
+
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct DispatchSummary {
+    pub delivered: usize,
+    /// Failed attempts that will be retried.
+    pub retrying: usize,
+    /// Deliveries that used their last attempt.
+    pub failed: usize,
+}
+
+pub struct Dispatcher {
+    client: reqwest::Client,
+    config: WebhookConfig,
+}
+
+impl Dispatcher {
+    pub fn new(config: &WebhookConfig) -> Result<Self> {
+        let client = outbound::client_builder(config.allow_private_urls)
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()?;
+        Ok(Dispatcher {
+            client,
+            config: config.clone(),
+        })
+    }
+
+    /// Delay before retrying after `attempts` failed attempts.
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let exponent = attempts.saturating_sub(1).min(31);
+        let delay = self.config.backoff_base_secs.saturating_mul(1 << exponent);
+        Duration::from_secs(delay.min(self.config.backoff_max_secs))
+    }
+
+    /// Sends every delivery due at `now` and records each attempt. The
+    /// deliveries are claimed first, so several servers can dispatch from
+    /// the same database without sending any twice.
+    #[tracing::instrument(skip_all)]
+    pub async fn deliver_due(&self, storage: &dyn Storage, now: NaiveDateTime) -> Result<DispatchSummary> {
+        let mut summary = DispatchSummary::default();
+        // Long enough to send the whole batch, each send timing out
+        let lease = Duration::from_secs(self.config.timeout_secs.saturating_mul(BATCH_SIZE as u64 + 1));
+        let lease_until = now + chrono::Duration::from_std(lease).unwrap_or_default();
+        for delivery in storage.claim_due_deliveries(now, lease_until, BATCH_SIZE).await? {
+            let (response_status, error) = match self.send(&delivery).await {
+                Ok(status) => (Some(status), None),
+                Err((status, error)) => (status, Some(error)),
+            };
+            let attempts = delivery.attempts as u32 + 1;
+            let attempt = match error {
+                None => {
+                    summary.delivered += 1;
+                    DeliveryAttempt {
+                        status: "delivered".to_string(),
+                        response_status,
+                        error: None,
+                        next_attempt_at: None,
+                    }
+                }
+                Some(error) if attempts >= self.config.max_attempts => {
+                    summary.failed += 1;
+                    tracing::warn!(delivery_id = delivery.id, webhook_id = delivery.webhook_id, %error, "Webhook delivery failed for good");
+                    DeliveryAttempt {
+                        status: "failed".to_string(),
+                        response_status,
+                        error: Some(error),
+                        next_attempt_at: None,
+                    }
+                }
+                Some(error) => {
+                    summary.retrying += 1;
+                    let retry_at = now + chrono::Duration::from_std(self.backoff(attempts)).unwrap_or_default();
+                    tracing::info!(delivery_id = delivery.id, attempts, %retry_at, %error, "Webhook delivery will be retried");
+                    DeliveryAttempt {
+                        status: "pending".to_string(),
+                        response_status,
+                        error: Some(error),
+                        next_attempt_at: Some(retry_at),
+                    }
+                }
+            };
+            storage.record_delivery_attempt(delivery.id, &attempt).await?;
+        }
+        Ok(summary)
+    }
+
+    /// Posts one delivery and returns the HTTP status. Errors carry the
+    /// status too when the endpoint answered.
+    async fn send(&self, delivery: &PendingDelivery) -> std::result::Result<i32, (Option<i32>, String)> {
+        let body = envelope(delivery).map_err(|e| (None, e.to_string()))?.to_string();
+        let response = self
+            .client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, sign(&delivery.secret, body.as_bytes()))
+            .header(EVENT_HEADER, &delivery.event)
+            .header(DELIVERY_HEADER, delivery.id.to_string())
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| (None, e.to_string()))?;
+        let status = response.status().as_u16();
+        if response.status().is_success() {
+            Ok(status as i32)
+        } else {
+            Err((Some(status as i32), format!("HTTP {}", status)))
+        }
+    }
+}
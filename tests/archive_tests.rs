@@ -0,0 +1,82 @@
+use euromillions_bot::archive::{compress, content_hash, decompress};
+use euromillions_bot::fetcher::FetchedPage;
+use euromillions_bot::models::{ArchivedPage, NewArchivedPage};
+use euromillions_bot::parser::parse_results_page;
+
+fn fixture() -> String {
+    std::fs::read_to_string(format!(
+        "{}/tests/fixtures/results-history-2025.html",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap()
+}
+
+fn fetched(body: String) -> FetchedPage {
+    FetchedPage {
+        url: "https://www.euro-millions.com/results-history-2025".to_string(),
+        status: 200,
+        etag: Some("\"abc\"".to_string()),
+        last_modified: None,
+        not_modified: false,
+        body,
+    }
+}
+
+fn archived(page: NewArchivedPage) -> ArchivedPage {
+    let now = chrono::Utc::now().naive_utc();
+    ArchivedPage {
+        id: 1,
+        url: page.url,
+        year: page.year,
+        http_status: page.http_status,
+        etag: page.etag,
+        last_modified: page.last_modified,
+        content_sha256: page.content_sha256,
+        content_length: page.content_length,
+        body_gzip: page.body_gzip,
+        fetched_at: now,
+        last_seen_at: now,
+        fetch_count: 1,
+    }
+}
+
+#[test]
+fn test_compression_round_trip() {
+    let body = fixture();
+    let compressed = compress(&body).unwrap();
+
+    assert!(compressed.len() < body.len());
+    assert_eq!(decompress(&compressed).unwrap(), body);
+}
+
+#[test]
+fn test_content_hash_is_stable() {
+    assert_eq!(
+        content_hash("hello"),
+        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+    );
+    assert_ne!(content_hash("hello"), content_hash("hello "));
+}
+
+#[test]
+fn test_archived_page_reparses_like_original() {
+    let body = fixture();
+    let page = NewArchivedPage::from_fetched(&fetched(body.clone()), Some(2025)).unwrap();
+
+    assert_eq!(page.content_length as usize, body.len());
+    assert_eq!(page.etag.as_deref(), Some("\"abc\""));
+
+    let restored = archived(page).body().unwrap();
+    assert_eq!(
+        parse_results_page(&restored).draws.len(),
+        parse_results_page(&body).draws.len()
+    );
+}
+
+#[test]
+fn test_archived_page_detects_corruption() {
+    let mut page = NewArchivedPage::from_fetched(&fetched(fixture()), Some(2025)).unwrap();
+    page.content_sha256 = content_hash("something else");
+
+    assert!(archived(page).body().is_err());
+}
@@ -0,0 +1,286 @@
+mod common;
+
+use actix_web::test::TestRequest;
+use chrono::NaiveDate;
+use common::TestApp;
+use ed25519_dalek::{Signer, SigningKey};
+use euromillions_bot::bot::discord::{SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use euromillions_bot::bot::telegram::TelegramBot;
+use euromillions_bot::bot::{self, Chat, Command, Platform};
+use euromillions_bot::grids::{self, GridStatus};
+use euromillions_bot::models::{Budget, NewDraw, NewGenerationProfile};
+use euromillions_bot::storage::{SqliteStorage, Storage};
+use serde_json::json;
+use std::time::Duration;
+use wiremock::matchers::{body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn telegram_chat() -> Chat {
+    Chat {
+        platform: Platform::Telegram,
+        id: "42".to_string(),
+    }
+}
+
+async fn storage_with_draws() -> SqliteStorage {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    for (day, numbers, stars) in [(18, [3, 12, 19, 27, 44], [2, 9]), (21, [7, 20, 32, 41, 48], [3, 11])] {
+        storage
+            .upsert_draw(NewDraw {
+                game: "euromillions".to_string(),
+                date: NaiveDate::from_ymd_opt(2025, 3, day).unwrap(),
+                numbers: numbers.to_vec(),
+                stars: stars.to_vec(),
+            })
+            .await
+            .unwrap();
+    }
+    storage
+}
+
+#[test]
+fn test_parse_commands() {
+    let parse = |text| bot::parse(text).map(|r| r.map_err(|e| e.to_string()));
+
+    assert_eq!(parse("hello"), None);
+    assert_eq!(parse("/last"), Some(Ok(Command::Last)));
+    assert_eq!(parse("/last@EuroMillionsBot"), Some(Ok(Command::Last)));
+    assert_eq!(parse("/generate"), Some(Ok(Command::Generate { count: None })));
+    assert_eq!(parse("/generate 2"), Some(Ok(Command::Generate { count: Some(2) })));
+    assert!(matches!(parse("/generate 500"), Some(Err(_))));
+    assert_eq!(
+        parse("/check 3 12 19 27 44 * 2 9"),
+        Some(Ok(Command::Check { numbers: vec![3, 12, 19, 27, 44], stars: vec![2, 9] }))
+    );
+    assert_eq!(parse("/check 3 12 19 27 44 *2 9").unwrap().unwrap(), parse("/check 3 12 19 27 44 * 2 9").unwrap().unwrap());
+    assert!(parse("/check 3 12 19 27 44 2 9").unwrap().unwrap_err().contains("usage"));
+    assert!(parse("/check 3 12 19 27 60 * 2 9").unwrap().unwrap_err().contains("between 1 and 50"));
+    assert!(parse("/frobnicate").unwrap().unwrap_err().contains("/help"));
+}
+
+#[tokio::test]
+async fn test_commands() {
+    let storage = storage_with_draws().await;
+    let chat = telegram_chat();
+    let run = |command| bot::handle(&storage, &chat, command);
+
+    assert_eq!(run(Command::Last).await.unwrap(), "Draw of 2025-03-21: 7 20 32 41 48 ★ 3 11");
+
+    let check = run(Command::Check { numbers: vec![7, 20, 32, 1, 2], stars: vec![3, 1] }).await.unwrap();
+    assert!(check.ends_with("You matched 3 number(s) and 1 star(s): prize tier 9."), "{}", check);
+
+    let stats = run(Command::Stats).await.unwrap();
+    assert!(stats.starts_with("2 draws stored."), "{}", stats);
+    assert!(stats.contains("Most drawn stars: 2 (1×), 3 (1×)"), "{}", stats);
+
+    let generated = run(Command::Generate { count: Some(2) }).await.unwrap();
+    assert_eq!(generated.lines().count(), 3, "{}", generated);
+    // The grids belong to the chat's user
+    let user = storage.get_user_by_name("telegram:42").await.unwrap().unwrap();
+    let grids = storage.recent_grids(10, None).await.unwrap();
+    assert!(grids.iter().all(|g| g.user_id == Some(user.id)));
+
+    assert!(run(Command::Subscribe { target: None }).await.unwrap().starts_with("Subscribed"));
+    assert_eq!(run(Command::Subscribe { target: None }).await.unwrap(), "Already subscribed.");
+    let channels = storage.list_notification_channels(user.id).await.unwrap();
+    assert_eq!((channels[0].kind.as_str(), channels[0].target.as_str()), ("telegram", "42"));
+}
+
+#[tokio::test]
+async fn test_generate_applies_the_users_profile() {
+    let storage = storage_with_draws().await;
+    let chat = telegram_chat();
+    let user = storage.create_user("telegram:42").await.unwrap();
+    let profile = NewGenerationProfile {
+        forced_numbers: vec![44],
+        forced_stars: vec![12],
+        grid_count: Some(3),
+        ..Default::default()
+    };
+    storage.upsert_generation_profile(user.id, "euromillions", &profile).await.unwrap();
+
+    let generated = bot::handle(&storage, &chat, Command::Generate { count: None }).await.unwrap();
+    let lines: Vec<&str> = generated.lines().skip(1).collect();
+    assert_eq!(lines.len(), 3, "{}", generated);
+    for line in lines {
+        let (numbers, stars) = line.split_once(" ★ ").unwrap();
+        assert!(numbers.split(' ').any(|n| n == "44"), "{}", line);
+        assert!(stars.split(' ').any(|n| n == "12"), "{}", line);
+    }
+    // An explicit count wins over the profile's
+    let generated = bot::handle(&storage, &chat, Command::Generate { count: Some(1) }).await.unwrap();
+    assert_eq!(generated.lines().count(), 2, "{}", generated);
+}
+
+#[tokio::test]
+async fn test_generate_keeps_to_the_users_budget() {
+    let storage = storage_with_draws().await;
+    let chat = telegram_chat();
+    let user = storage.create_user("telegram:42").await.unwrap();
+    let budget = Budget {
+        user_id: user.id,
+        period: "week".to_string(),
+        currency: "EUR".to_string(),
+        limit_cents: 1000,
+    };
+    storage.set_budget(&budget).await.unwrap();
+
+    // Only the lines marked as played are spent
+    let generated_ids = || async {
+        storage.recent_grids(10, Some("generated")).await.unwrap().iter().map(|g| g.id).collect::<Vec<i32>>()
+    };
+    let generated = bot::respond(&storage, &chat, "/generate 3").await.unwrap();
+    assert!(!generated.contains("Careful"), "{}", generated);
+    grids::set_status(&storage, &generated_ids().await, GridStatus::Played).await.unwrap();
+    for _ in 0..2 {
+        let generated = bot::respond(&storage, &chat, "/generate 1").await.unwrap();
+        assert!(generated.ends_with("Careful: weekly budget: 10.00 EUR of 10.00 EUR spent."), "{}", generated);
+    }
+    // Both suggestions fit, but not once both are played
+    let ids = generated_ids().await;
+    assert!(grids::set_status(&storage, &ids, GridStatus::Played).await.is_err());
+    grids::set_status(&storage, &ids[..1], GridStatus::Played).await.unwrap();
+    let refused = bot::respond(&storage, &chat, "/generate 1").await.unwrap();
+    assert!(refused.contains("over the weekly budget"), "{}", refused);
+}
+
+#[tokio::test]
+async fn test_commands_on_empty_database() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    let chat = telegram_chat();
+    assert_eq!(bot::handle(&storage, &chat, Command::Last).await.unwrap(), "No draw stored yet.");
+    assert_eq!(bot::handle(&storage, &chat, Command::Stats).await.unwrap(), "No draw stored yet.");
+    // Invalid input is explained rather than failing
+    assert_eq!(bot::respond(&storage, &chat, "/generate 0").await.unwrap(), "count must be a number between 1 and 10");
+}
+
+#[tokio::test]
+async fn test_telegram_bot_answers_updates() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/botTOKEN/getUpdates"))
+        .and(body_partial_json(json!({ "offset": 0 })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "ok": true,
+            "result": [
+                { "update_id": 10, "message": { "chat": { "id": 42 }, "text": "/last" } },
+                { "update_id": 11, "message": { "chat": { "id": 42 }, "text": "just chatting" } },
+                { "update_id": 12, "message": { "chat": { "id": 7 }, "text": "/check 7 20 32 41 48 * 3 11" } },
+            ],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/botTOKEN/sendMessage"))
+        .and(body_partial_json(json!({ "chat_id": 42, "text": "Draw of 2025-03-21: 7 20 32 41 48 ★ 3 11" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "ok": true, "result": {} })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/botTOKEN/sendMessage"))
+        .and(body_partial_json(json!({ "chat_id": 7 })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "ok": true, "result": {} })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let storage = storage_with_draws().await;
+    let bot = TelegramBot::new(&server.uri(), "TOKEN", Duration::from_secs(0)).unwrap();
+    let offset = bot.poll_once(&storage, 0).await.unwrap();
+    assert_eq!(offset, 13);
+
+    let requests = server.received_requests().await.unwrap();
+    let jackpot: serde_json::Value = requests.last().unwrap().body_json().unwrap();
+    assert!(jackpot["text"].as_str().unwrap().contains("prize tier 1"), "{}", jackpot);
+}
+
+#[tokio::test]
+async fn test_telegram_api_errors_hide_the_token() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({ "ok": false, "description": "Unauthorized" })))
+        .mount(&server)
+        .await;
+
+    let bot = TelegramBot::new(&server.uri(), "TOKEN", Duration::from_secs(0)).unwrap();
+    let error = bot.get_updates(0).await.unwrap_err().to_string();
+    assert!(error.contains("Unauthorized"), "{}", error);
+    assert!(!error.contains("TOKEN"), "{}", error);
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+async fn discord_app() -> (TestApp, SigningKey) {
+    let key = SigningKey::from_bytes(&[7; 32]);
+    let public = hex(key.verifying_key().as_bytes());
+    let app = TestApp::new().await.with_config(|config| config.bot.discord_public_key = Some(public));
+    (app, key)
+}
+
+async fn interaction(app: &TestApp, key: &SigningKey, body: serde_json::Value) -> common::TestResponse {
+    let body = body.to_string();
+    let timestamp = "1760000000";
+    let signature = key.sign(format!("{}{}", timestamp, body).as_bytes());
+    let req = TestRequest::post()
+        .uri("/discord/interactions")
+        .insert_header((SIGNATURE_HEADER, hex(&signature.to_bytes())))
+        .insert_header((TIMESTAMP_HEADER, timestamp))
+        .insert_header(("content-type", "application/json"))
+        .set_payload(body);
+    app.request(req).await
+}
+
+#[actix_web::test]
+async fn test_discord_interactions() {
+    let (app, key) = discord_app().await;
+    app.get("/history").await;
+
+    let pong = interaction(&app, &key, json!({ "type": 1 })).await;
+    assert_eq!(pong.json(), json!({ "type": 1 }));
+
+    let check = interaction(
+        &app,
+        &key,
+        json!({
+            "type": 2,
+            "channel_id": "99",
+            "data": { "name": "check", "options": [{ "name": "line", "type": 3, "value": "7 20 32 41 48 * 3 11" }] },
+        }),
+    )
+    .await
+    .json();
+    assert_eq!(check["type"], 4);
+    assert!(check["data"]["content"].as_str().unwrap().contains("prize tier 1"), "{}", check);
+
+    let subscribe = interaction(
+        &app,
+        &key,
+        json!({ "type": 2, "channel_id": "99", "data": { "name": "subscribe" } }),
+    )
+    .await
+    .json();
+    assert!(subscribe["data"]["content"].as_str().unwrap().starts_with("usage"), "{}", subscribe);
+
+    // Tampered or unsigned requests are refused
+    let forged = app
+        .request(
+            TestRequest::post()
+                .uri("/discord/interactions")
+                .insert_header((SIGNATURE_HEADER, hex(&[0; 64])))
+                .insert_header((TIMESTAMP_HEADER, "1760000000"))
+                .set_payload(r#"{"type":1}"#),
+        )
+        .await;
+    assert_eq!(forged.status.as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn test_discord_is_disabled_without_key() {
+    let app = TestApp::new().await;
+    let resp = app.request(TestRequest::post().uri("/discord/interactions").set_payload("{}")).await;
+    assert_eq!(resp.status.as_u16(), 404);
+}
@@ -0,0 +1,48 @@
+use chrono::NaiveDate;
+use euromillions_bot::checker::check_line;
+use euromillions_bot::games::EUROMILLIONS;
+use euromillions_bot::models::Draw;
+
+fn draw() -> Draw {
+    Draw {
+        id: 1,
+        game: "euromillions".to_string(),
+        date: NaiveDate::from_ymd_opt(2025, 3, 18).unwrap(),
+        numbers: vec![3, 12, 19, 27, 44],
+        stars: vec![2, 9],
+    }
+}
+
+#[test]
+fn test_prize_tiers() {
+    assert_eq!(EUROMILLIONS.prize_tier(5, 2), Some(1));
+    assert_eq!(EUROMILLIONS.prize_tier(5, 0), Some(3));
+    assert_eq!(EUROMILLIONS.prize_tier(4, 0), Some(7));
+    assert_eq!(EUROMILLIONS.prize_tier(2, 0), Some(13));
+    assert_eq!(EUROMILLIONS.prize_tier(1, 1), None);
+    assert_eq!(EUROMILLIONS.prize_tier(0, 2), None);
+}
+
+#[test]
+fn test_check_line() {
+    let jackpot = check_line(&EUROMILLIONS, &[3, 12, 19, 27, 44], &[2, 9], &draw());
+    assert_eq!(jackpot.tier, Some(1));
+
+    let result = check_line(&EUROMILLIONS, &[3, 12, 20, 30, 40], &[9, 10], &draw());
+    assert_eq!(result.matched_numbers, vec![3, 12]);
+    assert_eq!(result.matched_stars, vec![9]);
+    assert_eq!(result.tier, Some(12));
+
+    let losing = check_line(&EUROMILLIONS, &[1, 2, 4, 5, 6], &[1, 3], &draw());
+    assert!(!losing.is_winning());
+}
+
+#[test]
+fn test_validate_line() {
+    assert!(EUROMILLIONS.validate_line(&[1, 2, 3, 4, 5], &[1, 12]).is_ok());
+    assert!(EUROMILLIONS.validate_line(&[1, 2, 3, 4], &[1, 2]).is_err());
+    assert!(EUROMILLIONS.validate_line(&[1, 2, 3, 4, 51], &[1, 2]).is_err());
+    assert!(EUROMILLIONS.validate_line(&[1, 2, 3, 4, 4], &[1, 2]).is_err());
+    assert!(EUROMILLIONS.validate_line(&[1, 2, 3, 4, 5], &[13, 2]).is_err());
+    assert!(EUROMILLIONS.validate_line(&[1, 2, 3, 4, 5], &[2, 2]).is_err());
+}
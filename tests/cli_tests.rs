@@ -0,0 +1,94 @@
+use clap::{CommandFactory, Parser};
+use euromillions_bot::cli::{year_range, Cli, Command};
+use euromillions_bot::generator::{AntiPopular, Strategy};
+
+#[test]
+fn test_cli_definition() {
+    Cli::command().debug_assert();
+}
+
+#[test]
+fn test_parse_generate() {
+    let cli = Cli::try_parse_from(["euromillions-bot", "generate", "--strategy", "random", "--count", "2", "--seed", "7", "--json"]).unwrap();
+    assert!(cli.json);
+    match cli.command {
+        Some(Command::Generate { game, strategy, count, seed, anti_popular, min_score, constraints, save }) => {
+            assert_eq!(game.id, "euromillions");
+            assert_eq!(strategy, Strategy::Random);
+            assert_eq!(count, 2);
+            assert_eq!(seed, Some(7));
+            assert_eq!((anti_popular, min_score), (AntiPopular::Off, 60));
+            assert!(constraints.is_empty());
+            assert!(!save);
+        }
+        other => panic!("unexpected command {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_generate_anti_popular() {
+    let cli = Cli::try_parse_from(["euromillions-bot", "generate", "--anti-popular", "filter", "--min-score", "75"]).unwrap();
+    match cli.command {
+        Some(Command::Generate { anti_popular, min_score, .. }) => {
+            assert_eq!((anti_popular, min_score), (AntiPopular::Filter, 75));
+        }
+        other => panic!("unexpected command {:?}", other),
+    }
+    assert!(Cli::try_parse_from(["euromillions-bot", "generate", "--min-score", "101"]).is_err());
+}
+
+#[test]
+fn test_parse_generate_constraints() {
+    let cli = Cli::try_parse_from([
+        "euromillions-bot", "generate", "--sum-min", "100", "--sum-max", "150", "--odd", "3", "--low", "2",
+        "--max-consecutive", "2", "--every-decade", "--include", "7,23", "--exclude", "13",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Generate { constraints, .. }) => {
+            assert_eq!((constraints.sum_min, constraints.sum_max), (Some(100), Some(150)));
+            assert_eq!((constraints.odd, constraints.low), (Some(3), Some(2)));
+            assert_eq!(constraints.max_consecutive, Some(2));
+            assert!(constraints.every_decade);
+            assert_eq!(constraints.include, vec![7, 23]);
+            assert_eq!(constraints.exclude, vec![13]);
+        }
+        other => panic!("unexpected command {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_check() {
+    let cli = Cli::try_parse_from([
+        "euromillions-bot", "check", "--numbers", "3,12,19,27,44", "--stars", "2,9", "--date", "2025-03-18",
+    ])
+    .unwrap();
+    match cli.command {
+        Some(Command::Check { numbers, stars, date, .. }) => {
+            assert_eq!(numbers, vec![3, 12, 19, 27, 44]);
+            assert_eq!(stars, vec![2, 9]);
+            assert_eq!(date.unwrap().to_string(), "2025-03-18");
+        }
+        other => panic!("unexpected command {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_game() {
+    let cli = Cli::try_parse_from(["euromillions-bot", "check", "--game", "uk_lotto", "--numbers", "1,2,3,4,5,6"]).unwrap();
+    match cli.command {
+        Some(Command::Check { game, stars, .. }) => {
+            assert_eq!(game.id, "uk_lotto");
+            assert!(stars.is_empty());
+        }
+        other => panic!("unexpected command {:?}", other),
+    }
+    assert!(Cli::try_parse_from(["euromillions-bot", "stats", "--game", "keno"]).is_err());
+}
+
+#[test]
+fn test_fetch_year_range() {
+    assert!(Cli::try_parse_from(["euromillions-bot", "fetch", "--to", "2025"]).is_err());
+    assert_eq!(year_range(2023, 2025).unwrap(), vec![2023, 2024, 2025]);
+    assert!(year_range(2025, 2023).is_err());
+}
@@ -0,0 +1,171 @@
+//! End-to-end harness: the real application wired to an in-memory SQLite
+//! database and a draw source that serves local fixture pages.
+#![allow(dead_code)]
+
+use actix_web::http::header::HeaderMap;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use async_trait::async_trait;
+use euromillions_bot::app::{build_app, AppState};
+use euromillions_bot::config::Config;
+use euromillions_bot::fetcher::{DrawSource, FetchFailureKind, FetchReport, FetchedPage, YearFailure};
+use euromillions_bot::metrics::Metrics;
+use euromillions_bot::notify::Notifier;
+use euromillions_bot::parser;
+use euromillions_bot::storage::{SqliteStorage, Storage};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+pub fn fixture(name: &str) -> String {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e))
+}
+
+/// Serves canned pages per year instead of hitting the network. Years
+/// without a page fail with a 404.
+#[derive(Default)]
+pub struct FakeSource {
+    pages: Mutex<HashMap<i32, String>>,
+}
+
+impl FakeSource {
+    pub fn with_page(self, year: i32, html: impl Into<String>) -> Self {
+        self.pages.lock().unwrap().insert(year, html.into());
+        self
+    }
+}
+
+#[async_trait]
+impl DrawSource for FakeSource {
+    fn name(&self) -> String {
+        "fake".to_string()
+    }
+
+    fn year_url(&self, year: i32) -> String {
+        format!("fake://results-history-{}", year)
+    }
+
+    async fn fetch_years(&self, years: &[i32]) -> FetchReport {
+        let mut report = FetchReport::default();
+        for &year in years {
+            let url = self.year_url(year);
+            match self.pages.lock().unwrap().get(&year).cloned() {
+                Some(body) => {
+                    let parse = parser::parse_results_page(&body);
+                    let page = FetchedPage {
+                        url,
+                        status: 200,
+                        etag: None,
+                        last_modified: None,
+                        not_modified: false,
+                        body,
+                    };
+                    report.add_page(year, page, parse);
+                }
+                None => report.failures.push(YearFailure {
+                    year,
+                    url,
+                    attempts: 1,
+                    kind: FetchFailureKind::Status { status: 404 },
+                }),
+            }
+        }
+        report
+    }
+}
+
+pub struct TestApp {
+    pub config: Arc<Config>,
+    pub storage: Arc<SqliteStorage>,
+    pub source: Arc<FakeSource>,
+    /// Shared by every request so counters survive between calls.
+    pub metrics: Arc<Metrics>,
+    pub notifier: Arc<Notifier>,
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: actix_web::web::Bytes,
+}
+
+impl TestResponse {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body)
+            .unwrap_or_else(|e| panic!("Body is not JSON ({}): {:?}", e, self.body))
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+impl TestApp {
+    /// Empty database, every year serves the 2025 fixture page.
+    pub async fn new() -> Self {
+        let source = FakeSource::default()
+            .with_page(2024, "<html><body></body></html>")
+            .with_page(2025, fixture("results-history-2025.html"));
+        Self::with_source(source).await
+    }
+
+    pub async fn with_source(source: FakeSource) -> Self {
+        let storage = SqliteStorage::in_memory().await.expect("Failed to create in-memory storage");
+        let mut config = Config::default();
+        config.database.url = "sqlite::memory:".to_string();
+        TestApp {
+            storage: Arc::new(storage),
+            source: Arc::new(source),
+            metrics: Arc::new(Metrics::new()),
+            // Webhook, Slack and Discord; email and Telegram need credentials
+            notifier: Arc::new(Notifier::from_config(&config.notify).unwrap()),
+            config: Arc::new(config),
+        }
+    }
+
+    pub fn with_config(mut self, configure: impl FnOnce(&mut Config)) -> Self {
+        configure(Arc::make_mut(&mut self.config));
+        self
+    }
+
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = Arc::new(notifier);
+        self
+    }
+
+    pub fn state(&self) -> AppState {
+        let storage: Arc<dyn Storage> = self.storage.clone();
+        let source: Arc<dyn DrawSource> = self.source.clone();
+        AppState {
+            storage,
+            source,
+            metrics: self.metrics.clone(),
+            notifier: self.notifier.clone(),
+        }
+    }
+
+    pub async fn request(&self, req: test::TestRequest) -> TestResponse {
+        let app = test::init_service(build_app(self.config.clone(), self.state())).await;
+        let resp = test::call_service(&app, req.to_request()).await;
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        let body = test::read_body(resp).await;
+        TestResponse { status, headers, body }
+    }
+
+    pub async fn get(&self, uri: &str) -> TestResponse {
+        self.request(test::TestRequest::get().uri(uri)).await
+    }
+
+    pub async fn post(&self, uri: &str) -> TestResponse {
+        self.request(test::TestRequest::post().uri(uri)).await
+    }
+
+    pub async fn post_json(&self, uri: &str, body: serde_json::Value) -> TestResponse {
+        self.request(test::TestRequest::post().uri(uri).set_json(body)).await
+    }
+
+    pub async fn delete(&self, uri: &str) -> TestResponse {
+        self.request(test::TestRequest::delete().uri(uri)).await
+    }
+}
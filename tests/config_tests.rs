@@ -0,0 +1,140 @@
+use euromillions_bot::config::{Config, ConfigArgs, LogFormat, SmtpTls};
+use std::collections::HashMap;
+use std::time::Duration;
+
+fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: HashMap<String, String> =
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    move |key| vars.get(key).cloned()
+}
+
+#[test]
+fn test_defaults() {
+    let config = Config::default();
+
+    assert_eq!(config.server.host, "0.0.0.0");
+    assert_eq!(config.server.port, 8080);
+    assert_eq!(config.database.max_connections, 5);
+    assert_eq!(config.fetch.years, vec![2024, 2025]);
+    assert_eq!(config.fetch.schedule, "0 0 23 * * Wed,Sat");
+    assert!(config.validate().is_err(), "database URL is required");
+}
+
+#[test]
+fn test_example_file_parses() {
+    let text = std::fs::read_to_string(format!("{}/config.example.toml", env!("CARGO_MANIFEST_DIR"))).unwrap();
+    let config = Config::from_toml(&text).unwrap();
+
+    assert!(config.validate().is_ok());
+    assert_eq!(config.fetch.fetcher_config().min_request_interval, Duration::from_secs(1));
+}
+
+#[test]
+fn test_partial_file_keeps_defaults() {
+    let config = Config::from_toml("[server]\nport = 9000\n").unwrap();
+
+    assert_eq!(config.server.port, 9000);
+    assert_eq!(config.server.host, "0.0.0.0");
+    assert_eq!(config.fetch.max_retries, 3);
+}
+
+#[test]
+fn test_unknown_keys_are_rejected() {
+    assert!(Config::from_toml("[server]\nprot = 9000\n").is_err());
+}
+
+#[test]
+fn test_precedence_file_env_args() {
+    let mut config = Config::from_toml(
+        "[server]\nport = 9000\nhost = \"127.0.0.1\"\n[database]\nurl = \"sqlite://file.db\"\n",
+    )
+    .unwrap();
+
+    config
+        .apply_env(env(&[("PORT", "9100"), ("FETCH_YEARS", "2023, 2024,2025")]))
+        .unwrap();
+    assert_eq!(config.server.port, 9100);
+    assert_eq!(config.server.host, "127.0.0.1");
+    assert_eq!(config.fetch.years, vec![2023, 2024, 2025]);
+
+    config.apply_args(&ConfigArgs {
+        port: Some(9200),
+        database_url: Some("sqlite::memory:".to_string()),
+        ..Default::default()
+    });
+    assert_eq!(config.server.port, 9200);
+    assert_eq!(config.database.url, "sqlite::memory:");
+    assert!(config.validate().is_ok());
+}
+
+#[test]
+fn test_invalid_env_value() {
+    let mut config = Config::default();
+    let err = config.apply_env(env(&[("PORT", "eighty")])).unwrap_err();
+
+    assert!(err.to_string().contains("PORT"));
+}
+
+#[test]
+fn test_invalid_schedule_is_rejected() {
+    let mut config = Config::default();
+    config.database.url = "sqlite::memory:".to_string();
+    config.fetch.schedule = "every wednesday".to_string();
+
+    assert!(config.validate().unwrap_err().to_string().contains("fetch.schedule"));
+}
+
+#[test]
+fn test_log_format() {
+    let mut config = Config::default();
+    assert_eq!(config.log.format, LogFormat::Text);
+
+    config.apply_env(env(&[("LOG_FORMAT", "json")])).unwrap();
+    assert_eq!(config.log.format, LogFormat::Json);
+    assert!(config.apply_env(env(&[("LOG_FORMAT", "xml")])).is_err());
+
+    config.apply_args(&ConfigArgs { log_format: Some(LogFormat::Text), ..Default::default() });
+    assert_eq!(config.log.format, LogFormat::Text);
+}
+
+#[test]
+fn test_notify_settings() {
+    let mut config = Config::from_toml("[database]\nurl = \"sqlite::memory:\"\n[notify.smtp]\ntls = \"none\"\n").unwrap();
+    assert_eq!(config.notify.smtp.tls, SmtpTls::None);
+    assert!(config.notify.smtp.host.is_none());
+
+    config
+        .apply_env(env(&[
+            ("SMTP_HOST", "mail.example.com"),
+            ("SMTP_PORT", "2525"),
+            ("SMTP_FROM", "Bot <bot@example.com>"),
+            ("TELEGRAM_BOT_TOKEN", "123:abc"),
+        ]))
+        .unwrap();
+    assert_eq!(config.notify.smtp.host.as_deref(), Some("mail.example.com"));
+    assert_eq!(config.notify.smtp.port, 2525);
+    assert_eq!(config.notify.telegram.bot_token.as_deref(), Some("123:abc"));
+    assert!(config.validate().is_ok());
+
+    config.notify.smtp.from = "not an address".to_string();
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_bot_settings() {
+    let mut config = Config::from_toml("[database]\nurl = \"sqlite::memory:\"\n[bot]\npoll_timeout_secs = 5\n").unwrap();
+    assert!(!config.bot.telegram);
+    assert_eq!(config.bot.poll_timeout_secs, 5);
+
+    config.apply_env(env(&[("BOT_TELEGRAM", "true")])).unwrap();
+    assert!(config.bot.telegram);
+    // The Telegram bot needs the notification bot token
+    assert!(config.validate().is_err());
+    config.apply_env(env(&[("TELEGRAM_BOT_TOKEN", "123:abc")])).unwrap();
+    assert!(config.validate().is_ok());
+
+    config.apply_env(env(&[("DISCORD_PUBLIC_KEY", "not hex")])).unwrap();
+    assert!(config.validate().is_err());
+    config.bot.discord_public_key = Some("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a".to_string());
+    assert!(config.validate().is_ok());
+}
@@ -0,0 +1,246 @@
+use euromillions_bot::constraints::{Constraints, Solver};
+use euromillions_bot::error::AppError;
+use euromillions_bot::games::{EUROMILLIONS, UK_LOTTO};
+use euromillions_bot::generator::{generate, AntiPopular, GenerateOptions, Strategy};
+use euromillions_bot::models::NewDraw;
+use euromillions_bot::storage::{SqliteStorage, Storage};
+use chrono::NaiveDate;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+fn all_numbers(max: i32) -> Vec<i32> {
+    (1..=max).collect()
+}
+
+fn solve_many(constraints: &Constraints, lines: usize) -> Vec<Vec<i32>> {
+    let pool = all_numbers(EUROMILLIONS.numbers.max);
+    let solver = Solver::new(&EUROMILLIONS, &pool, constraints).unwrap();
+    let mut rng = StdRng::seed_from_u64(11);
+    (0..lines).map(|_| solver.solve(&mut rng).unwrap()).collect()
+}
+
+fn longest_run(line: &[i32]) -> usize {
+    let mut longest = 1;
+    let mut run = 1;
+    for pair in line.windows(2) {
+        run = if pair[1] == pair[0] + 1 { run + 1 } else { 1 };
+        longest = longest.max(run);
+    }
+    longest
+}
+
+fn validation_message(result: Result<impl std::fmt::Debug, AppError>) -> String {
+    match result {
+        Err(AppError::Validation(message)) => message,
+        other => panic!("expected a validation error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_lines_meet_every_constraint() {
+    let constraints = Constraints {
+        sum_min: Some(110),
+        sum_max: Some(140),
+        odd: Some(3),
+        low: Some(2),
+        max_consecutive: Some(1),
+        every_decade: true,
+        include: vec![7],
+        exclude: vec![13, 42],
+        include_stars: vec![],
+    };
+    for line in solve_many(&constraints, 200) {
+        assert_eq!(line.len(), 5);
+        assert!(line.windows(2).all(|w| w[0] < w[1]), "{:?}", line);
+        let sum: i32 = line.iter().sum();
+        assert!((110..=140).contains(&sum), "{:?}", line);
+        assert_eq!(line.iter().filter(|n| *n % 2 == 1).count(), 3, "{:?}", line);
+        assert_eq!(line.iter().filter(|n| **n <= 25).count(), 2, "{:?}", line);
+        assert_eq!(longest_run(&line), 1, "{:?}", line);
+        let mut decades: Vec<i32> = line.iter().map(|n| (n - 1) / 10).collect();
+        decades.dedup();
+        assert_eq!(decades, vec![0, 1, 2, 3, 4], "{:?}", line);
+        assert!(line.contains(&7));
+        assert!(!line.contains(&13) && !line.contains(&42));
+    }
+}
+
+#[test]
+fn test_unconstrained_lines_cover_the_pool() {
+    let mut seen = [false; 51];
+    for line in solve_many(&Constraints::default(), 200) {
+        assert_eq!(line.len(), 5);
+        for n in line {
+            seen[n as usize] = true;
+        }
+    }
+    assert!(seen[1..].iter().all(|s| *s));
+}
+
+#[test]
+fn test_lines_keep_to_the_pool_and_includes() {
+    let constraints = Constraints {
+        include: vec![50],
+        ..Constraints::default()
+    };
+    let pool = vec![1, 2, 3, 4, 5, 6, 7, 8];
+    let solver = Solver::new(&EUROMILLIONS, &pool, &constraints).unwrap();
+    let mut rng = StdRng::seed_from_u64(5);
+    for _ in 0..50 {
+        let line = solver.solve(&mut rng).unwrap();
+        assert_eq!(line.last(), Some(&50));
+        assert!(line[..4].iter().all(|n| pool.contains(n)), "{:?}", line);
+    }
+}
+
+#[test]
+fn test_stars_hold_the_included_ones() {
+    let constraints = Constraints {
+        include_stars: vec![12],
+        ..Constraints::default()
+    };
+    let pool = all_numbers(EUROMILLIONS.numbers.max);
+    let solver = Solver::new(&EUROMILLIONS, &pool, &constraints).unwrap();
+    let mut rng = StdRng::seed_from_u64(4);
+    for _ in 0..50 {
+        let stars = solver.stars(2, &[1, 2, 3, 12], &mut rng);
+        assert_eq!(stars.len(), 2);
+        assert!(stars[0] < stars[1] && stars[1] == 12, "{:?}", stars);
+    }
+}
+
+#[test]
+fn test_every_decade_for_uk_lotto() {
+    let constraints = Constraints {
+        every_decade: true,
+        ..Constraints::default()
+    };
+    let pool = all_numbers(UK_LOTTO.numbers.max);
+    let solver = Solver::new(&UK_LOTTO, &pool, &constraints).unwrap();
+    let mut rng = StdRng::seed_from_u64(2);
+    for _ in 0..50 {
+        let line = solver.solve(&mut rng).unwrap();
+        let decades: Vec<i32> = line.iter().map(|n| (n - 1) / 10).collect();
+        assert_eq!(decades, vec![0, 1, 2, 3, 4, 5]);
+    }
+}
+
+#[test]
+fn test_validate_rejects_contradictions() {
+    let cases = [
+        (Constraints { include: vec![51], ..Constraints::default() }, "51 is not a EuroMillions number"),
+        (Constraints { include: vec![4, 4], ..Constraints::default() }, "distinct"),
+        (Constraints { include: vec![1, 2, 3, 4, 5, 6], ..Constraints::default() }, "cannot include 6"),
+        (Constraints { include: vec![9], exclude: vec![9], ..Constraints::default() }, "both included and excluded"),
+        (Constraints { odd: Some(6), ..Constraints::default() }, "cannot have 6 odd"),
+        (Constraints { odd: Some(1), include: vec![1, 3], ..Constraints::default() }, "2 included numbers are odd"),
+        (Constraints { low: Some(5), include: vec![40], ..Constraints::default() }, "1 included numbers are high"),
+        (Constraints { max_consecutive: Some(0), ..Constraints::default() }, "max_consecutive"),
+        (Constraints { include_stars: vec![13], ..Constraints::default() }, "13 is not a EuroMillions star"),
+        (Constraints { include_stars: vec![2, 2], ..Constraints::default() }, "stars must be distinct"),
+        (Constraints { include_stars: vec![1, 2, 3], ..Constraints::default() }, "cannot include 3 stars"),
+        (Constraints { sum_min: Some(100), sum_max: Some(90), ..Constraints::default() }, "above sum_max"),
+        (
+            Constraints { sum_min: Some(100), sum_max: Some(100), odd: Some(1), ..Constraints::default() },
+            "1 odd numbers cannot add up to 100",
+        ),
+    ];
+    for (constraints, expected) in cases {
+        let message = validation_message(constraints.validate(&EUROMILLIONS));
+        assert!(message.contains(expected), "{:?}: {}", constraints, message);
+    }
+}
+
+#[test]
+fn test_unsatisfiable_constraints_are_reported() {
+    let pool = all_numbers(EUROMILLIONS.numbers.max);
+    let cases = [
+        // The smallest line, 1 to 5, already adds up to 15
+        Constraints { sum_max: Some(14), ..Constraints::default() },
+        // Nothing left in 1-10
+        Constraints { every_decade: true, exclude: (1..=10).collect(), ..Constraints::default() },
+        // Five odd numbers add up to an odd sum
+        Constraints { odd: Some(5), sum_min: Some(30), sum_max: Some(30), ..Constraints::default() },
+        // Three neighbours forced in with runs of two at most
+        Constraints { include: vec![20, 21, 22], max_consecutive: Some(2), ..Constraints::default() },
+    ];
+    for constraints in cases {
+        let message = match Solver::new(&EUROMILLIONS, &pool, &constraints) {
+            Ok(solver) => validation_message(solver.solve(&mut StdRng::seed_from_u64(1))),
+            Err(error) => validation_message(Err::<(), _>(error)),
+        };
+        assert!(message.contains("meets the constraints") || message.contains("odd numbers cannot"), "{}", message);
+    }
+
+    // Only 1-8 are candidates: their 4 odd numbers cannot make 5
+    let constraints = Constraints { odd: Some(5), ..Constraints::default() };
+    let message = validation_message(Solver::new(&EUROMILLIONS, &pool[..8], &constraints));
+    assert_eq!(
+        message,
+        "no line of 5 numbers from the 8 candidates meets the constraints (5 odd)"
+    );
+}
+
+#[test]
+fn test_searches_share_a_budget() {
+    let pool = all_numbers(EUROMILLIONS.numbers.max);
+    let constraints = Constraints { odd: Some(3), ..Constraints::default() };
+    let solver = Solver::new(&EUROMILLIONS, &pool, &constraints).unwrap();
+    let mut rng = StdRng::seed_from_u64(3);
+
+    let mut budget = 1_000;
+    let line = solver.solve_within(&mut rng, &mut budget).unwrap();
+    assert_eq!(line.iter().filter(|n| *n % 2 == 1).count(), 3);
+    assert!(budget < 1_000);
+    // Once spent, no search may go on
+    let mut spent = 0;
+    let message = validation_message(solver.solve_within(&mut rng, &mut spent));
+    assert!(message.contains("too tight"), "{}", message);
+}
+
+#[tokio::test]
+async fn test_generate_applies_constraints_with_every_strategy() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    for (day, numbers) in [(3, vec![2, 9, 17, 33, 48]), (7, vec![4, 11, 26, 39, 45]), (10, vec![6, 15, 21, 30, 50])] {
+        storage
+            .upsert_draw(NewDraw {
+                game: "euromillions".to_string(),
+                date: NaiveDate::from_ymd_opt(2025, 1, day).unwrap(),
+                numbers,
+                stars: vec![1, 2],
+            })
+            .await
+            .unwrap();
+    }
+
+    let constraints = Constraints {
+        low: Some(2),
+        include: vec![7],
+        exclude: vec![9],
+        ..Constraints::default()
+    };
+    for strategy in [Strategy::Frequent, Strategy::Random] {
+        for anti_popular in [AntiPopular::Off, AntiPopular::Weight, AntiPopular::Filter] {
+            let options = GenerateOptions {
+                strategy,
+                anti_popular,
+                min_score: 0,
+                count: 5,
+                seed: Some(8),
+                constraints: constraints.clone(),
+                ..GenerateOptions::default()
+            };
+            for grid in generate(&storage, &options).await.unwrap() {
+                assert!(grid.numbers.contains(&7), "{:?}", grid.numbers);
+                assert!(!grid.numbers.contains(&9), "{:?}", grid.numbers);
+                assert_eq!(grid.numbers.iter().filter(|n| **n <= 25).count(), 2, "{:?}", grid.numbers);
+            }
+        }
+    }
+
+    let options = GenerateOptions {
+        constraints: Constraints { sum_max: Some(10), ..Constraints::default() },
+        ..GenerateOptions::default()
+    };
+    assert!(matches!(generate(&storage, &options).await, Err(AppError::Validation(_))));
+}
@@ -0,0 +1,66 @@
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App};
+use euromillions_bot::error::AppError;
+
+async fn failing(error: fn() -> AppError) -> (StatusCode, serde_json::Value) {
+    let app = test::init_service(
+        App::new().route("/fail", web::get().to(move || async move { Err::<String, _>(error()) }))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/fail").to_request();
+    let resp = test::call_service(&app, req).await;
+    let status = resp.status();
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    (status, body)
+}
+
+#[actix_web::test]
+async fn test_validation_error_response() {
+    let (status, body) = failing(|| AppError::Validation("count must be positive".to_string())).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "invalid_request");
+    assert_eq!(body["error"]["message"], "count must be positive");
+}
+
+#[actix_web::test]
+async fn test_fetch_error_response() {
+    let (status, body) = failing(|| AppError::Fetch("upstream is down".to_string())).await;
+
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(body["error"]["code"], "fetch_failed");
+}
+
+#[actix_web::test]
+async fn test_conflict_error_response() {
+    let (status, body) = failing(|| AppError::Conflict("key already used".to_string())).await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "conflict");
+    assert_eq!(body["error"]["message"], "key already used");
+}
+
+#[actix_web::test]
+async fn test_unavailable_error_response() {
+    let (status, body) = failing(|| AppError::Unavailable("2 database migration(s) pending".to_string())).await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["code"], "service_unavailable");
+}
+
+#[actix_web::test]
+async fn test_database_error_hides_details() {
+    let (status, body) = failing(|| {
+        AppError::Database(sqlx::Error::Protocol("relation \"grids\" does not exist".to_string()))
+    }).await;
+
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(body["error"]["code"], "database_error");
+    assert!(!body["error"]["message"].as_str().unwrap().contains("grids"));
+}
+
+#[actix_web::test]
+async fn test_row_not_found_maps_to_not_found() {
+    let err: AppError = sqlx::Error::RowNotFound.into();
+    assert_eq!(err.code(), "not_found");
+}
@@ -1,10 +1,36 @@
-use euromillions_bot::fetcher::fetch_history;
+use euromillions_bot::fetcher::{fetch_history, FetchFailureKind, Fetcher, FetcherConfig};
 use chrono::NaiveDate;
+use std::time::Duration;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const RESULTS_PAGE: &str = r#"
+<html><body><table>
+<tr class="resultRow">
+  <td><a href="/results/18-03-2025">Tuesday
+    18th March 2025</a></td>
+  <td><ul><li>3</li><li>12</li><li>19</li><li>27</li><li>44</li><li>2</li><li>9</li></ul></td>
+</tr>
+</table></body></html>
+"#;
+
+fn test_config(base_url: String) -> FetcherConfig {
+    FetcherConfig {
+        base_url,
+        user_agent: "euromillions-bot-tests".to_string(),
+        timeout: Duration::from_secs(5),
+        connect_timeout: Duration::from_secs(1),
+        max_retries: 2,
+        backoff_base: Duration::from_millis(1),
+        backoff_max: Duration::from_millis(5),
+        min_request_interval: Duration::ZERO,
+    }
+}
 
 #[test]
 fn test_date_parsing() {
     // Test parsing date with ordinal suffix
-    let date_parts = vec!["Tuesday", "3rd", "January", "2025"];
+    let date_parts = ["Tuesday", "3rd", "January", "2025"];
     let day = date_parts[1]
         .trim_end_matches("st")
         .trim_end_matches("nd")
@@ -48,7 +74,8 @@ async fn test_fetch_history_integration() {
     let result = fetch_history().await;
     
     match result {
-        Ok(draws) => {
+        Ok(report) => {
+            let draws = report.draws;
             println!("Fetched {} draws", draws.len());
             assert!(!draws.is_empty(), "Should fetch at least some draws");
             
@@ -76,10 +103,167 @@ async fn test_fetch_history_integration() {
 #[test]
 fn test_number_validation() {
     // Valid numbers
-    let numbers = vec![1, 15, 30, 45, 50];
+    let numbers = [1, 15, 30, 45, 50];
     assert!(numbers.iter().all(|n| *n >= 1 && *n <= 50));
     
     // Valid stars
-    let stars = vec![1, 12];
+    let stars = [1, 12];
     assert!(stars.iter().all(|s| *s >= 1 && *s <= 12));
 }
+
+#[tokio::test]
+async fn test_fetch_retries_server_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/results-history-2025"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/results-history-2025"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(RESULTS_PAGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let fetcher = Fetcher::new(test_config(server.uri())).unwrap();
+    let report = fetcher.fetch_years(&[2025]).await;
+
+    assert!(report.is_complete());
+    assert_eq!(report.draws.len(), 1);
+    assert_eq!(report.draws[0].date, NaiveDate::from_ymd_opt(2025, 3, 18).unwrap());
+    assert_eq!(report.draws[0].numbers, vec![3, 12, 19, 27, 44]);
+    assert_eq!(report.draws[0].stars, vec![2, 9]);
+}
+
+#[tokio::test]
+async fn test_fetch_reports_failed_years() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/results-history-2024"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(3)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/results-history-2025"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(RESULTS_PAGE))
+        .mount(&server)
+        .await;
+
+    let fetcher = Fetcher::new(test_config(server.uri())).unwrap();
+    let report = fetcher.fetch_years(&[2024, 2025]).await;
+
+    assert_eq!(report.draws.len(), 1);
+    assert_eq!(report.failures.len(), 1);
+    let failure = &report.failures[0];
+    assert_eq!(failure.year, 2024);
+    assert_eq!(failure.attempts, 3);
+    assert_eq!(failure.kind, FetchFailureKind::Status { status: 500 });
+}
+
+#[tokio::test]
+async fn test_fetch_does_not_retry_client_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(404))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let fetcher = Fetcher::new(test_config(server.uri())).unwrap();
+    let report = fetcher.fetch_years(&[2025]).await;
+
+    assert_eq!(report.failures.len(), 1);
+    assert_eq!(report.failures[0].attempts, 1);
+}
+
+#[tokio::test]
+async fn test_fetch_sends_user_agent() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(header("user-agent", "euromillions-bot-tests"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(RESULTS_PAGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let fetcher = Fetcher::new(test_config(server.uri())).unwrap();
+    let report = fetcher.fetch_years(&[2025]).await;
+
+    assert!(report.is_complete());
+}
+
+#[tokio::test]
+async fn test_fetch_uses_conditional_requests() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(header("if-none-match", "\"v1\""))
+        .respond_with(ResponseTemplate::new(304))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("ETag", "\"v1\"")
+                .set_body_string(RESULTS_PAGE),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let fetcher = Fetcher::new(test_config(server.uri())).unwrap();
+    let first = fetcher.fetch_years(&[2025]).await;
+    let second = fetcher.fetch_years(&[2025]).await;
+
+    assert_eq!(first.draws.len(), 1);
+    // The 304 response is served from the cached copy of the page
+    assert!(second.is_complete());
+    assert_eq!(second.draws.len(), 1);
+}
+
+#[tokio::test]
+async fn test_fetch_flags_markup_change() {
+    let page = std::fs::read_to_string(format!(
+        "{}/tests/fixtures/results-history-redesigned.html",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap();
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(page))
+        .mount(&server)
+        .await;
+
+    let fetcher = Fetcher::new(test_config(server.uri())).unwrap();
+    let report = fetcher.fetch_years(&[2025]).await;
+
+    assert!(report.markup_changed());
+    assert!(report.draws.is_empty());
+    assert_eq!(report.failures.len(), 1);
+    assert_eq!(report.failures[0].kind, FetchFailureKind::MarkupChanged { rows_seen: 0 });
+}
+
+#[tokio::test]
+async fn test_fetch_accepts_year_without_draws() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(
+            r#"<html><body><h1>EuroMillions Results for 2026</h1>
+            <table class="resultsHistory"><thead><tr><th>Draw Date</th></tr></thead></table>
+            </body></html>"#,
+        ))
+        .mount(&server)
+        .await;
+
+    let fetcher = Fetcher::new(test_config(server.uri())).unwrap();
+    let report = fetcher.fetch_years(&[2026]).await;
+
+    assert!(!report.markup_changed());
+    assert!(report.is_complete());
+    assert!(report.draws.is_empty());
+    assert_eq!(report.pages.len(), 1);
+}
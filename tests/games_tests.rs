@@ -0,0 +1,116 @@
+use chrono::{Datelike, NaiveDate, Weekday};
+use euromillions_bot::checker::check_line;
+use euromillions_bot::games::{self, EURODREAMS, EUROMILLIONS, LOTO, UK_LOTTO};
+use euromillions_bot::generator::{generate, GenerateOptions, Strategy};
+use euromillions_bot::models::Draw;
+use euromillions_bot::storage::SqliteStorage;
+
+fn draw(game: &str, numbers: &[i32], stars: &[i32]) -> Draw {
+    Draw {
+        id: 1,
+        game: game.to_string(),
+        date: NaiveDate::from_ymd_opt(2025, 3, 22).unwrap(),
+        numbers: numbers.to_vec(),
+        stars: stars.to_vec(),
+    }
+}
+
+#[test]
+fn test_registry() {
+    assert_eq!(games::GAMES.map(|g| g.id), ["euromillions", "eurodreams", "loto", "uk_lotto"]);
+    assert_eq!(games::get("loto").unwrap(), &LOTO);
+    let err = games::get("keno").unwrap_err().to_string();
+    assert!(err.contains("euromillions, eurodreams, loto, uk_lotto"), "{}", err);
+}
+
+#[test]
+fn test_prize_tiers() {
+    assert_eq!(EURODREAMS.prize_tier(6, 1), Some(1));
+    assert_eq!(EURODREAMS.prize_tier(6, 0), Some(2));
+    assert_eq!(EURODREAMS.prize_tier(2, 1), Some(6));
+    assert_eq!(EURODREAMS.prize_tier(1, 1), None);
+
+    assert_eq!(LOTO.prize_tier(5, 0), Some(2));
+    assert_eq!(LOTO.prize_tier(1, 1), Some(9));
+    assert_eq!(LOTO.prize_tier(0, 1), Some(9));
+    assert_eq!(LOTO.prize_tier(1, 0), None);
+
+    assert_eq!(UK_LOTTO.prize_tier(6, 0), Some(1));
+    assert_eq!(UK_LOTTO.prize_tier(5, 1), Some(2));
+    assert_eq!(UK_LOTTO.prize_tier(5, 0), Some(3));
+    assert_eq!(UK_LOTTO.prize_tier(1, 1), None);
+}
+
+#[test]
+fn test_validation() {
+    assert!(EURODREAMS.validate_line(&[1, 2, 3, 4, 5, 40], &[5]).is_ok());
+    assert!(EURODREAMS.validate_line(&[1, 2, 3, 4, 5, 41], &[5]).is_err());
+    assert!(EURODREAMS.validate_line(&[1, 2, 3, 4, 5, 6], &[6]).is_err());
+    assert!(LOTO.validate_line(&[1, 2, 3, 4, 49], &[10]).is_ok());
+    assert!(LOTO.validate_line(&[1, 2, 3, 4, 49], &[]).unwrap_err().to_string().contains("chance number"));
+
+    // UK Lotto players pick no bonus ball, but one is drawn
+    assert!(UK_LOTTO.validate_line(&[1, 2, 3, 4, 5, 59], &[]).is_ok());
+    assert!(UK_LOTTO.validate_line(&[1, 2, 3, 4, 5, 59], &[7]).is_err());
+    assert!(UK_LOTTO.validate_draw(&[1, 2, 3, 4, 5, 59], &[7]).is_ok());
+    assert!(UK_LOTTO.validate_draw(&[1, 2, 3, 4, 5, 59], &[5]).is_err());
+    assert!(UK_LOTTO.validate_draw(&[1, 2, 3, 4, 5, 59], &[]).is_err());
+}
+
+#[test]
+fn test_next_draw() {
+    let monday = NaiveDate::from_ymd_opt(2025, 3, 17).unwrap();
+    assert_eq!(EUROMILLIONS.next_draw_after(monday).unwrap().weekday(), Weekday::Tue);
+    assert_eq!(EURODREAMS.next_draw_after(monday).unwrap().weekday(), Weekday::Thu);
+    assert_eq!(LOTO.next_draw_after(monday).unwrap().weekday(), Weekday::Wed);
+    let saturday = NaiveDate::from_ymd_opt(2025, 3, 22).unwrap();
+    assert_eq!(UK_LOTTO.next_draw_after(saturday), Some(NaiveDate::from_ymd_opt(2025, 3, 26).unwrap()));
+    assert_eq!(EUROMILLIONS.next_draw_after(NaiveDate::MAX), None);
+
+    // Tuesdays and Fridays from Monday 17 March
+    let day = |d| NaiveDate::from_ymd_opt(2025, 3, d).unwrap();
+    assert_eq!(EUROMILLIONS.draws_between(monday, monday), 0);
+    assert_eq!(EUROMILLIONS.draws_between(monday, day(18)), 1);
+    assert_eq!(EUROMILLIONS.draws_between(day(18), day(21)), 1);
+    assert_eq!(EUROMILLIONS.draws_between(monday, day(31)), 4);
+    assert_eq!(EUROMILLIONS.draws_between(monday, NaiveDate::from_ymd_opt(2026, 3, 16).unwrap()), 104);
+    assert_eq!(EUROMILLIONS.draws_between(day(21), day(18)), 0);
+}
+
+#[test]
+fn test_check_uk_lotto_bonus_ball() {
+    let drawn = draw("uk_lotto", &[4, 11, 23, 35, 47, 58], &[19]);
+    let second = check_line(&UK_LOTTO, &[4, 11, 23, 35, 47, 19], &[], &drawn);
+    assert_eq!(second.matched_stars, vec![19]);
+    assert_eq!(second.tier, Some(2));
+    let third = check_line(&UK_LOTTO, &[4, 11, 23, 35, 47, 1], &[], &drawn);
+    assert_eq!(third.tier, Some(3));
+}
+
+#[test]
+fn test_check_eurodreams() {
+    let drawn = draw("eurodreams", &[3, 9, 14, 22, 31, 40], &[2]);
+    assert_eq!(check_line(&EURODREAMS, &[3, 9, 14, 22, 31, 40], &[2], &drawn).tier, Some(1));
+    assert_eq!(check_line(&EURODREAMS, &[3, 9, 14, 1, 2, 4], &[5], &drawn).tier, Some(5));
+}
+
+#[tokio::test]
+async fn test_generate_for_every_game() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    for game in games::GAMES {
+        for strategy in [Strategy::Frequent, Strategy::Random] {
+            let options = GenerateOptions {
+                strategy,
+                count: 5,
+                seed: Some(1),
+                ..GenerateOptions::for_game(game)
+            };
+            assert!(game.draw_days.contains(&options.draw_date.weekday()));
+            for grid in generate(&storage, &options).await.unwrap() {
+                assert_eq!(grid.game, game.id);
+                assert_eq!(grid.draw_date, options.draw_date);
+                game.validate_line(&grid.numbers, &grid.stars).unwrap();
+            }
+        }
+    }
+}
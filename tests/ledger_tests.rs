@@ -0,0 +1,243 @@
+use chrono::NaiveDate;
+use euromillions_bot::error::AppError;
+use euromillions_bot::games::{EUROMILLIONS, UK_LOTTO};
+use euromillions_bot::generator::{generate, GenerateOptions};
+use euromillions_bot::grids;
+use euromillions_bot::ledger::{self, format_cents, BudgetPeriod};
+use euromillions_bot::models::{Budget, NewDraw, NewDrawPrize, NewGrid, TicketAddon, TicketPrice};
+use euromillions_bot::storage::{SqliteStorage, Storage};
+
+fn date(month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2025, month, day).unwrap()
+}
+
+fn grid(game: &str, draw_date: NaiveDate, numbers: Vec<i32>, stars: Vec<i32>, user_id: i32, cost_cents: i64) -> NewGrid {
+    NewGrid {
+        game: game.to_string(),
+        draw_date,
+        numbers,
+        stars,
+        user_id: Some(user_id),
+        cost_cents: Some(cost_cents),
+        addons: Vec::new(),
+    }
+}
+
+/// Stores the grids as lines the user played.
+async fn play(storage: &SqliteStorage, grids: &[NewGrid]) {
+    let ids: Vec<i32> = storage.insert_grids(grids).await.unwrap().iter().map(|g| g.id).collect();
+    storage.set_grid_status(&ids, &["generated"], "played").await.unwrap();
+}
+
+#[test]
+fn test_format_cents() {
+    assert_eq!(format_cents(250, "EUR"), "2.50 EUR");
+    assert_eq!(format_cents(100_005, "GBP"), "1000.05 GBP");
+    assert_eq!(format_cents(-50, "EUR"), "-0.50 EUR");
+}
+
+#[test]
+fn test_budget_periods() {
+    // Wednesday 19 March 2025
+    assert_eq!(BudgetPeriod::Week.bounds(date(3, 19)), (date(3, 17), date(3, 23)));
+    assert_eq!(BudgetPeriod::Week.bounds(date(3, 17)), (date(3, 17), date(3, 23)));
+    assert_eq!(BudgetPeriod::Month.bounds(date(2, 14)), (date(2, 1), date(2, 28)));
+    assert_eq!(BudgetPeriod::Month.bounds(date(12, 31)), (date(12, 1), date(12, 31)));
+    assert_eq!("month".parse::<BudgetPeriod>().unwrap(), BudgetPeriod::Month);
+    assert!("day".parse::<BudgetPeriod>().is_err());
+}
+
+#[tokio::test]
+async fn test_prices_change_over_time() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    assert_eq!(ledger::price_cents(&storage, &EUROMILLIONS, date(3, 18)).await.unwrap(), 250);
+
+    let price = |valid_from, price_cents| TicketPrice { game: "euromillions".to_string(), valid_from, price_cents };
+    storage.set_ticket_price(&price(date(1, 1), 250)).await.unwrap();
+    storage.set_ticket_price(&price(date(4, 1), 300)).await.unwrap();
+    assert_eq!(ledger::price_cents(&storage, &EUROMILLIONS, date(3, 28)).await.unwrap(), 250);
+    assert_eq!(ledger::price_cents(&storage, &EUROMILLIONS, date(4, 1)).await.unwrap(), 300);
+    assert_eq!(ledger::price_cents(&storage, &UK_LOTTO, date(4, 2)).await.unwrap(), 200);
+
+    // Generated grids carry the price of their draw
+    let options = GenerateOptions { count: 2, draw_date: date(4, 4), ..GenerateOptions::default() };
+    let grids = generate(&storage, &options).await.unwrap();
+    assert!(grids.iter().all(|g| g.cost_cents == Some(300)));
+}
+
+#[tokio::test]
+async fn test_addons_add_to_the_line_price() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    let addon = |valid_from, price_cents| TicketAddon {
+        game: "euromillions".to_string(),
+        addon: "second_chance".to_string(),
+        valid_from,
+        price_cents,
+    };
+    storage.set_ticket_addon(&addon(date(2, 1), 50)).await.unwrap();
+    storage.set_ticket_addon(&addon(date(4, 1), 80)).await.unwrap();
+    let addons = ledger::normalize_addons([" second_chance", "", "second_chance"]);
+    assert_eq!(addons, vec!["second_chance".to_string()]);
+
+    assert_eq!(ledger::line_cost_cents(&storage, &EUROMILLIONS, date(3, 18), &[]).await.unwrap(), 250);
+    assert_eq!(ledger::line_cost_cents(&storage, &EUROMILLIONS, date(3, 18), &addons).await.unwrap(), 300);
+    assert_eq!(ledger::line_cost_cents(&storage, &EUROMILLIONS, date(4, 4), &addons).await.unwrap(), 330);
+    // Not sold yet, or not sold at all
+    let early = ledger::line_cost_cents(&storage, &EUROMILLIONS, date(1, 7), &addons).await;
+    assert!(matches!(early, Err(AppError::Validation(_))));
+    assert!(ledger::line_cost_cents(&storage, &UK_LOTTO, date(4, 2), &addons).await.is_err());
+
+    let options = GenerateOptions { count: 2, draw_date: date(4, 4), addons: addons.clone(), ..GenerateOptions::default() };
+    let grids = generate(&storage, &options).await.unwrap();
+    assert!(grids.iter().all(|g| g.cost_cents == Some(330) && g.addons == addons));
+    // Each of the lines counts with its add-ons against the budget
+    let user = storage.create_user("alice").await.unwrap();
+    let budget = Budget { user_id: user.id, period: "week".to_string(), currency: "EUR".to_string(), limit_cents: 600 };
+    storage.set_budget(&budget).await.unwrap();
+    assert!(ledger::check_budgets(&storage, user.id, &EUROMILLIONS, date(4, 4), 2, &[]).await.is_ok());
+    assert!(ledger::check_budgets(&storage, user.id, &EUROMILLIONS, date(4, 4), 2, &addons).await.is_err());
+}
+
+#[tokio::test]
+async fn test_budgets_are_enforced_with_a_warning_near_the_limit() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    let user = storage.create_user("alice").await.unwrap();
+    let budget = |period: &str, currency: &str, limit_cents| Budget {
+        user_id: user.id,
+        period: period.to_string(),
+        currency: currency.to_string(),
+        limit_cents,
+    };
+    storage.set_budget(&budget("week", "EUR", 1000)).await.unwrap();
+    storage.set_budget(&budget("month", "GBP", 400)).await.unwrap();
+
+    // 5 EUR spent on Tuesday, 2 GBP the same week
+    play(
+        &storage,
+        &[
+            grid("euromillions", date(3, 18), vec![1, 2, 3, 4, 5], vec![1, 2], user.id, 250),
+            grid("euromillions", date(3, 18), vec![6, 7, 8, 9, 10], vec![1, 2], user.id, 250),
+            grid("uk_lotto", date(3, 19), vec![1, 2, 3, 4, 5, 6], vec![], user.id, 200),
+            // The week before does not count
+            grid("euromillions", date(3, 14), vec![1, 2, 3, 4, 5], vec![1, 2], user.id, 250),
+        ],
+    )
+    .await;
+    // Nor do lines only generated
+    storage
+        .insert_grids(&[grid("euromillions", date(3, 18), vec![1, 2, 3, 4, 6], vec![1, 2], user.id, 250)])
+        .await
+        .unwrap();
+
+    let statuses = ledger::check_budgets(&storage, user.id, &EUROMILLIONS, date(3, 21), 1, &[]).await.unwrap();
+    assert_eq!(statuses.len(), 1);
+    assert_eq!((statuses[0].spent_cents, statuses[0].remaining_cents), (750, 250));
+    assert!(statuses[0].warning().is_none());
+
+    let statuses = ledger::check_budgets(&storage, user.id, &EUROMILLIONS, date(3, 21), 2, &[]).await.unwrap();
+    assert_eq!(statuses[0].warning().unwrap(), "weekly budget: 10.00 EUR of 10.00 EUR spent");
+
+    let over = ledger::check_budgets(&storage, user.id, &EUROMILLIONS, date(3, 21), 3, &[]).await;
+    match over {
+        Err(AppError::Validation(message)) => assert_eq!(
+            message,
+            "3 line(s) cost 7.50 EUR, over the weekly budget: 5.00 EUR of 10.00 EUR already spent from 2025-03-17 to 2025-03-23"
+        ),
+        other => panic!("unexpected {:?}", other),
+    }
+    // Next week starts afresh
+    assert!(ledger::check_budgets(&storage, user.id, &EUROMILLIONS, date(3, 25), 4, &[]).await.is_ok());
+    assert!(ledger::check_budgets(&storage, user.id, &UK_LOTTO, date(3, 22), 2, &[]).await.is_err());
+
+    let statuses = ledger::budget_statuses(&storage, user.id, date(3, 20)).await.unwrap();
+    assert_eq!(statuses.iter().map(|s| s.spent_cents).collect::<Vec<_>>(), vec![500, 200]);
+
+    // Lines about to be played count per period they fall in
+    let new = |day, cost_cents| grid("euromillions", day, vec![1, 2, 3, 4, 7], vec![1, 2], user.id, cost_cents);
+    let saved = storage.insert_grids(&[new(date(3, 21), 250), new(date(3, 25), 250), new(date(3, 28), 250)]).await.unwrap();
+    let ids: Vec<i32> = saved.iter().map(|g| g.id).collect();
+    grids::play(&storage, &ids, None).await.unwrap();
+    let spent = |statuses: Vec<ledger::BudgetStatus>| statuses.iter().map(|s| s.spent_cents).collect::<Vec<_>>();
+    assert_eq!(spent(ledger::budget_statuses(&storage, user.id, date(3, 21)).await.unwrap()), vec![750, 200]);
+    assert_eq!(spent(ledger::budget_statuses(&storage, user.id, date(3, 25)).await.unwrap()), vec![500, 200]);
+    let pricier = storage.insert_grids(&[new(date(3, 21), 600)]).await.unwrap();
+    assert!(grids::play(&storage, &[pricier[0].id], None).await.is_err());
+    assert!(!grids::is_played(&storage.get_grids(&[pricier[0].id]).await.unwrap()[0]));
+
+    // Lines already played count again at their new cost
+    let superstar = TicketAddon {
+        game: "euromillions".to_string(),
+        addon: "superstar".to_string(),
+        valid_from: date(1, 1),
+        price_cents: 300,
+    };
+    storage.set_ticket_addon(&superstar).await.unwrap();
+    let addons = vec!["superstar".to_string()];
+    match grids::play(&storage, &ids[..1], Some(&addons)).await {
+        Err(AppError::Validation(message)) => assert_eq!(
+            message,
+            "1 line(s) cost 5.50 EUR, over the weekly budget: 5.00 EUR of 10.00 EUR already spent from 2025-03-17 to 2025-03-23"
+        ),
+        other => panic!("unexpected {:?}", other),
+    }
+    let unchanged = &storage.get_grids(&ids[..1]).await.unwrap()[0];
+    assert_eq!((unchanged.cost_cents, unchanged.addons.is_empty()), (Some(250), true));
+    let played = grids::play(&storage, &ids[1..2], Some(&addons)).await.unwrap();
+    assert_eq!((played[0].cost_cents, played[0].addons.clone()), (Some(550), addons));
+}
+
+#[tokio::test]
+async fn test_ledger_counts_spend_and_winnings() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    let user = storage.create_user("alice").await.unwrap();
+    let other = storage.create_user("bob").await.unwrap();
+    storage
+        .upsert_draw(NewDraw {
+            game: "euromillions".to_string(),
+            date: date(3, 21),
+            numbers: vec![7, 20, 32, 41, 48],
+            stars: vec![3, 11],
+        })
+        .await
+        .unwrap();
+    let draw = storage.get_draw("euromillions", date(3, 21)).await.unwrap().unwrap();
+    // Only tier 9 (3 numbers and 1 star) has a stored payout
+    storage
+        .upsert_draw_prizes(draw.id, &[NewDrawPrize { tier: 9, amount_cents: 1_520, winners: None }])
+        .await
+        .unwrap();
+
+    play(
+        &storage,
+        &[
+            // Tier 9
+            grid("euromillions", date(3, 21), vec![7, 20, 32, 1, 2], vec![3, 1], user.id, 250),
+            // Tier 7 (4 numbers), payout unknown
+            grid("euromillions", date(3, 21), vec![7, 20, 32, 41, 2], vec![1, 2], user.id, 250),
+            grid("euromillions", date(3, 21), vec![1, 2, 3, 4, 5], vec![1, 2], user.id, 250),
+            grid("euromillions", date(3, 25), vec![1, 2, 3, 4, 5], vec![1, 2], user.id, 250),
+            grid("euromillions", date(3, 21), vec![7, 20, 32, 1, 2], vec![3, 1], other.id, 250),
+            grid("uk_lotto", date(3, 22), vec![1, 2, 3, 4, 5, 6], vec![], user.id, 200),
+        ],
+    )
+    .await;
+    // A winning line generated but not played
+    storage
+        .insert_grids(&[grid("euromillions", date(3, 21), vec![7, 20, 32, 41, 48], vec![3, 11], user.id, 250)])
+        .await
+        .unwrap();
+
+    let ledger = ledger::ledger(&storage, user.id, &EUROMILLIONS, None, None).await.unwrap();
+    assert_eq!((ledger.game, ledger.currency), ("euromillions", "EUR"));
+    assert_eq!(ledger.entries.len(), 2);
+    let played = &ledger.entries[0];
+    assert_eq!((played.grids, played.spent_cents, played.won_cents), (3, 750, 1_520));
+    assert_eq!((played.winning_grids, played.unknown_payouts, played.pending), (2, 1, false));
+    assert!(ledger.entries[1].pending);
+    assert_eq!((ledger.spent_cents, ledger.won_cents, ledger.net_cents), (1_000, 1_520, 520));
+
+    let ledger = ledger::ledger(&storage, user.id, &EUROMILLIONS, Some(date(3, 22)), None).await.unwrap();
+    assert_eq!((ledger.entries.len(), ledger.spent_cents), (1, 250));
+    let ledger = ledger::ledger(&storage, user.id, &UK_LOTTO, None, Some(date(3, 22))).await.unwrap();
+    assert_eq!((ledger.spent_cents, ledger.currency), (200, "GBP"));
+}
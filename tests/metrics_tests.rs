@@ -0,0 +1,72 @@
+use euromillions_bot::fetcher::{FetchFailureKind, FetchReport, FetchedPage, PageReport, YearFailure};
+use euromillions_bot::parser::ParseReport;
+use euromillions_bot::ingest::IngestSummary;
+use euromillions_bot::metrics::{Metrics, RunOutcome};
+use std::time::Duration;
+
+fn summary(failed_years: &[i32]) -> IngestSummary {
+    let mut report = FetchReport::default();
+    report.pages.push(PageReport {
+        year: 2025,
+        url: "https://example.com/2025".to_string(),
+        page: FetchedPage {
+            url: "https://example.com/2025".to_string(),
+            status: 200,
+            etag: None,
+            last_modified: None,
+            not_modified: false,
+            body: String::new(),
+        },
+        parse: ParseReport {
+            rows_seen: 1,
+            rows_parsed: 1,
+            ..Default::default()
+        },
+    });
+    for &year in failed_years {
+        report.failures.push(YearFailure {
+            year,
+            url: format!("https://example.com/{}", year),
+            attempts: 3,
+            kind: FetchFailureKind::Status { status: 503 },
+        });
+    }
+    IngestSummary {
+        run_id: 1,
+        inserted: 0,
+        new_draws: Vec::new(),
+        report,
+    }
+}
+
+#[test]
+fn test_run_outcome() {
+    assert_eq!(RunOutcome::of(&summary(&[])), RunOutcome::Success);
+    assert_eq!(RunOutcome::of(&summary(&[2024])), RunOutcome::Partial);
+
+    let mut nothing_fetched = summary(&[2024]);
+    nothing_fetched.report.pages.clear();
+    assert_eq!(RunOutcome::of(&nothing_fetched), RunOutcome::Failure);
+}
+
+#[test]
+fn test_scheduler_runs_are_counted_per_outcome() {
+    let metrics = Metrics::new();
+    metrics.observe_scheduler_run(RunOutcome::Success);
+    metrics.observe_scheduler_run(RunOutcome::Success);
+    metrics.observe_scheduler_run(RunOutcome::Failure);
+    metrics.observe_fetch(&summary(&[]), Duration::from_millis(1500));
+
+    let text = metrics.render();
+    assert!(text.contains(r#"euromillions_scheduler_runs_total{outcome="success"} 2"#), "{}", text);
+    assert!(text.contains(r#"euromillions_scheduler_runs_total{outcome="failure"} 1"#));
+    assert!(text.contains("euromillions_fetch_duration_seconds_sum 1.5"));
+}
+
+#[test]
+fn test_registries_are_independent() {
+    let first = Metrics::new();
+    first.observe_scheduler_run(RunOutcome::Partial);
+
+    assert!(!Metrics::new().render().contains("scheduler_runs_total{"));
+}
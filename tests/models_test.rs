@@ -1,9 +1,10 @@
 use euromillions_bot::models::{NewDraw, NewGrid};
-use chrono::NaiveDate;
+use chrono::{Datelike, NaiveDate};
 
 #[test]
 fn test_new_draw_creation() {
     let draw = NewDraw {
+        game: "euromillions".to_string(),
         date: NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
         numbers: vec![1, 15, 23, 42, 50],
         stars: vec![3, 9],
@@ -17,9 +18,13 @@ fn test_new_draw_creation() {
 #[test]
 fn test_new_grid_creation() {
     let grid = NewGrid {
+        game: "euromillions".to_string(),
         draw_date: NaiveDate::from_ymd_opt(2025, 11, 28).unwrap(),
         numbers: vec![7, 18, 29, 41, 48],
         stars: vec![1, 12],
+        user_id: None,
+        cost_cents: None,
+        addons: Vec::new(),
     };
 
     assert_eq!(grid.numbers.len(), 5);
@@ -29,6 +34,7 @@ fn test_new_grid_creation() {
 #[test]
 fn test_draw_serialization() {
     let draw = NewDraw {
+        game: "euromillions".to_string(),
         date: NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
         numbers: vec![1, 2, 3, 4, 5],
         stars: vec![1, 2],
@@ -0,0 +1,335 @@
+mod common;
+
+use chrono::NaiveDate;
+use common::FakeSource;
+use euromillions_bot::config::{SmtpConfig, SmtpTls, TemplateConfig};
+use euromillions_bot::games::EUROMILLIONS;
+use euromillions_bot::ingest::{self, FetchTrigger};
+use euromillions_bot::models::{Draw, NewDraw, NewGrid, NewNotificationChannel};
+use euromillions_bot::notify::{
+    self, ChannelKind, DiscordChannel, EmailChannel, Message, Notifier, NotifySummary, SlackChannel,
+    TelegramChannel, WebhookChannel,
+};
+use euromillions_bot::storage::{SqliteStorage, Storage};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use wiremock::matchers::{body_json, body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn message() -> Message {
+    Message {
+        subject: "EuroMillions results for 2025-03-21".to_string(),
+        text: "EuroMillions draw of 2025-03-21: 7 20 32 41 48 ★ 3 11".to_string(),
+        payload: serde_json::json!({ "event": "draw.result" }),
+    }
+}
+
+fn draw() -> Draw {
+    Draw {
+        id: 1,
+        game: "euromillions".to_string(),
+        date: NaiveDate::from_ymd_opt(2025, 3, 21).unwrap(),
+        numbers: vec![7, 20, 32, 41, 48],
+        stars: vec![3, 11],
+    }
+}
+
+#[test]
+fn test_render_replaces_known_placeholders() {
+    let text = notify::render("{date}: {numbers} {unknown}", &[("date", "2025-03-21".to_string()), ("numbers", "1 2".to_string())]);
+    assert_eq!(text, "2025-03-21: 1 2 {unknown}");
+}
+
+#[test]
+fn test_channel_kinds_and_targets() {
+    assert_eq!("slack".parse::<ChannelKind>().unwrap(), ChannelKind::Slack);
+    assert!("sms".parse::<ChannelKind>().is_err());
+
+    assert!(ChannelKind::Webhook.validate_target("https://example.com/hook", false).is_ok());
+    assert!(ChannelKind::Discord.validate_target("ftp://example.com", false).is_err());
+    assert!(ChannelKind::Slack.validate_target("http://example.com/hook", false).is_err());
+    assert!(ChannelKind::Webhook.validate_target("https://127.0.0.1/hook", false).is_err());
+    assert!(ChannelKind::Webhook.validate_target("http://127.0.0.1:8080/hook", true).is_ok());
+    assert!(ChannelKind::Email.validate_target("alice@example.com", false).is_ok());
+    assert!(ChannelKind::Email.validate_target("alice", false).is_err());
+    assert!(ChannelKind::Telegram.validate_target("-100123", false).is_ok());
+    assert!(ChannelKind::Telegram.validate_target("@results", false).is_ok());
+    assert!(ChannelKind::Telegram.validate_target("results", false).is_err());
+}
+
+#[tokio::test]
+async fn test_draw_message_lists_grids_with_prize() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    let grids = storage
+        .insert_grids(&[
+            NewGrid { game: "euromillions".to_string(), draw_date: draw().date, numbers: vec![7, 20, 1, 2, 3], stars: vec![3, 1], user_id: None, cost_cents: None, addons: Vec::new() },
+            NewGrid { game: "euromillions".to_string(), draw_date: draw().date, numbers: vec![1, 2, 3, 4, 5], stars: vec![1, 2], user_id: None, cost_cents: None, addons: Vec::new() },
+        ])
+        .await
+        .unwrap();
+    let checked: Vec<_> = grids
+        .iter()
+        .map(|g| (g, euromillions_bot::checker::check_line(&EUROMILLIONS, &g.numbers, &g.stars, &draw())))
+        .collect();
+
+    let message = notify::draw_message(&TemplateConfig::default(), &draw(), &checked);
+
+    assert_eq!(message.subject, "EuroMillions results for 2025-03-21");
+    let lines: Vec<_> = message.text.lines().collect();
+    assert_eq!(lines[0], "EuroMillions draw of 2025-03-21: 7 20 32 41 48 ★ 3 11");
+    assert_eq!(lines[1], "7 20 1 2 3 ★ 3 1: 2 number(s), 1 star(s) — prize tier 12");
+    assert_eq!(lines[2], "1 2 3 4 5 ★ 1 2: 0 number(s), 0 star(s) — no prize");
+    assert_eq!(message.payload["grids"][0]["tier"], 12);
+    assert_eq!(message.payload["draw"]["numbers"], serde_json::json!([7, 20, 32, 41, 48]));
+}
+
+#[tokio::test]
+async fn test_http_channels_post_their_format() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/hook"))
+        .and(body_json(serde_json::json!({ "event": "draw.result" })))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/slack"))
+        .and(body_json(serde_json::json!({ "text": message().text })))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/discord"))
+        .and(body_json(serde_json::json!({ "content": message().text })))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = reqwest::Client::new();
+    let notifier = Notifier::default()
+        .with_channel(ChannelKind::Webhook, WebhookChannel::new(client.clone()))
+        .with_channel(ChannelKind::Slack, SlackChannel::new(client.clone()))
+        .with_channel(ChannelKind::Discord, DiscordChannel::new(client));
+    for (kind, route) in [(ChannelKind::Webhook, "hook"), (ChannelKind::Slack, "slack"), (ChannelKind::Discord, "discord")] {
+        let target = format!("{}/{}", server.uri(), route);
+        notifier.send(kind, &target, &message()).await.unwrap();
+    }
+
+    let error = notifier
+        .send(ChannelKind::Webhook, &format!("{}/missing", server.uri()), &message())
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("404"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_telegram_channel_uses_bot_api() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/botSECRET/sendMessage"))
+        .and(body_partial_json(serde_json::json!({ "chat_id": "42", "text": message().text })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "ok": true })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/botSECRET/sendMessage"))
+        .and(body_partial_json(serde_json::json!({ "chat_id": "-1" })))
+        .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({ "ok": false })))
+        .mount(&server)
+        .await;
+
+    let channel = TelegramChannel::new(reqwest::Client::new(), &server.uri(), "SECRET");
+    let notifier = Notifier::default().with_channel(ChannelKind::Telegram, channel);
+    notifier.send(ChannelKind::Telegram, "42", &message()).await.unwrap();
+
+    let error = notifier.send(ChannelKind::Telegram, "-1", &message()).await.unwrap_err();
+    assert!(!error.to_string().contains("SECRET"), "the token must not leak: {}", error);
+}
+
+#[tokio::test]
+async fn test_unconfigured_channel_fails() {
+    let error = Notifier::default()
+        .send(ChannelKind::Email, "alice@example.com", &message())
+        .await
+        .unwrap_err();
+    assert_eq!(error.code(), "notification_failed");
+}
+
+/// Minimal SMTP server accepting a single message and handing back the DATA
+/// section.
+async fn smtp_stand_in() -> (u16, oneshot::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = oneshot::channel();
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (read, mut write) = socket.into_split();
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+        let mut data = String::new();
+        let mut in_data = false;
+        while let Some(line) = lines.next_line().await.unwrap() {
+            if in_data {
+                if line == "." {
+                    in_data = false;
+                    write.write_all(b"250 queued\r\n").await.unwrap();
+                } else {
+                    data.push_str(&line);
+                    data.push('\n');
+                }
+                continue;
+            }
+            let reply: &[u8] = match line.split_whitespace().next().unwrap_or("").to_uppercase().as_str() {
+                "EHLO" | "HELO" => b"250 localhost\r\n",
+                "DATA" => {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                }
+                "QUIT" => {
+                    write.write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                }
+                _ => b"250 ok\r\n",
+            };
+            write.write_all(reply).await.unwrap();
+        }
+        tx.send(data).ok();
+    });
+    (port, rx)
+}
+
+#[tokio::test]
+async fn test_email_channel_sends_through_smtp() {
+    let (port, received) = smtp_stand_in().await;
+    let config = SmtpConfig {
+        host: Some("127.0.0.1".to_string()),
+        port,
+        tls: SmtpTls::None,
+        from: "Bot <bot@example.com>".to_string(),
+        ..SmtpConfig::default()
+    };
+    let channel = EmailChannel::new(&config, Duration::from_secs(5)).unwrap();
+    let notifier = Notifier::default().with_channel(ChannelKind::Email, channel);
+
+    notifier.send(ChannelKind::Email, "alice@example.com", &message()).await.unwrap();
+    drop(notifier);
+
+    let data = tokio::time::timeout(Duration::from_secs(5), received).await.unwrap().unwrap();
+    assert!(data.contains("To: alice@example.com"), "{}", data);
+    assert!(data.contains("Subject: EuroMillions results for 2025-03-21"), "{}", data);
+    assert!(data.contains("7 20 32 41 48"), "{}", data);
+}
+
+#[tokio::test]
+async fn test_announce_follows_preferences() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    let date = draw().date;
+    storage
+        .upsert_draw(NewDraw { game: "euromillions".to_string(), date, numbers: draw().numbers, stars: draw().stars })
+        .await
+        .unwrap();
+    let winner = storage.create_user("winner").await.unwrap();
+    let loser = storage.create_user("loser").await.unwrap();
+    let watcher = storage.create_user("watcher").await.unwrap();
+    let grids = storage
+        .insert_grids(&[
+            NewGrid { game: "euromillions".to_string(), draw_date: date, numbers: vec![7, 20, 32, 1, 2], stars: vec![1, 2], user_id: Some(winner.id), cost_cents: None, addons: Vec::new() },
+            NewGrid { game: "euromillions".to_string(), draw_date: date, numbers: vec![1, 2, 3, 4, 5], stars: vec![1, 2], user_id: Some(loser.id), cost_cents: None, addons: Vec::new() },
+            // Generated but never played: no win for the loser
+            NewGrid { game: "euromillions".to_string(), draw_date: date, numbers: vec![7, 20, 32, 41, 48], stars: vec![3, 11], user_id: Some(loser.id), cost_cents: None, addons: Vec::new() },
+        ])
+        .await
+        .unwrap();
+    storage.set_grid_status(&[grids[0].id, grids[1].id], &["generated"], "played").await.unwrap();
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/winner"))
+        .and(body_partial_json(serde_json::json!({ "grids": [{ "tier": 10 }] })))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/watcher"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let wins_only = |user_id, kind: &str, target: String, notify_results| NewNotificationChannel {
+        user_id,
+        kind: kind.to_string(),
+        target,
+        notify_results,
+        notify_wins: true,
+    };
+    for channel in [
+        wins_only(winner.id, "webhook", format!("{}/winner", server.uri()), false),
+        wins_only(loser.id, "webhook", format!("{}/loser", server.uri()), false),
+        wins_only(watcher.id, "webhook", format!("{}/watcher", server.uri()), true),
+        // Email is not configured on this notifier
+        wins_only(watcher.id, "email", "watcher@example.com".to_string(), true),
+    ] {
+        storage.add_notification_channel(&channel).await.unwrap();
+    }
+
+    let notifier = Notifier::default().with_channel(ChannelKind::Webhook, WebhookChannel::new(reqwest::Client::new()));
+    let summary = notify::announce(&storage, &notifier, &storage.get_draw("euromillions", date).await.unwrap().unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(summary, NotifySummary { sent: 2, skipped: 1, failed: 1 });
+}
+
+#[tokio::test]
+async fn test_every_new_draw_is_checked_and_the_latest_announced() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    let user = storage.create_user("alice").await.unwrap();
+    let line = |day| NewGrid {
+        game: "euromillions".to_string(),
+        draw_date: NaiveDate::from_ymd_opt(2025, 3, day).unwrap(),
+        numbers: vec![1, 2, 3, 4, 5],
+        stars: vec![1, 2],
+        user_id: Some(user.id),
+        cost_cents: None,
+        addons: Vec::new(),
+    };
+    let grids = storage.insert_grids(&[line(18), line(21)]).await.unwrap();
+    let ids: Vec<i32> = grids.iter().map(|g| g.id).collect();
+    storage.set_grid_status(&ids, &["generated"], "played").await.unwrap();
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(serde_json::json!({ "draw": { "date": "2025-03-21" } })))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+    storage
+        .add_notification_channel(&NewNotificationChannel {
+            user_id: user.id,
+            kind: "webhook".to_string(),
+            target: server.uri(),
+            notify_results: true,
+            notify_wins: true,
+        })
+        .await
+        .unwrap();
+
+    // Both draws of the page are new in one fetch
+    let source = FakeSource::default().with_page(2025, common::fixture("results-history-2025.html"));
+    let summary = ingest::run(&storage, &source, &[2025], FetchTrigger::Cli).await.unwrap();
+    assert_eq!(summary.new_draws.len(), 2);
+
+    let notifier = Notifier::default().with_channel(ChannelKind::Webhook, WebhookChannel::new(reqwest::Client::new()));
+    notify::announce_new_draws(&storage, &notifier, &summary).await;
+    let statuses: Vec<String> = storage.get_grids(&ids).await.unwrap().into_iter().map(|g| g.status).collect();
+    assert_eq!(statuses, vec!["checked", "checked"]);
+}
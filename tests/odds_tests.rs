@@ -0,0 +1,127 @@
+use chrono::NaiveDate;
+use euromillions_bot::games::{EURODREAMS, EUROMILLIONS, LOTO, UK_LOTTO};
+use euromillions_bot::models::{NewDraw, NewDrawPrize, TicketPrice};
+use euromillions_bot::odds::{self, expected_value, parse_amount, JackpotSource};
+use euromillions_bot::storage::{SqliteStorage, Storage};
+
+fn date(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2025, 3, day).unwrap()
+}
+
+fn one_in(odds: &odds::Odds, tier: u8) -> f64 {
+    odds.tiers.iter().find(|t| t.tier == tier).unwrap().one_in
+}
+
+#[test]
+fn test_euromillions_odds() {
+    let odds = odds::odds(&EUROMILLIONS);
+    assert_eq!(odds.combinations, 139_838_160);
+    assert_eq!(odds.tiers.len(), 13);
+    assert_eq!(odds.tiers[0].combinations, 1);
+    assert_eq!(odds.tiers[1].combinations, 20);
+    assert_eq!(one_in(&odds, 13).round(), 22.0);
+    assert_eq!((1.0 / odds.any_prize).round(), 13.0);
+}
+
+#[test]
+fn test_other_games_odds() {
+    let lotto = odds::odds(&UK_LOTTO);
+    assert_eq!(one_in(&lotto, 1), 45_057_474.0);
+    assert_eq!(one_in(&lotto, 2).round(), 7_509_579.0);
+    assert_eq!(one_in(&lotto, 5).round(), 96.0);
+
+    // Tier 9 is won with 1 + chance or the chance number alone
+    let loto = odds::odds(&LOTO);
+    assert_eq!(one_in(&loto, 1), 19_068_840.0);
+    assert_eq!(loto.tiers.len(), 9);
+    assert_eq!(loto.tiers[8].combinations, 5 * 135_751 + 1_086_008);
+
+    let dreams = odds::odds(&EURODREAMS);
+    assert_eq!(one_in(&dreams, 1), 19_191_900.0);
+    assert!(dreams.any_prize < 1.0);
+}
+
+#[test]
+fn test_parse_amount() {
+    assert_eq!(parse_amount("€17,000,000"), Some(1_700_000_000));
+    assert_eq!(parse_amount(" £2,500,000.50 "), Some(250_000_050));
+    assert_eq!(parse_amount("Rollover"), None);
+    assert_eq!(parse_amount("€1.5"), None);
+}
+
+async fn storage_with_jackpot(amount_cents: i64, winners: Option<i32>) -> SqliteStorage {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    let draw = NewDraw {
+        game: "euromillions".to_string(),
+        date: date(21),
+        numbers: vec![7, 20, 32, 41, 48],
+        stars: vec![3, 11],
+    };
+    storage.upsert_draw(draw).await.unwrap();
+    let id = storage.get_draw("euromillions", date(21)).await.unwrap().unwrap().id;
+    let prizes = [
+        NewDrawPrize { tier: 1, amount_cents, winners },
+        NewDrawPrize { tier: 2, amount_cents: 50_000_000, winners: Some(4) },
+        NewDrawPrize { tier: 13, amount_cents: 450, winners: None },
+    ];
+    storage.upsert_draw_prizes(id, &prizes).await.unwrap();
+    storage
+}
+
+#[tokio::test]
+async fn test_expected_value_rolls_jackpot_over() {
+    let storage = storage_with_jackpot(1_700_000_000, None).await;
+
+    let stored = expected_value(&storage, &EUROMILLIONS, date(21)).await.unwrap();
+    assert_eq!(stored.jackpot.as_ref().unwrap().source, JackpotSource::Stored);
+
+    let value = expected_value(&storage, &EUROMILLIONS, date(28)).await.unwrap();
+    let jackpot = value.jackpot.as_ref().unwrap();
+    assert_eq!(jackpot.source, JackpotSource::Projected);
+    assert_eq!((jackpot.based_on, jackpot.rollovers), (Some(date(21)), 2));
+    assert_eq!(jackpot.amount_cents, 3_700_000_000);
+    assert_eq!(value.missing_tiers, (3..=12).collect::<Vec<u8>>());
+
+    let odds = odds::odds(&EUROMILLIONS);
+    let expected = 3_700_000_000.0 * odds.tiers[0].probability
+        + 50_000_000.0 * odds.tiers[1].probability
+        + 450.0 * odds.tiers[12].probability;
+    assert!((value.expected_cents - expected).abs() < 1e-9);
+    assert!((value.net_cents - (expected - 250.0)).abs() < 1e-9);
+
+    // Not a EuroMillions draw day
+    assert!(expected_value(&storage, &EUROMILLIONS, date(22)).await.is_err());
+
+    // A new price applies from its draw on
+    let price = TicketPrice { game: "euromillions".to_string(), valid_from: date(25), price_cents: 300 };
+    storage.set_ticket_price(&price).await.unwrap();
+    let before = expected_value(&storage, &EUROMILLIONS, date(21)).await.unwrap();
+    assert_eq!(before.price_cents, 250);
+    let value = expected_value(&storage, &EUROMILLIONS, date(28)).await.unwrap();
+    assert_eq!(value.price_cents, 300);
+    assert!((value.net_cents - (expected - 300.0)).abs() < 1e-9);
+    assert!((value.return_ratio - expected / 300.0).abs() < 1e-12);
+}
+
+#[tokio::test]
+async fn test_expected_value_after_a_win_and_at_the_cap() {
+    let won = storage_with_jackpot(9_000_000_000, Some(1)).await;
+    let jackpot = expected_value(&won, &EUROMILLIONS, date(25)).await.unwrap().jackpot.unwrap();
+    assert_eq!((jackpot.amount_cents, jackpot.rollovers), (1_700_000_000, 0));
+
+    let capped = storage_with_jackpot(24_500_000_000, Some(0)).await;
+    let value = expected_value(&capped, &EUROMILLIONS, date(25)).await.unwrap();
+    let jackpot = value.jackpot.as_ref().unwrap();
+    assert!(jackpot.capped);
+    assert_eq!((jackpot.amount_cents, jackpot.rolldown_cents), (25_000_000_000, 500_000_000));
+    // The rolldown is shared by the usual number of tier 2 winners
+    assert_eq!(value.tiers[1].payout_cents, Some(50_000_000.0 + 500_000_000.0 / 4.0));
+
+    let empty = SqliteStorage::in_memory().await.unwrap();
+    let value = expected_value(&empty, &EUROMILLIONS, date(25)).await.unwrap();
+    assert_eq!(value.jackpot.unwrap().source, JackpotSource::Minimum);
+    assert_eq!(value.missing_tiers.len(), 12);
+    let dreams = expected_value(&empty, &EURODREAMS, date(24)).await.unwrap();
+    assert!(dreams.jackpot.is_none());
+    assert_eq!(dreams.expected_cents, 0.0);
+}
@@ -0,0 +1,69 @@
+use euromillions_bot::outbound::{check_url, client_builder, is_private_ip};
+use std::net::IpAddr;
+use wiremock::matchers::method;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[test]
+fn test_public_https_urls_are_accepted() {
+    let url = check_url("https://hooks.slack.com/services/T/B/X", false).unwrap();
+    assert_eq!(url.host_str(), Some("hooks.slack.com"));
+    assert!(check_url("https://93.184.216.34/hook", false).is_ok());
+    assert!(check_url("https://[2606:2800:220:1:248:1893:25c8:1946]/hook", false).is_ok());
+}
+
+#[test]
+fn test_internal_urls_are_refused() {
+    for url in [
+        "http://example.com/hook",
+        "ftp://example.com/hook",
+        "not a url",
+        "https://localhost/hook",
+        "https://api.localhost/hook",
+        "https://db/hook",
+        "https://metadata.google.internal/computeMetadata/v1/",
+        "https://printer.local/",
+        "https://127.0.0.1/hook",
+        "https://2130706433/hook",
+        "https://10.0.0.5/hook",
+        "https://172.16.3.4/hook",
+        "https://192.168.1.1/hook",
+        "https://169.254.169.254/latest/meta-data/",
+        "https://100.64.0.1/hook",
+        "https://0.0.0.0/hook",
+        "https://[::1]/hook",
+        "https://[fd00::1]/hook",
+        "https://[fe80::1]/hook",
+        "https://[::ffff:127.0.0.1]/hook",
+    ] {
+        assert!(check_url(url, false).is_err(), "{} should be refused", url);
+    }
+}
+
+#[test]
+fn test_private_urls_can_be_allowed() {
+    assert!(check_url("http://127.0.0.1:8080/hook", true).is_ok());
+    assert!(check_url("http://localhost/hook", true).is_ok());
+    assert!(check_url("ftp://127.0.0.1/hook", true).is_err());
+}
+
+#[test]
+fn test_private_ips() {
+    let private = |ip: &str| is_private_ip(ip.parse::<IpAddr>().unwrap());
+    assert!(private("127.0.0.1"));
+    assert!(private("192.168.0.10"));
+    assert!(private("::ffff:10.1.2.3"));
+    assert!(!private("8.8.8.8"));
+    assert!(!private("2001:4860:4860::8888"));
+}
+
+#[tokio::test]
+async fn test_names_resolving_to_private_addresses_are_refused() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST")).respond_with(ResponseTemplate::new(204)).expect(1).mount(&server).await;
+    let url = format!("http://localhost:{}/hook", server.address().port());
+
+    let refused = client_builder(false).build().unwrap().post(&url).send().await;
+    assert!(refused.unwrap_err().is_connect());
+    let allowed = client_builder(true).build().unwrap().post(&url).send().await;
+    assert_eq!(allowed.unwrap().status(), 204);
+}
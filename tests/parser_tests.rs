@@ -0,0 +1,115 @@
+use euromillions_bot::parser::{parse_draw_date, parse_results_page, RejectReason};
+use euromillions_bot::raffle::RaffleKind;
+use chrono::NaiveDate;
+
+fn fixture(name: &str) -> String {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e))
+}
+
+#[test]
+fn test_results_page_snapshot() {
+    let report = parse_results_page(&fixture("results-history-2025.html"));
+    let expected: serde_json::Value =
+        serde_json::from_str(&fixture("results-history-2025.expected.json")).unwrap();
+
+    let actual = serde_json::json!({
+        "draws": report.draws,
+        "report": report,
+    });
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_results_page_counts() {
+    let report = parse_results_page(&fixture("results-history-2025.html"));
+
+    assert_eq!(report.rows_seen, 5);
+    assert_eq!(report.rows_parsed, 2);
+    assert_eq!(report.rejected.len(), 3);
+    assert!(!report.markup_changed());
+    assert_eq!(
+        report.rejected[0].reason,
+        RejectReason::WrongBallCount { numbers: 4, stars: 0 }
+    );
+}
+
+#[test]
+fn test_results_page_raffles() {
+    let report = parse_results_page(&fixture("results-history-2025.html"));
+    let raffles: Vec<(String, RaffleKind, &str)> = report
+        .raffles
+        .iter()
+        .map(|r| (r.draw_date.to_string(), r.kind, r.code.as_str()))
+        .collect();
+
+    assert_eq!(
+        raffles,
+        vec![
+            ("2025-03-21".to_string(), RaffleKind::MyMillion, "HZ6524871"),
+            ("2025-03-21".to_string(), RaffleKind::MillionaireMaker, "XBKL53681"),
+            ("2025-03-18".to_string(), RaffleKind::MyMillion, "HZ6524871"),
+        ]
+    );
+}
+
+#[test]
+fn test_results_page_jackpots() {
+    let report = parse_results_page(&fixture("results-history-2025.html"));
+    let jackpots: Vec<(String, i64)> = report
+        .jackpots
+        .iter()
+        .map(|j| (j.draw_date.to_string(), j.amount_cents))
+        .collect();
+
+    assert_eq!(
+        jackpots,
+        vec![("2025-03-21".to_string(), 1_700_000_000), ("2025-03-18".to_string(), 9_600_000_000)]
+    );
+}
+
+#[test]
+fn test_redesigned_page_is_flagged() {
+    let report = parse_results_page(&fixture("results-history-redesigned.html"));
+
+    assert_eq!(report.rows_seen, 0);
+    assert!(report.has_content);
+    assert!(report.markup_changed());
+}
+
+#[test]
+fn test_empty_page_is_not_flagged() {
+    let report = parse_results_page("<html><body>  </body></html>");
+
+    assert!(!report.has_content);
+    assert!(!report.markup_changed());
+}
+
+#[test]
+fn test_empty_results_table_is_not_flagged() {
+    let report = parse_results_page(
+        r#"<html><body>
+          <h1>EuroMillions Results for 2026</h1>
+          <table class="resultsHistory mobFormat">
+            <thead><tr><th>Draw Date</th><th>Results</th></tr></thead>
+            <tbody></tbody>
+          </table>
+        </body></html>"#,
+    );
+
+    assert!(report.has_content);
+    assert!(report.has_results_table);
+    assert_eq!(report.rows_seen, 0);
+    assert!(!report.markup_changed());
+}
+
+#[test]
+fn test_parse_draw_date() {
+    assert_eq!(
+        parse_draw_date("Tuesday\n            18th March 2025"),
+        NaiveDate::from_ymd_opt(2025, 3, 18)
+    );
+    assert_eq!(parse_draw_date("Friday 1st August 2025"), NaiveDate::from_ymd_opt(2025, 8, 1));
+    assert_eq!(parse_draw_date("18th March 2025"), None);
+    assert_eq!(parse_draw_date("Tuesday 31st February 2025"), None);
+}
@@ -0,0 +1,34 @@
+use euromillions_bot::games::{EUROMILLIONS, UK_LOTTO};
+use euromillions_bot::popularity::score;
+
+#[test]
+fn test_unpopular_line_scores_full_marks() {
+    let line = score(&EUROMILLIONS, &[7, 24, 36, 43, 49]);
+    assert_eq!((line.birthdays, line.longest_sequence, line.slip_line), (2, 2, 2));
+    assert_eq!(line.score, 100);
+}
+
+#[test]
+fn test_popular_patterns_are_penalized() {
+    // A row of the slip that is also a sequence of birthdays
+    assert_eq!(score(&EUROMILLIONS, &[1, 2, 3, 4, 5]).score, 0);
+
+    let birthdays = score(&EUROMILLIONS, &[3, 9, 14, 22, 28]);
+    assert_eq!((birthdays.birthdays, birthdays.score), (5, 60));
+
+    // A column of the slip, every ten
+    let column = score(&EUROMILLIONS, &[43, 3, 23, 13, 33]);
+    assert_eq!((column.longest_sequence, column.slip_line), (5, 5));
+    assert_eq!(column.score, 40);
+
+    let three_in_a_row = score(&EUROMILLIONS, &[7, 34, 41, 48, 50]);
+    assert_eq!(three_in_a_row.longest_sequence, 3);
+    assert!(three_in_a_row.score < 100 && three_in_a_row.score > 60);
+}
+
+#[test]
+fn test_score_uses_game_range() {
+    // Three birthdays are about what a random UK Lotto line holds
+    assert_eq!(score(&UK_LOTTO, &[2, 17, 30, 39, 45, 56]).score, 100);
+    assert!(score(&UK_LOTTO, &[1, 7, 12, 19, 25, 31]).score < 100);
+}
@@ -0,0 +1,26 @@
+use euromillions_bot::raffle::RaffleKind;
+
+#[test]
+fn test_detect_codes() {
+    assert_eq!(
+        RaffleKind::detect(" hz 652 4871 "),
+        Some((RaffleKind::MyMillion, "HZ6524871".to_string()))
+    );
+    assert_eq!(
+        RaffleKind::detect("XBKL 53681"),
+        Some((RaffleKind::MillionaireMaker, "XBKL53681".to_string()))
+    );
+    // Wrong length, letter count or stray characters
+    assert_eq!(RaffleKind::detect("HZ652487"), None);
+    assert_eq!(RaffleKind::detect("HZX652487"), None);
+    assert_eq!(RaffleKind::detect("HZ65248-1"), None);
+    assert_eq!(RaffleKind::detect("ÉZ6524871"), None);
+}
+
+#[test]
+fn test_kind_names() {
+    for kind in RaffleKind::ALL {
+        assert_eq!(kind.as_str().parse::<RaffleKind>().unwrap(), kind);
+    }
+    assert!("euromillions".parse::<RaffleKind>().is_err());
+}
@@ -0,0 +1,26 @@
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc, Weekday};
+use euromillions_bot::status::{is_stale, next_run};
+
+fn at(text: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(text).unwrap().with_timezone(&Utc)
+}
+
+#[test]
+fn test_next_run_follows_schedule() {
+    // Monday 2025-03-17 noon -> Wednesday 23:00
+    let next = next_run("0 0 23 * * Wed,Sat", at("2025-03-17T12:00:00Z")).unwrap();
+    assert_eq!(next.weekday(), Weekday::Wed);
+    assert_eq!(next.day(), 19);
+    assert_eq!(next.hour(), 23);
+
+    assert!(next_run("not a schedule", at("2025-03-17T12:00:00Z")).is_none());
+}
+
+#[test]
+fn test_is_stale() {
+    let friday = NaiveDate::from_ymd_opt(2025, 3, 21);
+
+    assert!(is_stale(None, at("2025-03-22T00:00:00Z"), 120));
+    assert!(!is_stale(friday, at("2025-03-25T12:00:00Z"), 120));
+    assert!(is_stale(friday, at("2025-03-26T01:00:00Z"), 120));
+}
@@ -0,0 +1,693 @@
+use chrono::NaiveDate;
+use euromillions_bot::archive;
+use euromillions_bot::raffle;
+use euromillions_bot::fetcher::FetchedPage;
+use euromillions_bot::models::{
+    BallFrequency, Budget, DeliveryAttempt, FinishedFetchRun, GridPlay, NewArchivedPage, NewDraw, NewDrawPrize,
+    NewFetchRun, NewGenerationBatch, NewGenerationProfile, NewGrid, NewNotificationChannel, NewRaffleCode, NewWebhook,
+    SpendingCap, TicketAddon, TicketPrice,
+};
+use euromillions_bot::storage::{self, SqliteStorage, Storage};
+
+fn draw(day: u32, numbers: [i32; 5], stars: [i32; 2]) -> NewDraw {
+    NewDraw {
+        game: "euromillions".to_string(),
+        date: NaiveDate::from_ymd_opt(2025, 3, day).unwrap(),
+        numbers: numbers.to_vec(),
+        stars: stars.to_vec(),
+    }
+}
+
+fn page(url: &str, body: &str) -> NewArchivedPage {
+    let fetched = FetchedPage {
+        url: url.to_string(),
+        status: 200,
+        etag: Some("\"v1\"".to_string()),
+        last_modified: None,
+        not_modified: false,
+        body: body.to_string(),
+    };
+    NewArchivedPage::from_fetched(&fetched, Some(2025)).unwrap()
+}
+
+async fn check_draws(storage: &dyn Storage) {
+    assert!(storage.upsert_draw(draw(18, [3, 12, 19, 27, 44], [2, 9])).await.unwrap());
+    assert!(!storage.upsert_draw(draw(18, [1, 2, 3, 4, 5], [1, 2])).await.unwrap());
+    assert!(storage.upsert_draw(draw(21, [3, 20, 32, 41, 48], [2, 11])).await.unwrap());
+
+    assert_eq!(storage.get_most_frequent_numbers("euromillions", 1).await.unwrap(), vec![3]);
+    assert_eq!(storage.get_most_frequent_stars("euromillions", 1).await.unwrap(), vec![2]);
+    assert_eq!(storage.get_most_frequent_numbers("euromillions", 50).await.unwrap().len(), 9);
+
+    // replace_draw only reports rows it actually changed
+    assert!(!storage.replace_draw(draw(18, [3, 12, 19, 27, 44], [2, 9])).await.unwrap());
+    assert!(storage.replace_draw(draw(18, [4, 12, 19, 27, 44], [2, 9])).await.unwrap());
+    assert_eq!(storage.get_most_frequent_numbers("euromillions", 50).await.unwrap().len(), 10);
+
+    let draws = storage.list_draws().await.unwrap();
+    assert_eq!(draws.len(), 2);
+    assert_eq!(draws[0].numbers, vec![4, 12, 19, 27, 44]);
+    assert_eq!(storage.count_draws("euromillions").await.unwrap(), 2);
+    assert_eq!(storage.latest_draw("euromillions").await.unwrap().unwrap().date, draws[1].date);
+    assert_eq!(storage.get_draw("euromillions", draws[0].date).await.unwrap(), Some(draws[0].clone()));
+    assert!(storage.get_draw("euromillions", NaiveDate::from_ymd_opt(2025, 3, 1).unwrap()).await.unwrap().is_none());
+
+    let stars = storage.star_frequencies("euromillions").await.unwrap();
+    assert_eq!(stars[0], BallFrequency { ball: 2, count: 2 });
+    assert_eq!(stars.len(), 3);
+    assert_eq!(storage.number_frequencies("euromillions").await.unwrap().len(), 10);
+}
+
+async fn check_games(storage: &dyn Storage) {
+    let date = NaiveDate::from_ymd_opt(2025, 4, 5).unwrap();
+    let lotto = NewDraw {
+        game: "uk_lotto".to_string(),
+        date,
+        numbers: vec![1, 2, 3, 4, 5, 59],
+        stars: vec![30],
+    };
+    let euromillions = NewDraw { date, ..draw(1, [1, 2, 3, 4, 5], [1, 2]) };
+    // The same date is unique per game only
+    assert!(storage.upsert_draw(lotto).await.unwrap());
+    assert!(storage.upsert_draw(euromillions).await.unwrap());
+
+    assert_eq!(storage.count_draws("uk_lotto").await.unwrap(), 1);
+    assert_eq!(storage.count_draws("loto").await.unwrap(), 0);
+    let latest = storage.latest_draw("uk_lotto").await.unwrap().unwrap();
+    assert_eq!((latest.game.as_str(), latest.stars.as_slice()), ("uk_lotto", &[30][..]));
+    assert_eq!(storage.get_draw("euromillions", date).await.unwrap().unwrap().numbers, vec![1, 2, 3, 4, 5]);
+    assert_eq!(storage.get_most_frequent_numbers("uk_lotto", 10).await.unwrap().len(), 6);
+    assert!(storage.star_frequencies("uk_lotto").await.unwrap().iter().all(|f| f.ball == 30));
+
+    let grid = |game: &str, stars: Vec<i32>| NewGrid {
+        game: game.to_string(),
+        draw_date: date,
+        numbers: vec![1, 2, 3, 4, 5, 6],
+        stars,
+        user_id: None,
+        cost_cents: None,
+        addons: Vec::new(),
+    };
+    let saved = storage.insert_grids(&[grid("uk_lotto", vec![]), grid("eurodreams", vec![3])]).await.unwrap();
+    assert_eq!(saved[0].game, "uk_lotto");
+    let played = storage.grids_for_draw("uk_lotto", date).await.unwrap();
+    assert_eq!(played.iter().map(|g| g.id).collect::<Vec<_>>(), vec![saved[0].id]);
+}
+
+async fn check_raffles(storage: &dyn Storage) {
+    let date = NaiveDate::from_ymd_opt(2025, 5, 2).unwrap();
+    assert!(storage.upsert_draw(NewDraw { date, ..draw(1, [8, 16, 24, 32, 40], [4, 8]) }).await.unwrap());
+    let draw_id = storage.get_draw("euromillions", date).await.unwrap().unwrap().id;
+
+    let code = |kind: &str, code: &str| NewRaffleCode { kind: kind.to_string(), code: code.to_string() };
+    let codes = [code("my_million", "AB1234567"), code("millionaire_maker", "WXYZ12345")];
+    assert_eq!(storage.add_raffle_codes(draw_id, &codes).await.unwrap(), 2);
+    // Codes already stored for the draw are skipped
+    assert_eq!(storage.add_raffle_codes(draw_id, &codes).await.unwrap(), 0);
+
+    let stored = storage.raffle_codes(draw_id).await.unwrap();
+    assert_eq!(stored.len(), 2);
+    assert_eq!((stored[0].kind.as_str(), stored[0].draw_date), ("millionaire_maker", date));
+    let found = storage.find_raffle_code("my_million", "AB1234567").await.unwrap();
+    assert_eq!(found.iter().map(|c| c.draw_id).collect::<Vec<_>>(), vec![draw_id]);
+    assert!(storage.find_raffle_code("millionaire_maker", "AB1234567").await.unwrap().is_empty());
+
+    let check = raffle::check(storage, "ab 123 4567", None).await.unwrap();
+    assert!(check.won);
+    assert_eq!(check.draws, vec![date]);
+    assert!(!raffle::check(storage, "AB1234567", date.succ_opt()).await.unwrap().won);
+}
+
+async fn check_prizes(storage: &dyn Storage) {
+    let date = NaiveDate::from_ymd_opt(2025, 5, 6).unwrap();
+    assert!(storage.upsert_draw(NewDraw { date, ..draw(1, [9, 18, 27, 36, 45], [5, 10]) }).await.unwrap());
+    let draw_id = storage.get_draw("euromillions", date).await.unwrap().unwrap().id;
+
+    let prize = |tier: i32, amount_cents: i64, winners: Option<i32>| NewDrawPrize { tier, amount_cents, winners };
+    let written = storage
+        .upsert_draw_prizes(draw_id, &[prize(1, 1_700_000_000, Some(0)), prize(13, 450, Some(90_000))])
+        .await
+        .unwrap();
+    assert_eq!(written, 2);
+    // A scraped jackpot updates the amount but keeps the winner count
+    storage.upsert_draw_prizes(draw_id, &[prize(1, 1_800_000_000, None)]).await.unwrap();
+
+    let prizes: Vec<_> = storage
+        .list_draw_prizes("euromillions")
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|p| p.draw_id == draw_id)
+        .collect();
+    assert_eq!(prizes.len(), 2);
+    assert_eq!((prizes[0].tier, prizes[0].amount_cents, prizes[0].winners), (1, 1_800_000_000, Some(0)));
+    assert_eq!((prizes[1].draw_date, prizes[1].winners), (date, Some(90_000)));
+    assert!(storage.list_draw_prizes("loto").await.unwrap().is_empty());
+}
+
+async fn check_grids(storage: &dyn Storage) {
+    let date = NaiveDate::from_ymd_opt(2025, 3, 25).unwrap();
+    let grids = vec![
+        NewGrid { game: "euromillions".to_string(), draw_date: date, numbers: vec![1, 2, 3, 4, 5], stars: vec![1, 2], user_id: None, cost_cents: None, addons: Vec::new() },
+        NewGrid { game: "euromillions".to_string(), draw_date: date, numbers: vec![6, 7, 8, 9, 10], stars: vec![3, 4], user_id: None, cost_cents: None, addons: Vec::new() },
+    ];
+
+    let saved = storage.insert_grids(&grids).await.unwrap();
+    assert_eq!(saved.len(), 2);
+    assert!(saved[0].id < saved[1].id);
+    assert_eq!(saved[1].numbers, vec![6, 7, 8, 9, 10]);
+    assert!(saved[0].created_at.is_some());
+
+    let recent = storage.recent_grids(20, None).await.unwrap();
+    assert_eq!(recent.len(), 2);
+    assert_eq!(recent[0].id, saved[1].id);
+
+    assert_eq!((saved[0].status.as_str(), saved[0].played_at), ("generated", None));
+    let ids = [saved[0].id, saved[1].id];
+    let fetched = storage.get_grids(&[ids[1], ids[0], ids[1] + 100]).await.unwrap();
+    assert_eq!(fetched.iter().map(|g| g.id).collect::<Vec<_>>(), ids);
+
+    let played = storage.set_grid_status(&ids[..1], &["generated"], "played").await.unwrap();
+    assert_eq!((played.len(), played[0].status.as_str()), (1, "played"));
+    assert!(played[0].played_at.is_some());
+    // Only grids in one of the `from` statuses move
+    assert!(storage.set_grid_status(&ids, &["checked"], "archived").await.unwrap().is_empty());
+    let checked = storage.set_grid_status(&ids, &["played"], "checked").await.unwrap();
+    assert_eq!((checked.len(), checked[0].played_at), (1, played[0].played_at));
+    storage.set_grid_status(&ids[1..], &["generated"], "played").await.unwrap();
+    let unplayed = storage.set_grid_status(&ids[1..], &["played"], "generated").await.unwrap();
+    assert_eq!((unplayed[0].status.as_str(), unplayed[0].played_at), ("generated", None));
+
+    let recent = storage.recent_grids(20, Some("checked")).await.unwrap();
+    assert_eq!(recent.iter().map(|g| g.id).collect::<Vec<_>>(), vec![ids[0]]);
+}
+
+async fn check_batches(storage: &dyn Storage) {
+    let date = NaiveDate::from_ymd_opt(2025, 4, 1).unwrap();
+    let grid = |n| NewGrid { game: "loto".to_string(), draw_date: date, numbers: vec![n, 2, 3, 4, 5], stars: vec![1], user_id: None, cost_cents: Some(220), addons: Vec::new() };
+    let batch = |key: Option<&str>| NewGenerationBatch {
+        idempotency_key: key.map(str::to_string),
+        game: "loto".to_string(),
+        user_id: None,
+        strategy: "random".to_string(),
+        params: r#"{"count":2}"#.to_string(),
+    };
+
+    let saved = storage.insert_generation_batch(&batch(Some("key-1")), &[grid(10), grid(11)]).await.unwrap().unwrap();
+    assert_eq!(saved.len(), 2);
+    let batch_id = saved[0].batch_id.unwrap();
+    assert_eq!(saved[1].batch_id, Some(batch_id));
+    let stored = storage.get_generation_batch("key-1").await.unwrap().unwrap();
+    assert_eq!((stored.id, stored.game.as_str(), stored.params.as_str()), (batch_id, "loto", r#"{"count":2}"#));
+    assert!(storage.get_generation_batch("key-2").await.unwrap().is_none());
+
+    // A taken key stores nothing
+    assert!(storage.insert_generation_batch(&batch(Some("key-1")), &[grid(12)]).await.unwrap().is_none());
+    let grids = storage.batch_grids(batch_id).await.unwrap();
+    assert_eq!(grids.iter().map(|g| g.numbers[0]).collect::<Vec<_>>(), vec![10, 11]);
+    // Batches without a key never conflict
+    for n in [13, 14] {
+        assert!(storage.insert_generation_batch(&batch(None), &[grid(n)]).await.unwrap().is_some());
+    }
+}
+
+async fn check_users(storage: &dyn Storage) {
+    let alice = storage.create_user("alice").await.unwrap();
+    let bob = storage.create_user("bob").await.unwrap();
+    assert!(storage.create_user("alice").await.is_err(), "names are unique");
+    assert_eq!(storage.get_user(alice.id).await.unwrap().unwrap().name, "alice");
+    assert!(storage.get_user(bob.id + 100).await.unwrap().is_none());
+    assert_eq!(storage.get_user_by_name("bob").await.unwrap().unwrap().id, bob.id);
+    assert!(storage.get_user_by_name("carol").await.unwrap().is_none());
+
+    let date = NaiveDate::from_ymd_opt(2025, 3, 28).unwrap();
+    let grid = |user_id| NewGrid { game: "euromillions".to_string(), draw_date: date, numbers: vec![1, 2, 3, 4, 5], stars: vec![1, 2], user_id, cost_cents: None, addons: Vec::new() };
+    storage.insert_grids(&[grid(Some(alice.id)), grid(None)]).await.unwrap();
+    let played = storage.grids_for_draw("euromillions", date).await.unwrap();
+    assert_eq!(played.iter().map(|g| g.user_id).collect::<Vec<_>>(), vec![Some(alice.id), None]);
+    assert!(storage.grids_for_draw("euromillions", date.succ_opt().unwrap()).await.unwrap().is_empty());
+
+    let channel = |user_id, kind: &str, target: &str| NewNotificationChannel {
+        user_id,
+        kind: kind.to_string(),
+        target: target.to_string(),
+        notify_results: false,
+        notify_wins: true,
+    };
+    let email = storage
+        .add_notification_channel(&channel(alice.id, "email", "alice@example.com"))
+        .await
+        .unwrap();
+    assert!(email.enabled);
+    assert!(!email.notify_results && email.notify_wins);
+    storage
+        .add_notification_channel(&channel(bob.id, "telegram", "12345"))
+        .await
+        .unwrap();
+
+    assert_eq!(storage.list_notification_channels(alice.id).await.unwrap().len(), 1);
+    assert_eq!(storage.enabled_notification_channels().await.unwrap().len(), 2);
+
+    assert!(!storage.delete_notification_channel(bob.id, email.id).await.unwrap());
+    assert!(storage.delete_notification_channel(alice.id, email.id).await.unwrap());
+    assert!(storage.list_notification_channels(alice.id).await.unwrap().is_empty());
+}
+
+async fn check_profiles(storage: &dyn Storage) {
+    let dave = storage.create_user("dave").await.unwrap();
+    assert!(storage.get_generation_profile(dave.id, "euromillions").await.unwrap().is_none());
+
+    let profile = NewGenerationProfile {
+        forced_numbers: vec![7, 23],
+        forced_stars: vec![3],
+        banned_numbers: vec![13],
+        strategy: Some("random".to_string()),
+        grid_count: Some(2),
+    };
+    let saved = storage.upsert_generation_profile(dave.id, "euromillions", &profile).await.unwrap();
+    assert_eq!((saved.user_id, saved.game.as_str()), (dave.id, "euromillions"));
+    assert_eq!((saved.forced_numbers, saved.forced_stars, saved.banned_numbers), (vec![7, 23], vec![3], vec![13]));
+    assert_eq!((saved.strategy.as_deref(), saved.grid_count), (Some("random"), Some(2)));
+
+    // Saving again replaces the whole profile
+    let replaced = storage
+        .upsert_generation_profile(dave.id, "euromillions", &NewGenerationProfile { banned_numbers: vec![1], ..Default::default() })
+        .await
+        .unwrap();
+    assert!(replaced.forced_numbers.is_empty() && replaced.strategy.is_none());
+    assert_eq!(storage.get_generation_profile(dave.id, "euromillions").await.unwrap(), Some(replaced));
+    assert!(storage.get_generation_profile(dave.id, "loto").await.unwrap().is_none());
+
+    assert!(storage.delete_generation_profile(dave.id, "euromillions").await.unwrap());
+    assert!(!storage.delete_generation_profile(dave.id, "euromillions").await.unwrap());
+}
+
+async fn check_money(storage: &dyn Storage) {
+    let price = |valid_from, price_cents| TicketPrice { game: "loto".to_string(), valid_from, price_cents };
+    let march = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+    let may = NaiveDate::from_ymd_opt(2025, 5, 1).unwrap();
+    storage.set_ticket_price(&price(may, 260)).await.unwrap();
+    storage.set_ticket_price(&price(march, 220)).await.unwrap();
+    storage.set_ticket_price(&price(may, 250)).await.unwrap();
+    assert_eq!(storage.list_ticket_prices("loto").await.unwrap(), vec![price(march, 220), price(may, 250)]);
+    assert!(storage.list_ticket_prices("euromillions").await.unwrap().is_empty());
+
+    let addon = |addon: &str, valid_from, price_cents| TicketAddon {
+        game: "loto".to_string(),
+        addon: addon.to_string(),
+        valid_from,
+        price_cents,
+    };
+    storage.set_ticket_addon(&addon("second_chance", may, 100)).await.unwrap();
+    storage.set_ticket_addon(&addon("joker", may, 150)).await.unwrap();
+    storage.set_ticket_addon(&addon("joker", march, 120)).await.unwrap();
+    storage.set_ticket_addon(&addon("joker", march, 130)).await.unwrap();
+    assert_eq!(
+        storage.list_ticket_addons("loto").await.unwrap(),
+        vec![addon("joker", march, 130), addon("joker", may, 150), addon("second_chance", may, 100)]
+    );
+    assert!(storage.list_ticket_addons("euromillions").await.unwrap().is_empty());
+
+    let erin = storage.create_user("erin").await.unwrap();
+    let day = |d| NaiveDate::from_ymd_opt(2025, 5, d).unwrap();
+    let grid = |draw_date, cost_cents| NewGrid {
+        game: "loto".to_string(),
+        draw_date,
+        numbers: vec![1, 2, 3, 4, 5],
+        stars: vec![1],
+        user_id: Some(erin.id),
+        cost_cents,
+        addons: Vec::new(),
+    };
+    let saved = storage.insert_grids(&[grid(day(12), Some(220)), grid(day(5), None), grid(day(19), Some(250))]).await.unwrap();
+    assert_eq!(saved[0].cost_cents, Some(220));
+    assert!(saved[0].addons.is_empty());
+    let joker = vec!["joker".to_string()];
+    let play = |id, cost_cents| GridPlay { id, addons: joker.clone(), cost_cents };
+    let cap = |limit_cents| SpendingCap {
+        user_id: erin.id,
+        games: vec!["loto".to_string()],
+        from: day(1),
+        to: day(31),
+        limit_cents,
+    };
+    // Over the cap, nothing is written
+    assert_eq!(storage.play_grids(&[play(saved[0].id, Some(370))], &[cap(300)]).await.unwrap(), vec![370]);
+    assert!(storage.get_grids(&[saved[0].id]).await.unwrap()[0].played_at.is_none());
+    let plays = [play(saved[0].id, Some(370)), play(saved[1].id, None)];
+    assert_eq!(storage.play_grids(&plays, &[cap(400)]).await.unwrap(), vec![370]);
+    let updated = storage.get_grids(&[saved[0].id]).await.unwrap();
+    assert_eq!((updated[0].addons.clone(), updated[0].cost_cents), (joker.clone(), Some(370)));
+    assert_eq!(updated[0].status, "played");
+    let played_at = updated[0].played_at;
+    assert!(played_at.is_some());
+    // Played lines count at their new cost and keep when they were played
+    assert_eq!(storage.play_grids(&[play(saved[0].id, Some(500))], &[cap(400)]).await.unwrap(), vec![500]);
+    assert_eq!(storage.play_grids(&[play(saved[0].id, Some(390))], &[cap(400)]).await.unwrap(), vec![390]);
+    assert_eq!(storage.get_grids(&[saved[0].id]).await.unwrap()[0].played_at, played_at);
+    let dates = |grids: Vec<euromillions_bot::models::Grid>| grids.iter().map(|g| g.draw_date).collect::<Vec<_>>();
+    assert_eq!(dates(storage.user_grids(erin.id, None, None).await.unwrap()), vec![day(5), day(12), day(19)]);
+    assert_eq!(dates(storage.user_grids(erin.id, Some(day(6)), Some(day(19))).await.unwrap()), vec![day(12), day(19)]);
+    assert_eq!(dates(storage.user_grids(erin.id, None, Some(day(11))).await.unwrap()), vec![day(5)]);
+
+    let budget = |period: &str, limit_cents| Budget {
+        user_id: erin.id,
+        period: period.to_string(),
+        currency: "EUR".to_string(),
+        limit_cents,
+    };
+    storage.set_budget(&budget("week", 1000)).await.unwrap();
+    assert_eq!(storage.set_budget(&budget("week", 1500)).await.unwrap().limit_cents, 1500);
+    storage.set_budget(&budget("month", 4000)).await.unwrap();
+    assert_eq!(storage.list_budgets(erin.id).await.unwrap(), vec![budget("month", 4000), budget("week", 1500)]);
+    assert!(storage.delete_budget(erin.id, "week", "EUR").await.unwrap());
+    assert!(!storage.delete_budget(erin.id, "week", "EUR").await.unwrap());
+    assert_eq!(storage.list_budgets(erin.id).await.unwrap().len(), 1);
+}
+
+async fn check_webhooks(storage: &dyn Storage) {
+    let webhook = |events: &[&str]| NewWebhook {
+        url: "https://example.com/hook".to_string(),
+        secret: "0123456789abcdef".to_string(),
+        events: events.iter().map(|e| e.to_string()).collect(),
+    };
+    let draws = storage.create_webhook(&webhook(&["draw.created"])).await.unwrap();
+    let everything = storage
+        .create_webhook(&webhook(&["draw.created", "fetch.failed"]))
+        .await
+        .unwrap();
+    assert_eq!(everything.events, vec!["draw.created", "fetch.failed"]);
+    assert_eq!(storage.list_webhooks().await.unwrap().len(), 2);
+    assert_eq!(storage.get_webhook(draws.id).await.unwrap().unwrap().secret, "0123456789abcdef");
+
+    // Nothing is stored for events nobody subscribed to
+    assert_eq!(storage.emit_event("grid.generated", &serde_json::json!({})).await.unwrap(), 0);
+    assert_eq!(storage.emit_event("fetch.failed", &serde_json::json!({ "run_id": 1 })).await.unwrap(), 1);
+
+    // New draws queue draw.created themselves, existing ones do not
+    assert!(storage.upsert_draw(draw(28, [1, 2, 3, 4, 5], [1, 2])).await.unwrap());
+    assert!(!storage.upsert_draw(draw(28, [1, 2, 3, 4, 5], [1, 2])).await.unwrap());
+
+    let now = chrono::Utc::now().naive_utc();
+    let lease_until = now + chrono::Duration::minutes(1);
+    let due = storage.claim_due_deliveries(now, lease_until, 10).await.unwrap();
+    assert_eq!(due.len(), 3);
+    assert_eq!(due[0].event, "fetch.failed");
+    assert_eq!(due[0].payload, r#"{"run_id":1}"#);
+    let created: serde_json::Value = serde_json::from_str(&due[1].payload).unwrap();
+    assert_eq!(created["numbers"], serde_json::json!([1, 2, 3, 4, 5]));
+    assert_eq!(due[1].url, "https://example.com/hook");
+
+    // A claimed delivery is not due again until its lease runs out
+    assert!(storage.claim_due_deliveries(now, lease_until, 10).await.unwrap().is_empty());
+
+    let retry_at = now + chrono::Duration::minutes(5);
+    storage
+        .record_delivery_attempt(
+            due[1].id,
+            &DeliveryAttempt {
+                status: "pending".to_string(),
+                response_status: Some(503),
+                error: Some("HTTP 503".to_string()),
+                next_attempt_at: Some(retry_at),
+            },
+        )
+        .await
+        .unwrap();
+    storage
+        .record_delivery_attempt(
+            due[2].id,
+            &DeliveryAttempt {
+                status: "delivered".to_string(),
+                response_status: Some(204),
+                error: None,
+                next_attempt_at: None,
+            },
+        )
+        .await
+        .unwrap();
+    // An expired claim is due again, a retry once its time comes, a delivery never
+    let claimed = storage.claim_due_deliveries(lease_until, lease_until, 10).await.unwrap();
+    assert_eq!(claimed.iter().map(|d| d.id).collect::<Vec<_>>(), vec![due[0].id]);
+    assert_eq!(claimed[0].payload, due[0].payload);
+    let due_later = storage
+        .claim_due_deliveries(retry_at, retry_at + chrono::Duration::minutes(1), 10)
+        .await
+        .unwrap();
+    assert_eq!(due_later.iter().map(|d| d.id).collect::<Vec<_>>(), vec![due[0].id, due[1].id]);
+    assert_eq!(due_later[1].attempts, 1);
+    assert!(storage.claim_due_deliveries(retry_at, retry_at, 10).await.unwrap().is_empty());
+
+    let delivered = storage.get_delivery(due[2].id).await.unwrap().unwrap();
+    assert_eq!((delivered.status.as_str(), delivered.response_status), ("delivered", Some(204)));
+    assert!(delivered.delivered_at.is_some());
+
+    let log = storage.list_deliveries(everything.id, 10).await.unwrap();
+    assert_eq!(log.len(), 2);
+    assert!(log[0].id > log[1].id, "newest first");
+
+    let replay = storage.replay_delivery(delivered.id).await.unwrap().unwrap();
+    assert_ne!(replay.id, delivered.id);
+    assert_eq!((replay.event_id, replay.status.as_str(), replay.attempts), (delivered.event_id, "pending", 0));
+    assert!(storage.replay_delivery(replay.id + 100).await.unwrap().is_none());
+
+    assert!(storage.delete_webhook(draws.id).await.unwrap());
+    assert!(!storage.delete_webhook(draws.id).await.unwrap());
+    assert_eq!(storage.list_deliveries(draws.id, 10).await.unwrap().len(), 0);
+}
+
+async fn check_archive(storage: &dyn Storage) {
+    let url = "https://www.euro-millions.com/results-history-2025";
+    let first = storage.archive_page(&page(url, "<html>v1</html>")).await.unwrap();
+    let again = storage.archive_page(&page(url, "<html>v1</html>")).await.unwrap();
+    assert_eq!(first, again, "identical content should be stored once");
+
+    let second = storage.archive_page(&page(url, "<html>v2</html>")).await.unwrap();
+    assert_ne!(first, second);
+    storage.archive_page(&page("https://example.com/other", "<html></html>")).await.unwrap();
+
+    let latest = storage.latest_archived_page(url).await.unwrap().unwrap();
+    assert_eq!(latest.id, second);
+    assert_eq!(latest.body().unwrap(), "<html>v2</html>");
+
+    let all = storage.latest_archived_pages().await.unwrap();
+    assert_eq!(all.len(), 2);
+    assert!(storage.latest_archived_page("https://example.com/none").await.unwrap().is_none());
+
+    // A 304 is served from the archived copy: nothing new is stored
+    let not_modified = FetchedPage {
+        url: url.to_string(),
+        status: 304,
+        etag: Some("\"v1\"".to_string()),
+        last_modified: None,
+        not_modified: true,
+        body: "<html>v2</html>".to_string(),
+    };
+    assert_eq!(archive::store(storage, &not_modified, Some(2025)).await.unwrap(), second);
+    let touched = storage.latest_archived_page(url).await.unwrap().unwrap();
+    assert_eq!((touched.id, touched.http_status, touched.fetch_count), (second, 200, 1));
+    assert!(touched.last_seen_at >= latest.last_seen_at);
+    assert_eq!(storage.latest_archived_pages().await.unwrap().len(), 2);
+    assert!(storage.touch_archived_page(url, &archive::content_hash("<html>v3</html>")).await.unwrap().is_none());
+}
+
+async fn check_fetch_runs(storage: &dyn Storage) {
+    let new_run = |trigger: &str| NewFetchRun {
+        trigger: trigger.to_string(),
+        source: "https://www.euro-millions.com".to_string(),
+        years: vec![2024, 2025],
+    };
+    assert!(storage.last_fetched_at().await.unwrap().is_none());
+    let first = storage.start_fetch_run(&new_run("cron")).await.unwrap();
+    let second = storage.start_fetch_run(&new_run("cli")).await.unwrap();
+
+    let running = storage.get_fetch_run(first).await.unwrap().unwrap();
+    assert_eq!(running.status, "running");
+    assert_eq!(running.years, vec![2024, 2025]);
+    assert!(running.finished_at.is_none());
+
+    storage
+        .finish_fetch_run(
+            first,
+            &FinishedFetchRun {
+                status: "partial".to_string(),
+                rows_parsed: 2,
+                rows_inserted: 1,
+                rows_rejected: 3,
+                error: Some("2024: HTTP 503".to_string()),
+            },
+        )
+        .await
+        .unwrap();
+    let finished = storage.get_fetch_run(first).await.unwrap().unwrap();
+    assert_eq!(finished.status, "partial");
+    assert_eq!((finished.rows_parsed, finished.rows_inserted, finished.rows_rejected), (2, 1, 3));
+    assert_eq!(finished.error.as_deref(), Some("2024: HTTP 503"));
+    assert!(finished.finished_at.unwrap() >= finished.started_at);
+    assert_eq!(storage.last_fetched_at().await.unwrap(), finished.finished_at);
+
+    let failed = FinishedFetchRun {
+        status: "failed".to_string(),
+        rows_parsed: 0,
+        rows_inserted: 0,
+        rows_rejected: 0,
+        error: Some("2025: markup changed".to_string()),
+    };
+    storage.finish_fetch_run(second, &failed).await.unwrap();
+    assert_eq!(storage.last_fetched_at().await.unwrap(), finished.finished_at, "failed runs do not count");
+
+    let runs = storage.list_fetch_runs(10).await.unwrap();
+    assert_eq!(runs.iter().map(|r| r.id).collect::<Vec<_>>(), vec![second, first]);
+    assert_eq!(storage.list_fetch_runs(1).await.unwrap().len(), 1);
+    assert!(storage.get_fetch_run(second + 100).await.unwrap().is_none());
+}
+
+async fn check_reparse(storage: &dyn Storage) {
+    let html = std::fs::read_to_string(format!(
+        "{}/tests/fixtures/results-history-2025.html",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap();
+    storage
+        .archive_page(&page("https://www.euro-millions.com/results-history-2025", &html))
+        .await
+        .unwrap();
+
+    let summary = archive::reparse(storage).await.unwrap();
+    assert_eq!(summary.pages.len(), 1);
+    assert_eq!(summary.pages[0].rows_parsed, 2);
+    assert_eq!(summary.draws_written, 2);
+
+    // Re-running is a no-op
+    assert_eq!(archive::reparse(storage).await.unwrap().draws_written, 0);
+}
+
+#[tokio::test]
+async fn test_sqlite_draws() {
+    check_draws(&SqliteStorage::in_memory().await.unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_games() {
+    check_games(&SqliteStorage::in_memory().await.unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_raffles() {
+    check_raffles(&SqliteStorage::in_memory().await.unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_prizes() {
+    check_prizes(&SqliteStorage::in_memory().await.unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_profiles() {
+    check_profiles(&SqliteStorage::in_memory().await.unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_money() {
+    check_money(&SqliteStorage::in_memory().await.unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_grids() {
+    check_grids(&SqliteStorage::in_memory().await.unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_batches() {
+    check_batches(&SqliteStorage::in_memory().await.unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_users() {
+    check_users(&SqliteStorage::in_memory().await.unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_webhooks() {
+    check_webhooks(&SqliteStorage::in_memory().await.unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_archive() {
+    check_archive(&SqliteStorage::in_memory().await.unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_fetch_runs() {
+    check_fetch_runs(&SqliteStorage::in_memory().await.unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_reparse() {
+    check_reparse(&SqliteStorage::in_memory().await.unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_migrations() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    assert!(storage.pending_migrations().await.unwrap().is_empty());
+    assert!(storage.last_fetched_at().await.unwrap().is_none());
+
+    let path = std::env::temp_dir().join(format!("euromillions-fresh-{}.db", std::process::id()));
+    let fresh = storage::connect(&format!("sqlite://{}", path.display()), 1).await.unwrap();
+    let bundled = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/migrations/sqlite")).unwrap().count();
+    assert_eq!(fresh.pending_migrations().await.unwrap().len(), bundled);
+    fresh.migrate().await.unwrap();
+    assert!(fresh.pending_migrations().await.unwrap().is_empty());
+    std::fs::remove_file(path).ok();
+}
+
+#[tokio::test]
+async fn test_sqlite_file_database() {
+    let path = std::env::temp_dir().join(format!("euromillions-{}.db", std::process::id()));
+    let url = format!("sqlite://{}", path.display());
+
+    let storage = storage::connect(&url, 2).await.unwrap();
+    storage.migrate().await.unwrap();
+    check_draws(storage.as_ref()).await;
+    drop(storage);
+
+    let reopened = storage::connect(&url, 2).await.unwrap();
+    reopened.migrate().await.unwrap();
+    assert_eq!(reopened.get_most_frequent_numbers("euromillions", 50).await.unwrap().len(), 10);
+    std::fs::remove_file(path).ok();
+}
+
+#[tokio::test]
+async fn test_unknown_scheme_is_rejected() {
+    assert!(storage::connect("mysql://localhost/db", 1).await.is_err());
+}
+
+/// Runs the same checks against an empty Postgres database named by
+/// `TEST_DATABASE_URL`.
+#[tokio::test]
+#[ignore] // Requires database
+async fn test_postgres_backend() {
+    let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set");
+    let storage = storage::connect(&url, 2).await.unwrap();
+    let bundled = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/migrations"))
+        .unwrap()
+        .filter(|entry| entry.as_ref().unwrap().path().is_file())
+        .count();
+    assert_eq!(storage.pending_migrations().await.unwrap().len(), bundled);
+    storage.migrate().await.unwrap();
+
+    assert!(storage.pending_migrations().await.unwrap().is_empty());
+    check_draws(storage.as_ref()).await;
+    check_grids(storage.as_ref()).await;
+    check_users(storage.as_ref()).await;
+    check_webhooks(storage.as_ref()).await;
+    check_archive(storage.as_ref()).await;
+    check_fetch_runs(storage.as_ref()).await;
+    check_games(storage.as_ref()).await;
+    check_raffles(storage.as_ref()).await;
+    check_prizes(storage.as_ref()).await;
+    check_profiles(storage.as_ref()).await;
+    check_money(storage.as_ref()).await;
+    check_batches(storage.as_ref()).await;
+}
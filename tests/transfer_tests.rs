@@ -0,0 +1,62 @@
+use euromillions_bot::games::{EUROMILLIONS, LOTO, UK_LOTTO};
+use euromillions_bot::storage::{SqliteStorage, Storage};
+use euromillions_bot::transfer::{self, Format};
+
+const CSV: &str = "date,n1,n2,n3,n4,n5,s1,s2
+2025-03-18,3,12,19,27,44,2,9
+2025-03-21,7,20,32,41,48,3,11
+";
+
+#[tokio::test]
+async fn test_csv_round_trip() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    let summary = transfer::import(&storage, CSV, Format::Csv, &EUROMILLIONS).await.unwrap();
+    assert_eq!((summary.read, summary.inserted), (2, 2));
+
+    // Dates already stored are skipped
+    let again = transfer::import(&storage, CSV, Format::Csv, &EUROMILLIONS).await.unwrap();
+    assert_eq!(again.inserted, 0);
+
+    assert_eq!(transfer::export(&storage, Format::Csv, &EUROMILLIONS).await.unwrap(), CSV);
+}
+
+#[tokio::test]
+async fn test_json_round_trip() {
+    let source = SqliteStorage::in_memory().await.unwrap();
+    transfer::import(&source, CSV, Format::Csv, &EUROMILLIONS).await.unwrap();
+    let json = transfer::export(&source, Format::Json, &EUROMILLIONS).await.unwrap();
+
+    let target = SqliteStorage::in_memory().await.unwrap();
+    let summary = transfer::import(&target, &json, Format::Json, &EUROMILLIONS).await.unwrap();
+    assert_eq!(summary.inserted, 2);
+    assert_eq!(target.list_draws().await.unwrap(), source.list_draws().await.unwrap());
+}
+
+#[tokio::test]
+async fn test_invalid_import_writes_nothing() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    let csv = format!("{}2025-03-25,1,2,3,4,51,1,2\n", CSV);
+
+    let err = transfer::import(&storage, &csv, Format::Csv, &EUROMILLIONS).await.unwrap_err();
+    assert!(err.to_string().contains("2025-03-25"), "{}", err);
+    assert_eq!(storage.count_draws("euromillions").await.unwrap(), 0);
+
+    assert!(transfer::parse_draws("2025-03-25,1,2,3\n", Format::Csv, &EUROMILLIONS).is_err());
+    assert!(transfer::parse_draws("not-a-date,1,2,3,4,5,1,2\n", Format::Csv, &EUROMILLIONS).is_err());
+}
+
+#[tokio::test]
+async fn test_import_other_game() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    let csv = "date,n1,n2,n3,n4,n5,n6,s1\n2025-03-22,4,11,23,35,47,58,19\n";
+    assert_eq!(transfer::csv_header(&UK_LOTTO), "date,n1,n2,n3,n4,n5,n6,s1");
+
+    // A EuroMillions line does not fit the Loto columns
+    assert!(transfer::import(&storage, CSV, Format::Csv, &LOTO).await.is_err());
+    assert_eq!(transfer::import(&storage, csv, Format::Csv, &UK_LOTTO).await.unwrap().inserted, 1);
+    assert_eq!(storage.count_draws("uk_lotto").await.unwrap(), 1);
+    assert_eq!(storage.count_draws("euromillions").await.unwrap(), 0);
+
+    assert_eq!(transfer::export(&storage, Format::Csv, &UK_LOTTO).await.unwrap(), csv);
+    assert_eq!(transfer::export(&storage, Format::Csv, &EUROMILLIONS).await.unwrap(), CSV.lines().next().unwrap().to_string() + "\n");
+}
@@ -0,0 +1,279 @@
+mod common;
+
+use chrono::{NaiveDate, Utc};
+use common::FakeSource;
+use euromillions_bot::checker;
+use euromillions_bot::config::WebhookConfig;
+use euromillions_bot::ingest::{self, FetchRunStatus, FetchTrigger};
+use euromillions_bot::models::{NewDraw, NewGrid, NewWebhook, PendingDelivery, Webhook};
+use euromillions_bot::storage::{SqliteStorage, Storage};
+use euromillions_bot::webhooks::{self, DispatchSummary, Dispatcher, Event, DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER};
+use std::time::Duration;
+use wiremock::matchers::{header, header_exists, method, path};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+const SECRET: &str = "0123456789abcdef";
+
+async fn subscribe(storage: &dyn Storage, url: String, events: &[Event]) -> Webhook {
+    storage
+        .create_webhook(&NewWebhook {
+            url,
+            secret: SECRET.to_string(),
+            events: events.iter().map(|e| e.as_str().to_string()).collect(),
+        })
+        .await
+        .unwrap()
+}
+
+/// Claims what a dispatcher would send at `at`, for a minute.
+async fn claim_due(storage: &dyn Storage, at: chrono::NaiveDateTime) -> Vec<PendingDelivery> {
+    storage.claim_due_deliveries(at, at + chrono::Duration::minutes(1), 10).await.unwrap()
+}
+
+fn new_draw() -> NewDraw {
+    NewDraw {
+        game: "euromillions".to_string(),
+        date: NaiveDate::from_ymd_opt(2025, 3, 21).unwrap(),
+        numbers: vec![7, 20, 32, 41, 48],
+        stars: vec![3, 11],
+    }
+}
+
+fn dispatcher(max_attempts: u32) -> Dispatcher {
+    Dispatcher::new(&WebhookConfig {
+        max_attempts,
+        backoff_base_secs: 30,
+        backoff_max_secs: 100,
+        ..WebhookConfig::default()
+    })
+    .unwrap()
+}
+
+#[test]
+fn test_signature_is_hmac_sha256() {
+    assert_eq!(
+        webhooks::sign("key", b"The quick brown fox jumps over the lazy dog"),
+        "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+    );
+    assert_eq!(webhooks::generate_secret().len(), 64);
+    assert_ne!(webhooks::generate_secret(), webhooks::generate_secret());
+}
+
+#[test]
+fn test_events_and_backoff() {
+    assert_eq!("grid.result".parse::<Event>().unwrap(), Event::GridResult);
+    assert!("grid.deleted".parse::<Event>().is_err());
+
+    let dispatcher = dispatcher(5);
+    let delays: Vec<_> = (1..=4).map(|n| dispatcher.backoff(n).as_secs()).collect();
+    assert_eq!(delays, vec![30, 60, 100, 100]);
+}
+
+#[tokio::test]
+async fn test_delivery_is_signed() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/hook"))
+        .and(header(EVENT_HEADER, "draw.created"))
+        .and(header_exists(DELIVERY_HEADER))
+        .respond_with(|request: &Request| {
+            let signature = request.headers.get(SIGNATURE_HEADER).unwrap().to_str().unwrap();
+            if signature == webhooks::sign(SECRET, &request.body) {
+                ResponseTemplate::new(204)
+            } else {
+                ResponseTemplate::new(401)
+            }
+        })
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    subscribe(&storage, format!("{}/hook", server.uri()), &[Event::DrawCreated]).await;
+    storage.upsert_draw(new_draw()).await.unwrap();
+
+    let summary = dispatcher(3).deliver_due(&storage, Utc::now().naive_utc()).await.unwrap();
+    assert_eq!(summary, DispatchSummary { delivered: 1, retrying: 0, failed: 0 });
+
+    let body: serde_json::Value = server.received_requests().await.unwrap()[0].body_json().unwrap();
+    assert_eq!(body["event"], "draw.created");
+    assert_eq!(body["data"]["date"], "2025-03-21");
+    assert!(body["id"].is_i64());
+
+    // Nothing left to send
+    let again = dispatcher(3).deliver_due(&storage, Utc::now().naive_utc()).await.unwrap();
+    assert_eq!(again, DispatchSummary::default());
+}
+
+#[tokio::test]
+async fn test_concurrent_dispatchers_send_once() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(204).set_delay(Duration::from_millis(200)))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    subscribe(&storage, format!("{}/hook", server.uri()), &[Event::DrawCreated]).await;
+    storage.upsert_draw(new_draw()).await.unwrap();
+
+    let (one, other) = (dispatcher(3), dispatcher(3));
+    let now = Utc::now().naive_utc();
+    let (first, second) = tokio::join!(one.deliver_due(&storage, now), other.deliver_due(&storage, now));
+    assert_eq!(first.unwrap().delivered + second.unwrap().delivered, 1);
+}
+
+#[tokio::test]
+async fn test_failed_delivery_is_retried_then_given_up() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    let webhook = subscribe(&storage, server.uri(), &[Event::GridGenerated]).await;
+    storage.emit_event("grid.generated", &serde_json::json!({ "grids": [] })).await.unwrap();
+    let dispatcher = dispatcher(2);
+    let now = Utc::now().naive_utc();
+
+    let first = dispatcher.deliver_due(&storage, now).await.unwrap();
+    assert_eq!(first.retrying, 1);
+    let delivery = &storage.list_deliveries(webhook.id, 1).await.unwrap()[0];
+    assert_eq!((delivery.status.as_str(), delivery.attempts, delivery.response_status), ("pending", 1, Some(500)));
+    assert_eq!(delivery.next_attempt_at, Some(now + chrono::Duration::seconds(30)));
+
+    // Not due before the backoff elapsed
+    assert_eq!(dispatcher.deliver_due(&storage, now).await.unwrap(), DispatchSummary::default());
+
+    let second = dispatcher.deliver_due(&storage, now + chrono::Duration::seconds(30)).await.unwrap();
+    assert_eq!(second.failed, 1);
+    let delivery = storage.get_delivery(delivery.id).await.unwrap().unwrap();
+    assert_eq!((delivery.status.as_str(), delivery.attempts), ("failed", 2));
+    assert_eq!(delivery.error.as_deref(), Some("HTTP 500"));
+}
+
+#[tokio::test]
+async fn test_replay_sends_the_same_event() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    let webhook = subscribe(&storage, server.uri(), &[Event::DrawCreated]).await;
+    storage.upsert_draw(new_draw()).await.unwrap();
+    let dispatcher = dispatcher(3);
+    dispatcher.deliver_due(&storage, Utc::now().naive_utc()).await.unwrap();
+
+    let original = &storage.list_deliveries(webhook.id, 1).await.unwrap()[0];
+    storage.replay_delivery(original.id).await.unwrap().unwrap();
+    let summary = dispatcher.deliver_due(&storage, Utc::now().naive_utc()).await.unwrap();
+    assert_eq!(summary.delivered, 1);
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests[0].body, requests[1].body);
+    assert_ne!(requests[0].headers.get(DELIVERY_HEADER), requests[1].headers.get(DELIVERY_HEADER));
+}
+
+#[tokio::test]
+async fn test_checker_emits_grid_results() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    subscribe(&storage, "https://example.com/hook".to_string(), &[Event::GridResult]).await;
+    storage.upsert_draw(new_draw()).await.unwrap();
+    let draw = storage.latest_draw("euromillions").await.unwrap().unwrap();
+    let grids = storage
+        .insert_grids(&[
+            NewGrid { game: "euromillions".to_string(), draw_date: draw.date, numbers: vec![7, 20, 32, 41, 48], stars: vec![1, 2], user_id: None, cost_cents: None, addons: Vec::new() },
+            NewGrid { game: "euromillions".to_string(), draw_date: draw.date, numbers: vec![1, 2, 3, 4, 5], stars: vec![1, 2], user_id: None, cost_cents: None, addons: Vec::new() },
+        ])
+        .await
+        .unwrap();
+    storage.set_grid_status(&[grids[0].id], &["generated"], "played").await.unwrap();
+
+    // Only the played line is checked, and only once
+    let checked = checker::check_grids(&storage, &draw).await.unwrap();
+    assert_eq!(checked.len(), 1);
+    assert_eq!(checked[0].1.tier, Some(3));
+    assert_eq!(checked[0].0.status, "checked");
+    assert!(checker::check_grids(&storage, &draw).await.unwrap().is_empty());
+
+    let due = claim_due(&storage, Utc::now().naive_utc()).await;
+    assert_eq!(due.len(), 1);
+    let data: serde_json::Value = serde_json::from_str(&due[0].payload).unwrap();
+    assert_eq!(data["grid"]["id"], checked[0].0.id);
+    assert_eq!(data["result"]["tier"], 3);
+}
+
+#[tokio::test]
+async fn test_failed_fetch_is_emitted() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    subscribe(&storage, "https://example.com/hook".to_string(), &[Event::FetchFailed, Event::DrawCreated]).await;
+
+    let summary = ingest::run(&storage, &FakeSource::default(), &[2024], FetchTrigger::Cli).await.unwrap();
+
+    let due = claim_due(&storage, Utc::now().naive_utc()).await;
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].event, "fetch.failed");
+    let data: serde_json::Value = serde_json::from_str(&due[0].payload).unwrap();
+    assert_eq!(data["run_id"], summary.run_id);
+    assert_eq!(data["trigger"], "cli");
+    assert_eq!(data["status"], "failed");
+    assert!(data["error"].as_str().unwrap().contains("2024"));
+}
+
+#[tokio::test]
+async fn test_fetch_with_only_changed_markup_failed() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    subscribe(&storage, "https://example.com/hook".to_string(), &[Event::FetchFailed]).await;
+    let source = FakeSource::default()
+        .with_page(2024, common::fixture("results-history-redesigned.html"))
+        .with_page(2025, common::fixture("results-history-redesigned.html"));
+
+    let summary = ingest::run(&storage, &source, &[2024, 2025], FetchTrigger::Cron).await.unwrap();
+
+    assert_eq!(summary.report.pages.len(), 2);
+    assert_eq!(summary.status(), FetchRunStatus::Failed);
+    let run = storage.get_fetch_run(summary.run_id).await.unwrap().unwrap();
+    assert_eq!(run.status, "failed");
+    let due = claim_due(&storage, Utc::now().naive_utc()).await;
+    let data: serde_json::Value = serde_json::from_str(&due[0].payload).unwrap();
+    assert_eq!(data["status"], "failed");
+    assert!(data["error"].as_str().unwrap().contains("markup changed"));
+}
+
+#[tokio::test]
+async fn test_private_address_is_not_posted_to() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST")).respond_with(ResponseTemplate::new(204)).expect(0).mount(&server).await;
+
+    // Stored directly: the API refuses the name itself
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    let url = format!("http://localhost:{}/hook", server.address().port());
+    subscribe(&storage, url, &[Event::DrawCreated]).await;
+    storage.upsert_draw(new_draw()).await.unwrap();
+
+    let summary = dispatcher(3).deliver_due(&storage, Utc::now().naive_utc()).await.unwrap();
+    assert_eq!(summary.retrying, 1);
+}
+
+#[tokio::test]
+async fn test_unreachable_endpoint_is_retried() {
+    let storage = SqliteStorage::in_memory().await.unwrap();
+    // Nothing listens on port 9 (discard) in the test environment
+    subscribe(&storage, "http://127.0.0.1:9/hook".to_string(), &[Event::DrawCreated]).await;
+    storage.upsert_draw(new_draw()).await.unwrap();
+
+    let dispatcher = Dispatcher::new(&WebhookConfig { timeout_secs: 2, ..WebhookConfig::default() }).unwrap();
+    let summary = tokio::time::timeout(Duration::from_secs(10), dispatcher.deliver_due(&storage, Utc::now().naive_utc()))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(summary.retrying, 1);
+    let delivery = &claim_due(&storage, Utc::now().naive_utc() + chrono::Duration::hours(1)).await[0];
+    assert_eq!(delivery.attempts, 1);
+}
@@ -1,4 +1,4 @@
-import type { Draw, Grid, NewGrid } from './types';
+import type { Draw, Grid } from './types';
 import { APIError } from './types';
 
 // Configure this with your deployed API URL
@@ -49,9 +49,13 @@ export const api = {
         return fetchAPI<string>('/history');
     },
 
-    // Generate 4 optimized grids for the next draw
-    async generateGrids(): Promise<NewGrid[]> {
-        return fetchAPI<NewGrid[]>('/generate');
+    // Generate 4 optimized grids for the next draw. Retrying with the same
+    // key returns the grids of the first request instead of new ones.
+    async generateGrids(idempotencyKey: string = crypto.randomUUID()): Promise<Grid[]> {
+        return fetchAPI<Grid[]>('/generate', {
+            method: 'POST',
+            headers: { 'Idempotency-Key': idempotencyKey },
+        });
     },
 
     // Get the 20 most recent generated grids